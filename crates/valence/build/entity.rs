@@ -120,7 +120,7 @@ impl Value {
             Value::String(_) => quote!(Box<str>),
             Value::TextComponent(_) => quote!(Text),
             Value::OptionalTextComponent(_) => quote!(Option<Text>),
-            Value::ItemStack(_) => quote!(()), // TODO
+            Value::ItemStack(_) => quote!(Option<ItemStack>),
             Value::Boolean(_) => quote!(bool),
             Value::Rotation { .. } => quote!(EulerAngle),
             Value::BlockPos(_) => quote!(BlockPos),
@@ -145,6 +145,7 @@ impl Value {
             Value::String(_) => quote!(&str),
             Value::TextComponent(_) => quote!(&Text),
             Value::OptionalTextComponent(_) => quote!(Option<&Text>),
+            Value::ItemStack(_) => quote!(Option<&ItemStack>),
             Value::NbtCompound(_) => quote!(&valence_nbt::Compound),
             _ => self.field_type(),
         }
@@ -155,7 +156,9 @@ impl Value {
             Value::String(_) | Value::TextComponent(_) | Value::NbtCompound(_) => {
                 quote!(&self.#field_name)
             }
-            Value::OptionalTextComponent(_) => quote!(self.#field_name.as_ref()),
+            Value::OptionalTextComponent(_) | Value::ItemStack(_) => {
+                quote!(self.#field_name.as_ref())
+            }
             _ => quote!(self.#field_name),
         }
     }
@@ -172,7 +175,11 @@ impl Value {
                 assert!(t.is_none());
                 quote!(None)
             }
-            Value::ItemStack(_) => quote!(()), // TODO
+            Value::ItemStack(s) => {
+                // The default is always an empty slot.
+                assert_eq!(s, "1 air");
+                quote!(None)
+            }
             Value::Boolean(b) => quote!(#b),
             Value::Rotation { pitch, yaw, roll } => quote! {
                 EulerAngle {
@@ -432,6 +439,79 @@ pub fn build() -> anyhow::Result<TokenStream> {
         }
     });
 
+    let type_name_arms = concrete_entities.iter().map(|(k, v)| {
+        let name = ident(k);
+        let typ = v.typ.as_ref().unwrap();
+
+        quote! {
+            Self::#name => #typ,
+        }
+    });
+
+    let from_type_name_arms = concrete_entities.iter().map(|(k, v)| {
+        let name = ident(k);
+        let typ = v.typ.as_ref().unwrap();
+
+        quote! {
+            #typ => Some(Self::#name),
+        }
+    });
+
+    // Every entity has the flags of the base entity, so accessors for them can be
+    // provided on `TrackedData` directly.
+    let base_flag_getter_setters = entities["Entity"]
+        .fields
+        .iter()
+        .flat_map(|field| &field.bits)
+        .map(|bit| {
+            let bit_name = ident(&bit.name);
+            let getter_name = ident(format!("get_{}", &bit.name));
+            let setter_name = ident(format!("set_{}", &bit.name));
+
+            quote! {
+                pub fn #getter_name(&self) -> bool {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#getter_name(),)*
+                    }
+                }
+
+                pub fn #setter_name(&mut self, #bit_name: bool) {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#setter_name(#bit_name),)*
+                    }
+                }
+            }
+        })
+        .collect::<TokenStream>();
+
+    // The same goes for the other fields of the base entity, such as the pose.
+    let base_field_getter_setters = entities["Entity"]
+        .fields
+        .iter()
+        .filter(|field| field.bits.is_empty())
+        .map(|field| {
+            let field_name = ident(&field.name);
+            let field_type = field.default_value.field_type();
+            let getter_name = ident(format!("get_{}", &field.name));
+            let setter_name = ident(format!("set_{}", &field.name));
+            let getter_return_type = field.default_value.getter_return_type();
+
+            quote! {
+                pub fn #getter_name(&self) -> #getter_return_type {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#getter_name(),)*
+                    }
+                }
+
+                pub fn #setter_name(&mut self, #field_name: impl Into<#field_type>) {
+                    match self {
+                        #(Self::#concrete_entity_names(e) => e.#setter_name(#field_name),)*
+                    }
+                }
+            }
+        })
+        .collect::<TokenStream>();
+
     Ok(quote! {
         /// Contains a variant for each concrete entity type.
         #[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
@@ -445,6 +525,23 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(#translation_key_arms)*
                 }
             }
+
+            /// Returns the name of this entity type without the namespace, such
+            /// as `zombie`.
+            pub fn type_name(self) -> &'static str {
+                match self {
+                    #(#type_name_arms)*
+                }
+            }
+
+            /// Gets the entity kind from the name of its type without the
+            /// namespace. The inverse of [`Self::type_name`].
+            pub fn from_type_name(name: &str) -> Option<Self> {
+                match name {
+                    #(#from_type_name_arms)*
+                    _ => None,
+                }
+            }
         }
 
         pub enum TrackedData {
@@ -493,6 +590,10 @@ pub fn build() -> anyhow::Result<TokenStream> {
                     #(Self::#concrete_entity_names(e) => e.clear_modifications(),)*
                 }
             }
+
+            #base_flag_getter_setters
+
+            #base_field_getter_setters
         }
 
         #(#concrete_entity_structs)*
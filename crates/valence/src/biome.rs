@@ -31,6 +31,15 @@ pub struct Biome {
     /// seen in the F3 debug menu.
     pub name: Ident<String>,
     pub precipitation: BiomePrecipitation,
+    /// Affects the colors of grass and foliage when [`Self::grass_color`] or
+    /// [`Self::foliage_color`] are `None`, and whether or not precipitation
+    /// falls as snow.
+    pub temperature: f32,
+    /// Affects the colors of grass and foliage when [`Self::grass_color`] or
+    /// [`Self::foliage_color`] are `None`.
+    pub downfall: f32,
+    /// See [`Biome::sky_color_from_temperature`] to derive the sky color the
+    /// way vanilla biomes do.
     pub sky_color: u32,
     pub water_fog_color: u32,
     pub fog_color: u32,
@@ -45,14 +54,19 @@ pub struct Biome {
     pub particle: Option<BiomeParticle>,
     // TODO
     // * depth: f32
-    // * temperature: f32
     // * scale: f32
-    // * downfall: f32
     // * category
     // * temperature_modifier
 }
 
 impl Biome {
+    /// Computes the sky color of a biome from its temperature in the same way
+    /// as vanilla biomes.
+    pub fn sky_color_from_temperature(temperature: f32) -> u32 {
+        let t = (temperature / 3.0).clamp(-1.0, 1.0);
+        hsv_to_rgb(0.622_222_24 - t * 0.05, 0.5 + t * 0.1, 1.0)
+    }
+
     pub(crate) fn to_biome_registry_item(&self, id: i32) -> Compound {
         let mut reg = compound! {
             "name" => self.name.clone(),
@@ -64,9 +78,9 @@ impl Biome {
                     BiomePrecipitation::None => "none",
                 },
                 "depth" => 0.125_f32,
-                "temperature" => 0.8_f32,
+                "temperature" => self.temperature,
                 "scale" => 0.05_f32,
-                "downfall" => 0.4_f32,
+                "downfall" => self.downfall,
                 "category" => "none",
                 // "temperature_modifier" =>
                 "effects" => {
@@ -141,6 +155,26 @@ impl Biome {
     }
 }
 
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> u32 {
+    let h = hue * 6.0;
+    let f = h - h.floor();
+    let p = value * (1.0 - saturation);
+    let q = value * (1.0 - f * saturation);
+    let t = value * (1.0 - (1.0 - f) * saturation);
+
+    let (r, g, b) = match h as i32 % 6 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+
+    let to_u8 = |c: f32| (c * 255.0).clamp(0.0, 255.0) as u32;
+    to_u8(r) << 16 | to_u8(g) << 8 | to_u8(b)
+}
+
 pub(crate) fn validate_biomes(biomes: &[Biome]) -> anyhow::Result<()> {
     ensure!(!biomes.is_empty(), "at least one biome must be present");
 
@@ -174,6 +208,8 @@ impl Default for Biome {
         Self {
             name: ident!("plains"),
             precipitation: BiomePrecipitation::default(),
+            temperature: 0.8,
+            downfall: 0.4,
             sky_color: 7907327,
             water_fog_color: 329011,
             fog_color: 12638463,
@@ -235,3 +271,49 @@ pub struct BiomeParticle {
     pub probability: f32,
     pub kind: Ident<String>,
 }
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::Value;
+
+    use super::*;
+
+    fn effects(biome: &Biome) -> Compound {
+        let reg = biome.to_biome_registry_item(0);
+        let Some(Value::Compound(element)) = reg.get("element") else {
+            panic!("missing biome element");
+        };
+        let Some(Value::Compound(effects)) = element.get("effects") else {
+            panic!("missing biome effects");
+        };
+        effects.clone()
+    }
+
+    #[test]
+    fn explicit_colors_are_serialized() {
+        let biome = Biome {
+            grass_color: Some(0x00ff00),
+            foliage_color: Some(0x123456),
+            water_color: 0xabcdef,
+            ..Default::default()
+        };
+
+        let eff = effects(&biome);
+        assert_eq!(eff.get("grass_color"), Some(&Value::Int(0x00ff00)));
+        assert_eq!(eff.get("foliage_color"), Some(&Value::Int(0x123456)));
+        assert_eq!(eff.get("water_color"), Some(&Value::Int(0xabcdef)));
+
+        let eff = effects(&Biome::default());
+        assert_eq!(eff.get("grass_color"), None);
+        assert_eq!(eff.get("foliage_color"), None);
+    }
+
+    #[test]
+    fn sky_color_from_temperature_matches_vanilla() {
+        let biome = Biome::default();
+        assert_eq!(
+            Biome::sky_color_from_temperature(biome.temperature),
+            biome.sky_color
+        );
+    }
+}
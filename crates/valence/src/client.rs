@@ -1,7 +1,9 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
 use std::net::IpAddr;
 use std::num::Wrapping;
 
-use anyhow::{bail, Context};
+use anyhow::{bail, ensure, Context};
 use bevy_ecs::prelude::*;
 use bytes::BytesMut;
 use glam::{DVec3, Vec3};
@@ -11,21 +13,26 @@ use valence_protocol::packets::s2c::particle::Particle;
 use valence_protocol::packets::s2c::play::{
     AcknowledgeBlockChange, CombatDeath, DisconnectPlay, EntityEvent, GameEvent, KeepAliveS2c,
     LoginPlayOwned, ParticleS2c, PluginMessageS2c, RemoveEntitiesEncode, ResourcePackS2c,
-    RespawnOwned, SetActionBarText, SetCenterChunk, SetDefaultSpawnPosition, SetEntityMetadata,
-    SetEntityVelocity, SetRenderDistance, SetSubtitleText, SetTitleAnimationTimes, SetTitleText,
-    SynchronizePlayerPosition, SystemChatMessage, UnloadChunk,
+    RespawnOwned, SetActionBarText, SetCenterChunk, SetContainerProperty, SetContainerSlotEncode,
+    SetDefaultSpawnPosition, SetEntityMetadata, SetEntityVelocity, SetRenderDistance,
+    SetSubtitleText, SetTitleAnimationTimes, SetTitleText, SynchronizePlayerPosition,
+    SystemChatMessage, UnloadChunk, UpdateTeams,
+};
+use valence_protocol::packets::s2c::update_teams::{
+    CollisionRule, NameTagVisibility, TeamColor, TeamFlags, UpdateTeamsMode,
 };
 use valence_protocol::types::{GameEventKind, GameMode, Property, SyncPlayerPosLookFlags};
 use valence_protocol::{
-    BlockPos, EncodePacket, Ident, ItemStack, PacketDecoder, PacketEncoder, RawBytes, Text,
+    BlockPos, Encode, EncodePacket, Ident, ItemStack, PacketDecoder, PacketEncoder, RawBytes, Text,
     Username, VarInt,
 };
 
+use crate::config::KeepaliveIdStrategy;
 use crate::dimension::DimensionId;
 use crate::entity::data::Player;
-use crate::entity::{velocity_to_packet_units, EntityStatus, McEntity};
+use crate::entity::{velocity_to_packet_units, EntityLodSettings, EntityStatus, McEntity};
 use crate::instance::Instance;
-use crate::packet::WritePacket;
+use crate::packet::{PacketWriter, WritePacket};
 use crate::server::{NewClientInfo, Server};
 use crate::view::{ChunkPos, ChunkView};
 use crate::{Despawned, NULL_ENTITY};
@@ -40,6 +47,11 @@ pub struct Client {
     enc: PacketEncoder,
     dec: PacketDecoder,
     scratch: Vec<u8>,
+    /// Compressed but unencrypted packets written with
+    /// [`PacketPriority::Low`], waiting to be sent.
+    low_priority_buf: Vec<u8>,
+    /// The length of each packet in `low_priority_buf`.
+    low_priority_lens: VecDeque<usize>,
     is_disconnected: bool,
     username: Username<String>,
     uuid: Uuid,
@@ -62,6 +74,9 @@ pub struct Client {
     player_data: Player,
     view_distance: u8,
     old_view_distance: u8,
+    /// The entity view distance set by the user, if any.
+    entity_view_distance: Option<u8>,
+    old_entity_view_distance: u8,
     death_location: Option<(DimensionId, BlockPos)>,
     entities_to_despawn: Vec<VarInt>,
     got_keepalive: bool,
@@ -79,6 +94,9 @@ pub struct Client {
     is_hardcore: bool,
     is_flat: bool,
     has_respawn_screen: bool,
+    /// Bitset of the glow color teams that have been created on the client,
+    /// indexed by [`TeamColor`].
+    glow_color_teams: u32,
     /// The item that the client thinks it's holding under the mouse
     /// cursor.
     pub(crate) cursor_item: Option<ItemStack>,
@@ -95,6 +113,33 @@ pub struct Client {
 pub trait ClientConnection: Send + Sync + 'static {
     fn try_send(&mut self, bytes: BytesMut) -> anyhow::Result<()>;
     fn try_recv(&mut self) -> anyhow::Result<BytesMut>;
+    /// Returns the number of bytes that can be sent before the outgoing
+    /// limit is reached, or `None` if there is no limit.
+    fn remaining_capacity(&self) -> Option<usize> {
+        None
+    }
+
+    /// Returns the outgoing limit in bytes, or `None` if there is no limit or
+    /// it is unknown.
+    fn capacity(&self) -> Option<usize> {
+        None
+    }
+}
+
+/// The priority of a packet written with
+/// [`Client::write_packet_with_priority`].
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Debug)]
+pub enum PacketPriority {
+    /// The packet is sent at the end of the tick like any other packet.
+    #[default]
+    High,
+    /// The packet is sent after all high priority packets, and only if the
+    /// connection has room for it. Otherwise, it is delayed until a later
+    /// tick. Useful for bulky packets such as chunk data.
+    ///
+    /// Packets larger than the outgoing limit of the connection can never be
+    /// sent, so they are dropped with a warning.
+    Low,
 }
 
 impl Client {
@@ -109,6 +154,8 @@ impl Client {
             enc,
             dec,
             scratch: vec![],
+            low_priority_buf: vec![],
+            low_priority_lens: VecDeque::new(),
             is_disconnected: false,
             username: info.username,
             uuid: info.uuid,
@@ -130,6 +177,8 @@ impl Client {
             player_data: Player::new(),
             view_distance: 2,
             old_view_distance: 2,
+            entity_view_distance: None,
+            old_entity_view_distance: 2,
             death_location: None,
             entities_to_despawn: vec![],
             is_new: true,
@@ -137,6 +186,7 @@ impl Client {
             is_hardcore: false,
             is_flat: false,
             has_respawn_screen: false,
+            glow_color_teams: 0,
             got_keepalive: true,
             last_keepalive_id: 0,
             teleport_id_counter: 0,
@@ -166,6 +216,40 @@ impl Client {
         self.enc.write_packet(pkt);
     }
 
+    /// Like [`Self::write_packet`], but low priority packets are sent after
+    /// high priority packets and only as long as the outgoing limit of the
+    /// connection is not exceeded. Low priority packets are sent in the
+    /// order they were written.
+    pub fn write_packet_with_priority<P>(&mut self, pkt: &P, priority: PacketPriority)
+    where
+        P: EncodePacket + ?Sized,
+    {
+        match priority {
+            PacketPriority::High => self.enc.write_packet(pkt),
+            PacketPriority::Low => {
+                let start = self.low_priority_buf.len();
+
+                PacketWriter::new(
+                    &mut self.low_priority_buf,
+                    self.enc.compression_threshold(),
+                    &mut self.scratch,
+                )
+                .write_packet(pkt);
+
+                let len = self.low_priority_buf.len() - start;
+                if len > 0 {
+                    self.low_priority_lens.push_back(len);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of low priority packets which have not been sent
+    /// yet.
+    pub fn queued_low_priority_packets(&self) -> usize {
+        self.low_priority_lens.len()
+    }
+
     /// Writes arbitrary bytes to this client's packet buffer. The packet data
     /// must be properly compressed for the current compression threshold but
     /// never encrypted.
@@ -252,6 +336,22 @@ impl Client {
         )
     }
 
+    /// Gets a [`ChunkView`] representing the chunks this client can see
+    /// entities in. This is never larger than [`Self::view`].
+    pub fn entity_view(&self) -> ChunkView {
+        ChunkView::new(
+            ChunkPos::from_dvec3(self.position),
+            self.entity_view_distance(),
+        )
+    }
+
+    pub fn old_entity_view(&self) -> ChunkView {
+        ChunkView::new(
+            ChunkPos::from_dvec3(self.old_position),
+            self.old_entity_view_distance,
+        )
+    }
+
     pub fn set_velocity(&mut self, velocity: impl Into<Vec3>) {
         self.enc.write_packet(&SetEntityVelocity {
             entity_id: VarInt(0),
@@ -352,6 +452,55 @@ impl Client {
         self.view_distance = dist.clamp(2, 32);
     }
 
+    /// The distance in chunks within which this client can see entities. This
+    /// is the same as [`Self::view_distance`] unless a smaller entity view
+    /// distance was set with [`Self::set_entity_view_distance`].
+    pub fn entity_view_distance(&self) -> u8 {
+        match self.entity_view_distance {
+            Some(dist) => dist.min(self.view_distance),
+            None => self.view_distance,
+        }
+    }
+
+    /// Sets the distance in chunks within which this client can see entities,
+    /// independently of the chunk view distance. Entities are never visible
+    /// past [`Self::view_distance`]. `None` uses the chunk view distance.
+    pub fn set_entity_view_distance(&mut self, dist: Option<u8>) {
+        self.entity_view_distance = dist;
+    }
+
+    /// Moves this client to the middle of the chunk at `center` and sets its
+    /// view distance to `view_distance`. At the end of the tick, the center
+    /// chunk and all loaded chunks of its instance in the new view are sent,
+    /// nearest to `center` first.
+    ///
+    /// The chunks are part of the client's [view](Self::view), so they are
+    /// kept up to date and unloaded like any other.
+    ///
+    /// Returns an error if `instance` is not the instance this client is
+    /// located in.
+    pub fn send_initial_chunks(
+        &mut self,
+        instance: Entity,
+        center: ChunkPos,
+        view_distance: u32,
+    ) -> anyhow::Result<()> {
+        ensure!(
+            instance == self.instance,
+            "cannot send chunks of instance {instance:?} to a client in instance {:?}",
+            self.instance
+        );
+
+        self.set_position([
+            center.x as f64 * 16.0 + 8.0,
+            self.position.y,
+            center.z as f64 * 16.0 + 8.0,
+        ]);
+        self.set_view_distance(view_distance.min(u8::MAX as u32) as u8);
+
+        Ok(())
+    }
+
     /// Gets the last death location of this client. The client will see
     /// `minecraft:recovery_compass` items point at the returned position.
     ///
@@ -458,6 +607,15 @@ impl Client {
         });
     }
 
+    /// Sends the server brand to this client over the `minecraft:brand` plugin
+    /// channel. The brand is visible in the client's F3 debug menu.
+    pub fn set_brand(&mut self, brand: &str) {
+        let mut data = vec![];
+        brand.encode(&mut data).unwrap();
+
+        self.send_plugin_message(Ident::new("minecraft:brand").unwrap(), &data);
+    }
+
     /// Get the slot id in the player's inventory that the client says it's
     /// holding.
     pub fn held_item_slot(&self) -> u16 {
@@ -469,9 +627,27 @@ impl Client {
         self.write_packet(&DisconnectPlay {
             reason: reason.into().into(),
         });
+
+        // Disconnected clients are no longer updated, so the packet must be
+        // sent now for the client to see the reason.
+        let _ = self.conn.try_send(self.enc.take());
+
         self.is_disconnected = true;
     }
 
+    /// Kick the client with a reason which is translated on the client, such
+    /// as [`translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN`]. `with`
+    /// holds the arguments of the translation.
+    ///
+    /// [`translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN`]: valence_protocol::translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN
+    pub fn kick_translated(
+        &mut self,
+        key: impl Into<Cow<'static, str>>,
+        with: impl Into<Vec<Text>>,
+    ) {
+        self.kick(Text::translate(key, with));
+    }
+
     /// Requests that the client download and enable a resource pack.
     ///
     /// # Arguments
@@ -535,6 +711,79 @@ impl Client {
         });
     }
 
+    /// Sets a single slot of a window open on this client. `None` clears the
+    /// slot.
+    ///
+    /// This only changes what the client sees. It is a lighter alternative to
+    /// resending the whole window, but the contents of any [`Inventory`] the
+    /// window belongs to are unchanged and will overwrite the slot the next
+    /// time the inventory is synchronized.
+    ///
+    /// [`Inventory`]: crate::inventory::Inventory
+    pub fn set_slot(&mut self, window_id: i8, state_id: i32, slot: i16, item: Option<ItemStack>) {
+        self.write_packet(&SetContainerSlotEncode {
+            window_id,
+            state_id: VarInt(state_id),
+            slot_idx: slot,
+            slot_data: item.as_ref(),
+        });
+    }
+
+    /// Sets a property of a window open on this client, such as the progress
+    /// of the arrow in a furnace. The meaning of `property` and `value`
+    /// depends on the kind of window.
+    ///
+    /// See [wiki.vg] for the available properties.
+    ///
+    /// [wiki.vg]: https://wiki.vg/Protocol#Set_Container_Property
+    pub fn set_container_property(&mut self, window_id: u8, property: i16, value: i16) {
+        self.write_packet(&SetContainerProperty {
+            window_id,
+            property,
+            value,
+        });
+    }
+
+    /// Sets the color of the glowing outline of `entity` as seen by this
+    /// client. The entity must also have its glowing flag set for the outline
+    /// to be visible.
+    ///
+    /// The protocol version supported by Valence has no per-entity glow
+    /// color, so this works by adding the entity to a team with the given
+    /// color on this client only. Player entities are added to teams by
+    /// username rather than UUID and are not affected by this function.
+    pub fn set_entity_glow_color(&mut self, entity: &McEntity, color: TeamColor) {
+        let idx = color as u32;
+        let team_name = format!("valence_glow_{idx}");
+        let entry = entity.uuid().to_string();
+
+        if self.glow_color_teams & (1 << idx) == 0 {
+            self.glow_color_teams |= 1 << idx;
+
+            self.write_packet(&UpdateTeams {
+                team_name: &team_name,
+                mode: UpdateTeamsMode::CreateTeam {
+                    team_display_name: Text::default().into(),
+                    friendly_flags: TeamFlags::new(),
+                    name_tag_visibility: NameTagVisibility::Always,
+                    collision_rule: CollisionRule::Always,
+                    team_color: color,
+                    team_prefix: Text::default().into(),
+                    team_suffix: Text::default().into(),
+                    entities: vec![&entry],
+                },
+            });
+        } else {
+            // Adding an entity to a team removes it from its previous team.
+            self.write_packet(&UpdateTeams {
+                team_name: &team_name,
+                mode: UpdateTeamsMode::AddEntities {
+                    entities: vec![&entry],
+                },
+            });
+        }
+    }
+
     /// Puts a particle effect at the given position, only for this client.
     ///
     /// If you want to show a particle effect to all players, use
@@ -575,6 +824,15 @@ impl WritePacket for Client {
 }
 
 /// A system for adding [`Despawned`] components to disconnected clients.
+/// Sorts chunk positions by their distance to `center` so the chunks nearest
+/// to the client are loaded first. The sort is stable, so chunks at the same
+/// distance keep their order.
+fn nearest_first(positions: impl Iterator<Item = ChunkPos>, center: ChunkPos) -> Vec<ChunkPos> {
+    let mut positions: Vec<_> = positions.collect();
+    positions.sort_by_key(|&pos| center.distance_squared(pos));
+    positions
+}
+
 pub fn despawn_disconnected_clients(mut commands: Commands, clients: Query<(Entity, &Client)>) {
     for (entity, client) in &clients {
         if client.is_disconnected() {
@@ -585,6 +843,7 @@ pub fn despawn_disconnected_clients(mut commands: Commands, clients: Query<(Enti
 
 pub(crate) fn update_clients(
     server: Res<Server>,
+    lod: Res<EntityLodSettings>,
     mut clients: Query<(Entity, &mut Client, Option<&McEntity>)>,
     instances: Query<&Instance>,
     entities: Query<&McEntity>,
@@ -599,6 +858,7 @@ pub(crate) fn update_clients(
                 &instances,
                 &entities,
                 &server,
+                &lod,
             ) {
                 client.write_packet(&DisconnectPlay {
                     reason: Text::from("").into(),
@@ -620,11 +880,12 @@ pub(crate) fn update_clients(
 #[inline]
 fn update_one_client(
     client: &mut Client,
-    _self_entity: Option<&McEntity>,
-    _self_id: Entity,
+    self_entity: Option<&McEntity>,
+    self_id: Entity,
     instances: &Query<&Instance>,
     entities: &Query<&McEntity>,
     server: &Server,
+    lod: &EntityLodSettings,
 ) -> anyhow::Result<()> {
     let Ok(instance) = instances.get(client.instance) else {
         bail!("client is in a nonexistent instance");
@@ -666,6 +927,8 @@ fn update_one_client(
                 .map(|(id, pos)| (id.dimension_name(), pos)),
         })?;
 
+        client.enc.append_packet(&instance.time_packet())?;
+
         /*
         // TODO: enable all the features?
         send.append_packet(&FeatureFlags {
@@ -682,6 +945,8 @@ fn update_one_client(
 
         if client.needs_respawn {
             client.needs_respawn = false;
+            // Teams are discarded by the client on respawn.
+            client.glow_color_teams = 0;
 
             client.enc.append_packet(&RespawnOwned {
                 dimension_type_name: instance.dimension().dimension_type_name(),
@@ -696,13 +961,18 @@ fn update_one_client(
                     .death_location
                     .map(|(id, pos)| (id.dimension_name(), pos)),
             })?;
+
+            client.enc.append_packet(&instance.time_packet())?;
         }
     }
 
     // Check if it's time to send another keepalive.
-    if server.current_tick() % (server.tps() * 10) == 0 {
+    if server.keepalives() && server.current_tick() % (server.tps() * 10) == 0 {
         if client.got_keepalive {
-            let id = rand::random();
+            let id = match server.keepalive_id_strategy() {
+                KeepaliveIdStrategy::Random => rand::random(),
+                KeepaliveIdStrategy::Counter => client.last_keepalive_id.wrapping_add(1),
+            };
             client.enc.write_packet(&KeepAliveS2c { id });
             client.last_keepalive_id = id;
             client.got_keepalive = false;
@@ -714,9 +984,21 @@ fn update_one_client(
     // Send instance-wide packet data.
     client.enc.append_bytes(&instance.packet_buf);
 
+    // Send local packet data if the client is close enough.
+    for (center, radius, range) in &instance.local_packets {
+        if client.position.distance_squared(*center) <= radius * radius {
+            client
+                .enc
+                .append_bytes(&instance.local_packet_buf[range.clone()]);
+        }
+    }
+
     let old_view = client.old_view();
     let view = client.view();
 
+    let old_entity_view = client.old_entity_view();
+    let entity_view = client.entity_view();
+
     // Make sure the center chunk is set before loading chunks!
     if old_view.pos != view.pos {
         // TODO: does the client initialize the center chunk to (0, 0)?
@@ -726,6 +1008,12 @@ fn update_one_client(
         });
     }
 
+    // The cell containing the update packets of the client's own entity, which
+    // shouldn't be sent back to the client.
+    let self_cell_pos = self_entity
+        .filter(|e| e.instance() == client.old_instance)
+        .map(|e| ChunkPos::from_dvec3(e.position()));
+
     // Iterate over all visible chunks from the previous tick.
     if let Ok(old_instance) = instances.get(client.old_instance) {
         old_view.for_each(|pos| {
@@ -742,32 +1030,45 @@ fn update_one_client(
                     chunk.mark_viewed();
                 }
 
-                // Send entity spawn packets for entities entering the client's view.
-                for &(id, src_pos) in &cell.incoming {
-                    if src_pos.map_or(true, |p| !old_view.contains(p)) {
-                        // The incoming entity originated from outside the view distance, so it
-                        // must be spawned.
-                        if let Ok(entity) = entities.get(id) {
-                            // Spawn the entity at the old position so that later relative entity
-                            // movement packets will not set the entity to the wrong position.
-                            entity.write_init_packets(
-                                &mut client.enc,
-                                entity.old_position(),
-                                &mut client.scratch,
-                            );
+                // Entity update packets in the cell's packet buffer are also sent for
+                // cells outside the entity view. The client ignores them since those
+                // entities are not spawned.
+                if old_entity_view.contains(pos) {
+                    // Send entity spawn packets for entities entering the client's view.
+                    for &(id, src_pos) in &cell.incoming {
+                        if src_pos.map_or(true, |p| !old_entity_view.contains(p)) {
+                            // The incoming entity originated from outside the view distance, so
+                            // it must be spawned.
+                            if let Ok(entity) = entities.get(id) {
+                                // Spawn the entity at the old position so that later relative
+                                // entity movement packets will not set the entity to the wrong
+                                // position. Entities arriving from another instance have no
+                                // movement packets and are spawned at their new position.
+                                let pos = if entity.changed_instance() {
+                                    entity.position()
+                                } else {
+                                    entity.old_position()
+                                };
+
+                                entity.write_init_packets(
+                                    &mut client.enc,
+                                    pos,
+                                    &mut client.scratch,
+                                );
+                            }
                         }
                     }
-                }
 
-                // Send entity despawn packets for entities exiting the client's view.
-                for &(id, dest_pos) in &cell.outgoing {
-                    if dest_pos.map_or(true, |p| !old_view.contains(p)) {
-                        // The outgoing entity moved outside the view distance, so it must be
-                        // despawned.
-                        if let Ok(entity) = entities.get(id) {
-                            client
-                                .entities_to_despawn
-                                .push(VarInt(entity.protocol_id()));
+                    // Send entity despawn packets for entities exiting the client's view.
+                    for &(id, dest_pos) in &cell.outgoing {
+                        if dest_pos.map_or(true, |p| !old_entity_view.contains(p)) {
+                            // The outgoing entity moved outside the view distance, so it must
+                            // be despawned.
+                            if let Ok(entity) = entities.get(id) {
+                                client
+                                    .entities_to_despawn
+                                    .push(VarInt(entity.protocol_id()));
+                            }
                         }
                     }
                 }
@@ -775,11 +1076,75 @@ fn update_one_client(
                 // Send all data in the chunk's packet buffer to this client. This will update
                 // entities in the cell, spawn or update the chunk in the cell, or send any
                 // other packet data that was added here by users.
-                client.enc.append_bytes(&cell.packet_buf);
+                match self_entity {
+                    Some(self_entity)
+                        if self_cell_pos == Some(pos) && cell.entities.contains(&self_id) =>
+                    {
+                        let range = self_entity.self_update_range.clone();
+
+                        client.enc.append_bytes(&cell.packet_buf[..range.start]);
+                        client.enc.append_bytes(&cell.packet_buf[range.end..]);
+                    }
+                    _ => client.enc.append_bytes(&cell.packet_buf),
+                }
+
+                // Send the entity movement for the client's LOD band.
+                let band = lod.band_at(view.pos, pos);
+
+                if band != lod.band_at(old_view.pos, pos) {
+                    // The client moved between bands, so the positions it has of the
+                    // entities in this cell may be out of date.
+                    if old_entity_view.contains(pos) {
+                        for &id in &cell.entities {
+                            if id != self_id {
+                                if let Ok(entity) = entities.get(id) {
+                                    entity.write_teleport_packets(&mut client.enc);
+                                }
+                            }
+                        }
+                    }
+                } else {
+                    if band == 0 {
+                        let buf = &cell.movement_bufs[0];
+
+                        match self_entity {
+                            Some(self_entity)
+                                if self_cell_pos == Some(pos)
+                                    && cell.entities.contains(&self_id) =>
+                            {
+                                let range = self_entity.self_movement_range.clone();
+
+                                client.enc.append_bytes(&buf[..range.start]);
+                                client.enc.append_bytes(&buf[range.end..]);
+                            }
+                            _ => client.enc.append_bytes(buf),
+                        }
+                    } else if server.current_tick() % (1 << band) == 0 {
+                        client.enc.append_bytes(&cell.movement_bufs[band]);
+                    }
+
+                    // Entities arriving from a cell in another band may also be out of
+                    // date.
+                    for &(id, src_pos) in &cell.incoming {
+                        if let Some(src_pos) = src_pos {
+                            if id != self_id
+                                && old_entity_view.contains(src_pos)
+                                && lod.band_at(old_view.pos, src_pos) != band
+                            {
+                                if let Ok(entity) = entities.get(id) {
+                                    entity.write_teleport_packets(&mut client.enc);
+                                }
+                            }
+                        }
+                    }
+                }
             }
         });
     }
 
+    // The first error encountered while loading chunks in the new view.
+    let mut chunk_error = None;
+
     // Was the client's instance changed?
     if client.old_instance != client.instance {
         if let Ok(old_instance) = instances.get(client.old_instance) {
@@ -798,11 +1163,13 @@ fn update_one_client(
                     }
 
                     // Unload all the entities in the cell.
-                    for &id in &cell.entities {
-                        if let Ok(entity) = entities.get(id) {
-                            client
-                                .entities_to_despawn
-                                .push(VarInt(entity.protocol_id()));
+                    if old_entity_view.contains(pos) {
+                        for &id in &cell.entities {
+                            if let Ok(entity) = entities.get(id) {
+                                client
+                                    .entities_to_despawn
+                                    .push(VarInt(entity.protocol_id()));
+                            }
                         }
                     }
                 }
@@ -810,85 +1177,110 @@ fn update_one_client(
         }
 
         // Load all chunks and entities in new view.
-        view.for_each(|pos| {
+        for pos in nearest_first(view.iter(), view.pos) {
             if let Some(cell) = instance.partition.get(&pos) {
                 // Load the chunk at this cell if there is one.
                 if let Some(chunk) = &cell.chunk {
-                    chunk.write_init_packets(
+                    match chunk.write_init_packets(
                         &instance.info,
                         pos,
                         &mut client.enc,
                         &mut client.scratch,
-                    );
-
-                    chunk.mark_viewed();
+                    ) {
+                        Ok(()) => chunk.mark_viewed(),
+                        Err(e) => {
+                            chunk_error.get_or_insert(e);
+                        }
+                    }
                 }
 
                 // Load all the entities in this cell.
-                for &id in &cell.entities {
-                    if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
-                            entity.position(),
-                            &mut client.scratch,
-                        );
+                if entity_view.contains(pos) {
+                    for &id in &cell.entities {
+                        if let Ok(entity) = entities.get(id) {
+                            entity.write_init_packets(
+                                &mut client.enc,
+                                entity.position(),
+                                &mut client.scratch,
+                            );
+                        }
                     }
                 }
             }
-        });
-    } else if old_view != view {
+        }
+    } else {
         // Client changed their view without changing the instance.
 
-        // Unload chunks and entities in the old view and load chunks and entities in
-        // the new view. We don't need to do any work where the old and new view
-        // overlap.
-        old_view.diff_for_each(view, |pos| {
-            if let Some(cell) = instance.partition.get(&pos) {
-                // Unload the chunk at this cell if it was loaded.
-                if cell.chunk.is_some() {
-                    client.enc.write_packet(&UnloadChunk {
-                        chunk_x: pos.x,
-                        chunk_z: pos.z,
-                    });
+        // Unload chunks in the old view and load chunks in the new view. We don't need
+        // to do any work where the old and new view overlap.
+        if old_view != view {
+            old_view.diff_for_each(view, |pos| {
+                if let Some(cell) = instance.partition.get(&pos) {
+                    // Unload the chunk at this cell if it was loaded.
+                    if cell.chunk.is_some() {
+                        client.enc.write_packet(&UnloadChunk {
+                            chunk_x: pos.x,
+                            chunk_z: pos.z,
+                        });
+                    }
                 }
+            });
 
-                // Unload all the entities in the cell.
-                for &id in &cell.entities {
-                    if let Ok(entity) = entities.get(id) {
-                        client
-                            .entities_to_despawn
-                            .push(VarInt(entity.protocol_id()));
+            for pos in nearest_first(view.diff(old_view), view.pos) {
+                if let Some(cell) = instance.partition.get(&pos) {
+                    // Load the chunk at this cell if there is one.
+                    if let Some(chunk) = &cell.chunk {
+                        match chunk.write_init_packets(
+                            &instance.info,
+                            pos,
+                            &mut client.enc,
+                            &mut client.scratch,
+                        ) {
+                            Ok(()) => chunk.mark_viewed(),
+                            Err(e) => {
+                                chunk_error.get_or_insert(e);
+                            }
+                        }
                     }
                 }
             }
-        });
-
-        view.diff_for_each(old_view, |pos| {
-            if let Some(cell) = instance.partition.get(&pos) {
-                // Load the chunk at this cell if there is one.
-                if let Some(chunk) = &cell.chunk {
-                    chunk.write_init_packets(
-                        &instance.info,
-                        pos,
-                        &mut client.enc,
-                        &mut client.scratch,
-                    );
+        }
 
-                    chunk.mark_viewed();
+        // Likewise for entities in the entity view. This is done after the chunks
+        // are loaded.
+        if old_entity_view != entity_view {
+            old_entity_view.diff_for_each(entity_view, |pos| {
+                if let Some(cell) = instance.partition.get(&pos) {
+                    // Unload all the entities in the cell.
+                    for &id in &cell.entities {
+                        if let Ok(entity) = entities.get(id) {
+                            client
+                                .entities_to_despawn
+                                .push(VarInt(entity.protocol_id()));
+                        }
+                    }
                 }
+            });
 
-                // Load all the entities in this cell.
-                for &id in &cell.entities {
-                    if let Ok(entity) = entities.get(id) {
-                        entity.write_init_packets(
-                            &mut client.enc,
-                            entity.position(),
-                            &mut client.scratch,
-                        );
+            entity_view.diff_for_each(old_entity_view, |pos| {
+                if let Some(cell) = instance.partition.get(&pos) {
+                    // Load all the entities in this cell.
+                    for &id in &cell.entities {
+                        if let Ok(entity) = entities.get(id) {
+                            entity.write_init_packets(
+                                &mut client.enc,
+                                entity.position(),
+                                &mut client.scratch,
+                            );
+                        }
                     }
                 }
-            }
-        });
+            });
+        }
+    }
+
+    if let Some(e) = chunk_error {
+        return Err(e.context("failed to load chunk"));
     }
 
     // Despawn all the entities that are queued to be despawned.
@@ -970,10 +1362,56 @@ fn update_one_client(
     client.old_instance = client.instance;
     client.old_position = client.position;
     client.old_view_distance = client.view_distance;
+    client.old_entity_view_distance = client.entity_view_distance();
+
+    let mut bytes = client.enc.take();
+
+    // Send as many low priority packets as the connection has room for after the
+    // high priority packets.
+    let mut available = client
+        .conn
+        .remaining_capacity()
+        .map(|cap| cap.saturating_sub(bytes.len()));
+
+    let capacity = client.conn.capacity();
+
+    let mut low_priority_len = 0;
+    while let Some(&len) = client.low_priority_lens.front() {
+        if let Some(available) = &mut available {
+            if len > *available {
+                if capacity.map_or(true, |cap| len <= cap) {
+                    break;
+                }
+
+                // The packet would block the queue forever.
+                warn!(
+                    username = %client.username,
+                    "dropping low priority packet of {len} bytes larger than the outgoing limit"
+                );
+                client
+                    .low_priority_buf
+                    .drain(low_priority_len..low_priority_len + len);
+                client.low_priority_lens.pop_front();
+                continue;
+            }
+            *available -= len;
+        }
+
+        low_priority_len += len;
+        client.low_priority_lens.pop_front();
+    }
+
+    if low_priority_len > 0 {
+        client
+            .enc
+            .append_bytes(&client.low_priority_buf[..low_priority_len]);
+        client.low_priority_buf.drain(..low_priority_len);
+        bytes.unsplit(client.enc.take());
+    }
 
     client
         .conn
-        .try_send(client.enc.take())
+        .try_send(bytes)
         .context("failed to flush packet queue")?;
 
     Ok(())
@@ -984,12 +1422,143 @@ mod tests {
     use std::collections::BTreeSet;
 
     use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{ClientCommand, KeepAliveC2s};
     use valence_protocol::packets::s2c::play::ChunkDataAndUpdateLight;
     use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{translation_key, BlockState, ItemKind};
 
     use super::*;
+    use crate::assert_packet_count;
+    use crate::biome::BiomeId;
+    use crate::client::event::PerformRespawn;
+    use crate::config::ServerPlugin;
+    use crate::entity::EntityKind;
     use crate::instance::Chunk;
-    use crate::unit_test::util::scenario_single_client;
+    use crate::unit_test::util::{scenario_single_client, scenario_single_client_with_plugin};
+
+    #[test]
+    fn kick_with_translated_reason() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .kick_translated(translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN, []);
+
+        // The reason is sent immediately since the client is not updated after
+        // disconnecting.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::DisconnectPlay(_));
+
+        let S2cPlayPacket::DisconnectPlay(pkt) = &sent_packets[0] else {
+            panic!("expected a disconnect packet");
+        };
+        assert_eq!(
+            *pkt.reason,
+            Text::translate(translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN, [])
+        );
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_disconnected());
+    }
+
+    #[test]
+    fn high_priority_packets_sent_before_low_priority() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+
+        let bulky = Text::from("x".repeat(100));
+        for _ in 0..3 {
+            client.write_packet_with_priority(
+                &SystemChatMessage {
+                    chat: Cow::Borrowed(&bulky),
+                    overlay: false,
+                },
+                PacketPriority::Low,
+            );
+        }
+
+        client.write_packet_with_priority(
+            &SetActionBarText {
+                action_bar_text: Text::from("urgent").into(),
+            },
+            PacketPriority::High,
+        );
+
+        // Only leave room for some of the low priority packets.
+        client_helper.set_capacity(Some(250));
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let low_count = sent_packets
+            .iter()
+            .filter(|p| matches!(p, S2cPlayPacket::SystemChatMessage(_)))
+            .count();
+
+        assert!(low_count > 0 && low_count < 3, "{low_count}");
+        assert!(matches!(
+            sent_packets
+                .iter()
+                .find(|p| matches!(
+                    p,
+                    S2cPlayPacket::SystemChatMessage(_) | S2cPlayPacket::SetActionBarText(_)
+                ))
+                .unwrap(),
+            S2cPlayPacket::SetActionBarText(_)
+        ));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.queued_low_priority_packets(), 3 - low_count);
+
+        // The remaining packets are sent once there is room.
+        client_helper.set_capacity(None);
+        app.update();
+
+        let remaining = 3 - low_count;
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, remaining, S2cPlayPacket::SystemChatMessage(_));
+    }
+
+    #[test]
+    fn low_priority_packet_over_capacity_is_dropped() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+
+        for text in ["x".repeat(500), "small".into()] {
+            client.write_packet_with_priority(
+                &SystemChatMessage {
+                    chat: Cow::Owned(Text::from(text)),
+                    overlay: false,
+                },
+                PacketPriority::Low,
+            );
+        }
+
+        client_helper.set_capacity(Some(100));
+        app.update();
+
+        // The oversized packet does not keep the packets behind it from being
+        // sent.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SystemChatMessage(_));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.queued_low_priority_packets(), 0);
+    }
 
     #[test]
     fn client_chunk_view_change() {
@@ -1072,4 +1641,499 @@ mod tests {
             assert!(loaded_chunks.contains(&pos), "{pos:?}");
         }
     }
+
+    #[test]
+    fn client_block_change_sends_only_delta() {
+        let mut app = App::new();
+
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        for z in -5..5 {
+            for x in -5..5 {
+                instance.insert_chunk([x, z], Chunk::default());
+            }
+        }
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([8.0, 0.0, 8.0]);
+
+        // Load the initial chunks.
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app
+            .world
+            .query::<&mut Instance>()
+            .single_mut(&mut app.world);
+
+        instance.set_block_state([3, 0, 3], BlockState::STONE);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::BlockUpdate(_));
+    }
+
+    #[test]
+    fn send_initial_chunks_nearest_first() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Far away from the client's own view so that all chunks in the new view
+        // are loaded.
+        let center = ChunkPos::new(100, 100);
+        let view = ChunkView::new(center, 2);
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        for pos in view.iter() {
+            instance.insert_chunk(pos, Chunk::default());
+        }
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .send_initial_chunks(instance_ent, center, 2)
+            .unwrap();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        let positions: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::ChunkDataAndUpdateLight(p) => {
+                    Some(ChunkPos::new(p.chunk_x, p.chunk_z))
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(positions.len(), view.iter().count());
+        assert_eq!(positions[0], center);
+        assert!(positions
+            .windows(2)
+            .all(|w| center.distance_squared(w[0]) <= center.distance_squared(w[1])));
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetCenterChunk(SetCenterChunk {
+                chunk_x: VarInt(100),
+                chunk_z: VarInt(100)
+            })
+        )));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.view(), view);
+    }
+
+    #[test]
+    fn chunk_with_unregistered_biome_is_not_sent() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut chunk = Chunk::default();
+        chunk.set_biome(1, 2, 3, BiomeId(1000));
+
+        let biome_count = app.world.resource::<Server>().biomes().len();
+        let err = chunk.validate_biomes(biome_count).unwrap_err();
+        assert!(err.to_string().contains("BiomeId(1000)"));
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], chunk);
+
+        // The client is disconnected instead of being sent a chunk it cannot
+        // decode.
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_disconnected());
+    }
+
+    #[test]
+    fn send_initial_chunks_are_tracked() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([50, 50], Chunk::default());
+
+        app.update();
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .send_initial_chunks(instance_ent, ChunkPos::new(50, 50), 4)
+            .unwrap();
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Changes to the sent chunks reach the client.
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_block_state([50 * 16, 0, 50 * 16], BlockState::STONE);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::BlockUpdate(_));
+    }
+
+    #[test]
+    fn send_initial_chunks_rejects_other_instance() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let other_ent = app.world.spawn_empty().id();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        let position = client.position();
+
+        assert!(client
+            .send_initial_chunks(other_ent, ChunkPos::new(50, 50), 4)
+            .is_err());
+        assert_eq!(client.position(), position);
+    }
+
+    #[test]
+    fn entity_outside_entity_view_not_spawned() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_position([8.0, 0.0, 8.0]);
+        client.set_view_distance(4);
+        client.set_entity_view_distance(Some(2));
+
+        // Within the chunk view but outside the entity view.
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([16.0 * 5.0 + 8.0, 0.0, 8.0]);
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.view().contains(ChunkPos::new(5, 0)));
+        assert!(!client.entity_view().contains(ChunkPos::new(5, 0)));
+
+        let zombie_ent = app.world.spawn(zombie).id();
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+
+        // Moving into the entity view spawns the entity.
+        app.world
+            .get_mut::<McEntity>(zombie_ent)
+            .unwrap()
+            .set_position([16.0 * 3.0 + 8.0, 0.0, 8.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        // Moving out of the entity view despawns the entity.
+        app.world
+            .get_mut::<McEntity>(zombie_ent)
+            .unwrap()
+            .set_position([16.0 * 5.0 + 8.0, 0.0, 8.0]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+
+        // Increasing the entity view distance spawns entities in the new range.
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_entity_view_distance(None);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+    }
+
+    #[test]
+    fn entity_glow_color_uses_teams() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.data_mut().set_glowing(true);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_entity_glow_color(&zombie, TeamColor::Red);
+        client.set_entity_glow_color(&zombie, TeamColor::Red);
+        client.set_entity_glow_color(&zombie, TeamColor::Blue);
+
+        app.world.spawn(zombie);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        // Protocol 761 has no per-entity glow color, so the team path is used.
+        assert_packet_count!(sent_packets, 3, S2cPlayPacket::UpdateTeams(_));
+        assert_packet_count!(
+            sent_packets,
+            2,
+            S2cPlayPacket::UpdateTeams(UpdateTeams {
+                mode: UpdateTeamsMode::CreateTeam { .. },
+                ..
+            })
+        );
+        assert_packet_count!(
+            sent_packets,
+            0,
+            S2cPlayPacket::UpdateTeams(UpdateTeams {
+                mode: UpdateTeamsMode::CreateTeam {
+                    team_color: TeamColor::Green,
+                    ..
+                },
+                ..
+            })
+        );
+    }
+
+    #[test]
+    fn set_brand_sends_plugin_message() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_brand("valence");
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let brand = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::PluginMessageS2c(pkt) => Some(pkt),
+                _ => None,
+            })
+            .expect("missing plugin message");
+
+        assert_eq!(brand.channel.as_str(), "minecraft:brand");
+        assert_eq!(brand.data.0, b"\x07valence");
+    }
+
+    #[test]
+    fn death_screen_and_respawn_request() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+
+        let mut query = app
+            .world
+            .query::<(Option<&mut Client>, Option<&McEntity>)>();
+        let [(Some(mut client), _), (_, Some(zombie))] = query
+            .get_many_mut(&mut app.world, [client_ent, zombie_ent])
+            .unwrap()
+        else {
+            panic!("missing client or zombie");
+        };
+        client.kill(Some(zombie), "killed by a zombie");
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let death = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::CombatDeath(pkt) => Some(pkt),
+                _ => None,
+            })
+            .expect("missing combat death packet");
+
+        // The client's own entity ID is always 0.
+        assert_eq!(death.player_id, VarInt(0));
+        assert_eq!(death.entity_id, zombie_id);
+        assert_eq!(death.message.as_ref(), &Text::from("killed by a zombie"));
+
+        client_helper.send(&ClientCommand::PerformRespawn);
+
+        app.update();
+
+        let events = app.world.resource::<Events<PerformRespawn>>();
+        let sent: Vec<_> = events.get_reader().iter(events).collect();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].client, client_ent);
+    }
+
+    #[test]
+    fn keepalive_counter_ids_are_sequential() {
+        let mut app = App::new();
+        let plugin = ServerPlugin::new(()).with_keepalive_id_strategy(KeepaliveIdStrategy::Counter);
+        let (client_ent, mut client_helper) = scenario_single_client_with_plugin(&mut app, plugin);
+
+        let period = app.world.resource::<Server>().tps() * 10;
+        let mut ids = vec![];
+
+        for _ in 0..period * 3 {
+            app.update();
+
+            let sent_packets = client_helper.collect_sent().unwrap();
+            let id = sent_packets.iter().find_map(|p| match p {
+                S2cPlayPacket::KeepAliveS2c(p) => Some(p.id),
+                _ => None,
+            });
+
+            if let Some(id) = id {
+                ids.push(id);
+                // Respond so the client isn't disconnected.
+                client_helper.send(&KeepAliveC2s { id });
+            }
+        }
+
+        assert_eq!(ids, [1, 2, 3]);
+        assert!(app.world.get::<Client>(client_ent).is_some());
+    }
+
+    #[test]
+    fn no_keepalives_when_disabled() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        assert!(!app.world.resource::<Server>().keepalives());
+
+        let period = app.world.resource::<Server>().tps() * 10;
+
+        // Nothing responds to keepalives, so the client would be disconnected
+        // after the second period if they were sent.
+        for _ in 0..period * 3 {
+            app.update();
+        }
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::KeepAliveS2c(_));
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn set_single_slot() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        let stack = ItemStack::new(ItemKind::Diamond, 5, None);
+
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_slot(0, 7, 36, Some(stack.clone()));
+        client.set_slot(-1, 8, -1, None);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let slots: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::SetContainerSlot(p) => Some(p),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(slots.len(), 2);
+
+        assert_eq!(slots[0].window_id, 0);
+        assert_eq!(slots[0].state_id, VarInt(7));
+        assert_eq!(slots[0].slot_idx, 36);
+        assert_eq!(slots[0].slot_data, Some(stack));
+
+        assert_eq!(slots[1].window_id, -1);
+        assert_eq!(slots[1].state_id, VarInt(8));
+        assert_eq!(slots[1].slot_idx, -1);
+        assert_eq!(slots[1].slot_data, None);
+    }
+
+    #[test]
+    fn furnace_burn_time_property() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.update();
+        client_helper.clear_sent();
+
+        // Property 0 of a furnace is the remaining fuel time.
+        let mut client = app.world.get_mut::<Client>(client_ent).unwrap();
+        client.set_container_property(3, 0, 160);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetContainerProperty(_));
+
+        let mut buf = vec![];
+        SetContainerProperty {
+            window_id: 3,
+            property: 0,
+            value: 160,
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        // Packet ID, window ID, then the property and value as big-endian shorts.
+        assert_eq!(buf, [0x11, 3, 0, 0, 0, 160]);
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetContainerProperty(SetContainerProperty {
+                window_id: 3,
+                property: 0,
+                value: 160,
+            })
+        )));
+    }
 }
@@ -12,16 +12,21 @@ use valence_protocol::entity_meta::Pose;
 use valence_protocol::packets::c2s::play::{
     ClientCommand, PlayerAbilitiesC2s, ResourcePackC2s, SeenAdvancements,
 };
+use valence_protocol::packets::s2c::play::BlockUpdate;
 use valence_protocol::packets::C2sPlayPacket;
 use valence_protocol::types::{
     Action, ChatMode, ClickContainerMode, CommandBlockMode, Difficulty, DiggingStatus,
     DisplayedSkinParts, EntityInteraction, Hand, MainHand, RecipeBookId, StructureBlockAction,
     StructureBlockFlags, StructureBlockMirror, StructureBlockMode, StructureBlockRotation,
 };
-use valence_protocol::{BlockFace, BlockPos, Ident, ItemStack};
+use valence_protocol::{BlockFace, BlockPos, Ident, ItemStack, PacketLimits, VarInt};
 
 use crate::client::Client;
-use crate::entity::{EntityAnimation, EntityKind, McEntity, TrackedData};
+use crate::config::IllegalCharPolicy;
+use crate::entity::{EntityAnimation, EntityKind, McEntity, McEntityManager, TrackedData};
+use crate::instance::protection::BuildAction;
+use crate::instance::Instance;
+use crate::server::Server;
 
 #[derive(Clone, Debug)]
 pub struct QueryBlockEntity {
@@ -130,6 +135,7 @@ pub struct PluginMessage {
 
 #[derive(Clone, Debug)]
 pub struct EditBook {
+    pub client: Entity,
     pub slot: i32,
     pub entries: Vec<Box<str>>,
     pub title: Option<Box<str>>,
@@ -524,7 +530,10 @@ pub struct SwingArm {
 #[derive(Clone, Debug)]
 pub struct TeleportToEntity {
     pub client: Entity,
+    /// The UUID of the entity the spectator wants to teleport to.
     pub target: Uuid,
+    /// The [`McEntity`] with the UUID `target`, if there is one.
+    pub target_entity: Option<Entity>,
 }
 
 #[derive(Clone, Debug)]
@@ -684,6 +693,9 @@ pub(crate) fn event_loop_run_criteria(
     mut clients: Query<(Entity, &mut Client)>,
     mut clients_to_check: Local<Vec<Entity>>,
     mut events: ClientEvents,
+    entities: Res<McEntityManager>,
+    instances: Query<&Instance>,
+    server: Res<Server>,
 ) -> ShouldRun {
     if clients_to_check.is_empty() {
         // First run of the criteria. Prepare packets.
@@ -706,7 +718,15 @@ pub(crate) fn event_loop_run_criteria(
 
             client.dec.queue_bytes(bytes);
 
-            match handle_one_packet(client, entity, &mut events) {
+            match handle_one_packet(
+                client,
+                entity,
+                &mut events,
+                &entities,
+                &instances,
+                server.packet_limits(),
+                server.illegal_char_policy(),
+            ) {
                 Ok(had_packet) => {
                     if had_packet {
                         // We decoded one packet, but there might be more.
@@ -734,7 +754,15 @@ pub(crate) fn event_loop_run_criteria(
                 return false;
             };
 
-            match handle_one_packet(&mut client, entity, &mut events) {
+            match handle_one_packet(
+                &mut client,
+                entity,
+                &mut events,
+                &entities,
+                &instances,
+                server.packet_limits(),
+                server.illegal_char_policy(),
+            ) {
                 Ok(had_packet) => had_packet,
                 Err(e) => {
                     // TODO: validate packets in separate systems.
@@ -763,12 +791,18 @@ fn handle_one_packet(
     client: &mut Client,
     entity: Entity,
     events: &mut ClientEvents,
+    entities: &McEntityManager,
+    instances: &Query<&Instance>,
+    limits: &PacketLimits,
+    illegal_char_policy: IllegalCharPolicy,
 ) -> anyhow::Result<bool> {
     let Some(pkt) = client.dec.try_next_packet::<C2sPlayPacket>()? else {
         // No packets to decode.
         return Ok(false);
     };
 
+    limits.check(&pkt)?;
+
     match pkt {
         C2sPlayPacket::ConfirmTeleport(p) => {
             if client.pending_teleports == 0 {
@@ -808,14 +842,14 @@ fn handle_one_packet(
         C2sPlayPacket::ChatCommand(p) => {
             events.0.chat_command.send(ChatCommand {
                 client: entity,
-                command: p.command.into(),
+                command: check_chat_chars(illegal_char_policy, p.command)?,
                 timestamp: p.timestamp,
             });
         }
         C2sPlayPacket::ChatMessage(p) => {
             events.0.chat_message.send(ChatMessage {
                 client: entity,
-                message: p.message.into(),
+                message: check_chat_chars(illegal_char_policy, p.message)?,
                 timestamp: p.timestamp,
             });
         }
@@ -885,6 +919,7 @@ fn handle_one_packet(
         }
         C2sPlayPacket::EditBook(p) => {
             events.0.edit_book.send(EditBook {
+                client: entity,
                 slot: p.slot.0,
                 entries: p.entries.into_iter().map(Into::into).collect(),
                 title: p.title.map(Box::from),
@@ -952,7 +987,7 @@ fn handle_one_packet(
                 old_pitch: client.pitch,
                 pitch: client.pitch,
                 old_on_ground: client.on_ground,
-                on_ground: client.on_ground,
+                on_ground: p.on_ground,
             });
 
             client.position = p.position.into();
@@ -1137,6 +1172,14 @@ fn handle_one_packet(
                 client.block_change_sequence = cmp::max(p.sequence.0, client.block_change_sequence);
             }
 
+            if matches!(
+                p.status,
+                DiggingStatus::StartedDigging | DiggingStatus::FinishedDigging
+            ) && build_denied(client, instances, p.position, BuildAction::Break, &[])
+            {
+                return Ok(true);
+            }
+
             match p.status {
                 DiggingStatus::StartedDigging => events.2.start_digging.send(StartDigging {
                     client: entity,
@@ -1317,10 +1360,17 @@ fn handle_one_packet(
                 })
         }
         C2sPlayPacket::UpdateSign(p) => {
+            let [a, b, c, d] = p.lines;
+
             events.4.update_sign.send(UpdateSign {
                 client: entity,
                 position: p.position,
-                lines: p.lines.map(Into::into),
+                lines: [
+                    check_chat_chars(illegal_char_policy, a)?,
+                    check_chat_chars(illegal_char_policy, b)?,
+                    check_chat_chars(illegal_char_policy, c)?,
+                    check_chat_chars(illegal_char_policy, d)?,
+                ],
             });
         }
         C2sPlayPacket::SwingArm(p) => {
@@ -1333,6 +1383,7 @@ fn handle_one_packet(
             events.4.teleport_to_entity.send(TeleportToEntity {
                 client: entity,
                 target: p.target,
+                target_entity: entities.get_with_uuid(p.target),
             });
         }
         C2sPlayPacket::UseItemOn(p) => {
@@ -1340,6 +1391,18 @@ fn handle_one_packet(
                 client.block_change_sequence = cmp::max(p.sequence.0, client.block_change_sequence);
             }
 
+            let placed_pos = p.position.get_in_direction(p.face);
+
+            if build_denied(
+                client,
+                instances,
+                placed_pos,
+                BuildAction::Place,
+                &[p.position],
+            ) {
+                return Ok(true);
+            }
+
             events.4.use_item_on_block.send(UseItemOnBlock {
                 client: entity,
                 hand: p.hand,
@@ -1366,6 +1429,51 @@ fn handle_one_packet(
     Ok(true)
 }
 
+/// Applies `policy` to text sent by a client in chat or on a sign. An error is
+/// returned if the text is rejected.
+fn check_chat_chars(policy: IllegalCharPolicy, text: &str) -> anyhow::Result<Box<str>> {
+    if text.chars().all(IllegalCharPolicy::is_allowed_char) {
+        return Ok(text.into());
+    }
+
+    match policy {
+        IllegalCharPolicy::Reject => bail!("illegal characters in chat or sign text"),
+        IllegalCharPolicy::Sanitize => Ok(text
+            .chars()
+            .filter(|&c| IllegalCharPolicy::is_allowed_char(c))
+            .collect()),
+        IllegalCharPolicy::Allow => Ok(text.into()),
+    }
+}
+
+/// Checks the build permission of the instance `client` is in. If `action` at
+/// `pos` is denied, the blocks at `pos` and `also_resend` are sent to the
+/// client to undo its prediction of the change, and `true` is returned.
+fn build_denied(
+    client: &mut Client,
+    instances: &Query<&Instance>,
+    pos: BlockPos,
+    action: BuildAction,
+    also_resend: &[BlockPos],
+) -> bool {
+    let Ok(instance) = instances.get(client.instance()) else {
+        return false
+    };
+
+    if instance.allows_build(client, pos, action) {
+        return false;
+    }
+
+    for &position in [pos].iter().chain(also_resend) {
+        client.write_packet(&BlockUpdate {
+            position,
+            block_id: VarInt(instance.block_state(position).to_raw() as i32),
+        });
+    }
+
+    true
+}
+
 /// The default event handler system which handles client events in a
 /// reasonable default way.
 ///
@@ -1504,3 +1612,330 @@ pub fn default_event_handler(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{
+        ChatMessage as ChatMessageC2s, ConfirmTeleport, EditBook as EditBookC2s,
+        PlayerInput as PlayerInputC2s, SetPlayerPosition as SetPlayerPositionC2s,
+        SwingArm as SwingArmC2s, TeleportToEntity as TeleportToEntityC2s,
+        UpdateSign as UpdateSignC2s,
+    };
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::PlayerInputFlags;
+    use valence_protocol::{Decode, VarInt};
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::config::ServerPlugin;
+    use crate::entity::OnGround;
+    use crate::inventory::{Inventory, InventoryKind};
+    use crate::server::EventLoop;
+    use crate::unit_test::util::{
+        create_mock_client, gen_client_info, scenario_single_client,
+        scenario_single_client_with_plugin,
+    };
+
+    #[test]
+    fn update_sign_event() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        client_helper.send(&UpdateSignC2s {
+            position: BlockPos::new(1, 2, 3),
+            lines: ["first", "second", "", "fourth"],
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<UpdateSign>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].client, client_ent);
+        assert_eq!(sent[0].position, BlockPos::new(1, 2, 3));
+        assert_eq!(
+            sent[0].lines.iter().map(|l| &**l).collect::<Vec<_>>(),
+            ["first", "second", "", "fourth"]
+        );
+    }
+
+    fn chat_message(message: &str) -> ChatMessageC2s {
+        ChatMessageC2s {
+            message,
+            timestamp: 0,
+            salt: 0,
+            signature: None,
+            message_count: VarInt(0),
+            acknowledgement: &[0; 3],
+        }
+    }
+
+    #[test]
+    fn illegal_chat_characters_are_sanitized() {
+        let mut app = App::new();
+        let plugin = ServerPlugin::new(()).with_illegal_char_policy(IllegalCharPolicy::Sanitize);
+        let (client_ent, mut client_helper) = scenario_single_client_with_plugin(&mut app, plugin);
+
+        client_helper.send(&chat_message("§chello\nworld"));
+        client_helper.send(&UpdateSignC2s {
+            position: BlockPos::new(1, 2, 3),
+            lines: ["§lbold", "", "", ""],
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<ChatMessage>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].client, client_ent);
+        assert_eq!(&*sent[0].message, "chelloworld");
+
+        let events = app.world.resource::<Events<UpdateSign>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(&*sent[0].lines[0], "lbold");
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(!client.is_disconnected());
+    }
+
+    #[test]
+    fn illegal_chat_characters_are_rejected() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        client_helper.send(&chat_message("§chello\nworld"));
+
+        app.update();
+
+        let events = app.world.resource::<Events<ChatMessage>>();
+        assert!(events.is_empty());
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert!(client.is_disconnected());
+    }
+
+    #[test]
+    fn edit_book_event() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        client_helper.send(&EditBookC2s {
+            slot: VarInt(36),
+            entries: vec!["page one", "page two"],
+            title: Some("My Book"),
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<EditBook>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].client, client_ent);
+        assert_eq!(sent[0].slot, 36);
+        assert_eq!(
+            sent[0].entries.iter().map(|e| &**e).collect::<Vec<_>>(),
+            ["page one", "page two"]
+        );
+        assert_eq!(sent[0].title.as_deref(), Some("My Book"));
+    }
+
+    #[test]
+    fn resource_pack_status_event() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let cases = [
+            (0, ResourcePackStatus::Loaded),
+            (1, ResourcePackStatus::Declined),
+            (2, ResourcePackStatus::FailedDownload),
+            (3, ResourcePackStatus::Accepted),
+        ];
+
+        for (raw, status) in &cases {
+            let pkt = ResourcePackC2s::decode(&mut [*raw].as_slice()).unwrap();
+            assert_eq!(&ResourcePackStatus::from(pkt), status);
+
+            client_helper.send(&pkt);
+        }
+
+        app.update();
+
+        let events = app.world.resource::<Events<ResourcePackStatusChange>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), cases.len());
+
+        for (event, (_, status)) in sent.iter().zip(&cases) {
+            assert_eq!(event.client, client_ent);
+            assert_eq!(&event.status, status);
+        }
+    }
+
+    #[test]
+    fn teleport_to_entity_event_resolves_target() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let uuid = Uuid::from_u128(0x1234);
+        let target_ent = app
+            .world
+            .spawn(McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid))
+            .id();
+
+        // Registers the entity with the entity manager.
+        app.update();
+
+        client_helper.send(&TeleportToEntityC2s { target: uuid });
+        client_helper.send(&TeleportToEntityC2s {
+            target: Uuid::from_u128(0x5678),
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<TeleportToEntity>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].client, client_ent);
+        assert_eq!(sent[0].target, uuid);
+        assert_eq!(sent[0].target_entity, Some(target_ent));
+        assert_eq!(sent[1].target_entity, None);
+    }
+
+    #[test]
+    fn player_input_event_with_unmount() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        client_helper.send(&PlayerInputC2s {
+            sideways: 0.5,
+            forward: -1.0,
+            flags: PlayerInputFlags::new().with_unmount(true),
+        });
+
+        app.update();
+
+        let events = app.world.resource::<Events<PlayerInput>>();
+        let sent: Vec<_> = events.get_reader().iter(events).cloned().collect();
+
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].client, client_ent);
+        assert_eq!(sent[0].sideways, 0.5);
+        assert_eq!(sent[0].forward, -1.0);
+        assert!(!sent[0].jump);
+        assert!(sent[0].unmount);
+    }
+
+    #[test]
+    fn swing_arm_animation_sent_to_viewers_only() {
+        let mut app = App::new();
+        let (swinger_ent, mut swinger_helper) = scenario_single_client(&mut app);
+        app.add_system_to_stage(EventLoop, default_event_handler);
+
+        let swinger = app.world.get::<Client>(swinger_ent).unwrap();
+        let instance_ent = swinger.instance();
+        let swinger_entity = McEntity::with_uuid(EntityKind::Player, instance_ent, swinger.uuid());
+        app.world.entity_mut(swinger_ent).insert(swinger_entity);
+
+        let (mut viewer, mut viewer_helper) = create_mock_client(gen_client_info("viewer"));
+        viewer.set_instance(instance_ent);
+        app.world.spawn((
+            viewer,
+            Inventory::new(InventoryKind::Player),
+            OnGround::default(),
+        ));
+
+        app.update();
+        swinger_helper.clear_sent();
+        viewer_helper.clear_sent();
+
+        swinger_helper.send(&SwingArmC2s { hand: Hand::Main });
+
+        app.update();
+
+        let sent_packets = viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntityAnimationS2c(_));
+
+        let sent_packets = swinger_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::EntityAnimationS2c(_));
+    }
+
+    #[test]
+    fn on_ground_updated_from_movement() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Sends the initial teleport to the client.
+        app.update();
+
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(0),
+        });
+        client_helper.send(&SetPlayerPositionC2s {
+            position: [1.0, 64.0, 1.0],
+            on_ground: true,
+        });
+
+        app.update();
+
+        assert_eq!(app.world.get::<OnGround>(client_ent), Some(&OnGround(true)));
+
+        client_helper.send(&SetPlayerPositionC2s {
+            position: [1.0, 65.0, 1.0],
+            on_ground: false,
+        });
+
+        app.update();
+
+        assert_eq!(
+            app.world.get::<OnGround>(client_ent),
+            Some(&OnGround(false))
+        );
+    }
+
+    #[test]
+    fn movement_ignored_until_teleport_confirmed() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([0.0, 64.0, 0.0]);
+
+        // Sends the teleport to the client.
+        app.update();
+
+        // Position updates sent before the teleport is confirmed are stale.
+        client_helper.send(&SetPlayerPositionC2s {
+            position: [100.0, 64.0, 100.0],
+            on_ground: true,
+        });
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(0.0, 64.0, 0.0));
+
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(0),
+        });
+        client_helper.send(&SetPlayerPositionC2s {
+            position: [1.0, 64.0, 1.0],
+            on_ground: true,
+        });
+
+        app.update();
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(1.0, 64.0, 1.0));
+    }
+}
@@ -1,13 +1,16 @@
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::Path;
 use std::sync::Arc;
 
+use anyhow::{ensure, Context};
 use async_trait::async_trait;
+use base64::prelude::*;
 use bevy_app::{App, Plugin};
 use serde::Serialize;
 use tokio::runtime::Handle;
 use tracing::error;
 use uuid::Uuid;
-use valence_protocol::{Text, Username};
+use valence_protocol::{PacketLimits, Text, Username};
 
 use crate::biome::Biome;
 use crate::dimension::Dimension;
@@ -26,6 +29,9 @@ pub struct ServerPlugin<A> {
     pub tokio_handle: Option<Handle>,
     /// The maximum number of simultaneous connections allowed to the server.
     /// This includes all connections, not just those past the login stage.
+    /// Clients connecting past this limit are disconnected with a "server full"
+    /// message. If too many clients are connecting past the limit at once,
+    /// their connections are closed without a message.
     ///
     /// You will want this value to be somewhere above the maximum number of
     /// players, since status pings should still succeed even when the server is
@@ -101,6 +107,44 @@ pub struct ServerPlugin<A> {
     /// An unspecified value is used that should be adequate for most
     /// situations. This default may change in future versions.
     pub outgoing_capacity: usize,
+    /// Limits on the contents of packets sent by clients. Clients sending
+    /// packets which exceed these limits are disconnected.
+    ///
+    /// # Default Value
+    ///
+    /// `PacketLimits::default()`, which matches the vanilla server.
+    pub packet_limits: PacketLimits,
+    /// The number of ticks an entity may move using relative movement packets
+    /// before its absolute position is sent to clients again. Relative moves
+    /// lose precision, so periodic teleports correct drift that accumulates
+    /// over time. `None` disables periodic teleports.
+    ///
+    /// # Default Value
+    ///
+    /// `Some(60)`
+    pub entity_teleport_interval: Option<u32>,
+    /// If keepalive packets are sent to clients, and clients which stop
+    /// responding to them are disconnected. Disabling keepalives is mostly
+    /// useful for tests which run many ticks.
+    ///
+    /// # Default Value
+    ///
+    /// `true`
+    pub keepalives: bool,
+    /// How the IDs of keepalive packets sent to clients are chosen.
+    ///
+    /// # Default Value
+    ///
+    /// [`KeepaliveIdStrategy::Random`], which matches the vanilla server.
+    pub keepalive_id_strategy: KeepaliveIdStrategy,
+    /// How chat messages, commands, and sign text sent by clients containing
+    /// characters which are not allowed in chat are handled.
+    ///
+    /// # Default Value
+    ///
+    /// [`IllegalCharPolicy::Reject`], which matches the vanilla server's
+    /// handling of chat.
+    pub illegal_char_policy: IllegalCharPolicy,
     /// The list of [`Dimension`]s usable on the server.
     ///
     /// The dimensions returned by [`ServerPlugin::dimensions`] will be in the
@@ -148,6 +192,11 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
             compression_threshold: Some(256),
             incoming_capacity: 2097152, // 2 MiB
             outgoing_capacity: 8388608, // 8 MiB
+            packet_limits: PacketLimits::default(),
+            entity_teleport_interval: Some(60),
+            keepalives: true,
+            keepalive_id_strategy: KeepaliveIdStrategy::Random,
+            illegal_char_policy: IllegalCharPolicy::Reject,
             dimensions: [Dimension::default()].as_slice().into(),
             biomes: [Biome::default()].as_slice().into(),
         }
@@ -209,6 +258,44 @@ impl<A: AsyncCallbacks> ServerPlugin<A> {
         self
     }
 
+    /// See [`Self::packet_limits`].
+    #[must_use]
+    pub fn with_packet_limits(mut self, packet_limits: PacketLimits) -> Self {
+        self.packet_limits = packet_limits;
+        self
+    }
+
+    /// See [`Self::entity_teleport_interval`].
+    #[must_use]
+    pub fn with_entity_teleport_interval(mut self, entity_teleport_interval: Option<u32>) -> Self {
+        self.entity_teleport_interval = entity_teleport_interval;
+        self
+    }
+
+    /// See [`Self::keepalives`].
+    #[must_use]
+    pub fn with_keepalives(mut self, keepalives: bool) -> Self {
+        self.keepalives = keepalives;
+        self
+    }
+
+    /// See [`Self::keepalive_id_strategy`].
+    #[must_use]
+    pub fn with_keepalive_id_strategy(
+        mut self,
+        keepalive_id_strategy: KeepaliveIdStrategy,
+    ) -> Self {
+        self.keepalive_id_strategy = keepalive_id_strategy;
+        self
+    }
+
+    /// See [`Self::illegal_char_policy`].
+    #[must_use]
+    pub fn with_illegal_char_policy(mut self, illegal_char_policy: IllegalCharPolicy) -> Self {
+        self.illegal_char_policy = illegal_char_policy;
+        self
+    }
+
     /// See [`Self::dimensions`].
     #[must_use]
     pub fn with_dimensions(mut self, dimensions: impl Into<Arc<[Dimension]>>) -> Self {
@@ -325,6 +412,105 @@ pub trait AsyncCallbacks: Send + Sync + 'static {
 /// The default async callbacks.
 impl AsyncCallbacks for () {}
 
+/// Async callbacks which respond to every Server List Ping with the same
+/// preconfigured information. All other callbacks use their default
+/// implementations.
+///
+/// ```
+/// use valence::config::{ServerPlugin, StaticServerListPing};
+///
+/// let plugin = ServerPlugin::new(StaticServerListPing {
+///     max_players: 100,
+///     description: "My Server".into(),
+///     ..Default::default()
+/// });
+/// ```
+#[derive(Clone, Debug)]
+pub struct StaticServerListPing {
+    /// Displayed as the number of players on the server.
+    pub online_players: i32,
+    /// Displayed as the maximum number of players allowed on the server at a
+    /// time.
+    pub max_players: i32,
+    /// The list of players visible by hovering over the player count.
+    pub player_sample: Vec<PlayerSampleEntry>,
+    /// A description of the server.
+    pub description: Text,
+    /// The server's icon as the bytes of a 64x64 PNG image. No icon is used if
+    /// this is empty.
+    pub favicon_png: Vec<u8>,
+}
+
+impl Default for StaticServerListPing {
+    fn default() -> Self {
+        Self {
+            online_players: 0,
+            max_players: -1,
+            player_sample: vec![],
+            description: "A Valence Server".into(),
+            favicon_png: vec![],
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncCallbacks for StaticServerListPing {
+    async fn server_list_ping(
+        &self,
+        _shared: &SharedServer,
+        _remote_addr: SocketAddr,
+        _protocol_version: i32,
+    ) -> ServerListPing {
+        ServerListPing::Respond {
+            online_players: self.online_players,
+            max_players: self.max_players,
+            player_sample: self.player_sample.clone(),
+            description: self.description.clone(),
+            favicon_png: &self.favicon_png,
+        }
+    }
+}
+
+/// Reads the PNG image at `path` and returns it as the `data:image/png;base64`
+/// URL used for the server icon in the status response.
+///
+/// An error is returned if the file is not a PNG image or if the image is not
+/// 64x64 pixels.
+pub fn load_favicon(path: impl AsRef<Path>) -> anyhow::Result<String> {
+    let path = path.as_ref();
+
+    let png = std::fs::read(path)
+        .with_context(|| format!("failed to read favicon at {}", path.display()))?;
+
+    let (width, height) = png_dimensions(&png).context("favicon is not a PNG image")?;
+
+    ensure!(
+        width == 64 && height == 64,
+        "favicon must be 64x64 pixels (got {width}x{height})"
+    );
+
+    let mut url = "data:image/png;base64,".to_owned();
+    BASE64_STANDARD.encode_string(png, &mut url);
+
+    Ok(url)
+}
+
+/// Reads the width and height from the `IHDR` chunk of a PNG image, which is
+/// always the first chunk after the signature.
+fn png_dimensions(png: &[u8]) -> Option<(u32, u32)> {
+    const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+    let rest = png.strip_prefix(SIGNATURE)?;
+
+    // Skip the length of the chunk.
+    let ihdr = rest.get(4..)?.strip_prefix(b"IHDR")?;
+
+    let width = u32::from_be_bytes(ihdr.get(0..4)?.try_into().ok()?);
+    let height = u32::from_be_bytes(ihdr.get(4..8)?.try_into().ok()?);
+
+    Some((width, height))
+}
+
 /// Describes how new connections to the server are handled.
 #[derive(Clone, PartialEq)]
 #[non_exhaustive]
@@ -391,6 +577,39 @@ pub enum ConnectionMode {
     },
 }
 
+/// Determines the IDs of keepalive packets sent to clients. Clients must
+/// respond with the ID of the most recent keepalive regardless of the strategy
+/// used.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum KeepaliveIdStrategy {
+    /// Every keepalive uses a random ID.
+    Random,
+    /// Keepalive IDs count up from `1` for each client. This can make
+    /// debugging easier.
+    Counter,
+}
+
+/// Determines what happens when a client sends a chat message, command, or sign
+/// text containing characters which are not allowed in chat. These are the
+/// section sign (`§`), control characters such as newlines, and the delete
+/// character.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum IllegalCharPolicy {
+    /// The client is disconnected and no event is sent.
+    Reject,
+    /// The illegal characters are removed before the event is sent.
+    Sanitize,
+    /// The text is passed along unchanged.
+    Allow,
+}
+
+impl IllegalCharPolicy {
+    /// Returns `true` if `c` is allowed in chat by the vanilla server.
+    pub fn is_allowed_char(c: char) -> bool {
+        c != '§' && c >= ' ' && c != '\u{7f}'
+    }
+}
+
 /// Minecraft's standard ticks per second (TPS).
 pub const DEFAULT_TPS: i64 = 20;
 
@@ -434,3 +653,22 @@ pub struct PlayerSampleEntry {
     /// The player UUID.
     pub id: Uuid,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_favicon_from_png() {
+        let assets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets");
+
+        let favicon = load_favicon(assets.join("logo-64x64.png")).unwrap();
+        assert!(favicon.starts_with("data:image/png;base64,iVBORw0KGgo"));
+
+        let err = load_favicon(assets.join("logo-256x256.png")).unwrap_err();
+        assert!(err.to_string().contains("256x256"), "{err}");
+
+        assert!(load_favicon(assets.join("logo.svg")).is_err());
+        assert!(load_favicon(assets.join("missing.png")).is_err());
+    }
+}
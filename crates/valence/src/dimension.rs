@@ -1,6 +1,7 @@
 //! Dimension configuration and identification.
 
 use anyhow::ensure;
+use glam::DVec3;
 use valence_nbt::{compound, Compound};
 use valence_protocol::ident;
 use valence_protocol::ident::Ident;
@@ -74,6 +75,11 @@ pub struct Dimension {
     /// * `0 <= height <= 4064`
     /// * `min_y + height <= 2032`
     pub height: i32,
+    /// The multiplier applied to horizontal coordinates when moving between
+    /// dimensions. The Overworld uses `1.0` and the Nether uses `8.0`.
+    ///
+    /// Must be between `0.00001` and `30000000.0`.
+    pub coordinate_scale: f64,
     // TODO: add other fields.
     //       * infiniburn
     //       * monster_spawn_light_level
@@ -83,7 +89,6 @@ pub struct Dimension {
     //       * bed_works
     //       * has_raids
     //       * logical_height
-    //       * coordinate_scale
     //       * ultrawarm
     //       * has_ceiling
 }
@@ -109,7 +114,7 @@ impl Dimension {
             "min_y" => self.min_y,
             "height" => self.height,
             "logical_height" => self.height,
-            "coordinate_scale" => 1.0,
+            "coordinate_scale" => self.coordinate_scale,
             "ultrawarm" => false,
             "has_ceiling" => false,
         };
@@ -120,6 +125,27 @@ impl Dimension {
 
         item
     }
+
+    /// Converts a position in a dimension of this type to the corresponding
+    /// position in a dimension of type `target`.
+    ///
+    /// The X and Z coordinates are scaled by the ratio of the two coordinate
+    /// scales and clamped to the bounds of the world border. The Y coordinate
+    /// is left unchanged.
+    pub fn scale_position(&self, target: &Dimension, pos: impl Into<DVec3>) -> DVec3 {
+        /// The maximum horizontal distance from the origin an entity may be
+        /// teleported to.
+        const MAX_HORIZONTAL: f64 = 29_999_984.0;
+
+        let pos = pos.into();
+        let ratio = self.coordinate_scale / target.coordinate_scale;
+
+        DVec3::new(
+            (pos.x * ratio).clamp(-MAX_HORIZONTAL, MAX_HORIZONTAL),
+            pos.y,
+            (pos.z * ratio).clamp(-MAX_HORIZONTAL, MAX_HORIZONTAL),
+        )
+    }
 }
 
 pub(crate) fn validate_dimensions(dimensions: &[Dimension]) -> anyhow::Result<()> {
@@ -151,6 +177,11 @@ pub(crate) fn validate_dimensions(dimensions: &[Dimension]) -> anyhow::Result<()
             "ambient_light is out of range in dimension #{i}",
         );
 
+        ensure!(
+            (0.00001..=30_000_000.0).contains(&dim.coordinate_scale),
+            "coordinate_scale is out of range in dimension #{i}",
+        );
+
         if let Some(fixed_time) = dim.fixed_time {
             ensure!(
                 (0..=24_000).contains(&fixed_time),
@@ -171,6 +202,7 @@ impl Default for Dimension {
             effects: DimensionEffects::default(),
             min_y: -64,
             height: 384,
+            coordinate_scale: 1.0,
         }
     }
 }
@@ -183,3 +215,28 @@ pub enum DimensionEffects {
     TheNether,
     TheEnd,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_position_between_overworld_and_nether() {
+        let overworld = Dimension::default();
+        let nether = Dimension {
+            effects: DimensionEffects::TheNether,
+            coordinate_scale: 8.0,
+            ..Default::default()
+        };
+
+        let in_overworld = DVec3::new(800.0, 70.0, -164.0);
+        let in_nether = overworld.scale_position(&nether, in_overworld);
+
+        assert_eq!(in_nether, DVec3::new(100.0, 70.0, -20.5));
+        assert_eq!(nether.scale_position(&overworld, in_nether), in_overworld);
+
+        // Positions are clamped to the world border.
+        let far = nether.scale_position(&overworld, [10_000_000.0, 0.0, 0.0]);
+        assert_eq!(far.x, 29_999_984.0);
+    }
+}
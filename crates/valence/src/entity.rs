@@ -1,25 +1,34 @@
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt;
 use std::fmt::Formatter;
 use std::ops::Range;
 
+use anyhow::{bail, ensure, Context};
 use bevy_ecs::prelude::*;
 pub use data::{EntityKind, TrackedData};
 use glam::{DVec3, UVec3, Vec3};
 use rustc_hash::FxHashMap;
 use tracing::warn;
 use uuid::Uuid;
+use valence_nbt::{compound, Compound, List, Value};
 use valence_protocol::entity_meta::{Facing, PaintingKind, Pose};
+use valence_protocol::packets::s2c::particle::Particle;
 use valence_protocol::packets::s2c::play::{
-    EntityAnimationS2c, EntityEvent as EntityEventS2c, SetEntityMetadata, SetEntityVelocity,
-    SetHeadRotation, SpawnEntity, SpawnExperienceOrb, SpawnPlayer, TeleportEntity,
-    UpdateEntityPosition, UpdateEntityPositionAndRotation, UpdateEntityRotation,
+    EntityAnimationS2c, EntityEvent as EntityEventS2c, LinkEntities, SetEntityMetadata,
+    SetEntityVelocity, SetHeadRotation, SetPassengers, SoundId, SpawnEntity, SpawnExperienceOrb,
+    SpawnPlayer, TeleportEntity, UpdateEntityPosition, UpdateEntityPositionAndRotation,
+    UpdateEntityRotation,
 };
+use valence_protocol::types::SoundCategory;
 use valence_protocol::{ByteAngle, RawBytes, VarInt};
 
+use crate::client::event::{MovePlayer, MoveVehicle};
 use crate::config::DEFAULT_TPS;
+use crate::instance::Instance;
 use crate::math::Aabb;
 use crate::packet::WritePacket;
+use crate::server::Server;
+use crate::view::ChunkPos;
 use crate::{Despawned, NULL_ENTITY};
 
 pub mod data;
@@ -31,6 +40,10 @@ include!(concat!(env!("OUT_DIR"), "/entity_event.rs"));
 #[derive(Resource)]
 pub struct McEntityManager {
     protocol_id_to_entity: FxHashMap<i32, Entity>,
+    uuid_to_entity: FxHashMap<Uuid, Entity>,
+    /// The protocol ID and UUID of every registered entity, for entities
+    /// whose [`McEntity`] component is no longer available.
+    entity_to_ids: FxHashMap<Entity, (i32, Uuid)>,
     next_protocol_id: i32,
 }
 
@@ -38,14 +51,39 @@ impl McEntityManager {
     pub(crate) fn new() -> Self {
         Self {
             protocol_id_to_entity: HashMap::default(),
+            uuid_to_entity: HashMap::default(),
+            entity_to_ids: HashMap::default(),
             next_protocol_id: 1,
         }
     }
 
+    /// Forgets an entity. Returns the protocol ID the entity had, if it was
+    /// known.
+    pub(crate) fn remove_entity(&mut self, entity: Entity) -> Option<i32> {
+        let (protocol_id, uuid) = self.entity_to_ids.remove(&entity)?;
+
+        self.protocol_id_to_entity.remove(&protocol_id);
+
+        // Another entity with the same UUID may have replaced this one.
+        if self.uuid_to_entity.get(&uuid) == Some(&entity) {
+            self.uuid_to_entity.remove(&uuid);
+        }
+
+        Some(protocol_id)
+    }
+
     /// Gets the [`Entity`] of the [`McEntity`] with the given protocol ID.
     pub fn get_with_protocol_id(&self, id: i32) -> Option<Entity> {
         self.protocol_id_to_entity.get(&id).cloned()
     }
+
+    /// Gets the [`Entity`] of the [`McEntity`] with the given UUID.
+    ///
+    /// Like protocol IDs, entities are not registered until the end of the
+    /// tick they were spawned in.
+    pub fn get_with_uuid(&self, uuid: Uuid) -> Option<Entity> {
+        self.uuid_to_entity.get(&uuid).cloned()
+    }
 }
 
 /// Sets the protocol ID of new entities.
@@ -66,21 +104,82 @@ pub(crate) fn init_entities(
         manager
             .protocol_id_to_entity
             .insert(mc_entity.protocol_id, entity);
+
+        manager
+            .entity_to_ids
+            .insert(entity, (mc_entity.protocol_id, mc_entity.uuid));
+
+        if manager
+            .uuid_to_entity
+            .insert(mc_entity.uuid, entity)
+            .is_some()
+        {
+            warn!(uuid = %mc_entity.uuid, "multiple entities share the same UUID");
+        }
     }
 }
 
 /// Removes despawned entities from the entity manager.
 pub(crate) fn deinit_despawned_entities(
-    entities: Query<&mut McEntity, With<Despawned>>,
+    entities: Query<Entity, (With<McEntity>, With<Despawned>)>,
     mut manager: ResMut<McEntityManager>,
 ) {
     for entity in &entities {
-        manager.protocol_id_to_entity.remove(&entity.protocol_id);
+        manager.remove_entity(entity);
     }
 }
 
-pub(crate) fn update_entities(mut entities: Query<&mut McEntity, Changed<McEntity>>) {
+/// Detaches live entities from vehicles and leash holders which are being
+/// despawned this tick. The despawned vehicles themselves dismount their
+/// passengers in [`update_instances_pre_client`].
+///
+/// [`update_instances_pre_client`]: crate::instance::update_instances_pre_client
+pub(crate) fn detach_from_despawned_entities(
+    despawned: Query<&McEntity, With<Despawned>>,
+    mut entities: Query<&mut McEntity, Without<Despawned>>,
+) {
+    let despawned: BTreeSet<_> = despawned.iter().map(|e| e.protocol_id).collect();
+
+    if despawned.is_empty() {
+        return;
+    }
+
     for mut entity in &mut entities {
+        if entity.passengers.iter().any(|id| despawned.contains(id)) {
+            let passengers: Vec<_> = entity
+                .passengers
+                .iter()
+                .copied()
+                .filter(|id| !despawned.contains(id))
+                .collect();
+
+            entity.set_passengers(passengers);
+        }
+
+        if entity
+            .leash_holder
+            .map_or(false, |id| despawned.contains(&id))
+        {
+            entity.set_leash_holder(None);
+        }
+    }
+}
+
+pub(crate) fn update_entities(
+    server: Res<Server>,
+    mut entities: Query<&mut McEntity, Changed<McEntity>>,
+) {
+    for mut entity in &mut entities {
+        if entity.needs_teleport() {
+            entity.ticks_since_teleport = 0;
+        } else if entity.position != entity.old_position {
+            entity.ticks_since_teleport = entity.ticks_since_teleport.saturating_add(1);
+        }
+
+        entity.teleport_due = server.entity_teleport_interval().map_or(false, |interval| {
+            entity.ticks_since_teleport.saturating_add(1) >= interval
+        });
+
         entity.data.clear_modifications();
         entity.old_position = entity.position;
         entity.old_instance = entity.instance;
@@ -89,25 +188,372 @@ pub(crate) fn update_entities(mut entities: Query<&mut McEntity, Changed<McEntit
         entity.yaw_or_pitch_modified = false;
         entity.head_yaw_modified = false;
         entity.velocity_modified = false;
+        entity.passengers_modified = false;
+        entity.leash_holder_modified = false;
+        entity.force_teleport = false;
     }
 }
 
-pub(crate) fn check_entity_invariants(removed: RemovedComponents<McEntity>) {
-    for entity in &removed {
-        warn!(
-            entity = ?entity,
-            "A `McEntity` component was removed from the world directly. You must use the \
-             `Despawned` marker component instead."
-        );
+/// Checks that the `data` field of a spawn entity packet is sensible for the
+/// given entity kind.
+///
+/// Most entity kinds ignore the data and expect it to be zero. Falling blocks
+/// use it as their block state, which must not be air. Item frames and
+/// paintings use it as the ID of the direction they face, from 0 to 5.
+pub fn validate_spawn_data(kind: EntityKind, data: i32) -> anyhow::Result<()> {
+    match kind {
+        EntityKind::FallingBlock => {
+            ensure!(data != 0, "falling block spawned with the air block state")
+        }
+        EntityKind::ItemFrame | EntityKind::GlowItemFrame | EntityKind::Painting => ensure!(
+            (0..6).contains(&data),
+            "invalid direction of {data} for {kind:?}"
+        ),
+        EntityKind::Warden => ensure!(
+            data == 0 || data == 1,
+            "invalid emerging flag of {data} for warden"
+        ),
+        // These use the data as the protocol ID of their owner.
+        EntityKind::FishingBobber => {}
+        _ if is_projectile(kind) => {}
+        _ => ensure!(
+            data == 0,
+            "{kind:?} does not use spawn data, but {data} was given"
+        ),
+    }
+
+    Ok(())
+}
+
+/// Returns the direction an item frame with the given `rotation` tracked data
+/// faces. The protocol only allows the six directions as the spawn data of item
+/// frames, so any other rotation is treated as [`Facing::Up`].
+fn item_frame_facing(rotation: i32) -> Facing {
+    match rotation {
+        0 => Facing::Down,
+        2 => Facing::North,
+        3 => Facing::South,
+        4 => Facing::West,
+        5 => Facing::East,
+        _ => Facing::Up,
+    }
+}
+
+/// Returns `true` if `kind` is a projectile which uses its spawn data as the
+/// protocol ID of the entity which shot it.
+pub(crate) fn is_projectile(kind: EntityKind) -> bool {
+    matches!(
+        kind,
+        EntityKind::Arrow
+            | EntityKind::SpectralArrow
+            | EntityKind::Trident
+            | EntityKind::Fireball
+            | EntityKind::SmallFireball
+            | EntityKind::DragonFireball
+            | EntityKind::WitherSkull
+            | EntityKind::LlamaSpit
+            | EntityKind::ShulkerBullet
+            | EntityKind::Snowball
+            | EntityKind::Egg
+            | EntityKind::EnderPearl
+            | EntityKind::Potion
+            | EntityKind::ExperienceBottle
+            | EntityKind::FireworkRocket
+    )
+}
+
+/// Plays a particle and sound at the position of a [`McEntity`] when it is
+/// spawned. Insert this component together with the [`McEntity`]; inserting
+/// it later has no effect.
+///
+/// The effects are only sent to clients which have the entity's chunk loaded,
+/// and respect the particle and sound switches of the [`Instance`].
+#[derive(Component, Clone, Debug)]
+pub struct SpawnEffect {
+    /// The particle to play, if any.
+    pub particle: Option<Particle>,
+    /// The number of particles to play.
+    pub particle_count: i32,
+    /// The sound to play, if any.
+    pub sound: Option<SoundId<'static>>,
+    pub sound_category: SoundCategory,
+    pub volume: f32,
+    pub pitch: f32,
+}
+
+pub(crate) fn play_spawn_effects(
+    entities: Query<(&McEntity, &SpawnEffect), Added<McEntity>>,
+    mut instances: Query<&mut Instance>,
+) {
+    for (entity, effect) in &entities {
+        let Ok(mut instance) = instances.get_mut(entity.instance()) else {
+            continue
+        };
+
+        if let Some(particle) = &effect.particle {
+            instance.play_particle(
+                particle,
+                false,
+                entity.position(),
+                Vec3::ZERO,
+                0.0,
+                effect.particle_count,
+            );
+        }
+
+        if let Some(sound) = effect.sound {
+            instance.play_sound(
+                sound,
+                effect.sound_category,
+                entity.position(),
+                effect.volume,
+                effect.pitch,
+            );
+        }
+    }
+}
+
+/// A resource controlling how often entity movement is sent to clients which
+/// are far away from the entity. Clients receive the position and rotation of
+/// an entity less often the farther they are from it, which saves bandwidth
+/// when many entities are in view.
+///
+/// Reduced rate updates are sent as absolute teleports, so skipped updates do
+/// not leave the entity at the wrong position. Other entity updates such as
+/// metadata and velocity are still sent every tick.
+#[derive(Resource, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct EntityLodSettings {
+    /// If the movement update rate is reduced for far entities. This is
+    /// `false` by default.
+    pub enabled: bool,
+    /// Clients within this many chunks of an entity receive every movement
+    /// update. This is `2` by default.
+    pub full_rate_distance: u8,
+    /// Beyond [`Self::full_rate_distance`], the number of ticks between
+    /// movement updates doubles every `band_width` chunks, up to once every
+    /// eight ticks. This is `2` by default.
+    pub band_width: u8,
+}
+
+impl Default for EntityLodSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            full_rate_distance: 2,
+            band_width: 2,
+        }
+    }
+}
+
+/// The number of reduced rate bands. Clients in band `n` receive movement
+/// updates every `2^n` ticks, where band zero receives every update.
+pub(crate) const LOD_BAND_COUNT: usize = 4;
+
+impl EntityLodSettings {
+    /// Returns the number of ticks between the movement updates sent to a
+    /// client which is `distance` chunks away from an entity.
+    pub fn update_interval(&self, distance: u32) -> u32 {
+        1 << self.band(distance)
+    }
+
+    /// Returns the band of a client in chunk `viewer` for entities in the
+    /// chunk at `pos`.
+    pub(crate) fn band_at(&self, viewer: ChunkPos, pos: ChunkPos) -> usize {
+        self.band((viewer.distance_squared(pos) as f64).sqrt() as u32)
+    }
+
+    fn band(&self, distance: u32) -> usize {
+        let full_rate_distance = self.full_rate_distance as u32;
+
+        if !self.enabled || distance <= full_rate_distance {
+            return 0;
+        }
+
+        let beyond = distance - full_rate_distance - 1;
+        let band = 1 + beyond / self.band_width.max(1) as u32;
+
+        (band as usize).min(LOD_BAND_COUNT - 1)
+    }
+}
+
+/// Sets a [`McEntity`] on fire for a number of ticks.
+///
+/// The on fire flag in the entity's tracked data is set while this component
+/// is present. Once `ticks_remaining` reaches zero, the component is removed
+/// and the flag is cleared. Removing the component early also clears the flag.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct OnFire {
+    pub ticks_remaining: u32,
+}
+
+pub(crate) fn update_on_fire(
+    mut commands: Commands,
+    mut entities: Query<(Entity, &mut McEntity, &mut OnFire)>,
+) {
+    for (id, mut entity, mut on_fire) in &mut entities {
+        if on_fire.ticks_remaining > 0 {
+            on_fire.ticks_remaining -= 1;
+
+            if !entity.data().get_on_fire() {
+                entity.data_mut().set_on_fire(true);
+            }
+        } else {
+            commands.entity(id).remove::<OnFire>();
+        }
+    }
+}
+
+pub(crate) fn clear_removed_on_fire(
+    removed: RemovedComponents<OnFire>,
+    mut entities: Query<&mut McEntity, Without<OnFire>>,
+) {
+    for id in &removed {
+        if let Ok(mut entity) = entities.get_mut(id) {
+            entity.data_mut().set_on_fire(false);
+        }
+    }
+}
+
+/// The position of a [`McEntity`] in its instance.
+///
+/// This component is optional. If present, it is copied to the entity's
+/// [`McEntity::set_position`] whenever it changes, which allows systems to use
+/// change detection on entity movement.
+#[derive(Component, Clone, Copy, PartialEq, Default, Debug)]
+pub struct Position(pub DVec3);
+
+impl Position {
+    /// Returns the position of the chunk containing this position.
+    pub fn chunk_pos(&self) -> ChunkPos {
+        ChunkPos::from_dvec3(self.0)
+    }
+}
+
+/// The velocity of a [`McEntity`] in meters per second.
+///
+/// This component is optional. If present, it is copied to the entity's
+/// [`McEntity::set_velocity`] whenever it changes.
+#[derive(Component, Clone, Copy, PartialEq, Default, Debug)]
+pub struct Velocity(pub Vec3);
+
+/// The yaw and pitch of a [`McEntity`] in degrees.
+///
+/// This component is optional. If present, it is copied to the entity's
+/// [`McEntity::set_yaw`] and [`McEntity::set_pitch`] whenever it changes.
+#[derive(Component, Clone, Copy, PartialEq, Default, Debug)]
+pub struct Look {
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+/// Whether an entity is standing on the ground.
+///
+/// This is updated for clients from the movement packets they send. If a
+/// [`McEntity`] has this component, it is copied to
+/// [`McEntity::set_on_ground`] whenever it changes.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct OnGround(pub bool);
+
+pub(crate) fn update_on_ground(
+    mut move_player: EventReader<MovePlayer>,
+    mut entities: Query<&mut OnGround>,
+) {
+    for MovePlayer {
+        client, on_ground, ..
+    } in move_player.iter()
+    {
+        if let Ok(mut og) = entities.get_mut(*client) {
+            if og.0 != *on_ground {
+                og.0 = *on_ground;
+            }
+        }
+    }
+}
+
+/// The vehicle entity a client is controlling.
+///
+/// When present on a client entity, the [`MoveVehicle`] events sent by the
+/// client are applied to the [`Position`] and [`Look`] of the vehicle. This is
+/// not set automatically; servers should insert it when a client starts
+/// riding an entity it is allowed to steer.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ControlledVehicle(pub Entity);
+
+pub(crate) fn update_controlled_vehicles(
+    mut move_vehicle: EventReader<MoveVehicle>,
+    clients: Query<&ControlledVehicle>,
+    mut vehicles: Query<(Option<&mut Position>, Option<&mut Look>)>,
+) {
+    for MoveVehicle {
+        client,
+        position,
+        yaw,
+        pitch,
+    } in move_vehicle.iter()
+    {
+        let Ok(ControlledVehicle(vehicle)) = clients.get(*client) else {
+            continue
+        };
+
+        let Ok((pos, look)) = vehicles.get_mut(*vehicle) else {
+            continue
+        };
+
+        if let Some(mut pos) = pos {
+            pos.0 = *position;
+        }
+
+        if let Some(mut look) = look {
+            look.yaw = *yaw;
+            look.pitch = *pitch;
+        }
+    }
+}
+
+pub(crate) fn update_entity_transforms(
+    mut entities: Query<
+        (
+            &mut McEntity,
+            Option<&Position>,
+            Option<&Velocity>,
+            Option<&Look>,
+            Option<&OnGround>,
+        ),
+        Or<(
+            Changed<Position>,
+            Changed<Velocity>,
+            Changed<Look>,
+            Changed<OnGround>,
+        )>,
+    >,
+) {
+    for (mut entity, pos, vel, look, on_ground) in &mut entities {
+        if let Some(pos) = pos {
+            entity.set_position(pos.0);
+        }
+
+        if let Some(vel) = vel {
+            entity.set_velocity(vel.0);
+        }
+
+        if let Some(look) = look {
+            entity.set_yaw(look.yaw);
+            entity.set_pitch(look.pitch);
+        }
+
+        if let Some(on_ground) = on_ground {
+            entity.set_on_ground(on_ground.0);
+        }
     }
 }
 
 /// A component for Minecraft entities. For Valence to recognize a
 /// Minecraft entity, it must have this component attached.
 ///
-/// ECS entities with this component are not allowed to be removed from the
-/// [`World`] directly. Instead, you must mark these entities with [`Despawned`]
-/// to allow deinitialization to occur.
+/// To remove these entities, mark them with [`Despawned`] so that
+/// deinitialization occurs at the end of the tick. If the ECS entity is instead
+/// removed from the [`World`] directly, clients in view of the entity are still
+/// sent a packet removing it.
 ///
 /// Every entity has common state which is accessible directly from this struct.
 /// This includes position, rotation, velocity, and UUID. To access data that is
@@ -120,6 +566,12 @@ pub struct McEntity {
     /// The range of bytes in the partition cell containing this entity's update
     /// packets.
     pub(crate) self_update_range: Range<usize>,
+    /// The range of bytes in the partition cell's movement buffer containing
+    /// this entity's movement packets.
+    pub(crate) self_movement_range: Range<usize>,
+    /// The last tick this entity moved or rotated. Used to decide which
+    /// entities are included in reduced rate movement updates.
+    pub(crate) last_moved_tick: Option<i64>,
     /// Contains a set bit for every status triggered this tick.
     statuses: u64,
     /// Contains a set bit for every animation triggered this tick.
@@ -128,6 +580,14 @@ pub struct McEntity {
     old_instance: Entity,
     position: DVec3,
     old_position: DVec3,
+    /// If the position update should be sent as an absolute teleport this tick.
+    force_teleport: bool,
+    /// The number of ticks this entity has moved since its absolute position
+    /// was last sent.
+    ticks_since_teleport: u32,
+    /// If the next position update should be sent as an absolute teleport to
+    /// correct the drift from relative moves.
+    teleport_due: bool,
     yaw: f32,
     pitch: f32,
     yaw_or_pitch_modified: bool,
@@ -136,6 +596,15 @@ pub struct McEntity {
     velocity: Vec3,
     velocity_modified: bool,
     on_ground: bool,
+    /// The protocol IDs of the entities riding this entity.
+    passengers: Vec<i32>,
+    passengers_modified: bool,
+    /// The protocol ID of the entity holding this entity's leash.
+    leash_holder: Option<i32>,
+    leash_holder_modified: bool,
+    /// The `data` field of the spawn packet for kinds which do not derive it
+    /// from their tracked data.
+    spawn_data: i32,
 }
 
 impl McEntity {
@@ -155,12 +624,17 @@ impl McEntity {
         Self {
             data: TrackedData::new(kind),
             self_update_range: 0..0,
+            self_movement_range: 0..0,
+            last_moved_tick: None,
             statuses: 0,
             animations: 0,
             instance,
             old_instance: NULL_ENTITY,
             position: DVec3::ZERO,
             old_position: DVec3::ZERO,
+            force_teleport: false,
+            ticks_since_teleport: 0,
+            teleport_due: false,
             yaw: 0.0,
             pitch: 0.0,
             yaw_or_pitch_modified: false,
@@ -171,6 +645,12 @@ impl McEntity {
             protocol_id: 0,
             uuid,
             on_ground: false,
+            passengers: vec![],
+            passengers_modified: false,
+            leash_holder: None,
+            leash_holder_modified: false,
+            // Falling blocks cannot be air.
+            spawn_data: if kind == EntityKind::FallingBlock { 1 } else { 0 },
         }
     }
 
@@ -180,6 +660,10 @@ impl McEntity {
     }
 
     /// Returns a mutable reference to this entity's tracked data.
+    ///
+    /// Clients already viewing this entity are only sent the fields modified
+    /// during the tick. Clients which start viewing the entity are sent every
+    /// field which differs from its default value.
     pub fn data_mut(&mut self) -> &mut TrackedData {
         &mut self.data
     }
@@ -189,6 +673,23 @@ impl McEntity {
         self.data.kind()
     }
 
+    /// Returns the `data` field sent when this entity is spawned on a client.
+    /// Falling blocks use it as their block state and projectiles as the
+    /// protocol ID of their owner.
+    pub fn spawn_data(&self) -> i32 {
+        self.spawn_data
+    }
+
+    /// Sets the `data` field sent when this entity is spawned on a client. See
+    /// [`validate_spawn_data`] for the values each kind expects.
+    ///
+    /// Clients already viewing this entity are not sent the new value. Item
+    /// frames, paintings, fishing bobbers, and wardens derive their spawn data
+    /// from their tracked data and ignore this.
+    pub fn set_spawn_data(&mut self, data: i32) {
+        self.spawn_data = data;
+    }
+
     /// Returns a handle to the [`Instance`] this entity is located in.
     ///
     /// [`Instance`]: crate::instance::Instance
@@ -207,6 +708,13 @@ impl McEntity {
         self.old_instance
     }
 
+    /// Returns `true` if this entity was moved from one instance to another
+    /// since the end of the previous tick. Newly spawned entities have not
+    /// changed instances.
+    pub(crate) fn changed_instance(&self) -> bool {
+        self.old_instance != NULL_ENTITY && self.old_instance != self.instance
+    }
+
     /// Gets the UUID of this entity.
     pub fn uuid(&self) -> Uuid {
         self.uuid
@@ -234,6 +742,14 @@ impl McEntity {
         self.position = pos.into();
     }
 
+    /// Like [`Self::set_position`], but clients are sent the absolute position
+    /// of the entity this tick regardless of the distance moved. Useful for
+    /// snapping entities into place instead of moving them by a small delta.
+    pub fn teleport(&mut self, pos: impl Into<DVec3>) {
+        self.position = pos.into();
+        self.force_teleport = true;
+    }
+
     /// Returns the position of this entity as it existed at the end of the
     /// previous tick.
     pub(crate) fn old_position(&self) -> DVec3 {
@@ -305,6 +821,38 @@ impl McEntity {
         // TODO: on ground modified flag?
     }
 
+    /// Returns the protocol IDs of the entities riding this entity.
+    pub fn passengers(&self) -> &[i32] {
+        &self.passengers
+    }
+
+    /// Sets the entities riding this entity by their protocol IDs. Passengers
+    /// which are despawned are removed from the list automatically.
+    pub fn set_passengers(&mut self, passengers: impl Into<Vec<i32>>) {
+        let passengers = passengers.into();
+
+        if self.passengers != passengers {
+            self.passengers = passengers;
+            self.passengers_modified = true;
+        }
+    }
+
+    /// Returns the protocol ID of the entity holding this entity's leash, if
+    /// any.
+    pub fn leash_holder(&self) -> Option<i32> {
+        self.leash_holder
+    }
+
+    /// Sets the entity holding this entity's leash by its protocol ID, or
+    /// removes the leash if `None`. The leash is removed automatically when
+    /// the holder is despawned.
+    pub fn set_leash_holder(&mut self, holder: Option<i32>) {
+        if self.leash_holder != holder {
+            self.leash_holder = holder;
+            self.leash_holder_modified = true;
+        }
+    }
+
     pub fn trigger_status(&mut self, status: EntityStatus) {
         self.statuses |= 1 << status as u64;
     }
@@ -313,6 +861,78 @@ impl McEntity {
         self.animations |= 1 << animation as u8;
     }
 
+    /// Writes the state common to all entities in the format of vanilla entity
+    /// NBT. This includes the `id`, `UUID`, `Pos`, `Motion`, `Rotation` and
+    /// `OnGround` tags. Tracked data is not included.
+    pub fn to_nbt(&self) -> Compound {
+        let uuid = self.uuid.as_u128();
+
+        compound! {
+            "id" => format!("minecraft:{}", self.kind().type_name()),
+            "UUID" => vec![
+                (uuid >> 96) as i32,
+                (uuid >> 64) as i32,
+                (uuid >> 32) as i32,
+                uuid as i32,
+            ],
+            "Pos" => List::Double(self.position.to_array().to_vec()),
+            "Motion" => List::Double(
+                self.velocity
+                    .to_array()
+                    .map(|v| v as f64 / DEFAULT_TPS as f64)
+                    .to_vec(),
+            ),
+            "Rotation" => List::Float(vec![self.yaw, self.pitch]),
+            "OnGround" => self.on_ground,
+        }
+    }
+
+    /// Creates an entity located in `instance` from vanilla entity NBT such as
+    /// the output of [`Self::to_nbt`]. An error is returned if any of the tags
+    /// read by [`Self::to_nbt`] are missing or malformed.
+    pub fn from_nbt(nbt: &Compound, instance: Entity) -> anyhow::Result<Self> {
+        let Some(Value::String(id)) = nbt.get("id") else {
+            bail!("missing entity ID");
+        };
+
+        let type_name = id.strip_prefix("minecraft:").unwrap_or(id);
+        let kind = EntityKind::from_type_name(type_name)
+            .with_context(|| format!("unknown entity ID \"{id}\""))?;
+
+        let uuid = match nbt.get("UUID") {
+            Some(Value::IntArray(ints)) if ints.len() == 4 => {
+                ints.iter().fold(0, |acc, &i| acc << 32 | i as u32 as u128)
+            }
+            _ => bail!("missing or malformed entity UUID"),
+        };
+
+        let doubles = |name: &str| match nbt.get(name) {
+            Some(Value::List(List::Double(l))) if l.len() == 3 => Ok([l[0], l[1], l[2]]),
+            _ => bail!("missing or malformed {name} tag"),
+        };
+
+        let pos = doubles("Pos")?;
+        let motion = doubles("Motion")?;
+
+        let Some(Value::List(List::Float(rotation))) = nbt.get("Rotation") else {
+            bail!("missing or malformed Rotation tag");
+        };
+        ensure!(rotation.len() == 2, "malformed Rotation tag");
+
+        let Some(&Value::Byte(on_ground)) = nbt.get("OnGround") else {
+            bail!("missing or malformed OnGround tag");
+        };
+
+        let mut entity = Self::with_uuid(kind, instance, Uuid::from_u128(uuid));
+        entity.set_position(pos);
+        entity.set_velocity(motion.map(|v| (v * DEFAULT_TPS as f64) as f32));
+        entity.set_yaw(rotation[0]);
+        entity.set_pitch(rotation[1]);
+        entity.set_on_ground(on_ground != 0);
+
+        Ok(entity)
+    }
+
     /// Returns the hitbox of this entity.
     ///
     /// The hitbox describes the space that an entity occupies. Clients interact
@@ -334,21 +954,21 @@ impl McEntity {
         fn item_frame(pos: DVec3, rotation: i32) -> Aabb {
             let mut center_pos = pos + 0.5;
 
-            match rotation {
-                0 => center_pos.y += 0.46875,
-                1 => center_pos.y -= 0.46875,
-                2 => center_pos.z += 0.46875,
-                3 => center_pos.z -= 0.46875,
-                4 => center_pos.x += 0.46875,
-                5 => center_pos.x -= 0.46875,
-                _ => center_pos.y -= 0.46875,
+            let facing = item_frame_facing(rotation);
+
+            match facing {
+                Facing::Down => center_pos.y += 0.46875,
+                Facing::Up => center_pos.y -= 0.46875,
+                Facing::North => center_pos.z += 0.46875,
+                Facing::South => center_pos.z -= 0.46875,
+                Facing::West => center_pos.x += 0.46875,
+                Facing::East => center_pos.x -= 0.46875,
             };
 
-            let bounds = DVec3::from(match rotation {
-                0 | 1 => [0.75, 0.0625, 0.75],
-                2 | 3 => [0.75, 0.75, 0.0625],
-                4 | 5 => [0.0625, 0.75, 0.75],
-                _ => [0.75, 0.0625, 0.75],
+            let bounds = DVec3::from(match facing {
+                Facing::Down | Facing::Up => [0.75, 0.0625, 0.75],
+                Facing::North | Facing::South => [0.75, 0.75, 0.0625],
+                Facing::West | Facing::East => [0.0625, 0.75, 0.75],
             });
 
             Aabb {
@@ -604,6 +1224,71 @@ impl McEntity {
         Aabb::from_bottom_size(self.position, dimensions)
     }
 
+    /// Returns the height above this entity's position at which its
+    /// passengers are mounted.
+    pub fn passengers_riding_offset(&self) -> f64 {
+        let height = || {
+            let hitbox = self.hitbox();
+            hitbox.max.y - hitbox.min.y
+        };
+
+        match &self.data {
+            TrackedData::Boat(_) | TrackedData::ChestBoat(_) => -0.1,
+            TrackedData::Minecart(_)
+            | TrackedData::ChestMinecart(_)
+            | TrackedData::CommandBlockMinecart(_)
+            | TrackedData::FurnaceMinecart(_)
+            | TrackedData::HopperMinecart(_)
+            | TrackedData::SpawnerMinecart(_)
+            | TrackedData::TntMinecart(_) => 0.0,
+            TrackedData::Spider(_) | TrackedData::CaveSpider(_) => height() * 0.5,
+            TrackedData::Ravager(_) => 2.1,
+            TrackedData::Strider(_) => height() - 0.19,
+            _ => height() * 0.75,
+        }
+    }
+
+    /// Returns the vertical offset applied to this entity's position while it
+    /// is riding another entity.
+    pub fn riding_offset(&self) -> f64 {
+        let zombie = |is_baby: bool| if is_baby { 0.0 } else { -0.45 };
+
+        match &self.data {
+            TrackedData::Player(_) => -0.35,
+            TrackedData::Skeleton(_) | TrackedData::Stray(_) | TrackedData::WitherSkeleton(_) => {
+                -0.6
+            }
+            TrackedData::Zombie(e) => zombie(e.get_baby()),
+            TrackedData::Husk(e) => zombie(e.get_baby()),
+            TrackedData::Drowned(e) => zombie(e.get_baby()),
+            TrackedData::ZombieVillager(e) => zombie(e.get_baby()),
+            TrackedData::ZombifiedPiglin(e) => zombie(e.get_baby()),
+            TrackedData::Piglin(e) => {
+                if e.get_baby() {
+                    -0.05
+                } else {
+                    -0.45
+                }
+            }
+            TrackedData::Endermite(_) | TrackedData::Silverfish(_) => 0.1,
+            TrackedData::Allay(_) => 0.4,
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the position `passenger` would have while riding this entity.
+    ///
+    /// This is useful for server-side logic such as computing the dismount
+    /// position of a passenger.
+    pub fn passenger_position(&self, passenger: &McEntity) -> DVec3 {
+        self.position
+            + DVec3::new(
+                0.0,
+                self.passengers_riding_offset() + passenger.riding_offset(),
+                0.0,
+            )
+    }
+
     /// Sends the appropriate packets to initialize the entity. This will spawn
     /// the entity and initialize tracked data.
     pub(crate) fn write_init_packets(
@@ -612,16 +1297,24 @@ impl McEntity {
         position: DVec3,
         scratch: &mut Vec<u8>,
     ) {
-        let with_object_data = |data| SpawnEntity {
-            entity_id: VarInt(self.protocol_id),
-            object_uuid: self.uuid,
-            kind: VarInt(self.kind() as i32),
-            position: position.to_array(),
-            pitch: ByteAngle::from_degrees(self.pitch),
-            yaw: ByteAngle::from_degrees(self.yaw),
-            head_yaw: ByteAngle::from_degrees(self.head_yaw),
-            data: VarInt(data),
-            velocity: velocity_to_packet_units(self.velocity),
+        let with_object_data = |data| {
+            if cfg!(debug_assertions) {
+                if let Err(e) = validate_spawn_data(self.kind(), data) {
+                    warn!(uuid = %self.uuid, "{e:#}");
+                }
+            }
+
+            SpawnEntity {
+                entity_id: VarInt(self.protocol_id),
+                object_uuid: self.uuid,
+                kind: VarInt(self.kind() as i32),
+                position: position.to_array(),
+                pitch: ByteAngle::from_degrees(self.pitch),
+                yaw: ByteAngle::from_degrees(self.yaw),
+                head_yaw: ByteAngle::from_degrees(self.head_yaw),
+                data: VarInt(data),
+                velocity: velocity_to_packet_units(self.velocity),
+            }
         };
 
         match &self.data {
@@ -646,9 +1339,13 @@ impl McEntity {
                     head_yaw: ByteAngle::from_degrees(self.head_yaw),
                 });
             }
-            TrackedData::ItemFrame(e) => writer.write_packet(&with_object_data(e.get_rotation())),
+            TrackedData::ItemFrame(e) => {
+                let facing = item_frame_facing(e.get_rotation());
+                writer.write_packet(&with_object_data(facing as i32))
+            }
             TrackedData::GlowItemFrame(e) => {
-                writer.write_packet(&with_object_data(e.get_rotation()))
+                let facing = item_frame_facing(e.get_rotation());
+                writer.write_packet(&with_object_data(facing as i32))
             }
 
             TrackedData::Painting(_) => writer.write_packet(&with_object_data(
@@ -659,15 +1356,13 @@ impl McEntity {
                     _ => 5,
                 },
             )),
-            // TODO: set block state ID for falling block.
-            TrackedData::FallingBlock(_) => writer.write_packet(&with_object_data(1)),
             TrackedData::FishingBobber(e) => {
                 writer.write_packet(&with_object_data(e.get_hook_entity_id()))
             }
             TrackedData::Warden(e) => {
                 writer.write_packet(&with_object_data((e.get_pose() == Pose::Emerging).into()))
             }
-            _ => writer.write_packet(&with_object_data(0)),
+            _ => writer.write_packet(&with_object_data(self.spawn_data)),
         }
 
         scratch.clear();
@@ -678,18 +1373,65 @@ impl McEntity {
                 metadata: RawBytes(scratch),
             });
         }
+
+        if !self.passengers.is_empty() {
+            writer.write_packet(&self.passengers_packet());
+        }
+
+        if self.leash_holder.is_some() {
+            writer.write_packet(&self.leash_packet());
+        }
     }
 
-    /// Writes the appropriate packets to update the entity (Position, tracked
-    /// data, events, animations).
-    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket, scratch: &mut Vec<u8>) {
+    fn passengers_packet(&self) -> SetPassengers {
+        SetPassengers {
+            entity_id: VarInt(self.protocol_id),
+            passengers: self.passengers.iter().copied().map(VarInt).collect(),
+        }
+    }
+
+    fn leash_packet(&self) -> LinkEntities {
+        LinkEntities {
+            attached_entity_id: self.protocol_id,
+            // -1 detaches the leash.
+            holding_entity_id: self.leash_holder.unwrap_or(-1),
+        }
+    }
+
+    /// If the position of this entity should be sent as an absolute teleport
+    /// this tick instead of a relative move.
+    fn needs_teleport(&self) -> bool {
+        let position_delta = self.position - self.old_position;
+
+        self.force_teleport
+            || position_delta.abs().max_element() >= 8.0
+            || (self.teleport_due && self.position != self.old_position)
+    }
+
+    /// If the position or rotation of this entity was modified this tick.
+    pub(crate) fn movement_modified(&self) -> bool {
+        !self.changed_instance()
+            && (self.position != self.old_position
+                || self.force_teleport
+                || self.yaw_or_pitch_modified
+                || self.head_yaw_modified)
+    }
+
+    /// Writes the packets to update the position and rotation of the entity
+    /// for clients which received the movement of the previous tick.
+    pub(crate) fn write_movement_packets(&self, mut writer: impl WritePacket) {
         let entity_id = VarInt(self.protocol_id);
 
+        // Viewers in a new instance spawn the entity at its current position
+        // and rotation, so there is no movement to send.
+        let moved_within_instance = !self.changed_instance();
+
         let position_delta = self.position - self.old_position;
-        let needs_teleport = position_delta.abs().max_element() >= 8.0;
-        let changed_position = self.position != self.old_position;
+        let needs_teleport = moved_within_instance && self.needs_teleport();
+        let changed_position = moved_within_instance && self.position != self.old_position;
+        let yaw_or_pitch_modified = moved_within_instance && self.yaw_or_pitch_modified;
 
-        if changed_position && !needs_teleport && self.yaw_or_pitch_modified {
+        if changed_position && !needs_teleport && yaw_or_pitch_modified {
             writer.write_packet(&UpdateEntityPositionAndRotation {
                 entity_id,
                 delta: (position_delta * 4096.0).to_array().map(|v| v as i16),
@@ -706,7 +1448,7 @@ impl McEntity {
                 });
             }
 
-            if self.yaw_or_pitch_modified {
+            if yaw_or_pitch_modified {
                 writer.write_packet(&UpdateEntityRotation {
                     entity_id,
                     yaw: ByteAngle::from_degrees(self.yaw),
@@ -726,17 +1468,42 @@ impl McEntity {
             });
         }
 
-        if self.velocity_modified {
-            writer.write_packet(&SetEntityVelocity {
+        if self.head_yaw_modified {
+            writer.write_packet(&SetHeadRotation {
                 entity_id,
-                velocity: velocity_to_packet_units(self.velocity),
+                head_yaw: ByteAngle::from_degrees(self.head_yaw),
             });
         }
+    }
 
-        if self.head_yaw_modified {
-            writer.write_packet(&SetHeadRotation {
+    /// Writes the packets setting the absolute position and rotation of the
+    /// entity. Used for clients which receive movement at a reduced rate.
+    pub(crate) fn write_teleport_packets(&self, mut writer: impl WritePacket) {
+        let entity_id = VarInt(self.protocol_id);
+
+        writer.write_packet(&TeleportEntity {
+            entity_id,
+            position: self.position.to_array(),
+            yaw: ByteAngle::from_degrees(self.yaw),
+            pitch: ByteAngle::from_degrees(self.pitch),
+            on_ground: self.on_ground,
+        });
+
+        writer.write_packet(&SetHeadRotation {
+            entity_id,
+            head_yaw: ByteAngle::from_degrees(self.head_yaw),
+        });
+    }
+
+    /// Writes the appropriate packets to update the entity other than its
+    /// movement (velocity, tracked data, events, animations).
+    pub(crate) fn write_update_packets(&self, mut writer: impl WritePacket, scratch: &mut Vec<u8>) {
+        let entity_id = VarInt(self.protocol_id);
+
+        if self.velocity_modified {
+            writer.write_packet(&SetEntityVelocity {
                 entity_id,
-                head_yaw: ByteAngle::from_degrees(self.head_yaw),
+                velocity: velocity_to_packet_units(self.velocity),
             });
         }
 
@@ -749,6 +1516,14 @@ impl McEntity {
             });
         }
 
+        if self.passengers_modified {
+            writer.write_packet(&self.passengers_packet());
+        }
+
+        if self.leash_holder_modified {
+            writer.write_packet(&self.leash_packet());
+        }
+
         if self.statuses != 0 {
             for i in 0..std::mem::size_of_val(&self.statuses) {
                 if (self.statuses >> i) & 1 == 1 {
@@ -791,3 +1566,852 @@ impl fmt::Debug for McEntity {
             .finish_non_exhaustive()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::c2s::play::{ConfirmTeleport, MoveVehicleC2s};
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{PacketDecoder, PacketEncoder};
+
+    use super::*;
+    use crate::client::Client;
+    use crate::dimension::DimensionId;
+    use crate::instance::{Chunk, Instance};
+    use crate::inventory::{Inventory, InventoryKind};
+    use crate::unit_test::util::{create_mock_client, gen_client_info, scenario_single_client};
+    use crate::{assert_packet_count, assert_packet_order};
+
+    #[test]
+    fn entity_ids_are_unique_and_looked_up_by_uuid() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let uuid_a = Uuid::from_u128(1);
+        let uuid_b = Uuid::from_u128(2);
+
+        let a = McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid_a);
+        let a = app.world.spawn(a).id();
+        let b = McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid_b);
+        let b = app.world.spawn(b).id();
+
+        app.update();
+
+        let id_a = app.world.get::<McEntity>(a).unwrap().protocol_id();
+        let id_b = app.world.get::<McEntity>(b).unwrap().protocol_id();
+
+        assert_ne!(id_a, id_b);
+
+        let manager = app.world.resource::<McEntityManager>();
+
+        assert_eq!(manager.get_with_uuid(uuid_a), Some(a));
+        assert_eq!(manager.get_with_uuid(uuid_b), Some(b));
+        assert_eq!(manager.get_with_protocol_id(id_a), Some(a));
+        assert_eq!(manager.get_with_protocol_id(id_b), Some(b));
+        assert_eq!(manager.get_with_uuid(Uuid::from_u128(3)), None);
+
+        app.world.entity_mut(a).insert(Despawned);
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+
+        assert_eq!(manager.get_with_uuid(uuid_a), None);
+        assert_eq!(manager.get_with_protocol_id(id_a), None);
+    }
+
+    #[test]
+    fn despawning_keeps_uuid_of_replacing_entity() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let uuid = Uuid::from_u128(1);
+
+        let old = McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid);
+        let old = app.world.spawn(old).id();
+
+        app.update();
+
+        let new = McEntity::with_uuid(EntityKind::Zombie, instance_ent, uuid);
+        let new = app.world.spawn(new).id();
+
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_uuid(uuid), Some(new));
+
+        app.world.entity_mut(old).insert(Despawned);
+        app.update();
+
+        let manager = app.world.resource::<McEntityManager>();
+        assert_eq!(manager.get_with_uuid(uuid), Some(new));
+    }
+
+    #[test]
+    fn special_spawn_constructors_match_entity_kinds() {
+        use valence_protocol::entity_meta::Facing;
+
+        let pos = [0.0; 3];
+        let uuid = Uuid::nil();
+
+        let painting = SpawnEntity::painting(VarInt(0), uuid, pos, Facing::North);
+        assert_eq!(painting.kind.0, EntityKind::Painting as i32);
+        assert!(validate_spawn_data(EntityKind::Painting, painting.data.0).is_ok());
+
+        let frame = SpawnEntity::item_frame(VarInt(0), uuid, pos, Facing::Up);
+        assert_eq!(frame.kind.0, EntityKind::ItemFrame as i32);
+        assert!(validate_spawn_data(EntityKind::ItemFrame, frame.data.0).is_ok());
+
+        let knot = SpawnEntity::leash_knot(VarInt(0), uuid, pos);
+        assert_eq!(knot.kind.0, EntityKind::LeashKnot as i32);
+        assert!(validate_spawn_data(EntityKind::LeashKnot, knot.data.0).is_ok());
+    }
+
+    #[test]
+    fn metadata_update_contains_only_modified_fields() {
+        let mut entity = McEntity::new(EntityKind::Zombie, Entity::from_raw(0));
+
+        let TrackedData::Zombie(zombie) = entity.data_mut() else {
+            unreachable!()
+        };
+        zombie.set_baby(true);
+        zombie.set_silent(true);
+        zombie.set_health(10.0);
+
+        entity.data.clear_modifications();
+
+        let TrackedData::Zombie(zombie) = entity.data_mut() else {
+            unreachable!()
+        };
+        // Setting a field to its current value is not a modification.
+        zombie.set_baby(true);
+        zombie.set_health(5.0);
+
+        let mut enc = PacketEncoder::new();
+        entity.write_update_packets(&mut enc, &mut vec![]);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        let packets = dec.collect_into_vec::<S2cPlayPacket>().unwrap();
+
+        let [S2cPlayPacket::SetEntityMetadata(pkt)] = packets.as_slice() else {
+            panic!("unexpected packets: {packets:?}")
+        };
+
+        let mut expected = vec![9, 3];
+        expected.extend_from_slice(&5.0_f32.to_be_bytes());
+        expected.push(0xff);
+
+        assert_eq!(pkt.metadata.0, expected);
+    }
+
+    #[test]
+    fn swimming_pose_metadata() {
+        let mut entity = McEntity::new(EntityKind::Player, Entity::from_raw(0));
+
+        entity.data_mut().set_pose(Pose::Swimming);
+        assert_eq!(entity.data().get_pose(), Pose::Swimming);
+
+        let mut buf = vec![];
+        entity.data().write_updated_tracked_data(&mut buf);
+
+        // Index 6, type ID 19 (pose), variant 3 (swimming), terminator.
+        assert_eq!(buf, [6, 19, 3, 0xff]);
+    }
+
+    #[test]
+    fn on_fire_sets_and_clears_flag() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let zombie = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance_ent),
+                OnFire { ticks_remaining: 3 },
+            ))
+            .id();
+
+        for _ in 0..3 {
+            app.update();
+            let entity = app.world.get::<McEntity>(zombie).unwrap();
+            assert!(entity.data().get_on_fire());
+        }
+
+        app.update();
+
+        assert!(app.world.get::<OnFire>(zombie).is_none());
+        let entity = app.world.get::<McEntity>(zombie).unwrap();
+        assert!(!entity.data().get_on_fire());
+    }
+
+    #[test]
+    fn player_riding_boat_position() {
+        let mut boat = McEntity::new(EntityKind::Boat, Entity::from_raw(0));
+        boat.set_position([10.0, 64.0, -5.0]);
+
+        let player = McEntity::new(EntityKind::Player, Entity::from_raw(0));
+
+        let pos = boat.passenger_position(&player);
+
+        assert_eq!(pos.x, 10.0);
+        assert_eq!(pos.z, -5.0);
+        assert!((pos.y - (64.0 - 0.1 - 0.35)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn position_component_change_detection() {
+        #[derive(Resource, Default)]
+        struct ChangedPositions(usize);
+
+        fn count_changed(
+            mut count: ResMut<ChangedPositions>,
+            positions: Query<(), Changed<Position>>,
+        ) {
+            count.0 += positions.iter().count();
+        }
+
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .get::<crate::client::Client>(client_ent)
+            .unwrap()
+            .instance();
+
+        app.init_resource::<ChangedPositions>()
+            .add_system(count_changed);
+
+        let entity = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Zombie, instance_ent),
+                Position(DVec3::new(1.0, 64.0, 2.0)),
+            ))
+            .id();
+
+        app.update();
+
+        assert_eq!(app.world.resource::<ChangedPositions>().0, 1);
+        assert_eq!(
+            app.world.get::<McEntity>(entity).unwrap().position(),
+            DVec3::new(1.0, 64.0, 2.0)
+        );
+
+        // No change.
+        app.update();
+        assert_eq!(app.world.resource::<ChangedPositions>().0, 1);
+
+        app.world.get_mut::<Position>(entity).unwrap().0 = DVec3::new(-0.5, 64.0, -16.5);
+
+        app.update();
+
+        assert_eq!(app.world.resource::<ChangedPositions>().0, 2);
+        assert_eq!(
+            app.world.get::<McEntity>(entity).unwrap().position(),
+            DVec3::new(-0.5, 64.0, -16.5)
+        );
+
+        let pos = *app.world.get::<Position>(entity).unwrap();
+        assert_eq!(pos.chunk_pos(), ChunkPos::new(-1, -2));
+        assert_eq!(
+            Position(DVec3::new(-16.0, 0.0, 15.9)).chunk_pos(),
+            ChunkPos::new(-1, 0)
+        );
+    }
+
+    #[test]
+    fn velocity_component_is_sent_in_meters_per_second() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([1.5, 64.0, 2.5]);
+        let entity = app.world.spawn((zombie, Velocity::default())).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.get_mut::<Velocity>(entity).unwrap().0 = Vec3::new(1.0, 0.0, -2.0);
+
+        app.update();
+
+        assert_eq!(
+            app.world.get::<McEntity>(entity).unwrap().velocity(),
+            Vec3::new(1.0, 0.0, -2.0)
+        );
+
+        // One meter per second is 1/20 of a block per tick, which is 400 in
+        // units of 1/8000 of a block per tick.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetEntityVelocity(_));
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityVelocity(p) if p.velocity == [400, 0, -800]
+        )));
+    }
+
+    #[test]
+    fn forced_teleport_sends_absolute_position() {
+        let decode = |entity: &McEntity| {
+            let mut enc = PacketEncoder::new();
+            entity.write_movement_packets(&mut enc);
+
+            let mut dec = PacketDecoder::new();
+            dec.queue_bytes(enc.take());
+            dec.collect_into_vec::<S2cPlayPacket>().unwrap()
+        };
+
+        let mut entity = McEntity::new(EntityKind::Zombie, Entity::from_raw(0));
+
+        entity.set_position([0.0, 0.0, 0.01]);
+        assert!(matches!(
+            decode(&entity).as_slice(),
+            [S2cPlayPacket::UpdateEntityPosition(_)]
+        ));
+
+        entity.teleport([0.0, 0.0, 0.01]);
+        let packets = decode(&entity);
+        let [S2cPlayPacket::TeleportEntity(pkt)] = packets.as_slice() else {
+            panic!("unexpected packets: {packets:?}")
+        };
+        assert_eq!(pkt.position, [0.0, 0.0, 0.01]);
+    }
+
+    #[test]
+    fn nbt_round_trip() {
+        let instance = Entity::from_raw(0);
+
+        let mut entity = McEntity::with_uuid(
+            EntityKind::Zombie,
+            instance,
+            Uuid::from_u128(0x0123_4567_89ab_cdef_fedc_ba98_7654_3210),
+        );
+        entity.set_position([1.5, 64.0, -3.25]);
+        entity.set_velocity([0.5, -9.8, 1.0]);
+        entity.set_yaw(90.0);
+        entity.set_pitch(-45.0);
+        entity.set_on_ground(true);
+
+        let nbt = entity.to_nbt();
+        assert_eq!(
+            nbt.get("id"),
+            Some(&Value::String("minecraft:zombie".into()))
+        );
+
+        let loaded = McEntity::from_nbt(&nbt, instance).unwrap();
+
+        assert_eq!(loaded.kind(), entity.kind());
+        assert_eq!(loaded.uuid(), entity.uuid());
+        assert_eq!(loaded.position(), entity.position());
+        assert_eq!(loaded.velocity(), entity.velocity());
+        assert_eq!(loaded.yaw(), entity.yaw());
+        assert_eq!(loaded.pitch(), entity.pitch());
+        assert_eq!(loaded.on_ground(), entity.on_ground());
+
+        let mut bad = nbt;
+        bad.insert("id", "minecraft:not_an_entity");
+        assert!(McEntity::from_nbt(&bad, instance).is_err());
+    }
+
+    #[test]
+    fn vehicle_moved_by_controlling_client() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let boat_ent = app
+            .world
+            .spawn((
+                McEntity::new(EntityKind::Boat, instance_ent),
+                Position::default(),
+                Look::default(),
+            ))
+            .id();
+        app.world
+            .entity_mut(client_ent)
+            .insert(ControlledVehicle(boat_ent));
+
+        // Sends the initial teleport to the client.
+        app.update();
+
+        client_helper.send(&ConfirmTeleport {
+            teleport_id: VarInt(0),
+        });
+        client_helper.send(&MoveVehicleC2s {
+            position: [10.0, 63.5, -4.0],
+            yaw: 45.0,
+            pitch: 0.0,
+        });
+
+        app.update();
+
+        let boat = app.world.entity(boat_ent);
+        assert_eq!(
+            boat.get::<Position>(),
+            Some(&Position(DVec3::new(10.0, 63.5, -4.0)))
+        );
+        assert_eq!(
+            boat.get::<Look>(),
+            Some(&Look {
+                yaw: 45.0,
+                pitch: 0.0
+            })
+        );
+        assert_eq!(
+            boat.get::<McEntity>().unwrap().position(),
+            DVec3::new(10.0, 63.5, -4.0)
+        );
+    }
+
+    #[test]
+    fn periodic_teleport_corrects_relative_moves() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut teleport_ticks = vec![];
+
+        for tick in 1..=120 {
+            app.world
+                .get_mut::<McEntity>(zombie_ent)
+                .unwrap()
+                .set_position([0.0, 0.0, tick as f64 * 0.01]);
+
+            app.update();
+
+            let sent_packets = client_helper.collect_sent().unwrap();
+            if sent_packets
+                .iter()
+                .any(|p| matches!(p, S2cPlayPacket::TeleportEntity(_)))
+            {
+                teleport_ticks.push(tick);
+            } else {
+                assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateEntityPosition(_));
+            }
+        }
+
+        // The default interval is 60 ticks.
+        assert_eq!(teleport_ticks, [60, 120]);
+    }
+
+    #[test]
+    fn changing_instance_despawns_and_spawns_for_viewers() {
+        let mut app = App::new();
+        let (old_viewer_ent, mut old_viewer_helper) = scenario_single_client(&mut app);
+
+        let old_instance_ent = app.world.get::<Client>(old_viewer_ent).unwrap().instance();
+        let new_instance = app
+            .world
+            .resource::<Server>()
+            .new_instance(DimensionId::default());
+        let new_instance_ent = app.world.spawn(new_instance).id();
+
+        let (mut new_viewer, mut new_viewer_helper) =
+            create_mock_client(gen_client_info("new_viewer"));
+        new_viewer.set_instance(new_instance_ent);
+        app.world.spawn((
+            new_viewer,
+            Inventory::new(InventoryKind::Player),
+            OnGround::default(),
+        ));
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, old_instance_ent);
+        zombie.set_position([1.0, 64.0, 1.0]);
+        let zombie_ent = app.world.spawn(zombie).id();
+
+        app.update();
+
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+
+        let sent_packets = old_viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+        let sent_packets = new_viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+
+        let mut zombie = app.world.get_mut::<McEntity>(zombie_ent).unwrap();
+        zombie.set_instance(new_instance_ent);
+        zombie.set_position([20.0, 64.0, 20.0]);
+
+        app.update();
+
+        let sent_packets = old_viewer_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::RemoveEntities(p) if p.entity_ids.contains(&VarInt(zombie_id))
+        )));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+
+        // The entity appears at its new position without moving there.
+        let sent_packets = new_viewer_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SpawnEntity(p)
+                if p.entity_id.0 == zombie_id && p.position == [20.0, 64.0, 20.0]
+        )));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::RemoveEntities(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::UpdateEntityPosition(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::TeleportEntity(_));
+    }
+
+    #[test]
+    fn existing_viewers_get_metadata_deltas_and_new_viewers_get_everything() {
+        let mut app = App::new();
+        let (_, mut old_viewer_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([1.0, 64.0, 1.0]);
+        let TrackedData::Zombie(data) = zombie.data_mut() else {
+            unreachable!()
+        };
+        data.set_health(10.0);
+        let zombie_ent = app.world.spawn(zombie).id();
+
+        app.update();
+
+        let sent_packets = old_viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+
+        // Modify the metadata in the same tick a new viewer joins.
+        let mut zombie = app.world.get_mut::<McEntity>(zombie_ent).unwrap();
+        let TrackedData::Zombie(data) = zombie.data_mut() else {
+            unreachable!()
+        };
+        data.set_baby(true);
+
+        let mut delta = vec![];
+        zombie.data().write_updated_tracked_data(&mut delta);
+        let mut full = vec![];
+        zombie.data().write_initial_tracked_data(&mut full);
+        assert_ne!(delta, full);
+
+        let (mut new_viewer, mut new_viewer_helper) =
+            create_mock_client(gen_client_info("new_viewer"));
+        new_viewer.set_instance(instance_ent);
+        app.world.spawn((
+            new_viewer,
+            Inventory::new(InventoryKind::Player),
+            OnGround::default(),
+        ));
+
+        app.update();
+
+        let metadata = |packets: &[S2cPlayPacket]| -> Vec<Vec<u8>> {
+            packets
+                .iter()
+                .filter_map(|p| match p {
+                    S2cPlayPacket::SetEntityMetadata(p) => Some(p.metadata.0.to_vec()),
+                    _ => None,
+                })
+                .collect()
+        };
+
+        let sent_packets = old_viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SpawnEntity(_));
+        assert_eq!(metadata(&sent_packets), [delta]);
+
+        let sent_packets = new_viewer_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+        assert_eq!(metadata(&sent_packets), [full]);
+    }
+
+    #[test]
+    fn despawning_vehicle_clears_passengers() {
+        let mut app = App::new();
+        let (_, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let boat_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Boat, instance_ent))
+            .id();
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+
+        let boat_id = app.world.get::<McEntity>(boat_ent).unwrap().protocol_id();
+        let zombie_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+
+        let mut boat = app.world.get_mut::<McEntity>(boat_ent).unwrap();
+        boat.set_passengers([zombie_id]);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetPassengers(p)
+                if p.entity_id.0 == boat_id && p.passengers == [VarInt(zombie_id)]
+        )));
+
+        app.world.entity_mut(boat_ent).insert(Despawned);
+
+        app.update();
+
+        // The passengers are dismounted before the vehicle is removed.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetPassengers(_));
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetPassengers(p) if p.entity_id.0 == boat_id && p.passengers.is_empty()
+        )));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::SetPassengers(_),
+            S2cPlayPacket::RemoveEntities(_)
+        );
+    }
+
+    #[test]
+    fn despawning_passenger_or_leash_holder_detaches() {
+        let mut app = App::new();
+        let (_, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app
+            .world
+            .query_filtered::<Entity, With<Instance>>()
+            .single(&app.world);
+
+        let [boat_ent, cow_ent, zombie_ent, player_ent] = [
+            EntityKind::Boat,
+            EntityKind::Cow,
+            EntityKind::Zombie,
+            EntityKind::Player,
+        ]
+        .map(|kind| app.world.spawn(McEntity::new(kind, instance_ent)).id());
+
+        app.update();
+
+        let id = |app: &App, e| app.world.get::<McEntity>(e).unwrap().protocol_id();
+        let (cow_id, zombie_id, player_id) = (
+            id(&app, cow_ent),
+            id(&app, zombie_ent),
+            id(&app, player_ent),
+        );
+
+        let mut boat = app.world.get_mut::<McEntity>(boat_ent).unwrap();
+        boat.set_passengers([cow_id, zombie_id]);
+        let mut cow = app.world.get_mut::<McEntity>(cow_ent).unwrap();
+        cow.set_leash_holder(Some(player_id));
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(zombie_ent).insert(Despawned);
+        app.world.entity_mut(player_ent).insert(Despawned);
+
+        app.update();
+
+        let boat = app.world.get::<McEntity>(boat_ent).unwrap();
+        assert_eq!(boat.passengers(), [cow_id]);
+        let cow = app.world.get::<McEntity>(cow_ent).unwrap();
+        assert_eq!(cow.leash_holder(), None);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetPassengers(p) if p.passengers == [VarInt(cow_id)]
+        )));
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::LinkEntities(p)
+                if p.attached_entity_id == cow_id && p.holding_entity_id == -1
+        )));
+    }
+
+    #[test]
+    fn spawn_data_validation() {
+        assert!(validate_spawn_data(EntityKind::Zombie, 0).is_ok());
+        assert!(validate_spawn_data(EntityKind::Zombie, 1).is_err());
+
+        assert!(validate_spawn_data(EntityKind::FallingBlock, 0).is_err());
+        assert!(validate_spawn_data(EntityKind::FallingBlock, 1).is_ok());
+
+        for kind in [
+            EntityKind::ItemFrame,
+            EntityKind::GlowItemFrame,
+            EntityKind::Painting,
+        ] {
+            assert!(validate_spawn_data(kind, -1).is_err());
+            assert!(validate_spawn_data(kind, 0).is_ok());
+            assert!(validate_spawn_data(kind, 5).is_ok());
+            assert!(validate_spawn_data(kind, 6).is_err());
+        }
+
+        // Every rotation an item frame can have is sent as a valid direction.
+        for rotation in -1..=8 {
+            let data = item_frame_facing(rotation) as i32;
+            assert!(validate_spawn_data(EntityKind::ItemFrame, data).is_ok());
+        }
+        assert_eq!(item_frame_facing(0), Facing::Down);
+        assert_eq!(item_frame_facing(5), Facing::East);
+        assert_eq!(item_frame_facing(6), Facing::Up);
+
+        assert!(validate_spawn_data(EntityKind::Arrow, 123).is_ok());
+        assert!(validate_spawn_data(EntityKind::Snowball, 123).is_ok());
+        assert!(validate_spawn_data(EntityKind::FireworkRocket, 123).is_ok());
+    }
+
+    #[test]
+    fn spawn_effect_is_played_with_spawn() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut zombie = McEntity::new(EntityKind::Zombie, instance_ent);
+        zombie.set_position([1.5, 64.0, 2.5]);
+        app.world.spawn((
+            zombie,
+            SpawnEffect {
+                particle: Some(Particle::Flame),
+                particle_count: 5,
+                sound: Some(SoundId::Reference { id: VarInt(42) }),
+                sound_category: SoundCategory::Hostile,
+                volume: 1.0,
+                pitch: 1.0,
+            },
+        ));
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SpawnEntity(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ParticleS2c(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SoundEffect(_));
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::SpawnEntity(_),
+            S2cPlayPacket::ParticleS2c(_)
+        );
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SoundEffect(p) if p.position == [12, 512, 20]
+        )));
+
+        // The effect is only played when the entity is spawned.
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ParticleS2c(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SoundEffect(_));
+    }
+
+    #[test]
+    fn far_entities_receive_fewer_movement_updates() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        // Every chunk beyond the client's own doubles the update interval.
+        app.insert_resource(EntityLodSettings {
+            enabled: true,
+            full_rate_distance: 0,
+            band_width: 1,
+        });
+
+        let lod = app.world.resource::<EntityLodSettings>();
+        assert_eq!(lod.update_interval(0), 1);
+        assert_eq!(lod.update_interval(1), 2);
+        assert_eq!(lod.update_interval(3), 8);
+        assert_eq!(lod.update_interval(100), 8);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut near = McEntity::new(EntityKind::Zombie, instance_ent);
+        near.set_position([1.0, 64.0, 1.0]);
+        let near_ent = app.world.spawn(near).id();
+
+        // Three chunks away from the client, but still in view.
+        let mut far = McEntity::new(EntityKind::Zombie, instance_ent);
+        far.set_position([50.0, 64.0, 1.0]);
+        let far_ent = app.world.spawn(far).id();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let near_id = app.world.get::<McEntity>(near_ent).unwrap().protocol_id();
+        let far_id = app.world.get::<McEntity>(far_ent).unwrap().protocol_id();
+
+        let mut near_updates = 0;
+        let mut far_updates = 0;
+
+        for tick in 1..=16 {
+            for ent in [near_ent, far_ent] {
+                let mut entity = app.world.get_mut::<McEntity>(ent).unwrap();
+                let pos = entity.position();
+                entity.set_position([pos.x, pos.y, 1.0 + tick as f64 * 0.1]);
+            }
+
+            app.update();
+
+            for pkt in client_helper.collect_sent().unwrap() {
+                match pkt {
+                    S2cPlayPacket::UpdateEntityPosition(p) if p.entity_id.0 == near_id => {
+                        near_updates += 1;
+                    }
+                    S2cPlayPacket::UpdateEntityPosition(p) => {
+                        panic!("unexpected relative move for entity {}", p.entity_id.0);
+                    }
+                    S2cPlayPacket::TeleportEntity(p) if p.entity_id.0 == far_id => {
+                        // Reduced rate updates set the absolute position.
+                        assert_eq!(p.position, [50.0, 64.0, 1.0 + tick as f64 * 0.1]);
+                        far_updates += 1;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        assert_eq!(near_updates, 16);
+        assert_eq!(far_updates, 2);
+    }
+}
@@ -1,28 +1,53 @@
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::hash::Hasher;
 use std::iter::FusedIterator;
+use std::ops::Range;
 
+use anyhow::{bail, ensure};
 use bevy_ecs::prelude::*;
 pub use chunk_entry::*;
 use glam::{DVec3, Vec3};
-use num::integer::div_ceil;
-use rustc_hash::FxHashMap;
-use valence_protocol::block::BlockState;
+use rustc_hash::{FxHashMap, FxHasher};
+use tracing::warn;
+use valence_protocol::block::{BlockKind, BlockState};
 use valence_protocol::packets::s2c::particle::{Particle, ParticleS2c};
-use valence_protocol::packets::s2c::play::SetActionBarText;
-use valence_protocol::{BlockPos, EncodePacket, LengthPrefixedArray, Text};
-
+use valence_protocol::packets::s2c::play::{
+    BlockAction, EntitySoundEffect, RemoveEntitiesEncode, SetActionBarText, SetEntityMetadata,
+    SetHeadRotation, SetPassengers, SoundEffect, SoundId, UpdateEntityRotation, UpdateTime,
+};
+use valence_protocol::types::SoundCategory;
+use valence_protocol::{
+    BlockPos, ByteAngle, Encode, EncodePacket, ItemStack, LengthPrefixedArray, RawBytes, Text,
+    VarInt,
+};
+
+use crate::biome::BiomeId;
+use crate::client::Client;
 use crate::dimension::DimensionId;
-use crate::entity::McEntity;
-pub use crate::instance::chunk::Chunk;
+use crate::entity::{
+    is_projectile, validate_spawn_data, EntityKind, EntityLodSettings, McEntity, McEntityManager,
+    TrackedData, LOD_BAND_COUNT,
+};
+use crate::instance::chunk::filler_sky_light;
+pub use crate::instance::chunk::{Chunk, ChunkPacketData, ChunkSection};
+use crate::instance::generator::ChunkGenerator;
+use crate::instance::protection::{BuildAction, BuildPermission};
+use crate::instance::structure::BlockArray;
+use crate::instance::weather::Weather;
 use crate::packet::{PacketWriter, WritePacket};
 use crate::server::{Server, SharedServer};
 use crate::view::ChunkPos;
-use crate::Despawned;
+use crate::{Despawned, NULL_ENTITY};
 
 mod chunk;
 mod chunk_entry;
+pub mod generator;
 mod paletted_container;
+pub mod protection;
+pub mod structure;
+pub mod weather;
 
 /// An Instance represents a Minecraft world, which consist of [`Chunk`]s.
 /// It manages updating clients when chunks change, and caches chunk and entity
@@ -52,11 +77,110 @@ mod paletted_container;
 pub struct Instance {
     pub(crate) partition: FxHashMap<ChunkPos, PartitionCell>,
     pub(crate) info: InstanceInfo,
+    /// The entity this instance is attached to, or [`NULL_ENTITY`] if it has
+    /// not been spawned yet.
+    entity: Entity,
     /// Packet data to send to all clients in this instance at the end of the
     /// tick.
     pub(crate) packet_buf: Vec<u8>,
+    /// Packet data to send only to clients within a radius of a position at
+    /// the end of the tick.
+    pub(crate) local_packet_buf: Vec<u8>,
+    /// The center, radius, and range in [`Self::local_packet_buf`] of each
+    /// local packet.
+    pub(crate) local_packets: Vec<(DVec3, f64, Range<usize>)>,
     /// Scratch space for writing packets.
     scratch: Vec<u8>,
+    /// The number of ticks this instance has existed for.
+    world_age: i64,
+    /// The time of day in ticks.
+    time_of_day: i64,
+    /// If `time_of_day` advances every tick.
+    daylight_cycle: bool,
+    /// If the particle helpers send anything.
+    particles_enabled: bool,
+    /// If the sound helpers send anything.
+    sounds_enabled: bool,
+    /// The number of Minecraft entities in the partition.
+    entity_count: usize,
+    /// The number of entities spawned with [`Self::spawn_entity`] this tick
+    /// which are not yet in the partition.
+    pending_entity_count: usize,
+    /// The maximum number of entities [`Self::spawn_entity`] allows.
+    max_entities: Option<usize>,
+    /// The Y level below which clients are considered to have fallen into the
+    /// void.
+    void_level: Option<f64>,
+    /// Checksums of the packet buffers, if packet buffer verification is
+    /// enabled.
+    packet_buf_checksums: Option<PacketBufChecksums>,
+    /// Fills in the contents of chunks created by [`Self::load_chunk`].
+    chunk_generator: Option<Box<dyn ChunkGenerator>>,
+    /// Decides which block changes clients in this instance may make.
+    build_permission: Option<Box<dyn BuildPermission>>,
+    /// Block changes made with [`Self::set_block_state`] this tick, to be sent
+    /// as [`BlockUpdateEvent`]s.
+    block_updates: Vec<(BlockPos, BlockState, BlockState)>,
+    /// The maximum number of [`BlockUpdateEvent`]s sent per tick, if any.
+    block_update_event_limit: Option<usize>,
+    /// The number of block changes this tick that did not fit in
+    /// `block_updates`.
+    dropped_block_updates: usize,
+}
+
+/// An event sent when a block is changed with [`Instance::set_block_state`].
+///
+/// Events are sent at the end of the tick the block was changed in, so systems
+/// reading them will observe the change on the following tick.
+///
+/// Every change produces an event unless a limit is set with
+/// [`Instance::set_block_update_event_limit`].
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlockUpdateEvent {
+    /// The [`Entity`] with the [`Instance`] the block was changed in.
+    pub instance: Entity,
+    pub position: BlockPos,
+    pub old: BlockState,
+    pub new: BlockState,
+}
+
+/// An event sent every tick a client is below the [void level] of its
+/// instance.
+///
+/// Handle this event to teleport or damage clients which fall out of the
+/// world. Like [`BlockUpdateEvent`], it is sent at the end of the tick.
+///
+/// [void level]: Instance::void_level
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct VoidFallEvent {
+    /// The [`Entity`] with the [`Client`] below the void level.
+    ///
+    /// [`Client`]: crate::client::Client
+    pub client: Entity,
+    /// The [`Entity`] with the [`Instance`] the client is in.
+    pub instance: Entity,
+    /// The position of the client.
+    pub position: DVec3,
+}
+
+#[derive(Default)]
+struct PacketBufChecksums {
+    instance: u64,
+    cells: FxHashMap<ChunkPos, u64>,
+}
+
+fn checksum(bytes: &[u8]) -> u64 {
+    let mut hasher = FxHasher::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Appends an entity metadata field with the given index, type ID, and value
+/// to `buf`.
+fn encode_metadata_field(buf: &mut Vec<u8>, index: u8, type_id: i32, value: &impl Encode) {
+    buf.push(index);
+    VarInt(type_id).encode(&mut *buf).unwrap();
+    value.encode(&mut *buf).unwrap();
 }
 
 pub(crate) struct InstanceInfo {
@@ -88,37 +212,55 @@ pub(crate) struct PartitionCell {
     /// A cache of packets to send to all clients that are in view of this cell
     /// at the end of the tick.
     pub(crate) packet_buf: Vec<u8>,
+    /// Entity movement packets for the clients in each [LOD band], which
+    /// receive movement every `2^band` ticks. Band zero contains relative
+    /// moves and is written every tick. The other bands contain absolute
+    /// teleports and are only written on ticks which are a multiple of their
+    /// interval.
+    ///
+    /// [LOD band]: crate::entity::EntityLodSettings
+    pub(crate) movement_bufs: [Vec<u8>; LOD_BAND_COUNT],
 }
 
 impl Instance {
     pub(crate) fn new(dimension: DimensionId, shared: &SharedServer) -> Self {
         let dim = shared.dimension(dimension);
 
-        let light_section_count = (dim.height / 16 + 2) as usize;
-
-        let mut sky_light_mask = vec![0; div_ceil(light_section_count, 16)];
+        let section_count = (dim.height / 16) as usize;
 
-        for i in 0..light_section_count {
-            sky_light_mask[i / 64] |= 1 << (i % 64);
-        }
+        let (sky_light_mask, sky_light_arrays) = filler_sky_light(section_count);
 
         Self {
             partition: FxHashMap::default(),
             info: InstanceInfo {
                 dimension,
-                section_count: (dim.height / 16) as usize,
+                section_count,
                 min_y: dim.min_y,
                 biome_registry_len: shared.biomes().len(),
                 compression_threshold: shared.compression_threshold(),
                 filler_sky_light_mask: sky_light_mask.into(),
-                filler_sky_light_arrays: vec![
-                    LengthPrefixedArray([0xff; 2048]);
-                    light_section_count
-                ]
-                .into(),
+                filler_sky_light_arrays: sky_light_arrays.into(),
             },
+            entity: NULL_ENTITY,
             packet_buf: vec![],
+            local_packet_buf: vec![],
+            local_packets: vec![],
             scratch: vec![],
+            world_age: 0,
+            time_of_day: 0,
+            daylight_cycle: true,
+            particles_enabled: true,
+            sounds_enabled: true,
+            entity_count: 0,
+            pending_entity_count: 0,
+            max_entities: None,
+            void_level: None,
+            packet_buf_checksums: None,
+            chunk_generator: None,
+            build_permission: None,
+            block_updates: vec![],
+            block_update_event_limit: None,
+            dropped_block_updates: 0,
         }
     }
 
@@ -186,6 +328,91 @@ impl Instance {
         }
     }
 
+    /// Unloads every chunk which is entirely air, was not in view of any client
+    /// at the end of the previous tick, and contains no entities. Returns the
+    /// number of chunks that were unloaded.
+    ///
+    /// This can be used to periodically compact instances where chunks are
+    /// loaded on demand.
+    pub fn unload_empty_chunks(&mut self) -> usize {
+        let mut count = 0;
+
+        for cell in self.partition.values_mut() {
+            if let Some(chunk) = &mut cell.chunk {
+                if !chunk.is_viewed_mut() && chunk.is_all_air() && cell.entities.is_empty() {
+                    cell.chunk = None;
+                    cell.chunk_removed = true;
+                    count += 1;
+                }
+            }
+        }
+
+        count
+    }
+
+    /// Gets the chunk at the given position, loading a new chunk if there isn't
+    /// one. New chunks are filled by the chunk generator if one is set, and
+    /// are empty otherwise.
+    pub fn load_chunk(&mut self, pos: impl Into<ChunkPos>) -> &mut Chunk<true> {
+        let pos = pos.into();
+
+        match ChunkEntry::new(self.info.section_count, self.partition.entry(pos)) {
+            ChunkEntry::Occupied(oe) => oe.into_mut(),
+            ChunkEntry::Vacant(ve) => {
+                let mut chunk = Chunk::new(self.info.section_count);
+
+                if let Some(generator) = &self.chunk_generator {
+                    generator.generate(pos, &mut chunk);
+                    // Generated chunks can be generated again, so they don't
+                    // need to be saved until they are modified.
+                    chunk.clear_dirty();
+                }
+
+                ve.insert(chunk)
+            }
+        }
+    }
+
+    /// Sets the generator used to fill in chunks created by
+    /// [`Self::load_chunk`]. This allows terrain to be generated as chunks are
+    /// loaded. Chunks inserted with [`Self::insert_chunk`] or
+    /// [`Self::chunk_entry`] are unaffected.
+    pub fn set_chunk_generator(&mut self, generator: impl ChunkGenerator) {
+        self.chunk_generator = Some(Box::new(generator));
+    }
+
+    /// Removes the chunk generator, if any. New chunks created by
+    /// [`Self::load_chunk`] will be empty.
+    pub fn remove_chunk_generator(&mut self) {
+        self.chunk_generator = None;
+    }
+
+    /// Sets the hook deciding which block changes clients in this instance are
+    /// allowed to make. See the [`protection`] module for details.
+    pub fn set_build_permission(&mut self, permission: impl BuildPermission) {
+        self.build_permission = Some(Box::new(permission));
+    }
+
+    /// Removes the build permission hook, if any. All block changes will be
+    /// allowed.
+    pub fn remove_build_permission(&mut self) {
+        self.build_permission = None;
+    }
+
+    /// Returns `true` if `client` is allowed to perform `action` at `pos`
+    /// according to the build permission hook of this instance. Everything is
+    /// allowed if there is no hook.
+    pub fn allows_build(
+        &self,
+        client: &Client,
+        pos: impl Into<BlockPos>,
+        action: BuildAction,
+    ) -> bool {
+        self.build_permission
+            .as_ref()
+            .map_or(true, |p| p.allows(client, pos.into(), action))
+    }
+
     /// Get a [`ChunkEntry`] for the given position.
     pub fn chunk_entry(&mut self, pos: impl Into<ChunkPos>) -> ChunkEntry {
         ChunkEntry::new(self.info.section_count, self.partition.entry(pos.into()))
@@ -207,6 +434,24 @@ impl Instance {
             .flat_map(|(&pos, par)| par.chunk.as_mut().map(|c| (pos, c)))
     }
 
+    /// Get an iterator over the "ticking" chunks in the instance. These are the
+    /// loaded chunks that were in view of a client at the end of the previous
+    /// tick. Gameplay systems can use this to skip work in chunks no client
+    /// can see. The order of the chunks is undefined.
+    pub fn ticking_chunks(
+        &self,
+    ) -> impl FusedIterator<Item = (ChunkPos, &Chunk<true>)> + Clone + '_ {
+        self.chunks().filter(|(_, chunk)| chunk.is_viewed())
+    }
+
+    /// Like [`Self::ticking_chunks`], but the chunks are mutable.
+    pub fn ticking_chunks_mut(
+        &mut self,
+    ) -> impl FusedIterator<Item = (ChunkPos, &mut Chunk<true>)> + '_ {
+        self.chunks_mut()
+            .filter_map(|(pos, chunk)| chunk.is_viewed_mut().then_some((pos, chunk)))
+    }
+
     /// Optimizes the memory usage of the instance.
     pub fn optimize(&mut self) {
         for (_, chunk) in self.chunks_mut() {
@@ -215,6 +460,8 @@ impl Instance {
 
         self.partition.shrink_to_fit();
         self.packet_buf.shrink_to_fit();
+        self.local_packet_buf.shrink_to_fit();
+        self.local_packets.shrink_to_fit();
     }
 
     /// Gets the block state at an absolute block position in world space. Only
@@ -264,12 +511,89 @@ impl Instance {
             return BlockState::AIR;
         };
 
-        chunk.set_block_state(
+        let old = chunk.set_block_state(
             pos.x.rem_euclid(16) as usize,
             y,
             pos.z.rem_euclid(16) as usize,
             block,
-        )
+        );
+
+        if old != block {
+            if self
+                .block_update_event_limit
+                .map_or(true, |limit| self.block_updates.len() < limit)
+            {
+                self.block_updates.push((pos, old, block));
+            } else {
+                self.dropped_block_updates += 1;
+            }
+        }
+
+        old
+    }
+
+    /// Sets the blocks in the box with its minimum corner at `origin` to the
+    /// blocks in `blocks`, including air. Blocks outside of loaded chunks are
+    /// skipped.
+    ///
+    /// Every block is set with [`Self::set_block_state`], so the changes to
+    /// each chunk section are sent to clients together in a single packet.
+    pub fn paste_structure(&mut self, origin: impl Into<BlockPos>, blocks: &BlockArray) {
+        let origin = origin.into();
+
+        for ([x, y, z], block) in blocks.iter() {
+            let pos = BlockPos::new(
+                origin.x + x as i32,
+                origin.y + y as i32,
+                origin.z + z as i32,
+            );
+
+            self.set_block_state(pos, block);
+        }
+    }
+
+    /// Sets the biome of the 4x4x4 biome cell containing the block position
+    /// `pos`. The previous biome of the cell is returned.
+    ///
+    /// There is no packet for changing individual biomes, so clients in view
+    /// of the chunk are sent the entire chunk again at the end of the tick.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pos` is not within a loaded chunk or is outside the
+    /// height of the instance, or if `biome` is not registered.
+    pub fn set_biome_at(
+        &mut self,
+        pos: impl Into<BlockPos>,
+        biome: BiomeId,
+    ) -> anyhow::Result<BiomeId> {
+        let pos = pos.into();
+
+        ensure!(
+            (biome.0 as usize) < self.info.biome_registry_len,
+            "{biome:?} is not registered (there are {} biomes)",
+            self.info.biome_registry_len
+        );
+
+        let Some(y) = pos
+            .y
+            .checked_sub(self.info.min_y)
+            .and_then(|y| usize::try_from(y).ok())
+            .filter(|&y| y < self.info.section_count * 16)
+        else {
+            bail!("block position {pos:?} is outside the height of the instance")
+        };
+
+        let Some(chunk) = self.chunk_mut(ChunkPos::from_block_pos(pos)) else {
+            bail!("block position {pos:?} is not in a loaded chunk")
+        };
+
+        Ok(chunk.set_biome(
+            pos.x.rem_euclid(16) as usize / 4,
+            y / 4,
+            pos.z.rem_euclid(16) as usize / 4,
+            biome,
+        ))
     }
 
     /// Writes a packet into the global packet buffer of this instance. All
@@ -336,9 +660,34 @@ impl Instance {
         }
     }
 
+    /// Writes a packet to all clients in this instance whose position is
+    /// within `radius` blocks of `center` at the end of the tick. This is
+    /// useful for localized effects such as sounds and particles.
+    pub fn write_packet_near<P>(&mut self, pkt: &P, center: impl Into<DVec3>, radius: f64)
+    where
+        P: EncodePacket + ?Sized,
+    {
+        let start = self.local_packet_buf.len();
+
+        PacketWriter::new(
+            &mut self.local_packet_buf,
+            self.info.compression_threshold,
+            &mut self.scratch,
+        )
+        .write_packet(pkt);
+
+        let end = self.local_packet_buf.len();
+
+        self.local_packets.push((center.into(), radius, start..end));
+    }
+
     /// Puts a particle effect at the given position in the world. The particle
     /// effect is visible to all players in the instance with the
     /// appropriate chunk in view.
+    ///
+    /// Does nothing if [particles are disabled].
+    ///
+    /// [particles are disabled]: Self::set_particles_enabled
     pub fn play_particle(
         &mut self,
         particle: &Particle,
@@ -348,6 +697,10 @@ impl Instance {
         max_speed: f32,
         count: i32,
     ) {
+        if !self.particles_enabled {
+            return;
+        }
+
         let position = position.into();
 
         self.write_packet_at(
@@ -363,114 +716,721 @@ impl Instance {
         );
     }
 
+    /// Puts particle effects at evenly spaced points along the line segment
+    /// from `from` to `to`, starting at `from`. The points are `spacing`
+    /// blocks apart unless that would exceed [`MAX_PARTICLE_LINE_POINTS`], in
+    /// which case the spacing is widened so that the points span the whole
+    /// segment. A zero-length segment has a single point. Returns the number
+    /// of points, which is zero if [particles are disabled].
+    ///
+    /// [particles are disabled]: Self::set_particles_enabled
+    ///
+    /// # Panics
+    ///
+    /// Panics if `spacing` is not positive.
+    pub fn play_particle_line(
+        &mut self,
+        particle: &Particle,
+        from: impl Into<DVec3>,
+        to: impl Into<DVec3>,
+        spacing: f64,
+    ) -> usize {
+        assert!(spacing > 0.0, "particle spacing must be positive");
+
+        if !self.particles_enabled {
+            return 0;
+        }
+
+        let from = from.into();
+        let to = to.into();
+        let len = from.distance(to);
+
+        let (count, step) = if len / spacing >= (MAX_PARTICLE_LINE_POINTS - 1) as f64 {
+            (
+                MAX_PARTICLE_LINE_POINTS,
+                len / (MAX_PARTICLE_LINE_POINTS - 1) as f64,
+            )
+        } else {
+            ((len / spacing) as usize + 1, spacing)
+        };
+
+        let dir = (to - from).normalize_or_zero();
+
+        for i in 0..count {
+            let pos = from + dir * (step * i as f64);
+            self.play_particle(particle, false, pos, Vec3::ZERO, 0.0, 1);
+        }
+
+        count
+    }
+
+    /// Plays a block action, such as a note block playing or a chest lid
+    /// opening, at `pos` for all players with the appropriate chunk in view.
+    /// The meaning of `action_id` and `action_param` depends on `block_type`.
+    pub fn send_block_action(
+        &mut self,
+        pos: impl Into<BlockPos>,
+        action_id: u8,
+        action_param: u8,
+        block_type: BlockKind,
+    ) {
+        let pos = pos.into();
+
+        self.write_packet_at(
+            &BlockAction {
+                position: pos,
+                action_id,
+                action_parameter: action_param,
+                block_type: VarInt(block_type.to_raw() as i32),
+            },
+            ChunkPos::from_block_pos(pos),
+        );
+    }
+
+    /// Plays a sound at `position` for all players with the appropriate chunk
+    /// in view.
+    ///
+    /// Does nothing if [sounds are disabled].
+    ///
+    /// [sounds are disabled]: Self::set_sounds_enabled
+    pub fn play_sound(
+        &mut self,
+        sound: SoundId,
+        category: SoundCategory,
+        position: impl Into<DVec3>,
+        volume: f32,
+        pitch: f32,
+    ) {
+        if !self.sounds_enabled {
+            return;
+        }
+
+        let position = position.into();
+
+        self.write_packet_at(
+            &SoundEffect {
+                id: sound,
+                category,
+                // Fixed point with three fractional bits.
+                position: position.to_array().map(|v| (v * 8.0) as i32),
+                volume,
+                pitch,
+                seed: rand::random(),
+            },
+            ChunkPos::from_dvec3(position),
+        );
+    }
+
+    /// Plays a sound which follows the entity with protocol ID `entity_id` as
+    /// it moves. The sound is sent to all players in the instance, but
+    /// clients which do not have the entity loaded will not hear it.
+    ///
+    /// Does nothing if [sounds are disabled].
+    ///
+    /// [sounds are disabled]: Self::set_sounds_enabled
+    pub fn play_sound_from_entity(
+        &mut self,
+        sound: SoundId,
+        category: SoundCategory,
+        entity_id: VarInt,
+        volume: f32,
+        pitch: f32,
+    ) {
+        if !self.sounds_enabled {
+            return;
+        }
+
+        self.write_packet(&EntitySoundEffect {
+            id: sound,
+            category,
+            entity_id,
+            volume,
+            pitch,
+            seed: rand::random(),
+        });
+    }
+
+    /// Spawns a falling block of `state` at `position` with `commands`, moving
+    /// with `velocity` in meters per second. This is subject to the same
+    /// limits as [`Self::spawn_entity`].
+    ///
+    /// Clients simulate the fall on their own. Insert [`Despawned`] on the
+    /// returned entity to remove the falling block once it has landed.
+    pub fn spawn_falling_block(
+        &mut self,
+        commands: &mut Commands,
+        position: impl Into<DVec3>,
+        state: BlockState,
+        velocity: impl Into<Vec3>,
+    ) -> anyhow::Result<Entity> {
+        let mut entity = McEntity::new(EntityKind::FallingBlock, self.entity);
+        entity.set_position(position);
+        entity.set_velocity(velocity);
+        entity.set_spawn_data(state.to_raw() as i32);
+
+        self.spawn_entity(commands, entity)
+    }
+
+    /// Spawns a projectile such as an arrow or snowball of `kind` at
+    /// `position` with `commands`, moving with `velocity` in meters per
+    /// second. `owner` is the protocol ID of the entity which shot the
+    /// projectile. This is subject to the same limits as
+    /// [`Self::spawn_entity`].
+    ///
+    /// Returns an error if `kind` is not a projectile. Clients orient and move
+    /// the projectile on their own.
+    pub fn spawn_projectile(
+        &mut self,
+        commands: &mut Commands,
+        kind: EntityKind,
+        position: impl Into<DVec3>,
+        owner: VarInt,
+        velocity: impl Into<Vec3>,
+    ) -> anyhow::Result<Entity> {
+        ensure!(is_projectile(kind), "{kind:?} is not a projectile");
+        validate_spawn_data(kind, owner.0)?;
+
+        let mut entity = McEntity::new(kind, self.entity);
+        entity.set_position(position);
+        entity.set_velocity(velocity);
+        entity.set_spawn_data(owner.0);
+
+        self.spawn_entity(commands, entity)
+    }
+
     /// Sets the action bar text of all players in the instance.
     pub fn set_action_bar(&mut self, text: impl Into<Text>) {
         self.write_packet(&SetActionBarText {
             action_bar_text: text.into().into(),
         });
     }
-}
 
-pub(crate) fn update_instances_pre_client(
-    mut instances: Query<&mut Instance>,
-    mut entities: Query<(Entity, &mut McEntity, Option<&Despawned>)>,
-    server: Res<Server>,
-) {
-    for (entity_id, entity, despawned) in &entities {
-        let pos = ChunkPos::at(entity.position().x, entity.position().z);
-        let old_pos = ChunkPos::at(entity.old_position().x, entity.old_position().z);
+    /// Sets the custom name of the entity with protocol ID `id` for all players
+    /// in the instance, and whether the name is shown when the entity is not
+    /// being looked at. Passing `None` clears the name.
+    ///
+    /// Both metadata fields are sent in a single packet.
+    ///
+    /// This and the other `set_entity_*` functions do not modify the
+    /// [`TrackedData`] of an [`McEntity`], so clients which start viewing the
+    /// entity later will not see the change.
+    pub fn set_entity_name(&mut self, id: VarInt, name: Option<Text>, always_visible: bool) {
+        self.write_entity_metadata(id, |metadata| {
+            // Custom name, an optional text component.
+            encode_metadata_field(metadata, 2, 6, &name);
+            // Custom name visible, a boolean.
+            encode_metadata_field(metadata, 3, 8, &always_visible);
+        });
+    }
 
-        let instance = entity.instance();
-        let old_instance = entity.old_instance();
+    /// Sets the bed position of the living entity with protocol ID `id` for all
+    /// players in the instance. Entities with a bed position are shown
+    /// sleeping in the bed. Passing `None` clears the bed position.
+    pub fn set_entity_bed_position(&mut self, id: VarInt, pos: Option<BlockPos>) {
+        // Sleeping position, an optional block position.
+        self.write_entity_metadata(id, |m| encode_metadata_field(m, 14, 11, &pos));
+    }
 
-        if despawned.is_some() {
-            // Entity was deleted. Remove it from the chunk it was in, if it was in a chunk
-            // at all.
-            if let Ok(mut old_instance) = instances.get_mut(old_instance) {
-                if let Some(old_cell) = old_instance.partition.get_mut(&old_pos) {
-                    if old_cell.entities.remove(&entity_id) {
-                        old_cell.outgoing.push((entity_id, None));
-                    }
-                }
-            }
-        } else if old_instance != instance {
-            // Entity changed the instance it is in. Remove it from old cell and
-            // insert it in the new cell.
+    /// Sets the remaining air of the entity with protocol ID `id` for all
+    /// players in the instance, in ticks. The bubbles shown to players are
+    /// based on this value. Entities start with 300 ticks of air.
+    pub fn set_entity_air(&mut self, id: VarInt, air: i32) {
+        // Air, an integer.
+        self.write_entity_metadata(id, |m| encode_metadata_field(m, 1, 1, &VarInt(air)));
+    }
 
-            // TODO: skip marker entity?
+    /// Sets the number of ticks the entity with protocol ID `id` has been
+    /// freezing in powder snow for all players in the instance. The ice
+    /// overlay and shaking are shown in proportion to this value, and are at
+    /// their maximum at 140 ticks.
+    pub fn set_entity_frozen_ticks(&mut self, id: VarInt, ticks: i32) {
+        // Frozen ticks, an integer.
+        self.write_entity_metadata(id, |m| encode_metadata_field(m, 7, 1, &VarInt(ticks)));
+    }
 
-            if let Ok(mut old_instance) = instances.get_mut(old_instance) {
-                if let Some(old_cell) = old_instance.partition.get_mut(&old_pos) {
-                    if old_cell.entities.remove(&entity_id) {
-                        old_cell.outgoing.push((entity_id, None));
-                    }
-                }
-            }
+    /// Writes a metadata packet which sets the fields of an entity encoded by
+    /// `fields`. Fields are encoded with [`encode_metadata_field`].
+    fn write_entity_metadata(&mut self, id: VarInt, fields: impl FnOnce(&mut Vec<u8>)) {
+        let mut metadata = vec![];
+        fields(&mut metadata);
+        metadata.push(0xff);
 
-            if let Ok(mut instance) = instances.get_mut(instance) {
-                match instance.partition.entry(pos) {
-                    Entry::Occupied(oe) => {
-                        let cell = oe.into_mut();
-                        if cell.entities.insert(entity_id) {
-                            cell.incoming.push((entity_id, None));
-                        }
-                    }
-                    Entry::Vacant(ve) => {
-                        ve.insert(PartitionCell {
-                            chunk: None,
-                            chunk_removed: false,
-                            entities: BTreeSet::from([entity_id]),
-                            incoming: vec![(entity_id, None)],
-                            outgoing: vec![],
-                            packet_buf: vec![],
-                        });
-                    }
-                }
-            }
-        } else if pos != old_pos {
-            // Entity changed its chunk position without changing instances. Remove
-            // it from old cell and insert it in new cell.
+        self.write_packet(&SetEntityMetadata {
+            entity_id: id,
+            metadata: RawBytes(&metadata),
+        });
+    }
 
-            // TODO: skip marker entity?
+    /// Rotates the entity with protocol ID `id` for all players in the
+    /// instance. The body and head are rotated independently, so the entity
+    /// can look around without turning its body. Angles are in degrees.
+    /// `on_ground` is whether the entity is reported as being on the ground.
+    pub fn set_entity_rotation(
+        &mut self,
+        id: VarInt,
+        body_yaw: f32,
+        head_yaw: f32,
+        pitch: f32,
+        on_ground: bool,
+    ) {
+        self.write_packet(&UpdateEntityRotation {
+            entity_id: id,
+            yaw: ByteAngle::from_degrees(body_yaw),
+            pitch: ByteAngle::from_degrees(pitch),
+            on_ground,
+        });
 
-            if let Ok(mut instance) = instances.get_mut(instance) {
-                if let Some(old_cell) = instance.partition.get_mut(&old_pos) {
-                    if old_cell.entities.remove(&entity_id) {
-                        old_cell.outgoing.push((entity_id, Some(pos)));
-                    }
-                }
+        self.write_packet(&SetHeadRotation {
+            entity_id: id,
+            head_yaw: ByteAngle::from_degrees(head_yaw),
+        });
+    }
 
-                match instance.partition.entry(pos) {
-                    Entry::Occupied(oe) => {
-                        let cell = oe.into_mut();
-                        if cell.entities.insert(entity_id) {
-                            cell.incoming.push((entity_id, Some(old_pos)));
-                        }
-                    }
-                    Entry::Vacant(ve) => {
-                        ve.insert(PartitionCell {
-                            chunk: None,
-                            chunk_removed: false,
-                            entities: BTreeSet::from([entity_id]),
-                            incoming: vec![(entity_id, Some(old_pos))],
-                            outgoing: vec![],
-                            packet_buf: vec![],
-                        });
-                    }
-                }
-            }
-        } else {
-            // The entity didn't change its chunk position so there is nothing
-            // we need to do.
-        }
+    /// The number of ticks this instance has existed for. This increases by
+    /// one every tick.
+    pub fn world_age(&self) -> i64 {
+        self.world_age
     }
 
-    let mut scratch_1 = vec![];
+    /// The current time of day in ticks. 6000 is noon, 12000 is sunset, and
+    /// 18000 is midnight. The time of day is not wrapped around, so the
+    /// number of days which have passed is `time_of_day / 24000`.
+    pub fn time_of_day(&self) -> i64 {
+        self.time_of_day
+    }
+
+    /// Sets the time of day of this instance. All clients in the instance are
+    /// updated immediately.
+    pub fn set_time_of_day(&mut self, time_of_day: i64) {
+        self.time_of_day = time_of_day;
+        self.write_packet(&self.time_packet());
+    }
+
+    /// If the time of day advances every tick. This is `true` by default.
+    pub fn daylight_cycle(&self) -> bool {
+        self.daylight_cycle
+    }
+
+    /// Sets if the time of day advances every tick. Clients stop advancing the
+    /// time on their own when the daylight cycle is disabled.
+    pub fn set_daylight_cycle(&mut self, daylight_cycle: bool) {
+        if self.daylight_cycle != daylight_cycle {
+            self.daylight_cycle = daylight_cycle;
+            self.write_packet(&self.time_packet());
+        }
+    }
+
+    /// If the particle helpers such as [`Self::play_particle`] send anything.
+    /// This is `true` by default.
+    pub fn particles_enabled(&self) -> bool {
+        self.particles_enabled
+    }
+
+    /// Sets if the particle helpers send anything. Disabling particles can
+    /// save bandwidth in busy instances or keep effects out of minigames.
+    /// Packets written directly with [`Self::write_packet`] are unaffected.
+    pub fn set_particles_enabled(&mut self, enabled: bool) {
+        self.particles_enabled = enabled;
+    }
+
+    /// If the sound helpers such as [`Self::play_sound_from_entity`] send
+    /// anything. This is `true` by default.
+    pub fn sounds_enabled(&self) -> bool {
+        self.sounds_enabled
+    }
+
+    /// Sets if the sound helpers send anything. Like
+    /// [`Self::set_particles_enabled`], this does not affect packets written
+    /// directly.
+    pub fn set_sounds_enabled(&mut self, enabled: bool) {
+        self.sounds_enabled = enabled;
+    }
+
+    /// The number of Minecraft entities in this instance, including entities
+    /// spawned with [`Self::spawn_entity`] this tick.
+    pub fn entity_count(&self) -> usize {
+        self.entity_count + self.pending_entity_count
+    }
+
+    /// The maximum number of entities allowed by [`Self::spawn_entity`], or
+    /// `None` if there is no limit. This is `None` by default.
+    pub fn max_entities(&self) -> Option<usize> {
+        self.max_entities
+    }
+
+    /// Sets the maximum number of entities allowed by [`Self::spawn_entity`].
+    /// Entities already in the instance are not affected.
+    pub fn set_max_entities(&mut self, max_entities: Option<usize>) {
+        self.max_entities = max_entities;
+    }
+
+    /// Returns the maximum number of [`BlockUpdateEvent`]s sent for this
+    /// instance per tick, or `None` if there is no limit. This is `None` by
+    /// default.
+    pub fn block_update_event_limit(&self) -> Option<usize> {
+        self.block_update_event_limit
+    }
+
+    /// Sets the maximum number of [`BlockUpdateEvent`]s sent for this
+    /// instance per tick. Changes past the limit, such as those made by a
+    /// large [`Self::paste_structure`], do not produce events and a warning is
+    /// logged. `None` removes the limit.
+    pub fn set_block_update_event_limit(&mut self, limit: Option<usize>) {
+        self.block_update_event_limit = limit;
+    }
+
+    /// The Y level below which clients in this instance are sent
+    /// [`VoidFallEvent`]s, or `None` if the void is not handled. This is
+    /// `None` by default.
+    pub fn void_level(&self) -> Option<f64> {
+        self.void_level
+    }
+
+    /// Sets the Y level below which clients in this instance are sent
+    /// [`VoidFallEvent`]s.
+    pub fn set_void_level(&mut self, void_level: Option<f64>) {
+        self.void_level = void_level;
+    }
+
+    /// Spawns `entity` with `commands`, returning an error if the
+    /// [entity limit] of this instance has been reached.
+    ///
+    /// An error is also returned if `entity` is located in a different
+    /// instance, or if this instance has not been spawned yet. Instances are
+    /// known to be spawned from the first tick after they are added to the
+    /// world.
+    ///
+    /// [entity limit]: Self::max_entities
+    pub fn spawn_entity(
+        &mut self,
+        commands: &mut Commands,
+        entity: McEntity,
+    ) -> anyhow::Result<Entity> {
+        ensure!(
+            self.entity != NULL_ENTITY,
+            "instance has not been spawned yet"
+        );
+        ensure!(
+            entity.instance() == self.entity,
+            "entity located in instance {:?} cannot be spawned in instance {:?}",
+            entity.instance(),
+            self.entity
+        );
+
+        if let Some(max) = self.max_entities {
+            ensure!(
+                self.entity_count() < max,
+                "instance entity limit of {max} has been reached"
+            );
+        }
+
+        self.pending_entity_count += 1;
+
+        Ok(commands.spawn(entity).id())
+    }
+
+    /// Spawns a dropped item entity holding `stack` at `position` with
+    /// `commands`. This is subject to the same limits as
+    /// [`Self::spawn_entity`].
+    pub fn spawn_item(
+        &mut self,
+        commands: &mut Commands,
+        position: impl Into<DVec3>,
+        stack: ItemStack,
+    ) -> anyhow::Result<Entity> {
+        let mut entity = McEntity::new(EntityKind::Item, self.entity);
+        entity.set_position(position);
+
+        if let TrackedData::Item(item) = entity.data_mut() {
+            item.set_stack(stack);
+        }
+
+        self.spawn_entity(commands, entity)
+    }
+
+    /// If the packet buffers of this instance are verified to be unmodified
+    /// while they are being sent to clients. This is `false` by default.
+    pub fn verify_packet_buffers(&self) -> bool {
+        self.packet_buf_checksums.is_some()
+    }
+
+    /// Enables or disables packet buffer verification. When enabled, the
+    /// packet buffers of this instance are checksummed once all packets for
+    /// the tick have been written, and a panic occurs if the buffers were
+    /// modified by the time they have been sent to every client.
+    ///
+    /// This is a debugging aid for catching accidental mutation of shared
+    /// packet data and has a performance cost.
+    pub fn set_verify_packet_buffers(&mut self, verify: bool) {
+        if verify != self.verify_packet_buffers() {
+            self.packet_buf_checksums = verify.then(PacketBufChecksums::default);
+        }
+    }
+
+    /// Returns a human-readable summary of the state of this instance for
+    /// debugging purposes. `weather` is the [`Weather`] component of the
+    /// instance entity, if any.
+    ///
+    /// The format of the report is unspecified and may change.
+    pub fn debug_report(&self, weather: Option<&Weather>) -> String {
+        let loaded_chunks = self.chunks().count();
+        let viewed_chunks = self.chunks().filter(|(_, c)| c.is_viewed()).count();
+
+        let mut report = String::new();
+
+        let _ = writeln!(report, "dimension: {:?}", self.info.dimension);
+        let _ = writeln!(
+            report,
+            "loaded chunks: {loaded_chunks} ({viewed_chunks} in view of clients)"
+        );
+        let _ = writeln!(report, "entities: {}", self.entity_count());
+        let _ = writeln!(report, "world age: {}", self.world_age);
+        let _ = writeln!(
+            report,
+            "time of day: {} (daylight cycle {})",
+            self.time_of_day,
+            if self.daylight_cycle { "on" } else { "off" }
+        );
+
+        match weather {
+            Some(Weather { rain, thunder }) => {
+                let _ = writeln!(report, "weather: rain {rain:?}, thunder {thunder:?}");
+            }
+            None => {
+                let _ = writeln!(report, "weather: clear");
+            }
+        }
+
+        report
+    }
+
+    /// Returns the packet which synchronizes the time of clients with this
+    /// instance.
+    pub(crate) fn time_packet(&self) -> UpdateTime {
+        // A negative time of day tells the client not to advance the time. Zero
+        // can't be negated, so -1 is used instead.
+        let time_of_day = if self.daylight_cycle {
+            self.time_of_day
+        } else {
+            (-self.time_of_day).min(-1)
+        };
+
+        UpdateTime {
+            world_age: self.world_age,
+            time_of_day,
+        }
+    }
+}
+
+/// The number of ticks between [`UpdateTime`] packets. Clients advance the
+/// time on their own in between.
+const TIME_UPDATE_PERIOD: i64 = 20;
+
+/// The maximum number of points [`Instance::play_particle_line`] puts
+/// particles at.
+pub const MAX_PARTICLE_LINE_POINTS: usize = 1024;
+
+pub(crate) fn update_instance_time(mut instances: Query<&mut Instance>) {
+    for mut instance in &mut instances {
+        instance.world_age += 1;
+
+        if instance.daylight_cycle {
+            instance.time_of_day += 1;
+        }
+
+        if instance.world_age % TIME_UPDATE_PERIOD == 0 {
+            let pkt = instance.time_packet();
+            instance.write_packet(&pkt);
+        }
+    }
+}
+
+pub(crate) fn send_block_update_events(
+    mut instances: Query<(Entity, &mut Instance)>,
+    mut events: EventWriter<BlockUpdateEvent>,
+) {
+    for (instance_ent, mut instance) in &mut instances {
+        if instance.dropped_block_updates > 0 {
+            warn!(
+                "{} block updates in instance {instance_ent:?} exceeded the limit of {} events \
+                 per tick",
+                instance.dropped_block_updates,
+                instance.block_update_event_limit.unwrap_or_default()
+            );
+            instance.dropped_block_updates = 0;
+        }
+
+        for (position, old, new) in instance.block_updates.drain(..) {
+            events.send(BlockUpdateEvent {
+                instance: instance_ent,
+                position,
+                old,
+                new,
+            });
+        }
+    }
+}
+
+pub(crate) fn send_void_fall_events(
+    clients: Query<(Entity, &Client)>,
+    instances: Query<&Instance>,
+    mut events: EventWriter<VoidFallEvent>,
+) {
+    for (client_ent, client) in &clients {
+        let Ok(instance) = instances.get(client.instance()) else {
+            continue
+        };
+
+        if let Some(void_level) = instance.void_level {
+            if client.position().y < void_level {
+                events.send(VoidFallEvent {
+                    client: client_ent,
+                    instance: client.instance(),
+                    position: client.position(),
+                });
+            }
+        }
+    }
+}
+
+pub(crate) fn init_instances(mut instances: Query<(Entity, &mut Instance), Added<Instance>>) {
+    for (entity, mut instance) in &mut instances {
+        instance.entity = entity;
+    }
+}
+
+pub(crate) fn update_instances_pre_client(
+    mut instances: Query<&mut Instance>,
+    mut entities: Query<(Entity, &mut McEntity, Option<&Despawned>)>,
+    server: Res<Server>,
+    lod: Res<EntityLodSettings>,
+) {
+    let current_tick = server.current_tick();
+
+    let mut scratch_1 = vec![];
     let mut scratch_2 = vec![];
 
+    for (entity_id, entity, despawned) in &entities {
+        let pos = ChunkPos::at(entity.position().x, entity.position().z);
+        let old_pos = ChunkPos::at(entity.old_position().x, entity.old_position().z);
+
+        let instance = entity.instance();
+        let old_instance = entity.old_instance();
+
+        if despawned.is_some() {
+            // Entity was deleted. Remove it from the chunk it was in, if it was in a chunk
+            // at all.
+            if let Ok(mut old_instance) = instances.get_mut(old_instance) {
+                let old_instance = old_instance.as_mut();
+
+                if let Some(old_cell) = old_instance.partition.get_mut(&old_pos) {
+                    if old_cell.entities.remove(&entity_id) {
+                        old_cell.outgoing.push((entity_id, None));
+                        old_instance.entity_count -= 1;
+
+                        // Dismount the passengers so they are not left riding an entity
+                        // which no longer exists.
+                        if !entity.passengers().is_empty() {
+                            PacketWriter::new(
+                                &mut old_cell.packet_buf,
+                                server.compression_threshold(),
+                                &mut scratch_2,
+                            )
+                            .write_packet(&SetPassengers {
+                                entity_id: VarInt(entity.protocol_id()),
+                                passengers: vec![],
+                            });
+                        }
+                    }
+                }
+            }
+        } else if old_instance != instance {
+            // Entity changed the instance it is in. Remove it from old cell and
+            // insert it in the new cell.
+
+            // TODO: skip marker entity?
+
+            if let Ok(mut old_instance) = instances.get_mut(old_instance) {
+                let old_instance = old_instance.as_mut();
+
+                if let Some(old_cell) = old_instance.partition.get_mut(&old_pos) {
+                    if old_cell.entities.remove(&entity_id) {
+                        old_cell.outgoing.push((entity_id, None));
+                        old_instance.entity_count -= 1;
+                    }
+                }
+            }
+
+            if let Ok(mut instance) = instances.get_mut(instance) {
+                let instance = instance.as_mut();
+
+                match instance.partition.entry(pos) {
+                    Entry::Occupied(oe) => {
+                        let cell = oe.into_mut();
+                        if cell.entities.insert(entity_id) {
+                            cell.incoming.push((entity_id, None));
+                            instance.entity_count += 1;
+                        }
+                    }
+                    Entry::Vacant(ve) => {
+                        ve.insert(PartitionCell {
+                            chunk: None,
+                            chunk_removed: false,
+                            entities: BTreeSet::from([entity_id]),
+                            incoming: vec![(entity_id, None)],
+                            outgoing: vec![],
+                            packet_buf: vec![],
+                            movement_bufs: Default::default(),
+                        });
+                        instance.entity_count += 1;
+                    }
+                }
+            }
+        } else if pos != old_pos {
+            // Entity changed its chunk position without changing instances. Remove
+            // it from old cell and insert it in new cell.
+
+            // TODO: skip marker entity?
+
+            if let Ok(mut instance) = instances.get_mut(instance) {
+                if let Some(old_cell) = instance.partition.get_mut(&old_pos) {
+                    if old_cell.entities.remove(&entity_id) {
+                        old_cell.outgoing.push((entity_id, Some(pos)));
+                    }
+                }
+
+                match instance.partition.entry(pos) {
+                    Entry::Occupied(oe) => {
+                        let cell = oe.into_mut();
+                        if cell.entities.insert(entity_id) {
+                            cell.incoming.push((entity_id, Some(old_pos)));
+                        }
+                    }
+                    Entry::Vacant(ve) => {
+                        ve.insert(PartitionCell {
+                            chunk: None,
+                            chunk_removed: false,
+                            entities: BTreeSet::from([entity_id]),
+                            incoming: vec![(entity_id, Some(old_pos))],
+                            outgoing: vec![],
+                            packet_buf: vec![],
+                            movement_bufs: Default::default(),
+                        });
+                    }
+                }
+            }
+        } else {
+            // The entity didn't change its chunk position so there is nothing
+            // we need to do.
+        }
+    }
+
     for instance in &mut instances {
         let instance = instance.into_inner();
 
+        // Entities spawned with `spawn_entity` are in the partition now.
+        instance.pending_entity_count = 0;
+
         for (&pos, cell) in &mut instance.partition {
             // Cache chunk update packets into the packet buffer of this cell.
             if let Some(chunk) = &mut cell.chunk {
@@ -492,6 +1452,8 @@ pub(crate) fn update_instances_pre_client(
                     .expect("missing entity in partition cell");
 
                 if despawned.is_some() {
+                    entity.self_update_range = 0..0;
+                    entity.self_movement_range = 0..0;
                     continue;
                 }
 
@@ -508,6 +1470,54 @@ pub(crate) fn update_instances_pre_client(
                 let end = cell.packet_buf.len();
 
                 entity.self_update_range = start..end;
+
+                if entity.movement_modified() {
+                    entity.last_moved_tick = Some(current_tick);
+                }
+
+                let start = cell.movement_bufs[0].len();
+
+                let writer = PacketWriter::new(
+                    &mut cell.movement_bufs[0],
+                    server.compression_threshold(),
+                    &mut scratch_2,
+                );
+
+                entity.write_movement_packets(writer);
+
+                let end = cell.movement_bufs[0].len();
+
+                entity.self_movement_range = start..end;
+
+                if !lod.enabled {
+                    continue;
+                }
+
+                // Clients in the other bands get the absolute position of every
+                // entity which moved since they were last sent movement.
+                for (band, buf) in cell.movement_bufs.iter_mut().enumerate().skip(1) {
+                    let interval = 1 << band;
+
+                    if current_tick % interval == 0
+                        && entity
+                            .last_moved_tick
+                            .map_or(false, |tick| tick > current_tick - interval)
+                    {
+                        let writer =
+                            PacketWriter::new(buf, server.compression_threshold(), &mut scratch_2);
+
+                        entity.write_teleport_packets(writer);
+                    }
+                }
+            }
+        }
+
+        if let Some(checksums) = &mut instance.packet_buf_checksums {
+            checksums.instance = checksum(&instance.packet_buf);
+            checksums.cells.clear();
+
+            for (&pos, cell) in &instance.partition {
+                checksums.cells.insert(pos, checksum(&cell.packet_buf));
             }
         }
     }
@@ -515,8 +1525,31 @@ pub(crate) fn update_instances_pre_client(
 
 pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>) {
     for mut instance in &mut instances {
+        if let Some(checksums) = &instance.packet_buf_checksums {
+            assert_eq!(
+                checksum(&instance.packet_buf),
+                checksums.instance,
+                "instance packet buffer was modified while being sent to clients"
+            );
+
+            for (pos, cell) in &instance.partition {
+                if let Some(&sum) = checksums.cells.get(pos) {
+                    assert_eq!(
+                        checksum(&cell.packet_buf),
+                        sum,
+                        "packet buffer of cell at {pos:?} was modified while being sent to clients"
+                    );
+                }
+            }
+        }
+
         instance.partition.retain(|_, cell| {
             cell.packet_buf.clear();
+
+            for buf in &mut cell.movement_bufs {
+                buf.clear();
+            }
+
             cell.chunk_removed = false;
             cell.incoming.clear();
             cell.outgoing.clear();
@@ -529,6 +1562,51 @@ pub(crate) fn update_instances_post_client(mut instances: Query<&mut Instance>)
         });
 
         instance.packet_buf.clear();
+        instance.local_packet_buf.clear();
+        instance.local_packets.clear();
+    }
+}
+
+/// Removes Minecraft entities from instances when their [`McEntity`] component
+/// was removed from the world directly instead of through [`Despawned`].
+/// Clients in view of the entities are sent a packet removing them.
+pub(crate) fn remove_deleted_entities(
+    removed: RemovedComponents<McEntity>,
+    mut instances: Query<&mut Instance>,
+    mut manager: ResMut<McEntityManager>,
+    server: Res<Server>,
+) {
+    let removed: Vec<_> = removed
+        .iter()
+        .filter_map(|entity| Some((entity, manager.remove_entity(entity)?)))
+        .collect();
+
+    if removed.is_empty() {
+        return;
+    }
+
+    let mut scratch = vec![];
+
+    for instance in &mut instances {
+        let instance = instance.into_inner();
+
+        for cell in instance.partition.values_mut() {
+            for &(entity, protocol_id) in &removed {
+                if cell.entities.remove(&entity) {
+                    instance.entity_count -= 1;
+
+                    let mut writer = PacketWriter::new(
+                        &mut cell.packet_buf,
+                        server.compression_threshold(),
+                        &mut scratch,
+                    );
+
+                    writer.write_packet(&RemoveEntitiesEncode {
+                        entity_ids: &[VarInt(protocol_id)],
+                    });
+                }
+            }
+        }
     }
 }
 
@@ -548,3 +1626,1194 @@ pub(crate) fn check_instance_invariants(instances: Query<&Instance>, entities: Q
     let _ = instances;
     let _ = entities;
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::{App, CoreStage};
+    use bevy_ecs::system::CommandQueue;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::{ident, ItemKind};
+
+    use super::*;
+    use crate::biome::Biome;
+    use crate::config::ServerPlugin;
+    use crate::entity::OnGround;
+    use crate::inventory::{Inventory, InventoryKind};
+    use crate::unit_test::util::{
+        create_mock_client, gen_client_info, scenario_single_client,
+        scenario_single_client_with_plugin,
+    };
+    use crate::{assert_packet_count, assert_packet_order};
+
+    #[test]
+    fn time_advances_and_is_sent_periodically() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+
+        // The time is sent to clients when they join.
+        {
+            let sent_packets = client_helper.collect_sent().unwrap();
+            assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateTime(_));
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let start_age = instance.world_age();
+        let start_time = instance.time_of_day();
+
+        for _ in 0..TIME_UPDATE_PERIOD * 2 {
+            app.update();
+        }
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.world_age(), start_age + TIME_UPDATE_PERIOD * 2);
+        assert_eq!(instance.time_of_day(), start_time + TIME_UPDATE_PERIOD * 2);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::UpdateTime(_));
+
+        // Disabling the daylight cycle stops the time of day.
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_daylight_cycle(false);
+        let stopped_time = instance.time_of_day();
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.time_of_day(), stopped_time);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::UpdateTime(p) if p.time_of_day == -stopped_time
+        )));
+    }
+
+    #[test]
+    fn chunk_generator_fills_new_chunks() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        instance.set_chunk_generator(|_: ChunkPos, chunk: &mut Chunk| {
+            for z in 0..16 {
+                for x in 0..16 {
+                    chunk.set_block_state(x, 0, z, BlockState::BEDROCK);
+                }
+            }
+        });
+
+        let chunk = instance.load_chunk([5, 5]);
+        assert_eq!(chunk.block_state(3, 0, 7), BlockState::BEDROCK);
+        assert_eq!(chunk.block_state(3, 1, 7), BlockState::AIR);
+        assert!(!chunk.is_dirty());
+
+        // Existing chunks are left alone.
+        chunk.set_block_state(3, 0, 7, BlockState::STONE);
+        let chunk = instance.load_chunk([5, 5]);
+        assert_eq!(chunk.block_state(3, 0, 7), BlockState::STONE);
+
+        instance.remove_chunk_generator();
+        let chunk = instance.load_chunk([6, 5]);
+        assert_eq!(chunk.block_state(3, 0, 7), BlockState::AIR);
+    }
+
+    #[test]
+    fn packets_near_position_only_sent_to_close_clients() {
+        let mut app = App::new();
+        let (near_ent, mut near_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(near_ent).unwrap().instance();
+
+        let (mut far, mut far_helper) = create_mock_client(gen_client_info("far"));
+        far.set_instance(instance_ent);
+        far.set_position([40.0, 64.0, 0.0]);
+        app.world.spawn((
+            far,
+            Inventory::new(InventoryKind::Player),
+            OnGround::default(),
+        ));
+
+        app.world
+            .get_mut::<Client>(near_ent)
+            .unwrap()
+            .set_position([3.0, 64.0, 4.0]);
+
+        app.update();
+        near_helper.clear_sent();
+        far_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.write_packet_near(
+            &SetActionBarText {
+                action_bar_text: Text::from("nearby").into(),
+            },
+            [0.0, 64.0, 0.0],
+            5.0,
+        );
+
+        app.update();
+
+        let sent_packets = near_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetActionBarText(_));
+
+        let sent_packets = far_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetActionBarText(_));
+
+        // Local packets are only sent for one tick.
+        app.update();
+
+        let sent_packets = near_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::SetActionBarText(_));
+    }
+
+    #[test]
+    fn particle_line_points() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        let count =
+            instance.play_particle_line(&Particle::Flame, [1.0, 64.0, 1.0], [3.0, 64.0, 1.0], 0.5);
+        assert_eq!(count, 5);
+
+        // Zero-length lines have a single point.
+        let count =
+            instance.play_particle_line(&Particle::Flame, [5.0, 70.0, 5.0], [5.0, 70.0, 5.0], 1.0);
+        assert_eq!(count, 1);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        let positions: Vec<_> = sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::ParticleS2c(p) => Some(p.position),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(
+            positions,
+            [
+                [1.0, 64.0, 1.0],
+                [1.5, 64.0, 1.0],
+                [2.0, 64.0, 1.0],
+                [2.5, 64.0, 1.0],
+                [3.0, 64.0, 1.0],
+                [5.0, 70.0, 5.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn long_particle_lines_are_capped() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        let count = instance.play_particle_line(
+            &Particle::Flame,
+            [0.0, 0.0, 0.0],
+            [1_000_000.0, 0.0, 0.0],
+            0.1,
+        );
+        assert_eq!(count, MAX_PARTICLE_LINE_POINTS);
+    }
+
+    #[test]
+    fn note_block_action_encoding() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.send_block_action([1, 64, 2], 0, 0, BlockKind::NoteBlock);
+        // Chunks which are not loaded are ignored.
+        instance.send_block_action([500, 64, 500], 0, 0, BlockKind::NoteBlock);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::BlockAction(_));
+
+        let pkt = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::BlockAction(p) => Some(p),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(pkt.position, BlockPos::new(1, 64, 2));
+        assert_eq!(pkt.action_id, 0);
+        assert_eq!(pkt.action_parameter, 0);
+        assert_eq!(pkt.block_type.0, BlockKind::NoteBlock.to_raw() as i32);
+
+        let mut bytes = vec![];
+        pkt.encode_packet(&mut bytes).unwrap();
+
+        let mut expected = vec![];
+        VarInt(0x08).encode(&mut expected).unwrap();
+        BlockPos::new(1, 64, 2).encode(&mut expected).unwrap();
+        expected.extend([0, 0]);
+        VarInt(BlockKind::NoteBlock.to_raw() as i32)
+            .encode(&mut expected)
+            .unwrap();
+
+        assert_eq!(bytes, expected);
+    }
+
+    #[test]
+    fn play_sound_from_entity_packet() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.play_sound_from_entity(
+            SoundId::Reference { id: VarInt(42) },
+            SoundCategory::Hostile,
+            VarInt(7),
+            0.5,
+            1.5,
+        );
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntitySoundEffect(_));
+
+        let pkt = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::EntitySoundEffect(p) => Some(p),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(pkt.id, SoundId::Reference { id: VarInt(42) });
+        assert_eq!(pkt.category, SoundCategory::Hostile);
+        assert_eq!(pkt.entity_id, VarInt(7));
+        assert_eq!(pkt.volume, 0.5);
+        assert_eq!(pkt.pitch, 1.5);
+    }
+
+    #[test]
+    fn disabled_effects_are_not_sent() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_particles_enabled(false);
+        instance.set_sounds_enabled(false);
+
+        instance.play_particle(&Particle::Flame, false, DVec3::ONE, Vec3::ZERO, 0.0, 1);
+        assert_eq!(
+            instance.play_particle_line(&Particle::Flame, [0.0; 3], [4.0, 0.0, 0.0], 1.0),
+            0
+        );
+        instance.play_sound_from_entity(
+            SoundId::Reference { id: VarInt(1) },
+            SoundCategory::Ambient,
+            VarInt(1),
+            1.0,
+            1.0,
+        );
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::ParticleS2c(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::EntitySoundEffect(_));
+
+        // Effects are sent again once re-enabled.
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_particles_enabled(true);
+        instance.set_sounds_enabled(true);
+
+        instance.play_particle(&Particle::Flame, false, DVec3::ONE, Vec3::ZERO, 0.0, 1);
+        instance.play_sound_from_entity(
+            SoundId::Reference { id: VarInt(1) },
+            SoundCategory::Ambient,
+            VarInt(1),
+            1.0,
+            1.0,
+        );
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ParticleS2c(_));
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::EntitySoundEffect(_));
+    }
+
+    #[test]
+    fn set_biome_at_updates_biome_cell() {
+        let mut app = App::new();
+        let biomes = [
+            Biome::default(),
+            Biome {
+                name: ident!("desert"),
+                ..Default::default()
+            },
+        ];
+        let plugin = ServerPlugin::new(()).with_biomes(biomes);
+        let (client_ent, mut client_helper) = scenario_single_client_with_plugin(&mut app, plugin);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        let min_y = instance.info.min_y;
+
+        let desert = BiomeId(1);
+
+        let old = instance.set_biome_at([5, 70, 9], desert).unwrap();
+        assert_eq!(old, BiomeId::default());
+
+        // Only the 4x4x4 cell containing the position is changed.
+        let chunk = instance.chunk([0, 0]).unwrap();
+        let y = (70 - min_y) as usize / 4;
+        assert_eq!(chunk.biome(1, y, 2), desert);
+        assert_eq!(chunk.biome(0, y, 2), BiomeId::default());
+        assert_eq!(chunk.biome(1, y + 1, 2), BiomeId::default());
+
+        // Out of loaded range or unregistered biomes are errors.
+        assert!(instance.set_biome_at([5, 70, -1], desert).is_err());
+        assert!(instance.set_biome_at([5, min_y - 1, 9], desert).is_err());
+        assert!(instance.set_biome_at([5, 70, 9], BiomeId(2)).is_err());
+
+        app.update();
+
+        // The chunk is sent again with the new biome.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::ChunkDataAndUpdateLight(_));
+    }
+
+    #[test]
+    fn spawn_falling_block_packet() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app
+            .world
+            .entity_mut(instance_ent)
+            .remove::<Instance>()
+            .unwrap();
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let sand_ent = instance
+            .spawn_falling_block(
+                &mut commands,
+                [1.5, 70.0, 2.5],
+                BlockState::SAND,
+                [0.0, 5.0, -2.0],
+            )
+            .unwrap();
+        let gravel_ent = instance
+            .spawn_falling_block(
+                &mut commands,
+                [3.5, 70.0, 3.5],
+                BlockState::GRAVEL,
+                Vec3::ZERO,
+            )
+            .unwrap();
+
+        queue.apply(&mut app.world);
+        app.world.entity_mut(instance_ent).insert(instance);
+
+        app.update();
+
+        let sand_id = VarInt(app.world.get::<McEntity>(sand_ent).unwrap().protocol_id());
+        let gravel_id = VarInt(app.world.get::<McEntity>(gravel_ent).unwrap().protocol_id());
+        assert_ne!(sand_id, gravel_id);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 2, S2cPlayPacket::SpawnEntity(_));
+
+        let pkt = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::SpawnEntity(p) if p.entity_id == sand_id => Some(p),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(pkt.kind.0, EntityKind::FallingBlock as i32);
+        assert_eq!(pkt.position, [1.5, 70.0, 2.5]);
+        assert_eq!(pkt.data.0, BlockState::SAND.to_raw() as i32);
+        // 8000 units per block per tick at 20 ticks per second.
+        assert_eq!(pkt.velocity, [0, 2000, -800]);
+
+        // Falling blocks are removed like any other entity.
+        app.world.entity_mut(sand_ent).insert(Despawned);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::RemoveEntities(p) if p.entity_ids.contains(&sand_id)
+        )));
+    }
+
+    #[test]
+    fn spawn_projectile_packet() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app
+            .world
+            .entity_mut(instance_ent)
+            .remove::<Instance>()
+            .unwrap();
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let arrow_ent = instance
+            .spawn_projectile(
+                &mut commands,
+                EntityKind::Arrow,
+                [1.5, 71.5, 2.5],
+                VarInt(42),
+                [10.0, -1.0, 0.5],
+            )
+            .unwrap();
+
+        // Only projectiles can be spawned.
+        for kind in [
+            EntityKind::Player,
+            EntityKind::Marker,
+            EntityKind::ItemFrame,
+        ] {
+            assert!(instance
+                .spawn_projectile(&mut commands, kind, DVec3::ZERO, VarInt(42), Vec3::ZERO)
+                .is_err());
+        }
+
+        queue.apply(&mut app.world);
+        app.world.entity_mut(instance_ent).insert(instance);
+
+        app.update();
+
+        let arrow_id = VarInt(app.world.get::<McEntity>(arrow_ent).unwrap().protocol_id());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        let pkt = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::SpawnEntity(p) if p.entity_id == arrow_id => Some(p),
+                _ => None,
+            })
+            .unwrap();
+
+        assert_eq!(pkt.kind.0, EntityKind::Arrow as i32);
+        assert_eq!(pkt.position, [1.5, 71.5, 2.5]);
+        assert_eq!(pkt.data, VarInt(42));
+        assert_eq!(pkt.velocity, [4000, -400, 200]);
+    }
+
+    #[test]
+    fn only_viewed_chunks_are_ticking() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.insert_chunk([100, 100], Chunk::default());
+
+        app.update();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        let ticking: Vec<_> = instance.ticking_chunks().map(|(pos, _)| pos).collect();
+        assert_eq!(ticking, [ChunkPos::new(0, 0)]);
+
+        let ticking: Vec<_> = instance.ticking_chunks_mut().map(|(pos, _)| pos).collect();
+        assert_eq!(ticking, [ChunkPos::new(0, 0)]);
+    }
+
+    #[test]
+    fn unload_empty_chunks_removes_unviewed_air_chunks() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.insert_chunk([100, 100], Chunk::default());
+        instance
+            .load_chunk([101, 100])
+            .set_block_state(0, 0, 0, BlockState::STONE);
+
+        app.update();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        assert!(instance.chunk([100, 100]).unwrap().is_all_air());
+
+        assert_eq!(instance.unload_empty_chunks(), 1);
+
+        // Viewed chunks and chunks with blocks are kept.
+        assert!(instance.chunk([0, 0]).is_some());
+        assert!(instance.chunk([100, 100]).is_none());
+        assert!(instance.chunk([101, 100]).is_some());
+
+        assert_eq!(instance.unload_empty_chunks(), 0);
+    }
+
+    #[test]
+    fn block_update_events_observed_by_systems() {
+        #[derive(Resource, Default)]
+        struct Observed(Vec<BlockUpdateEvent>);
+
+        fn observe(mut events: EventReader<BlockUpdateEvent>, mut observed: ResMut<Observed>) {
+            observed.0.extend(events.iter().copied());
+        }
+
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        app.init_resource::<Observed>().add_system(observe);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.load_chunk([0, 0]);
+
+        instance.set_block_state([1, 2, 3], BlockState::STONE);
+        // Setting a block to its current state is not an update.
+        instance.set_block_state([1, 2, 3], BlockState::STONE);
+        instance.set_block_state([1, 2, 3], BlockState::DIRT);
+
+        app.update();
+        app.update();
+
+        let observed = &app.world.resource::<Observed>().0;
+        assert_eq!(
+            observed,
+            &[
+                BlockUpdateEvent {
+                    instance: instance_ent,
+                    position: BlockPos::new(1, 2, 3),
+                    old: BlockState::AIR,
+                    new: BlockState::STONE,
+                },
+                BlockUpdateEvent {
+                    instance: instance_ent,
+                    position: BlockPos::new(1, 2, 3),
+                    old: BlockState::STONE,
+                    new: BlockState::DIRT,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn block_update_events_are_limited_per_tick() {
+        #[derive(Resource, Default)]
+        struct Observed(usize);
+
+        fn observe(mut events: EventReader<BlockUpdateEvent>, mut observed: ResMut<Observed>) {
+            observed.0 += events.iter().count();
+        }
+
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        app.init_resource::<Observed>().add_system(observe);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.load_chunk([0, 0]);
+        instance.set_block_update_event_limit(Some(1000));
+
+        // More changes than the limit.
+        for y in 0..4 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    instance.set_block_state([x, y, z], BlockState::STONE);
+                }
+            }
+        }
+
+        app.update();
+        app.update();
+
+        assert_eq!(app.world.resource::<Observed>().0, 1000);
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.dropped_block_updates, 0);
+
+        // Without a limit, every change is an event.
+        instance.set_block_update_event_limit(None);
+
+        for y in 0..4 {
+            for z in 0..16 {
+                for x in 0..16 {
+                    instance.set_block_state([x, y, z], BlockState::DIRT);
+                }
+            }
+        }
+
+        app.update();
+        app.update();
+
+        assert_eq!(app.world.resource::<Observed>().0, 1000 + 1024);
+    }
+
+    #[test]
+    fn void_fall_events_sent_below_void_level() {
+        #[derive(Resource, Default)]
+        struct Observed(Vec<VoidFallEvent>);
+
+        fn rescue(
+            mut events: EventReader<VoidFallEvent>,
+            mut clients: Query<&mut Client>,
+            mut observed: ResMut<Observed>,
+        ) {
+            for event in events.iter() {
+                observed.0.push(*event);
+                clients
+                    .get_mut(event.client)
+                    .unwrap()
+                    .set_position([0.0, 100.0, 0.0]);
+            }
+        }
+
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+        app.init_resource::<Observed>().add_system(rescue);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_void_level(Some(-64.0));
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([0.0, -63.0, 0.0]);
+
+        app.update();
+        app.update();
+
+        assert!(app.world.resource::<Observed>().0.is_empty());
+
+        app.world
+            .get_mut::<Client>(client_ent)
+            .unwrap()
+            .set_position([5.0, -70.0, 5.0]);
+
+        // The event is sent at the end of the first update and handled in the
+        // second.
+        app.update();
+        app.update();
+
+        assert_eq!(
+            app.world.resource::<Observed>().0,
+            [VoidFallEvent {
+                client: client_ent,
+                instance: instance_ent,
+                position: DVec3::new(5.0, -70.0, 5.0),
+            }]
+        );
+
+        let client = app.world.get::<Client>(client_ent).unwrap();
+        assert_eq!(client.position(), DVec3::new(0.0, 100.0, 0.0));
+    }
+
+    #[test]
+    fn spawn_entity_respects_limit() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+
+        let mut instance = app
+            .world
+            .entity_mut(instance_ent)
+            .remove::<Instance>()
+            .unwrap();
+
+        let base = instance.entity_count();
+        instance.set_max_entities(Some(base + 3));
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let mut spawned = vec![];
+
+        for _ in 0..3 {
+            let entity = McEntity::new(EntityKind::Zombie, instance_ent);
+            spawned.push(instance.spawn_entity(&mut commands, entity).unwrap());
+        }
+
+        let entity = McEntity::new(EntityKind::Zombie, instance_ent);
+        assert!(instance.spawn_entity(&mut commands, entity).is_err());
+        assert_eq!(instance.entity_count(), base + 3);
+
+        queue.apply(&mut app.world);
+        app.world.entity_mut(instance_ent).insert(instance);
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.entity_count(), base + 3);
+
+        // Despawning an entity frees up space for another.
+        app.world.entity_mut(spawned[0]).insert(Despawned);
+
+        app.update();
+
+        let mut instance = app
+            .world
+            .entity_mut(instance_ent)
+            .remove::<Instance>()
+            .unwrap();
+
+        assert_eq!(instance.entity_count(), base + 2);
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+
+        let entity = McEntity::new(EntityKind::Zombie, instance_ent);
+        assert!(instance.spawn_entity(&mut commands, entity).is_ok());
+
+        // Entities located in another instance are rejected.
+        let other_ent = app.world.spawn_empty().id();
+        let entity = McEntity::new(EntityKind::Zombie, other_ent);
+        assert!(instance.spawn_entity(&mut commands, entity).is_err());
+        assert_eq!(instance.entity_count(), base + 3);
+
+        // Instances which have not been spawned do not know which entities are
+        // located in them.
+        let mut unspawned = app
+            .world
+            .resource::<Server>()
+            .new_instance(DimensionId::default());
+        let entity = McEntity::new(EntityKind::Zombie, instance_ent);
+        assert!(unspawned.spawn_entity(&mut commands, entity).is_err());
+    }
+
+    #[test]
+    fn spawn_item_sends_item_metadata() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app
+            .world
+            .entity_mut(instance_ent)
+            .remove::<Instance>()
+            .unwrap();
+
+        let stack = ItemStack::new(ItemKind::Diamond, 3, None);
+
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let item_ent = instance
+            .spawn_item(&mut commands, [1.0, 2.0, 3.0], stack.clone())
+            .unwrap();
+
+        queue.apply(&mut app.world);
+        app.world.entity_mut(instance_ent).insert(instance);
+
+        app.update();
+
+        let entity = app.world.get::<McEntity>(item_ent).unwrap();
+        let TrackedData::Item(item) = entity.data() else {
+            panic!("item entity has the wrong kind");
+        };
+        assert_eq!(item.get_stack(), Some(&stack));
+        assert_eq!(entity.position(), DVec3::new(1.0, 2.0, 3.0));
+
+        let protocol_id = VarInt(entity.protocol_id());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SpawnEntity(p)
+                if p.entity_id == protocol_id && p.kind.0 == EntityKind::Item as i32
+        )));
+
+        // Index 8, type ID 7 (item stack), the stack, and the terminator.
+        let mut expected = vec![8, 7];
+        Some(stack).encode(&mut expected).unwrap();
+        expected.push(0xff);
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id == protocol_id && p.metadata.0 == expected
+        )));
+    }
+
+    #[test]
+    fn set_entity_name_sends_name_and_visibility() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_name(VarInt(42), Some("Steve".into()), true);
+        instance.set_entity_name(VarInt(43), None, false);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        // Index 2 with type ID 6 (optional text), then index 3 with type ID 8
+        // (boolean).
+        let mut named = vec![2, 6];
+        Some(Text::from("Steve")).encode(&mut named).unwrap();
+        named.extend([3, 8, 1, 0xff]);
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 42 && p.metadata.0 == named
+        )));
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 43 && p.metadata.0 == [2, 6, 0, 3, 8, 0, 0xff]
+        )));
+    }
+
+    #[test]
+    fn set_and_clear_entity_bed_position() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_bed_position(VarInt(7), Some(BlockPos::new(-5, 64, 12)));
+
+        app.update();
+
+        // Index 14 with type ID 11 (optional block position).
+        let mut expected = vec![14, 11, 1];
+        BlockPos::new(-5, 64, 12).encode(&mut expected).unwrap();
+        expected.push(0xff);
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 7 && p.metadata.0 == expected
+        )));
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_bed_position(VarInt(7), None);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 7 && p.metadata.0 == [14, 11, 0, 0xff]
+        )));
+    }
+
+    #[test]
+    fn set_entity_air_metadata() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_air(VarInt(3), 150);
+        instance.set_entity_air(VarInt(4), -20);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        // Index 1 with type ID 1 (VarInt). 150 is two bytes as a VarInt.
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 3 && p.metadata.0 == [1, 1, 0x96, 0x01, 0xff]
+        )));
+
+        let mut expected = vec![1, 1];
+        VarInt(-20).encode(&mut expected).unwrap();
+        expected.push(0xff);
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 4 && p.metadata.0 == expected
+        )));
+    }
+
+    #[test]
+    fn set_entity_frozen_ticks_metadata() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_entity_frozen_ticks(VarInt(5), 140);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::SetEntityMetadata(_));
+
+        // Index 7 with type ID 1 (VarInt). 140 is two bytes as a VarInt.
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetEntityMetadata(p)
+                if p.entity_id.0 == 5 && p.metadata.0 == [7, 1, 0x8c, 0x01, 0xff]
+        )));
+    }
+
+    #[test]
+    fn set_entity_rotation_sends_body_and_head_angles() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_entity_rotation(VarInt(42), 90.0, 135.0, -45.0, false);
+
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::UpdateEntityRotation(_),
+            S2cPlayPacket::SetHeadRotation(_)
+        );
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::UpdateEntityRotation(p)
+                if p.entity_id.0 == 42
+                    && p.yaw == ByteAngle(64)
+                    && p.pitch == ByteAngle(224)
+                    && !p.on_ground
+        )));
+
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::SetHeadRotation(p)
+                if p.entity_id.0 == 42 && p.head_yaw == ByteAngle(96)
+        )));
+    }
+
+    #[test]
+    fn directly_despawned_entity_removed_for_viewers() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let zombie_ent = app
+            .world
+            .spawn(McEntity::new(EntityKind::Zombie, instance_ent))
+            .id();
+
+        app.update();
+
+        let protocol_id = app.world.get::<McEntity>(zombie_ent).unwrap().protocol_id();
+        let count = app
+            .world
+            .get::<Instance>(instance_ent)
+            .unwrap()
+            .entity_count();
+
+        client_helper.clear_sent();
+        app.world.despawn(zombie_ent);
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::RemoveEntities(_));
+
+        let entity_ids = sent_packets
+            .iter()
+            .find_map(|p| match p {
+                S2cPlayPacket::RemoveEntities(p) => Some(&p.entity_ids),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(entity_ids, &[VarInt(protocol_id)]);
+
+        assert_eq!(
+            app.world
+                .get::<Instance>(instance_ent)
+                .unwrap()
+                .entity_count(),
+            count - 1
+        );
+        assert!(app
+            .world
+            .resource::<McEntityManager>()
+            .get_with_protocol_id(protocol_id)
+            .is_none());
+    }
+
+    #[test]
+    fn debug_report_contents() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        app.world.entity_mut(instance_ent).insert(Weather {
+            rain: Some(0.5),
+            thunder: None,
+        });
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        let report = instance.debug_report(app.world.get::<Weather>(instance_ent));
+
+        assert!(report.contains("loaded chunks: 1 (1 in view of clients)"));
+        assert!(report.contains("entities: 0"));
+        assert!(report.contains("weather: rain Some(0.5), thunder None"));
+        assert!(report.contains(&format!("world age: {}", instance.world_age())));
+
+        assert!(instance.debug_report(None).contains("weather: clear"));
+    }
+
+    fn verify_packet_buffers_app() -> (App, Entity) {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_verify_packet_buffers(true);
+
+        (app, instance_ent)
+    }
+
+    #[test]
+    fn unmodified_packet_buffers_pass_verification() {
+        let (mut app, instance_ent) = verify_packet_buffers_app();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .set_action_bar("hello");
+
+        app.update();
+        app.update();
+    }
+
+    #[test]
+    #[should_panic(expected = "was modified while being sent to clients")]
+    fn modified_packet_buffer_detected() {
+        fn tamper(mut instances: Query<&mut Instance>) {
+            for mut instance in &mut instances {
+                if let Some(cell) = instance.partition.get_mut(&ChunkPos::new(0, 0)) {
+                    cell.packet_buf.push(0);
+                }
+            }
+        }
+
+        let (mut app, _) = verify_packet_buffers_app();
+
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            tamper
+                .after(update_instances_pre_client)
+                .before(update_instances_post_client),
+        );
+
+        app.update();
+    }
+}
@@ -1,13 +1,16 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use anyhow::bail;
+use num::integer::div_ceil;
 // Using nonstandard mutex to avoid poisoning API.
 use parking_lot::Mutex;
-use valence_nbt::compound;
+use tracing::warn;
+use valence_nbt::{compound, Compound};
 use valence_protocol::block::BlockState;
 use valence_protocol::packets::s2c::play::{
     BlockUpdate, ChunkDataAndUpdateLightEncode, UpdateSectionBlocksEncode,
 };
-use valence_protocol::{BlockPos, Encode, VarInt, VarLong};
+use valence_protocol::{BlockPos, Encode, LengthPrefixedArray, VarInt, VarLong};
 
 use crate::biome::BiomeId;
 use crate::instance::paletted_container::PalettedContainer;
@@ -22,7 +25,8 @@ use crate::view::ChunkPos;
 /// All chunks in an instance have the same height.
 #[derive(Debug)]
 pub struct Chunk<const LOADED: bool = false> {
-    sections: Vec<Section>,
+    sections: Vec<ChunkSection>,
+    heightmaps: Heightmaps,
     /// Cached bytes of the chunk data packet. The cache is considered
     /// invalidated if empty.
     cached_init_packets: Mutex<Vec<u8>>,
@@ -32,10 +36,18 @@ pub struct Chunk<const LOADED: bool = false> {
     /// Tracks if any clients are in view of this (loaded) chunk. Useful for
     /// knowing when a chunk should be unloaded.
     viewed: AtomicBool,
+    /// If the blocks or biomes of this chunk have changed since the dirty
+    /// flag was last cleared. Useful for knowing when a chunk should be saved.
+    dirty: bool,
 }
 
+/// A 16x16x16 meter volume of blocks and biomes. Chunks are made of a
+/// vertical stack of sections.
+///
+/// Standalone sections can be used to build up prefabs which are copied into
+/// chunks with [`Chunk::copy_section_from`].
 #[derive(Clone, Default, Debug)]
-struct Section {
+pub struct ChunkSection {
     block_states: PalettedContainer<BlockState, SECTION_BLOCK_COUNT, { SECTION_BLOCK_COUNT / 2 }>,
     biomes: PalettedContainer<BiomeId, SECTION_BIOME_COUNT, { SECTION_BIOME_COUNT / 2 }>,
     /// Number of non-air blocks in this section. This invariant is maintained
@@ -46,9 +58,177 @@ struct Section {
     section_updates: Vec<VarLong>,
 }
 
+/// The contents of a chunk data packet, built with [`Chunk::to_packet_data`].
+///
+/// The packet borrows its data, so this owns it for the packet returned by
+/// [`Self::packet`].
+#[derive(Clone, Debug)]
+pub struct ChunkPacketData {
+    pos: ChunkPos,
+    heightmaps: Compound,
+    blocks_and_biomes: Vec<u8>,
+    sky_light_mask: Vec<u64>,
+    sky_light_arrays: Vec<LengthPrefixedArray<u8, 2048>>,
+}
+
+impl ChunkPacketData {
+    /// The position of the chunk this data was built for.
+    pub fn pos(&self) -> ChunkPos {
+        self.pos
+    }
+
+    /// Returns the chunk data packet, ready to be written to a client.
+    pub fn packet(&self) -> ChunkDataAndUpdateLightEncode<'_> {
+        chunk_data_packet(
+            self.pos,
+            &self.heightmaps,
+            &self.blocks_and_biomes,
+            &self.sky_light_mask,
+            &self.sky_light_arrays,
+        )
+    }
+}
+
+fn chunk_data_packet<'a>(
+    pos: ChunkPos,
+    heightmaps: &'a Compound,
+    blocks_and_biomes: &'a [u8],
+    sky_light_mask: &'a [u64],
+    sky_light_arrays: &'a [LengthPrefixedArray<u8, 2048>],
+) -> ChunkDataAndUpdateLightEncode<'a> {
+    ChunkDataAndUpdateLightEncode {
+        chunk_x: pos.x,
+        chunk_z: pos.z,
+        heightmaps,
+        blocks_and_biomes,
+        block_entities: &[],
+        trust_edges: true,
+        sky_light_mask,
+        block_light_mask: &[],
+        empty_sky_light_mask: &[],
+        empty_block_light_mask: &[],
+        sky_light_arrays,
+        block_light_arrays: &[],
+    }
+}
+
+/// Returns the sky light mask and arrays for full-bright sky light in a chunk
+/// with `section_count` sections. Light sections include one extra section
+/// above and below the chunk.
+pub(crate) fn filler_sky_light(
+    section_count: usize,
+) -> (Vec<u64>, Vec<LengthPrefixedArray<u8, 2048>>) {
+    let light_section_count = section_count + 2;
+
+    let mut sky_light_mask = vec![0; div_ceil(light_section_count, 64)];
+
+    for i in 0..light_section_count {
+        sky_light_mask[i / 64] |= 1 << (i % 64);
+    }
+
+    let sky_light_arrays = vec![LengthPrefixedArray([0xff; 2048]); light_section_count];
+
+    (sky_light_mask, sky_light_arrays)
+}
+
+/// The heights of the highest blocks in every column of a chunk. A height is
+/// the number of blocks from the bottom of the chunk to the top of the highest
+/// matching block, or zero if no block in the column matches.
+#[derive(Clone, Debug)]
+struct Heightmaps {
+    /// Blocks which have a collision box or contain a fluid.
+    motion_blocking: [u16; 16 * 16],
+    /// Blocks which are not air.
+    world_surface: [u16; 16 * 16],
+}
+
 const SECTION_BLOCK_COUNT: usize = 16 * 16 * 16;
 const SECTION_BIOME_COUNT: usize = 4 * 4 * 4;
 
+impl ChunkSection {
+    /// Constructs a new section containing only [`BlockState::AIR`] and
+    /// [`BiomeId::default()`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Gets the block state at the provided offsets in the section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the offsets are not less than 16.
+    #[track_caller]
+    pub fn block_state(&self, x: usize, y: usize, z: usize) -> BlockState {
+        assert!(
+            x < 16 && y < 16 && z < 16,
+            "section block offsets of ({x}, {y}, {z}) are out of bounds"
+        );
+
+        self.block_states.get(x + z * 16 + y * 16 * 16)
+    }
+
+    /// Sets the block state at the provided offsets in the section. The
+    /// previous block state at the position is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the offsets are not less than 16.
+    #[track_caller]
+    pub fn set_block_state(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        block: BlockState,
+    ) -> BlockState {
+        assert!(
+            x < 16 && y < 16 && z < 16,
+            "section block offsets of ({x}, {y}, {z}) are out of bounds"
+        );
+
+        let old_block = self.block_states.set(x + z * 16 + y * 16 * 16, block);
+
+        match (block.is_air(), old_block.is_air()) {
+            (true, false) => self.non_air_count -= 1,
+            (false, true) => self.non_air_count += 1,
+            _ => {}
+        }
+
+        old_block
+    }
+
+    /// Gets the biome at the provided biome offsets in the section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the offsets are not less than 4.
+    #[track_caller]
+    pub fn biome(&self, x: usize, y: usize, z: usize) -> BiomeId {
+        assert!(
+            x < 4 && y < 4 && z < 4,
+            "section biome offsets of ({x}, {y}, {z}) are out of bounds"
+        );
+
+        self.biomes.get(x + z * 4 + y * 4 * 4)
+    }
+
+    /// Sets the biome at the provided biome offsets in the section. The
+    /// previous biome at the position is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the offsets are not less than 4.
+    #[track_caller]
+    pub fn set_biome(&mut self, x: usize, y: usize, z: usize, biome: BiomeId) -> BiomeId {
+        assert!(
+            x < 4 && y < 4 && z < 4,
+            "section biome offsets of ({x}, {y}, {z}) are out of bounds"
+        );
+
+        self.biomes.set(x + z * 4 + y * 4 * 4, biome)
+    }
+}
+
 impl Chunk<false> {
     /// Constructs a new chunk containing only [`BlockState::AIR`] and
     /// [`BiomeId::default()`] with the given number of sections. A section is a
@@ -56,9 +236,11 @@ impl Chunk<false> {
     pub fn new(section_count: usize) -> Self {
         let mut chunk = Self {
             sections: vec![],
+            heightmaps: Heightmaps::default(),
             cached_init_packets: Mutex::new(vec![]),
             refresh: true,
             viewed: AtomicBool::new(false),
+            dirty: false,
         };
 
         chunk.resize(section_count);
@@ -76,9 +258,11 @@ impl Chunk<false> {
             self.sections
                 .reserve_exact(new_section_count - old_section_count);
             self.sections
-                .resize_with(new_section_count, Section::default);
-        } else {
+                .resize_with(new_section_count, ChunkSection::default);
+        } else if new_section_count < old_section_count {
+            self.dirty = true;
             self.sections.truncate(new_section_count);
+            self.heightmaps.recompute(&self.sections);
         }
     }
 
@@ -87,9 +271,11 @@ impl Chunk<false> {
 
         Chunk {
             sections: self.sections,
+            heightmaps: self.heightmaps,
             cached_init_packets: self.cached_init_packets,
             refresh: true,
             viewed: AtomicBool::new(false),
+            dirty: self.dirty,
         }
     }
 }
@@ -104,9 +290,11 @@ impl Clone for Chunk {
     fn clone(&self) -> Self {
         Self {
             sections: self.sections.clone(),
+            heightmaps: self.heightmaps.clone(),
             cached_init_packets: Mutex::new(vec![]),
             refresh: true,
             viewed: AtomicBool::new(false),
+            dirty: self.dirty,
         }
     }
 }
@@ -118,10 +306,10 @@ impl Chunk<true> {
             .sections
             .iter()
             .map(|sect| {
-                Section {
+                ChunkSection {
                     block_states: sect.block_states.clone(),
                     biomes: sect.biomes.clone(),
-                    non_air_count: 0,
+                    non_air_count: sect.non_air_count,
                     section_updates: vec![], // Don't clone the section updates.
                 }
             })
@@ -129,9 +317,11 @@ impl Chunk<true> {
 
         Chunk {
             sections,
+            heightmaps: self.heightmaps.clone(),
             cached_init_packets: Mutex::new(vec![]),
             refresh: true,
             viewed: AtomicBool::new(false),
+            dirty: self.dirty,
         }
     }
 
@@ -164,9 +354,11 @@ impl Chunk<true> {
 
         Chunk {
             sections: self.sections,
+            heightmaps: self.heightmaps,
             cached_init_packets: self.cached_init_packets,
             refresh: true,
             viewed: AtomicBool::new(false),
+            dirty: self.dirty,
         }
     }
 
@@ -178,7 +370,9 @@ impl Chunk<true> {
         info: &InstanceInfo,
     ) {
         if self.refresh {
-            self.write_init_packets(info, pos, writer, scratch)
+            if let Err(e) = self.write_init_packets(info, pos, writer, scratch) {
+                warn!("failed to refresh chunk at {pos:?}: {e:#}");
+            }
         } else {
             for (sect_y, sect) in &mut self.sections.iter_mut().enumerate() {
                 if sect.section_updates.len() == 1 {
@@ -213,41 +407,23 @@ impl Chunk<true> {
 
     /// Writes the chunk data packet for this chunk with the given position.
     /// This will initialize the chunk for the client.
+    ///
+    /// Returns an error without writing anything if the chunk contains a biome
+    /// which is not registered with the server.
     pub(crate) fn write_init_packets(
         &self,
         info: &InstanceInfo,
         pos: ChunkPos,
         mut writer: impl WritePacket,
         scratch: &mut Vec<u8>,
-    ) {
+    ) -> anyhow::Result<()> {
         let mut lck = self.cached_init_packets.lock();
 
         if lck.is_empty() {
-            scratch.clear();
+            self.validate_biomes(info.biome_registry_len)?;
 
-            for sect in &self.sections {
-                sect.non_air_count.encode(&mut *scratch).unwrap();
-
-                sect.block_states
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.to_raw().into(),
-                        4,
-                        8,
-                        bit_width(BlockState::max_raw().into()),
-                    )
-                    .expect("failed to encode block paletted container");
-
-                sect.biomes
-                    .encode_mc_format(
-                        &mut *scratch,
-                        |b| b.0.into(),
-                        0,
-                        3,
-                        bit_width(info.biome_registry_len - 1),
-                    )
-                    .expect("failed to encode biome paletted container");
-            }
+            scratch.clear();
+            self.encode_blocks_and_biomes(info.biome_registry_len, scratch);
 
             let mut compression_scratch = vec![];
 
@@ -257,25 +433,18 @@ impl Chunk<true> {
                 &mut compression_scratch,
             );
 
-            writer.write_packet(&ChunkDataAndUpdateLightEncode {
-                chunk_x: pos.x,
-                chunk_z: pos.z,
-                heightmaps: &compound! {
-                    // TODO: MOTION_BLOCKING heightmap
-                },
-                blocks_and_biomes: scratch,
-                block_entities: &[],
-                trust_edges: true,
-                sky_light_mask: &info.filler_sky_light_mask,
-                block_light_mask: &[],
-                empty_sky_light_mask: &[],
-                empty_block_light_mask: &[],
-                sky_light_arrays: &info.filler_sky_light_arrays,
-                block_light_arrays: &[],
-            });
+            writer.write_packet(&chunk_data_packet(
+                pos,
+                &self.heightmaps.to_nbt(self.section_count() * 16),
+                scratch,
+                &info.filler_sky_light_mask,
+                &info.filler_sky_light_arrays,
+            ));
         }
 
         writer.write_packet_bytes(&lck);
+
+        Ok(())
     }
 
     pub(super) fn update_post_client(&mut self) {
@@ -294,6 +463,23 @@ impl<const LOADED: bool> Chunk<LOADED> {
         self.sections.len()
     }
 
+    /// Returns `true` if every block in this chunk is air.
+    pub fn is_all_air(&self) -> bool {
+        self.sections.iter().all(|sect| sect.non_air_count == 0)
+    }
+
+    /// Returns `true` if the blocks or biomes of this chunk have changed since
+    /// the dirty flag was last cleared with [`Self::clear_dirty`]. New chunks
+    /// are not dirty.
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Clears the dirty flag, usually after the chunk has been saved.
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+
     /// Gets the block state at the provided offsets in the chunk.
     ///
     /// **Note**: The arguments to this function are offsets from the minimum
@@ -345,6 +531,8 @@ impl<const LOADED: bool> Chunk<LOADED> {
         let old_block = sect.block_states.set(idx, block);
 
         if block != old_block {
+            self.dirty = true;
+
             // Update non-air count.
             match (block.is_air(), old_block.is_air()) {
                 (true, false) => sect.non_air_count -= 1,
@@ -357,6 +545,8 @@ impl<const LOADED: bool> Chunk<LOADED> {
                 let compact = (block.to_raw() as i64) << 12 | (x << 8 | z << 4 | (y % 16)) as i64;
                 sect.section_updates.push(VarLong(compact));
             }
+
+            self.heightmaps.update_block(&self.sections, x, y, z, block);
         }
 
         old_block
@@ -417,6 +607,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
             }
         }
 
+        if !matches!(&sect.block_states, PalettedContainer::Single(single) if *single == block) {
+            self.dirty = true;
+        }
+
         if !block.is_air() {
             sect.non_air_count = SECTION_BLOCK_COUNT as u16;
         } else {
@@ -424,6 +618,43 @@ impl<const LOADED: bool> Chunk<LOADED> {
         }
 
         sect.block_states.fill(block);
+
+        self.heightmaps
+            .update_section(&self.sections, sect_y, block);
+    }
+
+    /// Returns the height of the `MOTION_BLOCKING` heightmap in the column at
+    /// the provided offsets in the chunk. This is the number of blocks from
+    /// the bottom of the chunk to the top of the highest block which has a
+    /// collision box or contains a fluid, or zero if there is no such block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not less than 16.
+    #[track_caller]
+    pub fn motion_blocking_height(&self, x: usize, z: usize) -> usize {
+        assert!(
+            x < 16 && z < 16,
+            "chunk column offsets of ({x}, {z}) are out of bounds"
+        );
+
+        self.heightmaps.motion_blocking[x + z * 16] as usize
+    }
+
+    /// Like [`Self::motion_blocking_height`], but for the `WORLD_SURFACE`
+    /// heightmap, which considers every block that is not air.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `x` or `z` is not less than 16.
+    #[track_caller]
+    pub fn world_surface_height(&self, x: usize, z: usize) -> usize {
+        assert!(
+            x < 16 && z < 16,
+            "chunk column offsets of ({x}, {z}) are out of bounds"
+        );
+
+        self.heightmaps.world_surface[x + z * 16] as usize
     }
 
     /// Gets the biome at the provided biome offsets in the chunk.
@@ -466,6 +697,10 @@ impl<const LOADED: bool> Chunk<LOADED> {
             .biomes
             .set(x + z * 4 + y % 4 * 4 * 4, biome);
 
+        if biome != old_biome {
+            self.dirty = true;
+        }
+
         if LOADED && biome != old_biome {
             self.cached_init_packets.get_mut().clear();
             self.refresh = true;
@@ -497,11 +732,95 @@ impl<const LOADED: bool> Chunk<LOADED> {
 
         sect.biomes.fill(biome);
 
+        self.dirty = true;
+
         // TODO: this is set unconditionally, but it doesn't have to be.
         self.cached_init_packets.get_mut().clear();
         self.refresh = true;
     }
 
+    /// Returns the section at index `sect_y`, counting up from the bottom of
+    /// the chunk.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` is out of bounds. `sect_y` must be less than the
+    /// section count.
+    #[track_caller]
+    pub fn section(&self, sect_y: usize) -> &ChunkSection {
+        let Some(sect) = self.sections.get(sect_y) else {
+            panic!(
+                "section index {sect_y} out of bounds for chunk with {} section(s)",
+                self.section_count()
+            )
+        };
+
+        sect
+    }
+
+    /// Replaces the blocks and biomes of the section at index `sect_y` with
+    /// those of `src`.
+    ///
+    /// This is semantically equivalent to setting every block and biome in the
+    /// section individually. Clients are sent only the blocks which changed,
+    /// unless the biomes changed as well.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `sect_y` is out of bounds. `sect_y` must be less than the
+    /// section count.
+    #[track_caller]
+    pub fn copy_section_from(&mut self, sect_y: usize, src: &ChunkSection) {
+        let Some(sect) = self.sections.get_mut(sect_y) else {
+            panic!(
+                "section index {sect_y} out of bounds for chunk with {} section(s)",
+                self.section_count()
+            )
+        };
+
+        let mut blocks_changed = false;
+
+        for idx in 0..SECTION_BLOCK_COUNT {
+            let block = src.block_states.get(idx);
+
+            if block != sect.block_states.get(idx) {
+                blocks_changed = true;
+
+                if LOADED && !self.refresh {
+                    // The index is already packed as y, z, x.
+                    let (y, z, x) = (idx >> 8, idx >> 4 & 15, idx & 15);
+                    let compact = (block.to_raw() as i64) << 12 | (x << 8 | z << 4 | y) as i64;
+                    sect.section_updates.push(VarLong(compact));
+                }
+            }
+        }
+
+        let biomes_changed =
+            (0..SECTION_BIOME_COUNT).any(|idx| src.biomes.get(idx) != sect.biomes.get(idx));
+
+        if !blocks_changed && !biomes_changed {
+            return;
+        }
+
+        sect.block_states = src.block_states.clone();
+        sect.biomes = src.biomes.clone();
+        sect.non_air_count = src.non_air_count;
+
+        self.dirty = true;
+
+        if LOADED {
+            self.cached_init_packets.get_mut().clear();
+
+            if biomes_changed {
+                self.refresh = true;
+            }
+        }
+
+        if blocks_changed {
+            self.heightmaps.recompute(&self.sections);
+        }
+    }
+
     /// Optimizes this chunk to use the minimum amount of memory possible. It
     /// has no observable effect on the contents of the chunk.
     ///
@@ -518,10 +837,212 @@ impl<const LOADED: bool> Chunk<LOADED> {
             sect.biomes.optimize();
         }
     }
+
+    /// Builds the contents of the chunk data packet for this chunk at `pos`.
+    /// This is useful for sending the chunk to clients outside of an
+    /// [`Instance`](crate::instance::Instance).
+    ///
+    /// `biome_count` is the number of biomes registered with the server. The
+    /// chunk has no block entities and full sky light.
+    ///
+    /// Returns an error if the chunk contains a biome which is not registered
+    /// with the server.
+    pub fn to_packet_data(
+        &self,
+        pos: impl Into<ChunkPos>,
+        biome_count: usize,
+    ) -> anyhow::Result<ChunkPacketData> {
+        self.validate_biomes(biome_count)?;
+
+        let pos = pos.into();
+
+        let mut blocks_and_biomes = vec![];
+        self.encode_blocks_and_biomes(biome_count, &mut blocks_and_biomes);
+
+        let (sky_light_mask, sky_light_arrays) = filler_sky_light(self.section_count());
+
+        Ok(ChunkPacketData {
+            pos,
+            heightmaps: self.heightmaps.to_nbt(self.section_count() * 16),
+            blocks_and_biomes,
+            sky_light_mask,
+            sky_light_arrays,
+        })
+    }
+
+    /// Writes the block states and biomes of every section in the format of
+    /// the chunk data packet.
+    fn encode_blocks_and_biomes(&self, biome_count: usize, buf: &mut Vec<u8>) {
+        for sect in &self.sections {
+            sect.non_air_count.encode(&mut *buf).unwrap();
+
+            sect.block_states
+                .encode_mc_format(
+                    &mut *buf,
+                    |b| b.to_raw().into(),
+                    4,
+                    8,
+                    bit_width(BlockState::max_raw().into()),
+                )
+                .expect("failed to encode block paletted container");
+
+            sect.biomes
+                .encode_mc_format(&mut *buf, |b| b.0.into(), 0, 3, bit_width(biome_count - 1))
+                .expect("failed to encode biome paletted container");
+        }
+    }
+
+    /// Returns an error if a biome in this chunk is not one of the
+    /// `biome_count` biomes in the registry codec. Clients cannot decode
+    /// chunks containing such biomes.
+    pub(crate) fn validate_biomes(&self, biome_count: usize) -> anyhow::Result<()> {
+        for (sect_y, sect) in self.sections.iter().enumerate() {
+            if let Some(biome) = sect.biomes.find(|b| b.0 as usize >= biome_count) {
+                bail!(
+                    "{biome:?} in chunk section {sect_y} is not registered (there are \
+                     {biome_count} biomes)"
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Heightmaps {
+    fn default() -> Self {
+        Self {
+            motion_blocking: [0; 16 * 16],
+            world_surface: [0; 16 * 16],
+        }
+    }
+}
+
+impl Heightmaps {
+    fn maps_mut(&mut self) -> [(&mut [u16; 16 * 16], fn(BlockState) -> bool); 2] {
+        [
+            (&mut self.motion_blocking, is_motion_blocking),
+            (&mut self.world_surface, is_world_surface),
+        ]
+    }
+
+    /// Updates the column containing the block at `(x, y, z)` after it was set
+    /// to `block`.
+    fn update_block(
+        &mut self,
+        sections: &[ChunkSection],
+        x: usize,
+        y: usize,
+        z: usize,
+        block: BlockState,
+    ) {
+        for (heights, pred) in self.maps_mut() {
+            let height = &mut heights[x + z * 16];
+
+            if pred(block) {
+                *height = (*height).max(y as u16 + 1);
+            } else if *height as usize == y + 1 {
+                *height = column_height(sections, x, z, y, pred);
+            }
+        }
+    }
+
+    /// Updates every column after the section at `sect_y` was filled with
+    /// `block`.
+    fn update_section(&mut self, sections: &[ChunkSection], sect_y: usize, block: BlockState) {
+        let bottom = sect_y * 16;
+        let top = bottom + 16;
+
+        for (heights, pred) in self.maps_mut() {
+            let matches = pred(block);
+
+            for z in 0..16 {
+                for x in 0..16 {
+                    let height = &mut heights[x + z * 16];
+
+                    if matches {
+                        *height = (*height).max(top as u16);
+                    } else if (bottom + 1..=top).contains(&(*height as usize)) {
+                        *height = column_height(sections, x, z, bottom, pred);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Recomputes every column from scratch.
+    fn recompute(&mut self, sections: &[ChunkSection]) {
+        let top = sections.len() * 16;
+
+        for (heights, pred) in self.maps_mut() {
+            for z in 0..16 {
+                for x in 0..16 {
+                    heights[x + z * 16] = column_height(sections, x, z, top, pred);
+                }
+            }
+        }
+    }
+
+    fn to_nbt(&self, chunk_height: usize) -> Compound {
+        compound! {
+            "MOTION_BLOCKING" => pack_heights(&self.motion_blocking, chunk_height),
+            "WORLD_SURFACE" => pack_heights(&self.world_surface, chunk_height),
+        }
+    }
+}
+
+fn is_motion_blocking(block: BlockState) -> bool {
+    block.is_liquid() || block.collision_shapes().next().is_some()
+}
+
+fn is_world_surface(block: BlockState) -> bool {
+    !block.is_air()
+}
+
+/// Finds the height of the highest block below `top` in the column at `(x, z)`
+/// matching `pred`.
+fn column_height(
+    sections: &[ChunkSection],
+    x: usize,
+    z: usize,
+    top: usize,
+    pred: fn(BlockState) -> bool,
+) -> u16 {
+    (0..top)
+        .rev()
+        .find(|&y| {
+            pred(
+                sections[y / 16]
+                    .block_states
+                    .get(x + z * 16 + y % 16 * 16 * 16),
+            )
+        })
+        .map_or(0, |y| y as u16 + 1)
+}
+
+/// Packs the heights into the long array format expected by the client.
+/// Entries are not split across longs.
+fn pack_heights(heights: &[u16; 16 * 16], chunk_height: usize) -> Vec<i64> {
+    let bits = bit_width(chunk_height).max(1);
+    let per_long = 64 / bits;
+
+    heights
+        .chunks(per_long)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .enumerate()
+                .fold(0, |acc, (i, &h)| acc | (h as u64) << (i * bits)) as i64
+        })
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
+    use valence_nbt::Value;
+    use valence_protocol::packets::s2c::play::ChunkDataAndUpdateLight;
+    use valence_protocol::{Decode, DecodePacket, EncodePacket};
+
     use super::*;
     use crate::protocol::block::BlockState;
 
@@ -565,4 +1086,179 @@ mod tests {
         chunk.fill_block_states(0, BlockState::AIR);
         check(&chunk, 6);
     }
+
+    #[test]
+    fn copy_section_between_chunks() {
+        let mut src = ChunkSection::new();
+        src.set_block_state(1, 2, 3, BlockState::STONE);
+        src.set_block_state(4, 5, 6, BlockState::GLASS);
+
+        let mut chunk = Chunk::new(3).into_loaded();
+        chunk.set_block_state(4, 16 + 5, 6, BlockState::GLASS);
+        chunk.set_block_state(0, 16, 0, BlockState::DIRT);
+        chunk.refresh = false;
+        chunk.clear_dirty();
+
+        chunk.copy_section_from(1, &src);
+
+        // Only the blocks which changed are sent.
+        check(&chunk, 2);
+        assert!(chunk.is_dirty());
+        assert_eq!(chunk.block_state(1, 16 + 2, 3), BlockState::STONE);
+        assert_eq!(chunk.block_state(4, 16 + 5, 6), BlockState::GLASS);
+        assert_eq!(chunk.block_state(0, 16, 0), BlockState::AIR);
+        assert_eq!(chunk.world_surface_height(1, 3), 16 + 3);
+        assert_eq!(chunk.world_surface_height(0, 0), 0);
+
+        let mut other = Chunk::new(1);
+        other.copy_section_from(0, chunk.section(1));
+        assert_eq!(other.block_state(1, 2, 3), BlockState::STONE);
+        assert_eq!(other.sections[0].non_air_count, 2);
+    }
+
+    #[test]
+    fn dirty_tracking() {
+        let mut chunk = Chunk::new(2).into_loaded();
+        assert!(!chunk.is_dirty());
+
+        chunk.set_block_state(0, 0, 0, BlockState::AIR);
+        chunk.fill_block_states(1, BlockState::AIR);
+        chunk.set_biome(0, 0, 0, BiomeId::default());
+        assert!(!chunk.is_dirty(), "unchanged blocks should not dirty chunk");
+
+        chunk.set_block_state(0, 0, 0, BlockState::STONE);
+        assert!(chunk.is_dirty());
+        assert!(chunk.to_unloaded().is_dirty());
+
+        chunk.clear_dirty();
+        assert!(!chunk.is_dirty());
+
+        chunk.fill_block_states(1, BlockState::DIRT);
+        assert!(chunk.is_dirty());
+    }
+
+    #[test]
+    fn non_air_count_tracking() {
+        let mut chunk = Chunk::new(2).into_loaded();
+        chunk.fill_block_states(1, BlockState::STONE);
+        chunk.refresh = false;
+
+        chunk.set_block_state(0, 0, 0, BlockState::STONE);
+        chunk.set_block_state(1, 0, 0, BlockState::DIRT);
+        chunk.set_block_state(1, 0, 0, BlockState::STONE);
+        chunk.set_block_state(0, 16, 0, BlockState::CAVE_AIR);
+        chunk.set_block_state(1, 16, 0, BlockState::VOID_AIR);
+        chunk.set_block_state(1, 16, 0, BlockState::AIR);
+        check(&chunk, 6);
+
+        assert_eq!(chunk.sections[0].non_air_count, 2);
+        assert_eq!(
+            chunk.sections[1].non_air_count,
+            SECTION_BLOCK_COUNT as u16 - 2
+        );
+
+        chunk.set_block_state(0, 0, 0, BlockState::AIR);
+        chunk.set_block_state(0, 16, 0, BlockState::GLASS);
+        assert_eq!(chunk.sections[0].non_air_count, 1);
+        assert_eq!(
+            chunk.sections[1].non_air_count,
+            SECTION_BLOCK_COUNT as u16 - 1
+        );
+
+        let unloaded = chunk.to_unloaded();
+        assert_eq!(unloaded.sections[0].non_air_count, 1);
+        assert_eq!(
+            unloaded.sections[1].non_air_count,
+            SECTION_BLOCK_COUNT as u16 - 1
+        );
+    }
+
+    #[test]
+    fn heightmaps_follow_block_changes() {
+        let mut chunk = Chunk::new(2);
+        assert_eq!(chunk.motion_blocking_height(3, 5), 0);
+        assert_eq!(chunk.world_surface_height(3, 5), 0);
+
+        chunk.set_block_state(3, 10, 5, BlockState::STONE);
+        assert_eq!(chunk.motion_blocking_height(3, 5), 11);
+        assert_eq!(chunk.world_surface_height(3, 5), 11);
+
+        // Torches have no collision box, so they only affect the world surface.
+        chunk.set_block_state(3, 11, 5, BlockState::TORCH);
+        assert_eq!(chunk.motion_blocking_height(3, 5), 11);
+        assert_eq!(chunk.world_surface_height(3, 5), 12);
+
+        chunk.set_block_state(3, 2, 5, BlockState::DIRT);
+        chunk.set_block_state(3, 10, 5, BlockState::AIR);
+        assert_eq!(chunk.motion_blocking_height(3, 5), 3);
+        assert_eq!(chunk.world_surface_height(3, 5), 12);
+
+        chunk.set_block_state(3, 11, 5, BlockState::AIR);
+        assert_eq!(chunk.world_surface_height(3, 5), 3);
+
+        chunk.fill_block_states(1, BlockState::STONE);
+        assert_eq!(chunk.motion_blocking_height(0, 0), 32);
+        chunk.fill_block_states(1, BlockState::AIR);
+        assert_eq!(chunk.motion_blocking_height(0, 0), 0);
+        assert_eq!(chunk.motion_blocking_height(3, 5), 3);
+
+        chunk.fill_block_states(0, BlockState::STONE);
+        chunk.resize(1);
+        chunk.set_block_state(0, 15, 0, BlockState::AIR);
+        assert_eq!(chunk.world_surface_height(0, 0), 15);
+
+        // A chunk height of 16 needs 5 bits per entry, so 12 entries fit in a
+        // long.
+        let nbt = chunk.heightmaps.to_nbt(16);
+        let Some(Value::LongArray(packed)) = nbt.get("WORLD_SURFACE") else {
+            panic!("missing heightmap")
+        };
+        assert_eq!(packed.len(), 22);
+        assert_eq!(packed[0] & 0b11111, 15);
+        assert_eq!(packed[0] >> 5 & 0b11111, 16);
+    }
+
+    #[test]
+    fn packet_data_round_trip() {
+        let mut chunk = Chunk::new(2);
+        chunk.set_block_state(1, 2, 3, BlockState::STONE);
+        chunk.set_block_state(4, 20, 5, BlockState::DIRT);
+        chunk.set_biome(0, 0, 0, BiomeId(1));
+
+        // The biome is not registered.
+        assert!(chunk.to_packet_data([0, 0], 1).is_err());
+
+        let data = chunk.to_packet_data([3, -7], 4).unwrap();
+        assert_eq!(data.pos(), ChunkPos::new(3, -7));
+
+        let mut buf = vec![];
+        data.packet().encode_packet(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = ChunkDataAndUpdateLight::decode_packet(&mut r).unwrap();
+        assert!(r.is_empty());
+
+        assert_eq!(pkt.chunk_x, 3);
+        assert_eq!(pkt.chunk_z, -7);
+        assert_eq!(pkt.heightmaps, chunk.heightmaps.to_nbt(32));
+        assert!(pkt.block_entities.is_empty());
+
+        // Two sections plus one above and one below for light.
+        assert_eq!(pkt.sky_light_mask, [0b1111]);
+        assert_eq!(pkt.sky_light_arrays.len(), 4);
+
+        // The blocks of the first section are in an indirect palette.
+        let mut r = pkt.blocks_and_biomes;
+        assert_eq!(i16::decode(&mut r).unwrap(), 1);
+        assert_eq!(u8::decode(&mut r).unwrap(), 4);
+        assert_eq!(VarInt::decode(&mut r).unwrap(), VarInt(2));
+        assert_eq!(
+            VarInt::decode(&mut r).unwrap(),
+            VarInt(BlockState::AIR.to_raw() as i32)
+        );
+        assert_eq!(
+            VarInt::decode(&mut r).unwrap(),
+            VarInt(BlockState::STONE.to_raw() as i32)
+        );
+    }
 }
@@ -104,6 +104,7 @@ impl<'a> VacantChunkEntry<'a> {
             incoming: vec![],
             outgoing: vec![],
             packet_buf: vec![],
+            movement_bufs: Default::default(),
         });
 
         debug_assert!(cell.chunk.is_none());
@@ -0,0 +1,122 @@
+//! Filling in new chunks.
+//!
+//! A [`ChunkGenerator`] set with [`Instance::set_chunk_generator`] is invoked
+//! for every chunk created by [`Instance::load_chunk`]. Closures with the
+//! signature `Fn(ChunkPos, &mut Chunk)` are generators too.
+//!
+//! [`Instance::set_chunk_generator`]: crate::instance::Instance::set_chunk_generator
+//! [`Instance::load_chunk`]: crate::instance::Instance::load_chunk
+
+use valence_protocol::block::BlockState;
+
+use crate::instance::Chunk;
+use crate::view::ChunkPos;
+
+/// Generates the contents of chunks which are not loaded yet.
+pub trait ChunkGenerator: Send + Sync + 'static {
+    /// Fills in `chunk`, which is the new chunk at `pos`. The chunk is empty
+    /// and has the section count of the instance.
+    fn generate(&self, pos: ChunkPos, chunk: &mut Chunk);
+}
+
+impl<F> ChunkGenerator for F
+where
+    F: Fn(ChunkPos, &mut Chunk) + Send + Sync + 'static,
+{
+    fn generate(&self, pos: ChunkPos, chunk: &mut Chunk) {
+        self(pos, chunk)
+    }
+}
+
+/// A generator for flat worlds. Every chunk is filled with the same layers of
+/// blocks starting from the bottom of the chunk.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FlatGenerator {
+    layers: Vec<BlockState>,
+}
+
+impl FlatGenerator {
+    /// Creates a flat generator from the layers of blocks listed bottom to
+    /// top. Layers above the top of the chunk are ignored.
+    pub fn new(layers: impl IntoIterator<Item = BlockState>) -> Self {
+        Self {
+            layers: layers.into_iter().collect(),
+        }
+    }
+
+    /// Returns the layers of blocks from bottom to top.
+    pub fn layers(&self) -> &[BlockState] {
+        &self.layers
+    }
+}
+
+/// A layer of bedrock, two layers of dirt, and a layer of grass, like a
+/// vanilla superflat world.
+impl Default for FlatGenerator {
+    fn default() -> Self {
+        Self::new([
+            BlockState::BEDROCK,
+            BlockState::DIRT,
+            BlockState::DIRT,
+            BlockState::GRASS_BLOCK,
+        ])
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, _pos: ChunkPos, chunk: &mut Chunk) {
+        let height = chunk.section_count() * 16;
+
+        for (y, &block) in self.layers.iter().enumerate().take(height) {
+            for z in 0..16 {
+                for x in 0..16 {
+                    chunk.set_block_state(x, y, z, block);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::instance::Instance;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn flat_generator_fills_new_chunks() {
+        let mut app = App::new();
+        let (client_ent, _) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+
+        instance.set_chunk_generator(FlatGenerator::default());
+
+        assert!(instance.chunk([-3, 8]).is_none());
+
+        let chunk = instance.load_chunk([-3, 8]);
+
+        for z in 0..16 {
+            for x in 0..16 {
+                assert_eq!(chunk.block_state(x, 0, z), BlockState::BEDROCK);
+                assert_eq!(chunk.block_state(x, 2, z), BlockState::DIRT);
+                assert_eq!(chunk.block_state(x, 3, z), BlockState::GRASS_BLOCK);
+                assert_eq!(chunk.block_state(x, 4, z), BlockState::AIR);
+                assert_eq!(chunk.world_surface_height(x, z), 4);
+            }
+        }
+    }
+
+    #[test]
+    fn flat_generator_layers_are_clamped_to_chunk_height() {
+        let mut chunk = Chunk::new(1);
+
+        FlatGenerator::new([BlockState::STONE; 20]).generate(ChunkPos::new(0, 0), &mut chunk);
+
+        assert_eq!(chunk.block_state(0, 15, 0), BlockState::STONE);
+    }
+}
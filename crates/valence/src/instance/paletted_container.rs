@@ -6,6 +6,13 @@ use valence_protocol::{Encode, VarInt};
 
 use crate::math::bit_width;
 
+/// The direct representation is only converted back to the indirect one by
+/// [`PalettedContainer::optimize`] when the number of unique elements is at or
+/// below this. Keeping this well below the capacity of the palette prevents
+/// edits hovering around the threshold from reallocating the container back
+/// and forth.
+const DOWNSIZE_MAX_PALETTE_LEN: usize = 8;
+
 /// `HALF_LEN` must be equal to `ceil(LEN / 2)`.
 #[derive(Clone, Debug)]
 pub enum PalettedContainer<T, const LEN: usize, const HALF_LEN: usize> {
@@ -85,6 +92,17 @@ impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize>
         }
     }
 
+    /// Returns the first value in the palette of this container for which `f`
+    /// returns `true`. Every value which could be encoded by
+    /// [`Self::encode_mc_format`] is checked.
+    pub fn find(&self, mut f: impl FnMut(T) -> bool) -> Option<T> {
+        match self {
+            Self::Single(val) => f(*val).then_some(*val),
+            Self::Indirect(ind) => ind.palette.iter().copied().find(|&val| f(val)),
+            Self::Direct(dir) => dir.iter().copied().find(|&val| f(val)),
+        }
+    }
+
     pub fn optimize(&mut self) {
         match self {
             Self::Single(_) => {}
@@ -116,6 +134,10 @@ impl<T: Copy + Eq + Default, const LEN: usize, const HALF_LEN: usize>
                     }
                 }
 
+                if ind.palette.len() > DOWNSIZE_MAX_PALETTE_LEN {
+                    return;
+                }
+
                 *self = if ind.palette.len() == 1 {
                     Self::Single(ind.palette[0])
                 } else {
@@ -333,4 +355,37 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn oscillating_palette_size_does_not_thrash() {
+        const LEN: usize = 100;
+
+        let mut p = PalettedContainer::<u32, LEN, { LEN / 2 }>::new();
+        for i in 0..16 {
+            p.set(i, i as u32);
+        }
+        assert!(matches!(p, PalettedContainer::Indirect(_)));
+
+        let mut reallocs = 0;
+        for i in 0..100 {
+            let was_direct = matches!(p, PalettedContainer::Direct(_));
+
+            // Alternate between 16 and 17 unique elements.
+            p.set(16, if i % 2 == 0 { 16 } else { 0 });
+            p.optimize();
+
+            if was_direct != matches!(p, PalettedContainer::Direct(_)) {
+                reallocs += 1;
+            }
+        }
+
+        assert_eq!(reallocs, 1);
+
+        // Downsizes once well below the threshold.
+        for i in 0..LEN {
+            p.set(i, (i % 4) as u32);
+        }
+        p.optimize();
+        assert!(matches!(p, PalettedContainer::Indirect(_)));
+    }
 }
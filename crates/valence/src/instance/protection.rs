@@ -0,0 +1,171 @@
+//! Restricting where clients may change blocks.
+//!
+//! A [`BuildPermission`] set with [`Instance::set_build_permission`] is
+//! consulted before [`StartDigging`], [`FinishDigging`], and
+//! [`UseItemOnBlock`] events are sent for clients in the instance. Denied
+//! attempts produce no event, and the affected blocks are resent to the client
+//! so that its prediction of the change is undone.
+//!
+//! [`Instance::set_build_permission`]: crate::instance::Instance::set_build_permission
+//! [`StartDigging`]: crate::client::event::StartDigging
+//! [`FinishDigging`]: crate::client::event::FinishDigging
+//! [`UseItemOnBlock`]: crate::client::event::UseItemOnBlock
+
+use valence_protocol::BlockPos;
+
+use crate::client::Client;
+
+/// The kind of block change a client is attempting.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum BuildAction {
+    /// Breaking the block at the position.
+    Break,
+    /// Placing a block at the position, or interacting with the block next to
+    /// it.
+    Place,
+}
+
+/// Decides which block changes clients are allowed to make.
+pub trait BuildPermission: Send + Sync + 'static {
+    /// Returns `true` if `client` may perform `action` at `pos`.
+    fn allows(&self, client: &Client, pos: BlockPos, action: BuildAction) -> bool;
+}
+
+impl<F> BuildPermission for F
+where
+    F: Fn(&Client, BlockPos, BuildAction) -> bool + Send + Sync + 'static,
+{
+    fn allows(&self, client: &Client, pos: BlockPos, action: BuildAction) -> bool {
+        self(client, pos, action)
+    }
+}
+
+/// Denies all block changes within a square area around a center position,
+/// like vanilla spawn protection.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SpawnProtection {
+    center: BlockPos,
+    radius: i32,
+}
+
+impl SpawnProtection {
+    /// Protects the blocks whose X and Z coordinates are both within `radius`
+    /// blocks of `center`, at any height. A negative radius protects nothing.
+    pub fn new(center: impl Into<BlockPos>, radius: i32) -> Self {
+        Self {
+            center: center.into(),
+            radius,
+        }
+    }
+
+    /// Returns the center of the protected area.
+    pub fn center(&self) -> BlockPos {
+        self.center
+    }
+
+    /// Returns the radius of the protected area.
+    pub fn radius(&self) -> i32 {
+        self.radius
+    }
+
+    /// Returns `true` if the block at `pos` is protected.
+    pub fn contains(&self, pos: BlockPos) -> bool {
+        let dx = (pos.x as i64 - self.center.x as i64).abs();
+        let dz = (pos.z as i64 - self.center.z as i64).abs();
+
+        dx.max(dz) <= self.radius as i64
+    }
+}
+
+impl BuildPermission for SpawnProtection {
+    fn allows(&self, _client: &Client, pos: BlockPos, _action: BuildAction) -> bool {
+        !self.contains(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use bevy_ecs::prelude::*;
+    use valence_protocol::block::BlockState;
+    use valence_protocol::packets::c2s::play::UseItemOn;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::types::Hand;
+    use valence_protocol::{BlockFace, VarInt};
+
+    use super::*;
+    use crate::client::event::UseItemOnBlock;
+    use crate::instance::{Chunk, Instance};
+    use crate::server::EventLoop;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn place_stone(mut events: EventReader<UseItemOnBlock>, mut instances: Query<&mut Instance>) {
+        for event in events.iter() {
+            for mut instance in &mut instances {
+                instance.set_block_state(
+                    event.position.get_in_direction(event.face),
+                    BlockState::STONE,
+                );
+            }
+        }
+    }
+
+    fn use_item_on(position: BlockPos) -> UseItemOn {
+        UseItemOn {
+            hand: Hand::Main,
+            position,
+            face: BlockFace::Top,
+            cursor_pos: [0.5, 1.0, 0.5],
+            head_inside_block: false,
+            sequence: VarInt(1),
+        }
+    }
+
+    #[test]
+    fn placements_in_spawn_protection_are_denied() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+        app.add_system_to_stage(EventLoop, place_stone);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.set_build_permission(SpawnProtection::new([0, 64, 0], 4));
+
+        app.update();
+        client_helper.clear_sent();
+
+        client_helper.send(&use_item_on(BlockPos::new(4, 64, 2)));
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.block_state([4, 65, 2]), BlockState::AIR);
+
+        // The client's predicted placement is undone.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert!(sent_packets.iter().any(|p| matches!(
+            p,
+            S2cPlayPacket::BlockUpdate(p)
+                if p.position == BlockPos::new(4, 65, 2)
+                    && p.block_id.0 == BlockState::AIR.to_raw() as i32
+        )));
+
+        client_helper.send(&use_item_on(BlockPos::new(5, 64, 2)));
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert_eq!(instance.block_state([5, 65, 2]), BlockState::STONE);
+    }
+
+    #[test]
+    fn spawn_protection_area() {
+        let protection = SpawnProtection::new([10, 0, -10], 2);
+
+        assert!(protection.contains(BlockPos::new(10, -64, -10)));
+        assert!(protection.contains(BlockPos::new(12, 300, -8)));
+        assert!(!protection.contains(BlockPos::new(13, 0, -10)));
+        assert!(!protection.contains(BlockPos::new(10, 0, -13)));
+
+        assert!(!SpawnProtection::new([0, 0, 0], -1).contains(BlockPos::new(0, 0, 0)));
+    }
+}
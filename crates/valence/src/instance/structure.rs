@@ -0,0 +1,158 @@
+//! Placing prefabricated groups of blocks.
+//!
+//! A [`BlockArray`] holds a box of blocks, such as a building loaded from a
+//! schematic, which is placed into the world with
+//! [`Instance::paste_structure`]. To copy whole sections between chunks, see
+//! [`Chunk::copy_section_from`].
+//!
+//! [`Instance::paste_structure`]: crate::instance::Instance::paste_structure
+//! [`Chunk::copy_section_from`]: crate::instance::Chunk::copy_section_from
+
+use valence_protocol::block::BlockState;
+
+/// A three dimensional array of block states.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct BlockArray {
+    size: [usize; 3],
+    /// Blocks ordered by Y, then Z, then X.
+    blocks: Vec<BlockState>,
+}
+
+impl BlockArray {
+    /// Constructs a new block array of [`BlockState::AIR`] with the given size
+    /// along the X, Y, and Z axes.
+    pub fn new(size: [usize; 3]) -> Self {
+        Self::filled(size, BlockState::AIR)
+    }
+
+    /// Constructs a new block array with the given size along the X, Y, and Z
+    /// axes where every block is `block`.
+    pub fn filled(size: [usize; 3], block: BlockState) -> Self {
+        Self {
+            size,
+            blocks: vec![block; size[0] * size[1] * size[2]],
+        }
+    }
+
+    /// Returns the size of this array along the X, Y, and Z axes.
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    /// Gets the block state at the provided offsets in the array.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are outside the bounds of the array.
+    #[track_caller]
+    pub fn block_state(&self, x: usize, y: usize, z: usize) -> BlockState {
+        self.blocks[self.index(x, y, z)]
+    }
+
+    /// Sets the block state at the provided offsets in the array. The
+    /// previous block state at the position is returned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the offsets are outside the bounds of the array.
+    #[track_caller]
+    pub fn set_block_state(
+        &mut self,
+        x: usize,
+        y: usize,
+        z: usize,
+        block: BlockState,
+    ) -> BlockState {
+        let idx = self.index(x, y, z);
+        std::mem::replace(&mut self.blocks[idx], block)
+    }
+
+    /// Returns an iterator over the offsets and block states of every block in
+    /// the array.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = ([usize; 3], BlockState)> + '_ {
+        let [size_x, _, size_z] = self.size;
+
+        self.blocks.iter().enumerate().map(move |(i, &block)| {
+            let x = i % size_x;
+            let z = i / size_x % size_z;
+            let y = i / (size_x * size_z);
+
+            ([x, y, z], block)
+        })
+    }
+
+    #[track_caller]
+    fn index(&self, x: usize, y: usize, z: usize) -> usize {
+        let [size_x, size_y, size_z] = self.size;
+
+        assert!(
+            x < size_x && y < size_y && z < size_z,
+            "block array offsets of ({x}, {y}, {z}) are out of bounds for size \
+             {size_x}x{size_y}x{size_z}"
+        );
+
+        x + z * size_x + y * size_x * size_z
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+    use valence_protocol::BlockPos;
+
+    use super::*;
+    use crate::assert_packet_count;
+    use crate::client::Client;
+    use crate::instance::{Chunk, Instance};
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn paste_small_structure() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.insert_chunk([0, 0], Chunk::default());
+
+        app.update();
+        client_helper.clear_sent();
+
+        let mut structure = BlockArray::filled([3, 3, 3], BlockState::STONE);
+        structure.set_block_state(1, 1, 1, BlockState::GLOWSTONE);
+        structure.set_block_state(2, 0, 1, BlockState::AIR);
+
+        let mut instance = app.world.get_mut::<Instance>(instance_ent).unwrap();
+        instance.set_block_state([3, 64, 4], BlockState::DIRT);
+        instance.paste_structure([2, 64, 3], &structure);
+
+        for ([x, y, z], block) in structure.iter() {
+            let pos = BlockPos::new(2 + x as i32, 64 + y as i32, 3 + z as i32);
+            assert_eq!(instance.block_state(pos), block);
+        }
+
+        assert_eq!(instance.block_state([3, 64, 4]), BlockState::STONE);
+        assert_eq!(instance.block_state([4, 64, 4]), BlockState::AIR);
+        assert_eq!(instance.block_state([3, 65, 4]), BlockState::GLOWSTONE);
+
+        app.update();
+
+        // The blocks are all in one section, so they are sent in one packet.
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_count!(sent_packets, 1, S2cPlayPacket::UpdateSectionBlocks(_));
+        assert_packet_count!(sent_packets, 0, S2cPlayPacket::BlockUpdate(_));
+    }
+
+    #[test]
+    fn block_array_iteration_order() {
+        let mut array = BlockArray::new([2, 3, 4]);
+        array.set_block_state(1, 2, 3, BlockState::STONE);
+
+        assert_eq!(array.iter().len(), 24);
+        assert_eq!(
+            array.iter().find(|&(_, b)| b == BlockState::STONE),
+            Some(([1, 2, 3], BlockState::STONE))
+        );
+    }
+}
@@ -0,0 +1,833 @@
+//! Rain and thunder.
+//!
+//! Weather is controlled by inserting a [`Weather`] component on an
+//! [`Instance`] entity. Clients in the instance are updated when the component
+//! is added, changed, or removed. Clients joining an instance with weather
+//! receive the current weather automatically. Inserting a
+//! [`WeatherTransition`] changes the levels gradually instead.
+//!
+//! A [`Weather`] component can also be inserted on a [`Client`] entity. The
+//! client then sees its own weather instead of the weather of its instance
+//! until the component is removed.
+//!
+//! Lightning can be spawned with [`strike_lightning`]. Inserting
+//! [`RandomLightning`] on an instance strikes lightning at random while it is
+//! thundering.
+
+use bevy_ecs::prelude::*;
+use glam::DVec3;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use valence_protocol::packets::s2c::play::GameEvent;
+use valence_protocol::types::GameEventKind;
+
+use crate::client::Client;
+use crate::entity::{EntityKind, McEntity};
+use crate::instance::Instance;
+use crate::math::normalized_clamp;
+use crate::Despawned;
+
+/// The minimum rain or thunder level.
+pub const WEATHER_LEVEL_MIN: f32 = 0.0;
+/// The maximum rain or thunder level.
+pub const WEATHER_LEVEL_MAX: f32 = 1.0;
+
+/// The weather of an [`Instance`], or of a single [`Client`]. The weather of a
+/// client takes precedence over the weather of its instance.
+///
+/// Levels outside of [`WEATHER_LEVEL_MIN`]..=[`WEATHER_LEVEL_MAX`] are
+/// clamped with [`normalized_clamp`], so NaN levels become zero. Removing this
+/// component ends the rain.
+#[derive(Component, Clone, Copy, PartialEq, Default, Debug)]
+pub struct Weather {
+    /// The rain level, or `None` if the rain level should not be changed.
+    pub rain: Option<f32>,
+    /// The thunder level, or `None` if the thunder level should not be
+    /// changed.
+    pub thunder: Option<f32>,
+}
+
+/// Removes the [`Weather`] of an [`Instance`] after a number of ticks have
+/// passed, like vanilla's random weather duration.
+///
+/// This component is removed together with the weather.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WeatherTimer {
+    /// The number of ticks until the weather is removed.
+    pub ticks_remaining: u32,
+}
+
+impl WeatherTimer {
+    pub fn new(ticks: u32) -> Self {
+        Self {
+            ticks_remaining: ticks,
+        }
+    }
+}
+
+/// The smallest change in a rain or thunder level that a [`WeatherTransition`]
+/// sends to clients.
+pub const WEATHER_TRANSITION_EPSILON: f32 = 0.01;
+
+/// Gradually moves the [`Weather`] levels of an [`Instance`] toward target
+/// levels over a number of ticks, instead of changing them instantly.
+///
+/// Clients are only sent a new level once it has changed by at least
+/// [`WEATHER_TRANSITION_EPSILON`]. The component is removed once the targets
+/// are reached. Has no effect on instances without [`Weather`].
+#[derive(Component, Clone, Copy, PartialEq, Debug)]
+pub struct WeatherTransition {
+    /// The target rain level, or `None` if the rain level should not be
+    /// changed.
+    pub rain: Option<f32>,
+    /// The target thunder level, or `None` if the thunder level should not be
+    /// changed.
+    pub thunder: Option<f32>,
+    /// The number of ticks until the targets are reached.
+    pub ticks_remaining: u32,
+    /// The interpolated levels. These can differ from the levels in
+    /// [`Weather`] by less than [`WEATHER_TRANSITION_EPSILON`].
+    current_rain: Option<f32>,
+    current_thunder: Option<f32>,
+}
+
+impl WeatherTransition {
+    pub fn new(rain: Option<f32>, thunder: Option<f32>, ticks: u32) -> Self {
+        Self {
+            rain,
+            thunder,
+            ticks_remaining: ticks,
+            current_rain: None,
+            current_thunder: None,
+        }
+    }
+
+    /// Sets new targets for the transition. The transition continues from
+    /// the current interpolated levels.
+    pub fn retarget(&mut self, rain: Option<f32>, thunder: Option<f32>, ticks: u32) {
+        self.rain = rain;
+        self.thunder = thunder;
+        self.ticks_remaining = ticks;
+    }
+}
+
+/// The default chance of lightning striking a loaded chunk each tick at full
+/// thunder level. This matches vanilla.
+pub const DEFAULT_LIGHTNING_CHANCE: f64 = 1.0 / 100_000.0;
+
+/// The number of ticks a lightning bolt spawned by [`strike_lightning`] exists
+/// for before it is despawned.
+pub const LIGHTNING_LIFETIME: u32 = 20;
+
+/// Randomly strikes lightning in the loaded chunks of an [`Instance`] while
+/// its [`Weather`] has a thunder level.
+///
+/// Every tick, lightning strikes each loaded chunk with a probability of
+/// `chance` multiplied by the thunder level. The random number generator can
+/// be seeded for reproducible results.
+#[derive(Component, Clone, Debug)]
+pub struct RandomLightning {
+    /// The chance of lightning striking a loaded chunk each tick at full
+    /// thunder level.
+    pub chance: f64,
+    rng: StdRng,
+}
+
+impl RandomLightning {
+    /// Creates a new `RandomLightning` with a random seed.
+    pub fn new() -> Self {
+        Self::with_seed(rand::random())
+    }
+
+    /// Creates a new `RandomLightning` whose strikes are determined by `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            chance: DEFAULT_LIGHTNING_CHANCE,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Default for RandomLightning {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Marks lightning bolts spawned by [`strike_lightning`]. The bolt is
+/// despawned once `ticks_remaining` reaches zero.
+#[derive(Component, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct LightningBolt {
+    pub ticks_remaining: u32,
+}
+
+/// Spawns a lightning bolt in `instance` at `position`. The lightning bolt is
+/// despawned automatically after [`LIGHTNING_LIFETIME`] ticks.
+pub fn strike_lightning(
+    commands: &mut Commands,
+    instance: Entity,
+    position: impl Into<DVec3>,
+) -> Entity {
+    let mut entity = McEntity::new(EntityKind::Lightning, instance);
+    entity.set_position(position);
+
+    commands
+        .spawn((
+            entity,
+            LightningBolt {
+                ticks_remaining: LIGHTNING_LIFETIME,
+            },
+        ))
+        .id()
+}
+
+impl Instance {
+    /// Begins rain for all clients in the instance.
+    pub fn begin_raining(&mut self) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::BeginRaining,
+            value: 0.0,
+        });
+    }
+
+    /// Ends rain and thunder for all clients in the instance.
+    pub fn end_raining(&mut self) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::EndRaining,
+            value: 0.0,
+        });
+    }
+
+    /// Sets the rain level for all clients in the instance. Has no visible
+    /// effect unless it is raining.
+    pub fn set_rain_level(&mut self, level: f32) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::RainLevelChange,
+            value: normalized_clamp(level),
+        });
+    }
+
+    /// Sets the thunder level for all clients in the instance. Has no visible
+    /// effect unless it is raining.
+    pub fn set_thunder_level(&mut self, level: f32) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::ThunderLevelChange,
+            value: normalized_clamp(level),
+        });
+    }
+}
+
+impl Client {
+    /// Begins rain for this client.
+    pub fn begin_raining(&mut self) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::BeginRaining,
+            value: 0.0,
+        });
+    }
+
+    /// Ends rain and thunder for this client.
+    pub fn end_raining(&mut self) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::EndRaining,
+            value: 0.0,
+        });
+    }
+
+    /// Sets the rain level for this client. Has no visible effect unless it is
+    /// raining.
+    pub fn set_rain_level(&mut self, level: f32) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::RainLevelChange,
+            value: normalized_clamp(level),
+        });
+    }
+
+    /// Sets the thunder level for this client. Has no visible effect unless it
+    /// is raining.
+    pub fn set_thunder_level(&mut self, level: f32) {
+        self.write_packet(&GameEvent {
+            kind: GameEventKind::ThunderLevelChange,
+            value: normalized_clamp(level),
+        });
+    }
+
+    fn set_weather(&mut self, weather: &Weather) {
+        if let Some(rain) = weather.rain {
+            self.set_rain_level(rain);
+        }
+
+        if let Some(thunder) = weather.thunder {
+            self.set_thunder_level(thunder);
+        }
+    }
+}
+
+fn handle_weather_for_joined_client(
+    mut clients: Query<(&mut Client, Option<&Weather>), Added<Client>>,
+    weathers: Query<&Weather, With<Instance>>,
+) {
+    for (mut client, own_weather) in &mut clients {
+        let weather = match own_weather {
+            Some(weather) => Some(weather),
+            None => weathers.get(client.instance()).ok(),
+        };
+
+        if let Some(weather) = weather {
+            client.begin_raining();
+            client.set_weather(weather);
+        }
+    }
+}
+
+fn handle_weather_begin_per_instance(
+    instances: Query<(Entity, &Weather), (Added<Weather>, With<Instance>)>,
+    mut clients: Query<&mut Client, Without<Weather>>,
+) {
+    for (instance, weather) in &instances {
+        for mut client in &mut clients {
+            if client.instance() == instance {
+                client.begin_raining();
+                client.set_weather(weather);
+            }
+        }
+    }
+}
+
+fn handle_weather_change_per_instance(
+    instances: Query<
+        (Entity, ChangeTrackers<Weather>, &Weather),
+        (Changed<Weather>, With<Instance>),
+    >,
+    mut clients: Query<&mut Client, Without<Weather>>,
+) {
+    for (instance, trackers, weather) in &instances {
+        // Newly added weather is handled by the begin system.
+        if trackers.is_added() {
+            continue;
+        }
+
+        for mut client in &mut clients {
+            if client.instance() == instance {
+                client.set_weather(weather);
+            }
+        }
+    }
+}
+
+fn handle_weather_end_per_instance(
+    instances: Query<(), With<Instance>>,
+    mut clients: Query<&mut Client, Without<Weather>>,
+    removed: RemovedComponents<Weather>,
+) {
+    for entity in &removed {
+        if instances.contains(entity) {
+            for mut client in &mut clients {
+                if client.instance() == entity {
+                    client.end_raining();
+                }
+            }
+        }
+    }
+}
+
+/// Sends the weather of clients with their own [`Weather`] when it is added or
+/// changed. The weather of the client's instance is not sent to these clients.
+fn handle_weather_change_per_client(
+    mut clients: Query<(&mut Client, ChangeTrackers<Weather>, &Weather), Changed<Weather>>,
+) {
+    for (mut client, trackers, weather) in &mut clients {
+        // Newly joined clients are handled by the join system.
+        if client.is_added() {
+            continue;
+        }
+
+        if trackers.is_added() {
+            client.begin_raining();
+        }
+
+        client.set_weather(weather);
+    }
+}
+
+/// Brings clients whose own [`Weather`] was removed back in sync with the
+/// weather of their instance.
+fn handle_weather_end_per_client(
+    mut clients: Query<&mut Client, Without<Weather>>,
+    weathers: Query<&Weather, With<Instance>>,
+    removed: RemovedComponents<Weather>,
+) {
+    for entity in &removed {
+        let Ok(mut client) = clients.get_mut(entity) else {
+            continue
+        };
+
+        match weathers.get(client.instance()) {
+            Ok(weather) => {
+                client.begin_raining();
+                client.set_weather(weather);
+            }
+            Err(_) => client.end_raining(),
+        }
+    }
+}
+
+/// Counts down [`WeatherTimer`]s and removes the weather once they run out.
+///
+/// This runs before the other weather systems so that the removal is observed
+/// in the same tick.
+pub(crate) fn tick_weather_timers(
+    mut commands: Commands,
+    mut timers: Query<(Entity, &mut WeatherTimer), With<Weather>>,
+) {
+    for (entity, mut timer) in &mut timers {
+        timer.ticks_remaining = timer.ticks_remaining.saturating_sub(1);
+
+        if timer.ticks_remaining == 0 {
+            commands
+                .entity(entity)
+                .remove::<Weather>()
+                .remove::<WeatherTimer>();
+        }
+    }
+}
+
+/// Moves the [`Weather`] of instances with a [`WeatherTransition`] one tick
+/// closer to the targets.
+pub(crate) fn update_weather_transitions(
+    mut commands: Commands,
+    mut instances: Query<(Entity, &mut Weather, &mut WeatherTransition), With<Instance>>,
+) {
+    for (entity, mut weather, mut transition) in &mut instances {
+        let transition = transition.as_mut();
+
+        let ticks = transition.ticks_remaining.max(1);
+        transition.ticks_remaining = ticks - 1;
+
+        let rain = step_weather_level(
+            weather.rain,
+            &mut transition.current_rain,
+            transition.rain,
+            ticks,
+        );
+
+        let thunder = step_weather_level(
+            weather.thunder,
+            &mut transition.current_thunder,
+            transition.thunder,
+            ticks,
+        );
+
+        // Only touch the weather when a level should be sent, since every
+        // change is sent to clients.
+        if weather.rain != rain {
+            weather.rain = rain;
+        }
+
+        if weather.thunder != thunder {
+            weather.thunder = thunder;
+        }
+
+        if transition.ticks_remaining == 0 {
+            commands.entity(entity).remove::<WeatherTransition>();
+        }
+    }
+}
+
+/// Moves the interpolated level `current` one step toward `target` with
+/// `ticks` steps remaining. Returns the level that clients should have, which
+/// is `sent` if the level did not change enough.
+fn step_weather_level(
+    sent: Option<f32>,
+    current: &mut Option<f32>,
+    target: Option<f32>,
+    ticks: u32,
+) -> Option<f32> {
+    let Some(target) = target else {
+        return sent
+    };
+
+    let target = normalized_clamp(target);
+    let level = current.or(sent).map_or(WEATHER_LEVEL_MIN, normalized_clamp);
+
+    let next = if ticks <= 1 {
+        target
+    } else {
+        (level + (target - level) / ticks as f32).clamp(WEATHER_LEVEL_MIN, WEATHER_LEVEL_MAX)
+    };
+
+    *current = Some(next);
+
+    match sent {
+        Some(sent) if next != target && (next - sent).abs() < WEATHER_TRANSITION_EPSILON => {
+            Some(sent)
+        }
+        _ => Some(next),
+    }
+}
+
+pub(crate) fn strike_random_lightning(
+    mut commands: Commands,
+    mut instances: Query<(Entity, &Instance, &Weather, &mut RandomLightning)>,
+) {
+    for (instance_ent, instance, weather, mut lightning) in &mut instances {
+        let Some(thunder) = weather.thunder else {
+            continue
+        };
+
+        let chance = lightning.chance * normalized_clamp(thunder) as f64;
+
+        if chance <= 0.0 {
+            continue;
+        }
+
+        for (pos, chunk) in instance.chunks() {
+            if !lightning.rng.gen_bool(chance.min(1.0)) {
+                continue;
+            }
+
+            let x = lightning.rng.gen_range(0..16);
+            let z = lightning.rng.gen_range(0..16);
+
+            // Strike the top of the highest block in the column.
+            let y = (0..chunk.section_count() * 16)
+                .rev()
+                .find(|&y| !chunk.block_state(x, y, z).is_air())
+                .map_or(0, |y| y + 1);
+
+            strike_lightning(
+                &mut commands,
+                instance_ent,
+                [
+                    (pos.x * 16 + x as i32) as f64 + 0.5,
+                    (instance.info.min_y + y as i32) as f64,
+                    (pos.z * 16 + z as i32) as f64 + 0.5,
+                ],
+            );
+        }
+    }
+}
+
+pub(crate) fn despawn_lightning_bolts(
+    mut commands: Commands,
+    mut bolts: Query<(Entity, &mut LightningBolt), Without<Despawned>>,
+) {
+    for (entity, mut bolt) in &mut bolts {
+        bolt.ticks_remaining = bolt.ticks_remaining.saturating_sub(1);
+
+        if bolt.ticks_remaining == 0 {
+            commands.entity(entity).insert(Despawned);
+        }
+    }
+}
+
+pub(crate) fn update_weather() -> SystemSet {
+    SystemSet::new()
+        .with_system(handle_weather_for_joined_client)
+        .with_system(handle_weather_begin_per_instance)
+        .with_system(handle_weather_change_per_instance)
+        .with_system(handle_weather_end_per_instance)
+        .with_system(handle_weather_change_per_client)
+        .with_system(handle_weather_end_per_client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use bevy_app::App;
+    use valence_protocol::packets::S2cPlayPacket;
+
+    use super::*;
+    use crate::instance::Chunk;
+    use crate::unit_test::util::scenario_single_client;
+    use crate::{assert_packet_count, assert_packet_order};
+
+    fn assert_weather_packets(sent_packets: Vec<S2cPlayPacket>) {
+        assert_packet_count!(sent_packets, 6, S2cPlayPacket::GameEvent(_));
+
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::BeginRaining,
+                value: _
+            }),
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::RainLevelChange,
+                value: _
+            }),
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::ThunderLevelChange,
+                value: _
+            }),
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::EndRaining,
+                value: _
+            })
+        );
+
+        // Levels are clamped.
+        assert!(sent_packets.iter().all(|p| match p {
+            S2cPlayPacket::GameEvent(GameEvent { value, .. }) =>
+                (WEATHER_LEVEL_MIN..=WEATHER_LEVEL_MAX).contains(value),
+            _ => true,
+        }));
+    }
+
+    #[test]
+    fn weather_instance() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        // Process a tick to get past the "on join" logic.
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(instance_ent).insert(Weather {
+            rain: Some(1.0),
+            thunder: Some(1.0),
+        });
+        app.update();
+
+        let mut weather = app.world.get_mut::<Weather>(instance_ent).unwrap();
+        weather.rain = Some(2.0);
+        weather.thunder = Some(f32::NAN);
+        app.update();
+
+        app.world.entity_mut(instance_ent).remove::<Weather>();
+        app.update();
+
+        assert_weather_packets(client_helper.collect_sent().unwrap());
+    }
+
+    #[test]
+    fn weather_timer_clears_weather() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(instance_ent).insert((
+            Weather {
+                rain: Some(1.0),
+                thunder: None,
+            },
+            WeatherTimer::new(3),
+        ));
+
+        for _ in 0..2 {
+            app.update();
+        }
+
+        assert!(app.world.get::<Weather>(instance_ent).is_some());
+
+        app.update();
+
+        assert!(app.world.get::<Weather>(instance_ent).is_none());
+        assert!(app.world.get::<WeatherTimer>(instance_ent).is_none());
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::BeginRaining,
+                value: _
+            }),
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::EndRaining,
+                value: _
+            })
+        );
+    }
+
+    fn rain_levels(sent_packets: Vec<S2cPlayPacket>) -> Vec<f32> {
+        sent_packets
+            .iter()
+            .filter_map(|p| match p {
+                S2cPlayPacket::GameEvent(GameEvent {
+                    kind: GameEventKind::RainLevelChange,
+                    value,
+                }) => Some(*value),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn weather_transition_interpolates_levels() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world.entity_mut(instance_ent).insert(Weather {
+            rain: Some(0.0),
+            thunder: None,
+        });
+        app.update();
+        client_helper.clear_sent();
+
+        app.world
+            .entity_mut(instance_ent)
+            .insert(WeatherTransition::new(Some(1.0), None, 10));
+
+        for _ in 0..5 {
+            app.update();
+        }
+
+        // Turn around halfway through.
+        app.world
+            .get_mut::<WeatherTransition>(instance_ent)
+            .unwrap()
+            .retarget(Some(0.0), None, 5);
+
+        for _ in 0..10 {
+            app.update();
+        }
+
+        assert!(app.world.get::<WeatherTransition>(instance_ent).is_none());
+        assert_eq!(
+            app.world.get::<Weather>(instance_ent).unwrap().rain,
+            Some(0.0)
+        );
+
+        let levels = rain_levels(client_helper.collect_sent().unwrap());
+        let expected = [0.1, 0.2, 0.3, 0.4, 0.5, 0.4, 0.3, 0.2, 0.1, 0.0];
+
+        assert_eq!(levels.len(), expected.len());
+        for (level, expected) in levels.into_iter().zip(expected) {
+            assert!((level - expected).abs() < 1e-5, "{level} != {expected}");
+        }
+    }
+
+    #[test]
+    fn weather_transition_skips_small_changes() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world.entity_mut(instance_ent).insert((
+            Weather {
+                rain: Some(0.0),
+                thunder: None,
+            },
+            WeatherTransition::new(Some(2.0), None, 1000),
+        ));
+
+        for _ in 0..1000 {
+            app.update();
+        }
+
+        let levels = rain_levels(client_helper.collect_sent().unwrap());
+
+        // About one packet per 10 ticks is sent.
+        assert!((90..=102).contains(&levels.len()), "{}", levels.len());
+        assert!(levels[..levels.len() - 1]
+            .windows(2)
+            .all(|w| w[1] - w[0] >= WEATHER_TRANSITION_EPSILON - 1e-5));
+        assert_eq!(levels.last(), Some(&WEATHER_LEVEL_MAX));
+
+        app.update();
+        assert!(rain_levels(client_helper.collect_sent().unwrap()).is_empty());
+    }
+
+    #[test]
+    fn client_weather_overrides_instance_weather() {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(client_ent).insert(Weather {
+            rain: Some(0.25),
+            thunder: None,
+        });
+        app.world.entity_mut(instance_ent).insert(Weather {
+            rain: Some(1.0),
+            thunder: None,
+        });
+        app.update();
+
+        app.world.get_mut::<Weather>(instance_ent).unwrap().rain = Some(0.5);
+        app.update();
+
+        assert_eq!(rain_levels(client_helper.collect_sent().unwrap()), [0.25]);
+
+        // The client is brought back in sync with the instance.
+        app.world.entity_mut(client_ent).remove::<Weather>();
+        app.update();
+
+        let sent_packets = client_helper.collect_sent().unwrap();
+        assert_packet_order!(
+            sent_packets,
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::BeginRaining,
+                value: _
+            }),
+            S2cPlayPacket::GameEvent(GameEvent {
+                kind: GameEventKind::RainLevelChange,
+                value: _
+            })
+        );
+        assert_eq!(rain_levels(sent_packets), [0.5]);
+    }
+
+    #[test]
+    fn random_lightning_during_thunder() {
+        let mut app = App::new();
+        let (client_ent, _client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.world
+            .get_mut::<Instance>(instance_ent)
+            .unwrap()
+            .insert_chunk([0, 0], Chunk::default());
+
+        let mut lightning = RandomLightning::with_seed(42);
+        lightning.chance = 0.25;
+
+        app.world.entity_mut(instance_ent).insert((
+            Weather {
+                rain: Some(1.0),
+                thunder: Some(0.0),
+            },
+            lightning,
+        ));
+
+        let mut bolts = app.world.query::<&LightningBolt>();
+
+        // No lightning without thunder.
+        for _ in 0..20 {
+            app.update();
+            assert_eq!(bolts.iter(&app.world).count(), 0);
+        }
+
+        app.world.get_mut::<Weather>(instance_ent).unwrap().thunder = Some(1.0);
+
+        let mut strikes = BTreeSet::new();
+        let mut bolts = app.world.query::<(Entity, &McEntity, &LightningBolt)>();
+
+        for _ in 0..20 {
+            app.update();
+
+            for (entity, mc_entity, _) in bolts.iter(&app.world) {
+                assert_eq!(mc_entity.kind(), EntityKind::Lightning);
+                assert_eq!(mc_entity.instance(), instance_ent);
+                strikes.insert(entity);
+            }
+        }
+
+        assert!(!strikes.is_empty());
+    }
+}
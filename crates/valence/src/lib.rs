@@ -42,6 +42,7 @@ pub mod math;
 mod packet;
 pub mod player_list;
 pub mod player_textures;
+pub mod scheduler;
 pub mod server;
 #[cfg(any(test, doctest))]
 mod unit_test;
@@ -52,15 +53,23 @@ pub mod prelude {
     pub use bevy_app::App;
     pub use bevy_ecs::prelude::*;
     pub use biome::{Biome, BiomeId};
-    pub use client::Client;
+    pub use client::{Client, PacketPriority};
     pub use config::{
-        AsyncCallbacks, ConnectionMode, PlayerSampleEntry, ServerListPing, ServerPlugin,
+        AsyncCallbacks, ConnectionMode, IllegalCharPolicy, KeepaliveIdStrategy, PlayerSampleEntry,
+        ServerListPing, ServerPlugin, StaticServerListPing,
     };
     pub use dimension::{Dimension, DimensionId};
     pub use entity::{
-        EntityAnimation, EntityKind, EntityStatus, McEntity, McEntityManager, TrackedData,
+        ControlledVehicle, EntityAnimation, EntityKind, EntityLodSettings, EntityStatus, Look,
+        McEntity, McEntityManager, OnFire, Position, SpawnEffect, TrackedData, Velocity,
     };
     pub use glam::DVec3;
+    pub use instance::generator::{ChunkGenerator, FlatGenerator};
+    pub use instance::protection::{BuildAction, BuildPermission, SpawnProtection};
+    pub use instance::structure::BlockArray;
+    pub use instance::weather::{
+        strike_lightning, RandomLightning, Weather, WeatherTimer, WeatherTransition,
+    };
     pub use instance::{Chunk, Instance};
     pub use inventory::{Inventory, InventoryKind, OpenInventory};
     pub use player_list::{PlayerList, PlayerListEntry};
@@ -70,6 +79,7 @@ pub mod prelude {
     pub use protocol::types::GameMode;
     pub use protocol::username::Username;
     pub use protocol::{ident, ItemKind, ItemStack};
+    pub use scheduler::Scheduler;
     pub use server::{EventLoop, NewClientInfo, Server, SharedServer};
     pub use uuid::Uuid;
     pub use valence_nbt::Compound;
@@ -82,10 +92,11 @@ pub mod prelude {
 /// A [`Component`] for marking entities that should be despawned at the end of
 /// the tick.
 ///
-/// In Valence, some built-in components such as [`McEntity`] are not allowed to
-/// be removed from the [`World`] directly. Instead, you must give the entities
-/// you wish to despawn the `Despawned` component. At the end of the tick,
-/// Valence will despawn all entities with this component for you.
+/// In Valence, some built-in components such as [`McEntity`] need to be
+/// deinitialized before they are removed from the [`World`]. Instead of
+/// despawning these entities directly, give them the `Despawned` component. At
+/// the end of the tick, Valence will despawn all entities with this component
+/// for you.
 ///
 /// It is legal to remove components or delete entities that Valence does not
 /// know about at any time.
@@ -62,6 +62,29 @@ pub fn from_yaw_and_pitch(yaw: f32, pitch: f32) -> Vec3 {
     Vec3::new(yaw_cos * pitch_cos, pitch_sin, yaw_sin * pitch_cos)
 }
 
+/// Returns the `(yaw, pitch)` angles in degrees for an entity at `from` to
+/// look at `to`.
+///
+/// The angles are the same as those returned by [`to_yaw_and_pitch`] for the
+/// direction from `from` to `to`. If the points are equal, both angles are 0.
+pub fn look_at(from: DVec3, to: DVec3) -> (f32, f32) {
+    if from == to {
+        return (0.0, 0.0);
+    }
+
+    to_yaw_and_pitch((to - from).normalize().as_vec3())
+}
+
+/// Clamps `value` to the range `0.0..=1.0`. Unlike [`f32::clamp`], NaN is
+/// mapped to `0.0` instead of being propagated.
+pub fn normalized_clamp(value: f32) -> f32 {
+    if value.is_nan() {
+        0.0
+    } else {
+        value.clamp(0.0, 1.0)
+    }
+}
+
 /// Returns the minimum number of bits needed to represent the integer `n`.
 pub(crate) const fn bit_width(n: usize) -> usize {
     (usize::BITS - n.leading_zeros()) as _
@@ -85,4 +108,63 @@ mod tests {
             assert_relative_eq!(d, d_new, epsilon = f32::EPSILON * 100.0);
         }
     }
+
+    #[test]
+    fn look_at_cardinal_directions() {
+        let from = DVec3::new(1.0, 2.0, 3.0);
+
+        for (to, expected_yaw) in [
+            (from + DVec3::Z, 0.0),
+            (from - DVec3::X * 5.0, 90.0),
+            (from + DVec3::X, -90.0),
+            (from + DVec3::new(1.0, 0.0, 1.0), -45.0),
+        ] {
+            let (yaw, pitch) = look_at(from, to);
+            assert_relative_eq!(yaw, expected_yaw, epsilon = 1e-4);
+            assert_relative_eq!(pitch, 0.0, epsilon = 1e-4);
+        }
+
+        let (yaw, _) = look_at(from, from - DVec3::Z);
+        assert_relative_eq!(yaw.rem_euclid(360.0), 180.0, epsilon = 1e-4);
+    }
+
+    #[test]
+    fn look_at_straight_up_and_down() {
+        let from = DVec3::new(1.0, 2.0, 3.0);
+
+        assert_relative_eq!(
+            look_at(from, from + DVec3::Y * 10.0).1,
+            -90.0,
+            epsilon = 1e-4
+        );
+        assert_relative_eq!(look_at(from, from - DVec3::Y).1, 90.0, epsilon = 1e-4);
+        assert_eq!(look_at(from, from), (0.0, 0.0));
+    }
+
+    #[test]
+    fn look_at_matches_yaw_and_pitch_direction() {
+        for _ in 0..=100 {
+            let from = DVec3::new(random(), random(), random()) * 100.0;
+            let to = DVec3::new(random(), random(), random()) * 100.0;
+
+            let (yaw, pitch) = look_at(from, to);
+            let d = from_yaw_and_pitch(yaw, pitch);
+
+            assert_relative_eq!(
+                d,
+                (to - from).normalize().as_vec3(),
+                epsilon = f32::EPSILON * 100.0
+            );
+        }
+    }
+
+    #[test]
+    fn normalized_clamp_range_and_nan() {
+        assert_eq!(normalized_clamp(0.5), 0.5);
+        assert_eq!(normalized_clamp(-1.0), 0.0);
+        assert_eq!(normalized_clamp(2.0), 1.0);
+        assert_eq!(normalized_clamp(f32::INFINITY), 1.0);
+        assert_eq!(normalized_clamp(f32::NEG_INFINITY), 0.0);
+        assert_eq!(normalized_clamp(f32::NAN), 0.0);
+    }
 }
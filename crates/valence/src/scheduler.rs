@@ -0,0 +1,189 @@
+//! Running code after a delay.
+//!
+//! The [`Scheduler`] resource holds tasks which are run a number of ticks in
+//! the future. This is useful for delayed effects such as removing an entity
+//! after a few seconds.
+//!
+//! ```
+//! # use valence::prelude::*;
+//! # use valence::Despawned;
+//! fn despawn_later(mut scheduler: ResMut<Scheduler>, query: Query<Entity, Added<McEntity>>) {
+//!     for entity in &query {
+//!         // Despawn the entity after five seconds.
+//!         scheduler.schedule_for(entity, 5 * 20, |world, entity| {
+//!             world.entity_mut(entity).insert(Despawned);
+//!         });
+//!     }
+//! }
+//! ```
+
+use bevy_ecs::prelude::*;
+
+/// A resource for scheduling tasks to run after a number of ticks.
+///
+/// Tasks are run in `CoreStage::PreUpdate` with exclusive access to the
+/// [`World`]. A task scheduled `n` ticks in the future runs during the `n`th
+/// call to `App::update` from now. A delay of zero is treated as a delay of
+/// one.
+#[derive(Resource, Default)]
+pub struct Scheduler {
+    /// The number of times the scheduled tasks have been checked.
+    tick: u64,
+    tasks: Vec<ScheduledTask>,
+}
+
+struct ScheduledTask {
+    run_at: u64,
+    entity: Option<Entity>,
+    task: Box<dyn FnOnce(&mut World, Option<Entity>) + Send + Sync>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Schedules a task to run after `ticks` ticks.
+    pub fn schedule(&mut self, ticks: u32, task: impl FnOnce(&mut World) + Send + Sync + 'static) {
+        self.push(ticks, None, Box::new(move |world, _| task(world)));
+    }
+
+    /// Schedules a task to run after `ticks` ticks on behalf of an entity, such
+    /// as an [`Instance`]. The entity is passed to the task. If the entity no
+    /// longer exists when the task is due, the task is dropped without
+    /// running.
+    ///
+    /// [`Instance`]: crate::instance::Instance
+    pub fn schedule_for(
+        &mut self,
+        entity: Entity,
+        ticks: u32,
+        task: impl FnOnce(&mut World, Entity) + Send + Sync + 'static,
+    ) {
+        self.push(
+            ticks,
+            Some(entity),
+            Box::new(move |world, entity| task(world, entity.expect("missing entity"))),
+        );
+    }
+
+    fn push(
+        &mut self,
+        ticks: u32,
+        entity: Option<Entity>,
+        task: Box<dyn FnOnce(&mut World, Option<Entity>) + Send + Sync>,
+    ) {
+        self.tasks.push(ScheduledTask {
+            run_at: self.tick + ticks.max(1) as u64,
+            entity,
+            task,
+        });
+    }
+
+    /// Returns the number of tasks waiting to run.
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    /// Returns `true` if there are no tasks waiting to run.
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Drops all scheduled tasks without running them.
+    pub fn clear(&mut self) {
+        self.tasks.clear();
+    }
+}
+
+/// Runs the tasks in the [`Scheduler`] which are due this tick.
+pub(crate) fn run_scheduled_tasks(world: &mut World) {
+    let mut scheduler = world.resource_mut::<Scheduler>();
+    scheduler.tick += 1;
+
+    let tick = scheduler.tick;
+
+    let mut due = vec![];
+    let mut i = 0;
+    while i < scheduler.tasks.len() {
+        if scheduler.tasks[i].run_at <= tick {
+            due.push(scheduler.tasks.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+
+    // Tasks are run in the order they were scheduled. Tasks may schedule more
+    // tasks since the resource is no longer borrowed.
+    for task in due {
+        if let Some(entity) = task.entity {
+            if world.get_entity(entity).is_none() {
+                continue;
+            }
+        }
+
+        (task.task)(world, task.entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use bevy_app::App;
+
+    use super::*;
+    use crate::unit_test::util::scenario_single_client;
+
+    #[test]
+    fn task_runs_on_third_tick() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        let runs = Arc::new(AtomicUsize::new(0));
+        let runs_clone = runs.clone();
+
+        app.world.resource_mut::<Scheduler>().schedule(3, move |_| {
+            runs_clone.fetch_add(1, Ordering::SeqCst);
+        });
+
+        app.update();
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        app.update();
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+        app.update();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(app.world.resource::<Scheduler>().is_empty());
+
+        app.update();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn entity_task_skipped_after_despawn() {
+        let mut app = App::new();
+        scenario_single_client(&mut app);
+
+        let kept = app.world.spawn_empty().id();
+        let removed = app.world.spawn_empty().id();
+
+        let runs = Arc::new(AtomicUsize::new(0));
+
+        let mut scheduler = app.world.resource_mut::<Scheduler>();
+        for entity in [kept, removed] {
+            let runs = runs.clone();
+            scheduler.schedule_for(entity, 2, move |_, e| {
+                assert_eq!(e, kept);
+                runs.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+
+        app.update();
+        app.world.despawn(removed);
+        app.update();
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(app.world.resource::<Scheduler>().is_empty());
+    }
+}
@@ -5,7 +5,7 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use anyhow::ensure;
+use anyhow::{ensure, Context};
 use bevy_app::prelude::*;
 use bevy_app::AppExit;
 use bevy_ecs::event::ManualEventReader;
@@ -13,24 +13,35 @@ use bevy_ecs::prelude::*;
 use flume::{Receiver, Sender};
 use rand::rngs::OsRng;
 use rsa::{PublicKeyParts, RsaPrivateKey};
+use tokio::net::TcpListener;
 use tokio::runtime::{Handle, Runtime};
 use tokio::sync::Semaphore;
 use uuid::Uuid;
 use valence_nbt::{compound, Compound, List};
 use valence_protocol::types::Property;
-use valence_protocol::{ident, Username};
+use valence_protocol::{ident, PacketLimits, Username};
 
 use crate::biome::{validate_biomes, Biome, BiomeId};
 use crate::client::event::{event_loop_run_criteria, register_client_events};
 use crate::client::{update_clients, Client};
-use crate::config::{AsyncCallbacks, ConnectionMode, ServerPlugin};
+use crate::config::{
+    AsyncCallbacks, ConnectionMode, IllegalCharPolicy, KeepaliveIdStrategy, ServerPlugin,
+};
 use crate::dimension::{validate_dimensions, Dimension, DimensionId};
 use crate::entity::{
-    check_entity_invariants, deinit_despawned_entities, init_entities, update_entities,
-    McEntityManager,
+    clear_removed_on_fire, deinit_despawned_entities, detach_from_despawned_entities,
+    init_entities, play_spawn_effects, update_controlled_vehicles, update_entities,
+    update_entity_transforms, update_on_fire, update_on_ground, EntityLodSettings, McEntityManager,
+    OnGround,
+};
+use crate::instance::weather::{
+    despawn_lightning_bolts, strike_random_lightning, tick_weather_timers, update_weather,
+    update_weather_transitions,
 };
 use crate::instance::{
-    check_instance_invariants, update_instances_post_client, update_instances_pre_client, Instance,
+    check_instance_invariants, init_instances, remove_deleted_entities, send_block_update_events,
+    send_void_fall_events, update_instance_time, update_instances_post_client,
+    update_instances_pre_client, BlockUpdateEvent, Instance, VoidFallEvent,
 };
 use crate::inventory::{
     handle_click_container, handle_close_container, handle_set_held_item, handle_set_slot_creative,
@@ -38,7 +49,9 @@ use crate::inventory::{
     Inventory, InventoryKind,
 };
 use crate::player_list::{update_player_list, PlayerList};
+use crate::scheduler::{run_scheduled_tasks, Scheduler};
 use crate::server::connect::do_accept_loop;
+pub use crate::server::connect::offline_uuid;
 use crate::Despawned;
 
 mod byte_channel;
@@ -87,6 +100,11 @@ struct SharedServerInner {
     max_connections: usize,
     incoming_capacity: usize,
     outgoing_capacity: usize,
+    packet_limits: PacketLimits,
+    entity_teleport_interval: Option<u32>,
+    keepalives: bool,
+    keepalive_id_strategy: KeepaliveIdStrategy,
+    illegal_char_policy: IllegalCharPolicy,
     /// The tokio handle used by the server.
     tokio_handle: Handle,
     /// Holding a runtime handle is not enough to keep tokio working. We need
@@ -160,6 +178,32 @@ impl SharedServer {
         self.0.outgoing_capacity
     }
 
+    /// Gets the configured limits on the contents of client packets.
+    pub fn packet_limits(&self) -> &PacketLimits {
+        &self.0.packet_limits
+    }
+
+    /// Gets the configured number of ticks between absolute entity position
+    /// updates.
+    pub fn entity_teleport_interval(&self) -> Option<u32> {
+        self.0.entity_teleport_interval
+    }
+
+    /// Gets whether keepalive packets are sent to clients.
+    pub fn keepalives(&self) -> bool {
+        self.0.keepalives
+    }
+
+    /// Gets the configured strategy for choosing keepalive IDs.
+    pub fn keepalive_id_strategy(&self) -> KeepaliveIdStrategy {
+        self.0.keepalive_id_strategy
+    }
+
+    /// Gets the configured policy for text containing illegal characters.
+    pub fn illegal_char_policy(&self) -> IllegalCharPolicy {
+        self.0.illegal_char_policy
+    }
+
     /// Gets a handle to the tokio instance this server is using.
     pub fn tokio_handle(&self) -> &Handle {
         &self.0.tokio_handle
@@ -287,6 +331,11 @@ pub fn build_plugin(
         max_connections: plugin.max_connections,
         incoming_capacity: plugin.incoming_capacity,
         outgoing_capacity: plugin.outgoing_capacity,
+        packet_limits: plugin.packet_limits,
+        entity_teleport_interval: plugin.entity_teleport_interval,
+        keepalives: plugin.keepalives,
+        keepalive_id_strategy: plugin.keepalive_id_strategy,
+        illegal_char_policy: plugin.illegal_char_policy,
         tokio_handle,
         _tokio_runtime: runtime,
         dimensions: plugin.dimensions.clone(),
@@ -313,8 +362,16 @@ pub fn build_plugin(
     let start_accept_loop = move || {
         let _guard = shared.tokio_handle().enter();
 
+        let shared = shared.clone();
+        let callbacks = callbacks.clone();
+
         // Start accepting new connections.
-        tokio::spawn(do_accept_loop(shared.clone(), callbacks.clone()));
+        tokio::spawn(async move {
+            match TcpListener::bind(shared.0.address).await {
+                Ok(listener) => do_accept_loop(shared, callbacks, listener).await,
+                Err(e) => shared.shutdown(Err(e).context("failed to start TCP listener")),
+            }
+        });
     };
 
     let shared = server.shared.clone();
@@ -326,7 +383,11 @@ pub fn build_plugin(
                 break
             };
 
-            world.spawn((client, Inventory::new(InventoryKind::Player)));
+            world.spawn((
+                client,
+                Inventory::new(InventoryKind::Player),
+                OnGround::default(),
+            ));
         }
     };
 
@@ -339,12 +400,23 @@ pub fn build_plugin(
     // Insert resources.
     app.insert_resource(server)
         .insert_resource(McEntityManager::new())
-        .insert_resource(PlayerList::new());
+        .insert_resource(PlayerList::new())
+        .insert_resource(Scheduler::new())
+        .init_resource::<EntityLodSettings>()
+        .add_event::<BlockUpdateEvent>()
+        .add_event::<VoidFallEvent>();
     register_client_events(&mut app.world);
 
     // Add core systems and stages. User code is expected to run in
     // `CoreStage::Update` and `EventLoop`.
-    app.add_system_to_stage(CoreStage::PreUpdate, spawn_new_clients)
+    app.add_system_to_stage(CoreStage::PreUpdate, init_instances)
+        .add_system_to_stage(CoreStage::PreUpdate, spawn_new_clients)
+        .add_system_to_stage(CoreStage::PreUpdate, run_scheduled_tasks)
+        .add_system_to_stage(CoreStage::PreUpdate, tick_weather_timers)
+        .add_system_to_stage(CoreStage::PreUpdate, update_weather_transitions)
+        .add_system_to_stage(CoreStage::PreUpdate, strike_random_lightning)
+        .add_system_to_stage(CoreStage::PreUpdate, despawn_lightning_bolts)
+        .add_system_to_stage(CoreStage::PreUpdate, update_on_fire)
         .add_stage_before(
             CoreStage::Update,
             EventLoop,
@@ -355,9 +427,18 @@ pub fn build_plugin(
             SystemSet::new()
                 .label("valence_core")
                 .with_system(init_entities)
-                .with_system(check_entity_invariants)
-                .with_system(check_instance_invariants.after(check_entity_invariants))
+                .with_system(remove_deleted_entities.before(update_instances_pre_client))
+                .with_system(check_instance_invariants.after(remove_deleted_entities))
                 .with_system(update_player_list.before(update_instances_pre_client))
+                .with_system(update_instance_time.before(update_instances_pre_client))
+                .with_system(send_block_update_events.before(update_instances_pre_client))
+                .with_system(send_void_fall_events.before(update_instances_pre_client))
+                .with_system(clear_removed_on_fire.before(update_instances_pre_client))
+                .with_system(detach_from_despawned_entities.before(update_instances_pre_client))
+                .with_system(play_spawn_effects.before(update_instances_pre_client))
+                .with_system(update_on_ground.before(update_entity_transforms))
+                .with_system(update_controlled_vehicles.before(update_entity_transforms))
+                .with_system(update_entity_transforms.before(update_instances_pre_client))
                 .with_system(update_instances_pre_client.after(init_entities))
                 .with_system(update_clients.after(update_instances_pre_client))
                 .with_system(update_instances_post_client.after(update_clients))
@@ -365,6 +446,10 @@ pub fn build_plugin(
                 .with_system(despawn_marked_entities.after(deinit_despawned_entities))
                 .with_system(update_entities.after(despawn_marked_entities)),
         )
+        .add_system_set_to_stage(
+            CoreStage::PostUpdate,
+            update_weather().before("valence_core"),
+        )
         .add_system_set_to_stage(
             CoreStage::PostUpdate,
             SystemSet::new()
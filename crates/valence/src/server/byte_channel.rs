@@ -122,6 +122,12 @@ impl ByteSender {
     pub fn limit(&self) -> usize {
         self.shared.limit
     }
+
+    /// Returns the number of bytes that can be sent before the limit is
+    /// reached.
+    pub fn remaining_capacity(&self) -> usize {
+        self.shared.limit - self.shared.mtx.lock().unwrap().bytes.len()
+    }
 }
 
 /// Contains any excess bytes not sent.
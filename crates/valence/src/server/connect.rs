@@ -6,9 +6,11 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, bail, ensure, Context};
+use async_trait::async_trait;
 use base64::prelude::*;
 use hmac::digest::Update;
 use hmac::{Hmac, Mac};
+use md5::Md5;
 use num::BigInt;
 use reqwest::StatusCode;
 use rsa::PaddingScheme;
@@ -16,10 +18,11 @@ use serde::Deserialize;
 use serde_json::{json, Value};
 use sha1::Sha1;
 use sha2::{Digest, Sha256};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
-use tokio::sync::OwnedSemaphorePermit;
-use tracing::{error, info, instrument, trace, warn};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore, TryAcquireError};
+use tokio::time::timeout;
+use tracing::{debug, error, info, instrument, trace, warn};
 use uuid::Uuid;
 use valence_protocol::packets::c2s::handshake::HandshakeOwned;
 use valence_protocol::packets::c2s::login::{EncryptionResponse, LoginPluginResponse, LoginStart};
@@ -38,65 +41,167 @@ use crate::config::{AsyncCallbacks, ConnectionMode, ServerListPing};
 use crate::server::connection::InitialConnection;
 use crate::server::{NewClientInfo, SharedServer};
 
-/// Accepts new connections to the server as they occur.
-#[instrument(skip_all)]
-pub async fn do_accept_loop(shared: SharedServer, callbacks: Arc<impl AsyncCallbacks>) {
-    let listener = match TcpListener::bind(shared.0.address).await {
-        Ok(listener) => listener,
-        Err(e) => {
-            shared.shutdown(Err(e).context("failed to start TCP listener"));
-            return;
+/// A source of incoming connections for the [accept loop](do_accept_loop).
+///
+/// The handshake, status, and login procedures only need a byte stream, so
+/// connections do not have to come from a TCP socket.
+#[async_trait]
+pub(crate) trait ConnectionListener: Send + 'static {
+    /// The byte stream of an accepted connection.
+    type Stream: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+    /// Waits for the next incoming connection and returns it along with the
+    /// address of the remote end.
+    async fn accept(&mut self) -> io::Result<(Self::Stream, SocketAddr)>;
+}
+
+#[async_trait]
+impl ConnectionListener for TcpListener {
+    type Stream = TcpStream;
+
+    async fn accept(&mut self) -> io::Result<(TcpStream, SocketAddr)> {
+        let (stream, remote_addr) = TcpListener::accept(self).await?;
+
+        if let Err(e) = stream.set_nodelay(true) {
+            error!("failed to set TCP_NODELAY: {e}");
         }
-    };
+
+        Ok((stream, remote_addr))
+    }
+}
+
+/// The maximum number of connections past the [connection limit] which are
+/// refused at the same time. Connections past both limits are closed without a
+/// response.
+///
+/// [connection limit]: crate::config::ServerPlugin::max_connections
+const MAX_REFUSED_CONNECTIONS: usize = 16;
+
+/// Accepts new connections from `listener` as they occur. Connections made
+/// while the server is at its [connection limit] are refused.
+///
+/// [connection limit]: crate::config::ServerPlugin::max_connections
+#[instrument(skip_all)]
+pub(crate) async fn do_accept_loop(
+    shared: SharedServer,
+    callbacks: Arc<impl AsyncCallbacks>,
+    mut listener: impl ConnectionListener,
+) {
+    let refusal_sema = Arc::new(Semaphore::new(MAX_REFUSED_CONNECTIONS));
 
     loop {
-        match shared.0.connection_sema.clone().acquire_owned().await {
-            Ok(permit) => match listener.accept().await {
-                Ok((stream, remote_addr)) => {
-                    tokio::spawn(handle_connection(
-                        shared.clone(),
-                        callbacks.clone(),
-                        stream,
-                        remote_addr,
-                        permit,
-                    ));
-                }
-                Err(e) => {
-                    error!("failed to accept incoming connection: {e}");
+        match listener.accept().await {
+            Ok((stream, remote_addr)) => {
+                match shared.0.connection_sema.clone().try_acquire_owned() {
+                    Ok(permit) => {
+                        tokio::spawn(handle_connection(
+                            shared.clone(),
+                            callbacks.clone(),
+                            stream,
+                            remote_addr,
+                            permit,
+                        ));
+                    }
+                    Err(TryAcquireError::NoPermits) => {
+                        match refusal_sema.clone().try_acquire_owned() {
+                            Ok(permit) => {
+                                tokio::spawn(refuse_connection(stream, remote_addr, permit));
+                            }
+                            Err(_) => {
+                                debug!(%remote_addr, "closing connection past the connection limit");
+                            }
+                        }
+                    }
+                    // Closed semaphore indicates server shutdown.
+                    Err(TryAcquireError::Closed) => return,
                 }
-            },
-            // Closed semaphore indicates server shutdown.
-            Err(_) => return,
+            }
+            Err(e) => {
+                error!("failed to accept incoming connection: {e}");
+            }
         }
     }
 }
 
-#[instrument(skip(shared, callbacks, stream, permit))]
-async fn handle_connection(
-    shared: SharedServer,
-    callbacks: Arc<impl AsyncCallbacks>,
-    stream: TcpStream,
+/// Refuses a connection made while the server is at its connection limit.
+/// Clients trying to log in are told the server is full and status pings are
+/// closed without a response.
+#[instrument(skip(stream, permit))]
+async fn refuse_connection(
+    stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
     remote_addr: SocketAddr,
     permit: OwnedSemaphorePermit,
 ) {
-    trace!("handling connection");
-
-    if let Err(e) = stream.set_nodelay(true) {
-        error!("failed to set TCP_NODELAY: {e}");
-    }
+    debug!("refusing connection because the server is at its connection limit");
 
-    let (read, write) = stream.into_split();
+    let (read, write) = tokio::io::split(stream);
 
-    let conn = InitialConnection::new(
+    let mut conn = InitialConnection::new(
         read,
         write,
         PacketEncoder::new(),
         PacketDecoder::new(),
-        Duration::from_secs(5),
+        Duration::from_secs(1),
         permit,
     );
 
-    // TODO: peek stream for 0xFE legacy ping
+    let res = async {
+        let handshake = conn.recv_packet::<HandshakeOwned>().await?;
+
+        if handshake.next_state == HandshakeNextState::Login {
+            let reason = Text::translate(translation_key::MULTIPLAYER_DISCONNECT_SERVER_FULL, []);
+            conn.send_packet(&DisconnectLogin {
+                reason: reason.into(),
+            })
+            .await?;
+        }
+
+        anyhow::Ok(())
+    };
+
+    if let Err(e) = res.await {
+        debug!("error refusing connection: {e:#}");
+    }
+}
+
+#[instrument(skip(shared, callbacks, stream, permit))]
+async fn handle_connection(
+    shared: SharedServer,
+    callbacks: Arc<impl AsyncCallbacks>,
+    mut stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    remote_addr: SocketAddr,
+    permit: OwnedSemaphorePermit,
+) {
+    trace!("handling connection");
+
+    let conn_timeout = Duration::from_secs(5);
+    let mut dec = PacketDecoder::new();
+
+    let mut first_byte = [0];
+
+    match timeout(conn_timeout, stream.read(&mut first_byte)).await {
+        Ok(Ok(1)) if first_byte[0] == LEGACY_PING_ID => {
+            if let Err(e) = handle_legacy_ping(shared, callbacks, stream, remote_addr).await {
+                warn!("legacy ping ended with error: {e:#}");
+            }
+            return;
+        }
+        // The byte is the start of a handshake.
+        Ok(Ok(1)) => dec.queue_slice(&first_byte),
+        Ok(Ok(_)) => return,
+        Ok(Err(e)) => {
+            warn!("failed to read from connection: {e}");
+            return;
+        }
+        Err(_) => {
+            warn!("connection timed out before sending any data");
+            return;
+        }
+    }
+
+    let (read, write) = tokio::io::split(stream);
+
+    let conn = InitialConnection::new(read, write, PacketEncoder::new(), dec, conn_timeout, permit);
 
     if let Err(e) = handle_handshake(shared, callbacks, conn, remote_addr).await {
         // EOF can happen if the client disconnects while joining, which isn't
@@ -110,12 +215,21 @@ async fn handle_connection(
     }
 }
 
-async fn handle_handshake(
+/// Routes a new connection to the status or login procedure. Connections which
+/// log in successfully are sent to the ECS as new clients.
+///
+/// This is independent of the underlying transport, so any byte stream can be
+/// used for the connection.
+async fn handle_handshake<R, W>(
     shared: SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    mut conn: InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    mut conn: InitialConnection<R, W>,
     remote_addr: SocketAddr,
-) -> anyhow::Result<()> {
+) -> anyhow::Result<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+    W: AsyncWrite + Unpin + Send + 'static,
+{
     let handshake = conn.recv_packet::<HandshakeOwned>().await?;
 
     ensure!(
@@ -155,16 +269,33 @@ async fn handle_handshake(
 async fn handle_status(
     shared: SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    mut conn: InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    mut conn: InitialConnection<impl AsyncRead + Unpin, impl AsyncWrite + Unpin>,
     remote_addr: SocketAddr,
     handshake: HandshakeOwned,
 ) -> anyhow::Result<()> {
     conn.recv_packet::<StatusRequest>().await?;
 
-    match callbacks
+    let ping = callbacks
         .server_list_ping(&shared, remote_addr, handshake.protocol_version.0)
-        .await
-    {
+        .await;
+
+    let Some(json) = status_response_json(&ping) else {
+        return Ok(())
+    };
+
+    conn.send_packet(&StatusResponse { json: &json }).await?;
+
+    let PingRequest { payload } = conn.recv_packet().await?;
+
+    conn.send_packet(&PingResponse { payload }).await?;
+
+    Ok(())
+}
+
+/// Builds the JSON sent in the [`StatusResponse`] packet. Returns `None` if
+/// the ping should be ignored.
+fn status_response_json(ping: &ServerListPing) -> Option<String> {
+    match ping {
         ServerListPing::Respond {
             online_players,
             max_players,
@@ -191,26 +322,90 @@ async fn handle_status(
                 json["favicon"] = Value::String(buf);
             }
 
-            conn.send_packet(&StatusResponse {
-                json: &json.to_string(),
-            })
-            .await?;
+            Some(json.to_string())
         }
-        ServerListPing::Ignore => return Ok(()),
+        ServerListPing::Ignore => None,
     }
+}
 
-    let PingRequest { payload } = conn.recv_packet().await?;
+/// The first byte sent by pre-1.7 clients when pinging the server. This is
+/// never the first byte of a modern handshake.
+const LEGACY_PING_ID: u8 = 0xfe;
 
-    conn.send_packet(&PingResponse { payload }).await?;
+/// The protocol version reported to legacy clients. Vanilla uses a version no
+/// legacy client supports so that the server is always shown as incompatible.
+const LEGACY_PROTOCOL_VERSION: i32 = 127;
+
+/// Responds to a legacy server list ping sent by clients older than 1.7. The
+/// packet ID has already been read from `stream`.
+async fn handle_legacy_ping(
+    shared: SharedServer,
+    callbacks: Arc<impl AsyncCallbacks>,
+    mut stream: impl AsyncRead + AsyncWrite + Unpin,
+    remote_addr: SocketAddr,
+) -> anyhow::Result<()> {
+    // Depending on the client version, the request may contain additional
+    // data after the packet ID. None of it is needed for the response, but it
+    // is read so the connection is not reset when it is closed with unread
+    // data. Beta clients send nothing else, so don't wait long.
+    let mut buf = [0; 256];
+    let _ = timeout(Duration::from_millis(100), stream.read(&mut buf)).await;
+
+    let ping = callbacks
+        .server_list_ping(&shared, remote_addr, LEGACY_PROTOCOL_VERSION)
+        .await;
+
+    if let Some(response) = legacy_ping_response(&ping) {
+        stream.write_all(&response).await?;
+        stream.flush().await?;
+    }
 
     Ok(())
 }
 
+/// Builds the kick packet sent in response to a legacy server list ping.
+/// Returns `None` if the ping should be ignored.
+///
+/// The response is a UTF-16BE string with the fields delimited by null
+/// characters, starting with `§1`.
+fn legacy_ping_response(ping: &ServerListPing) -> Option<Vec<u8>> {
+    let (online_players, max_players, description) = match ping {
+        ServerListPing::Respond {
+            online_players,
+            max_players,
+            description,
+            ..
+        } => (online_players, max_players, description),
+        ServerListPing::Ignore => return None,
+    };
+
+    let fields = [
+        "§1".to_owned(),
+        LEGACY_PROTOCOL_VERSION.to_string(),
+        MINECRAFT_VERSION.to_owned(),
+        description.to_string(),
+        online_players.to_string(),
+        max_players.to_string(),
+    ];
+
+    let chars: Vec<u16> = fields.join("\0").encode_utf16().collect();
+
+    let mut buf = Vec::with_capacity(3 + chars.len() * 2);
+    buf.push(0xff);
+    buf.extend_from_slice(&(chars.len() as u16).to_be_bytes());
+
+    for c in chars {
+        buf.extend_from_slice(&c.to_be_bytes());
+    }
+
+    Some(buf)
+}
+
 /// Handle the login process and return the new client's data if successful.
 async fn handle_login(
     shared: &SharedServer,
     callbacks: Arc<impl AsyncCallbacks>,
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    conn: &mut InitialConnection<impl AsyncRead + Unpin, impl AsyncWrite + Unpin>,
     remote_addr: SocketAddr,
     handshake: HandshakeOwned,
 ) -> anyhow::Result<Option<NewClientInfo>> {
@@ -253,11 +448,10 @@ async fn handle_login(
         return Ok(None);
     }
 
-    conn.send_packet(&LoginSuccess {
-        uuid: info.uuid,
-        username: info.username.as_str_username(),
-        properties: Default::default(),
-    })
+    conn.send_packet(
+        &LoginSuccess::new(info.uuid, info.username.as_str_username())
+            .with_properties(info.properties.as_slice()),
+    )
     .await?;
 
     Ok(Some(info))
@@ -267,7 +461,7 @@ async fn handle_login(
 pub(super) async fn login_online(
     shared: &SharedServer,
     callbacks: &Arc<impl AsyncCallbacks>,
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    conn: &mut InitialConnection<impl AsyncRead + Unpin, impl AsyncWrite + Unpin>,
     remote_addr: SocketAddr,
     username: Username<String>,
 ) -> anyhow::Result<NewClientInfo> {
@@ -366,14 +560,28 @@ fn auth_digest(bytes: &[u8]) -> String {
     BigInt::from_signed_bytes_be(bytes).to_str_radix(16)
 }
 
+/// Returns the UUID given to a player with the given username when the server
+/// is in [offline mode]. This is the same version 3 UUID the vanilla server
+/// derives from `"OfflinePlayer:<username>"`, so it is the same every time the
+/// player joins.
+///
+/// [offline mode]: ConnectionMode::Offline
+pub fn offline_uuid(username: &str) -> Uuid {
+    let hash = Md5::new()
+        .chain(b"OfflinePlayer:")
+        .chain(username)
+        .finalize();
+
+    uuid::Builder::from_md5_bytes(hash.into()).into_uuid()
+}
+
 /// Login procedure for offline mode.
 pub(super) fn login_offline(
     remote_addr: SocketAddr,
     username: Username<String>,
 ) -> anyhow::Result<NewClientInfo> {
     Ok(NewClientInfo {
-        // Derive the client's UUID from a hash of their username.
-        uuid: Uuid::from_slice(&Sha256::digest(username.as_str())[..16])?,
+        uuid: offline_uuid(username.as_str()),
         username,
         properties: vec![],
         ip: remote_addr.ip(),
@@ -407,7 +615,7 @@ pub(super) fn login_bungeecord(
 
 /// Login procedure for Velocity.
 pub(super) async fn login_velocity(
-    conn: &mut InitialConnection<OwnedReadHalf, OwnedWriteHalf>,
+    conn: &mut InitialConnection<impl AsyncRead + Unpin, impl AsyncWrite + Unpin>,
     username: Username<String>,
     velocity_secret: &str,
 ) -> anyhow::Result<NewClientInfo> {
@@ -481,9 +689,13 @@ pub(super) async fn login_velocity(
 
 #[cfg(test)]
 mod tests {
+    use bevy_app::App;
     use sha1::Digest;
+    use tokio::io::DuplexStream;
 
     use super::*;
+    use crate::config::{ServerPlugin, StaticServerListPing};
+    use crate::server::Server;
 
     #[test]
     fn auth_digest_usernames() {
@@ -500,4 +712,427 @@ mod tests {
             "88e16a1019277b15d58faf0541e11910eb756f6"
         );
     }
+
+    #[test]
+    fn status_response_contents() {
+        let ping = ServerListPing::Respond {
+            online_players: 3,
+            max_players: 20,
+            player_sample: vec![],
+            description: "Hello".into(),
+            favicon_png: &[1, 2, 3],
+        };
+
+        let json: Value = serde_json::from_str(&status_response_json(&ping).unwrap()).unwrap();
+
+        assert_eq!(json["version"]["protocol"], PROTOCOL_VERSION);
+        assert_eq!(json["version"]["name"], MINECRAFT_VERSION);
+        assert_eq!(json["players"]["online"], 3);
+        assert_eq!(json["players"]["max"], 20);
+        assert_eq!(json["description"]["text"], "Hello");
+        assert_eq!(json["favicon"], "data:image/png;base64,AQID");
+
+        assert!(status_response_json(&ServerListPing::Ignore).is_none());
+    }
+
+    fn ping_json(callbacks: impl AsyncCallbacks) -> Value {
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()));
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let remote_addr = "127.0.0.1:12345".parse().unwrap();
+
+        let json = shared.tokio_handle().block_on(async {
+            let ping = callbacks
+                .server_list_ping(&shared, remote_addr, PROTOCOL_VERSION)
+                .await;
+
+            status_response_json(&ping).unwrap()
+        });
+
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn custom_server_list_ping_player_count() {
+        struct MyCallbacks;
+
+        #[async_trait]
+        impl AsyncCallbacks for MyCallbacks {
+            async fn server_list_ping(
+                &self,
+                _shared: &SharedServer,
+                _remote_addr: SocketAddr,
+                _protocol_version: i32,
+            ) -> ServerListPing {
+                ServerListPing::Respond {
+                    online_players: 42,
+                    max_players: 420,
+                    player_sample: vec![],
+                    description: "Custom".into(),
+                    favicon_png: &[],
+                }
+            }
+        }
+
+        let json = ping_json(MyCallbacks);
+        assert_eq!(json["players"]["online"], 42);
+        assert_eq!(json["players"]["max"], 420);
+        assert_eq!(json["description"]["text"], "Custom");
+
+        let json = ping_json(StaticServerListPing {
+            online_players: 7,
+            max_players: 100,
+            ..Default::default()
+        });
+        assert_eq!(json["players"]["online"], 7);
+        assert_eq!(json["players"]["max"], 100);
+        assert_eq!(json["description"]["text"], "A Valence Server");
+    }
+
+    #[test]
+    fn legacy_ping() {
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()));
+
+        let shared = app.world.resource::<Server>().shared().clone();
+
+        let listener = start_mock_accept_loop(
+            &shared,
+            Arc::new(StaticServerListPing {
+                online_players: 5,
+                max_players: 10,
+                description: "Hi".into(),
+                ..Default::default()
+            }),
+        );
+
+        let response = shared.tokio_handle().block_on(async {
+            let mut client = connect_mock(&listener);
+
+            // Sent by 1.4 and 1.5 clients.
+            client.write_all(&[0xfe, 0x01]).await.unwrap();
+
+            let mut response = vec![];
+            client.read_to_end(&mut response).await.unwrap();
+            response
+        });
+
+        assert_eq!(response[0], 0xff);
+
+        let len = u16::from_be_bytes([response[1], response[2]]) as usize;
+        assert_eq!(response.len(), 3 + len * 2);
+
+        let chars: Vec<u16> = response[3..]
+            .chunks_exact(2)
+            .map(|c| u16::from_be_bytes([c[0], c[1]]))
+            .collect();
+
+        assert_eq!(
+            String::from_utf16(&chars).unwrap(),
+            format!("§1\0127\0{MINECRAFT_VERSION}\0Hi\05\010")
+        );
+    }
+
+    /// Reads from `stream` until `dec` has a complete packet.
+    async fn read_until_packet(stream: &mut (impl AsyncRead + Unpin), dec: &mut PacketDecoder) {
+        while !dec.has_next_packet().unwrap() {
+            let mut buf = [0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            assert!(n > 0, "unexpected EOF");
+            dec.queue_slice(&buf[..n]);
+        }
+    }
+
+    /// Accepts in-memory connections sent through a channel.
+    struct MockListener(flume::Receiver<DuplexStream>);
+
+    #[async_trait]
+    impl ConnectionListener for MockListener {
+        type Stream = DuplexStream;
+
+        async fn accept(&mut self) -> io::Result<(DuplexStream, SocketAddr)> {
+            match self.0.recv_async().await {
+                Ok(stream) => Ok((stream, "127.0.0.1:12345".parse().unwrap())),
+                // No more connections are coming.
+                Err(_) => std::future::pending().await,
+            }
+        }
+    }
+
+    /// Starts the accept loop with a [`MockListener`], returning the sender
+    /// used to make connections with [`connect_mock`].
+    fn start_mock_accept_loop(
+        shared: &SharedServer,
+        callbacks: Arc<impl AsyncCallbacks>,
+    ) -> flume::Sender<DuplexStream> {
+        let (send, recv) = flume::unbounded();
+
+        let _guard = shared.tokio_handle().enter();
+        tokio::spawn(do_accept_loop(
+            shared.clone(),
+            callbacks,
+            MockListener(recv),
+        ));
+
+        send
+    }
+
+    /// Makes a connection through the mock accept loop, returning the client's
+    /// end of the stream.
+    fn connect_mock(listener: &flume::Sender<DuplexStream>) -> DuplexStream {
+        let (client, server) = tokio::io::duplex(4096);
+        listener.send(server).unwrap();
+        client
+    }
+
+    #[test]
+    fn mock_connection_status_and_login() {
+        use valence_protocol::packets::c2s::handshake::Handshake;
+
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_compression_threshold(None),
+        );
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let listener = start_mock_accept_loop(
+            &shared,
+            Arc::new(StaticServerListPing {
+                online_players: 1,
+                ..Default::default()
+            }),
+        );
+
+        let (json, login_uuid, client) = shared.tokio_handle().block_on(async {
+            // Status
+            let mut stream = connect_mock(&listener);
+            let mut enc = PacketEncoder::new();
+            let mut dec = PacketDecoder::new();
+
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: HandshakeNextState::Status,
+            })
+            .unwrap();
+            enc.append_packet(&StatusRequest).unwrap();
+            stream.write_all(&enc.take()).await.unwrap();
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let json: Value = serde_json::from_str(
+                dec.try_next_packet::<StatusResponse>()
+                    .unwrap()
+                    .unwrap()
+                    .json,
+            )
+            .unwrap();
+
+            enc.append_packet(&PingRequest { payload: 0xabc }).unwrap();
+            stream.write_all(&enc.take()).await.unwrap();
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let pong = dec.try_next_packet::<PingResponse>().unwrap().unwrap();
+            assert_eq!(pong.payload, 0xabc);
+
+            // Login
+            let mut stream = connect_mock(&listener);
+            let mut dec = PacketDecoder::new();
+
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+            enc.append_packet(&LoginStart {
+                username: Username::new("tester").unwrap(),
+                profile_id: None,
+            })
+            .unwrap();
+            stream.write_all(&enc.take()).await.unwrap();
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let success = dec.try_next_packet::<LoginSuccess>().unwrap().unwrap();
+            assert_eq!(success.username.as_str(), "tester");
+
+            // The logged in connection is handed off to be spawned as a client
+            // entity.
+            let client = shared.0.new_clients_recv.recv_async().await.unwrap();
+
+            (json, success.uuid, client)
+        });
+
+        assert_eq!(json["players"]["online"], 1);
+
+        assert_eq!(client.username().as_str(), "tester");
+        assert_eq!(client.uuid(), login_uuid);
+    }
+
+    #[test]
+    fn connections_past_the_limit_are_refused() {
+        use valence_protocol::packets::c2s::handshake::Handshake;
+
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_compression_threshold(None)
+                .with_max_connections(2),
+        );
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let listener = start_mock_accept_loop(&shared, Arc::new(StaticServerListPing::default()));
+
+        shared.tokio_handle().block_on(async {
+            let mut enc = PacketEncoder::new();
+
+            // The first two connections are accepted. They stay open waiting
+            // for a ping after receiving the status.
+            let mut open = vec![];
+            for _ in 0..2 {
+                let mut stream = connect_mock(&listener);
+                let mut dec = PacketDecoder::new();
+
+                enc.append_packet(&Handshake {
+                    protocol_version: VarInt(PROTOCOL_VERSION),
+                    server_address: "localhost",
+                    server_port: 25565,
+                    next_state: HandshakeNextState::Status,
+                })
+                .unwrap();
+                enc.append_packet(&StatusRequest).unwrap();
+                stream.write_all(&enc.take()).await.unwrap();
+
+                read_until_packet(&mut stream, &mut dec).await;
+                assert!(dec.try_next_packet::<StatusResponse>().unwrap().is_some());
+
+                open.push(stream);
+            }
+
+            // The third is refused.
+            let mut stream = connect_mock(&listener);
+            let mut dec = PacketDecoder::new();
+
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+            enc.append_packet(&LoginStart {
+                username: Username::new("too_many").unwrap(),
+                profile_id: None,
+            })
+            .unwrap();
+            stream.write_all(&enc.take()).await.unwrap();
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let disconnect = dec.try_next_packet::<DisconnectLogin>().unwrap().unwrap();
+            assert_eq!(
+                *disconnect.reason,
+                Text::translate(translation_key::MULTIPLAYER_DISCONNECT_SERVER_FULL, [])
+            );
+
+            // The refused connection is closed.
+            let mut rest = vec![];
+            stream.read_to_end(&mut rest).await.unwrap();
+            assert!(rest.is_empty());
+        });
+    }
+
+    #[test]
+    fn connections_past_the_refusal_limit_are_closed() {
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_max_connections(0),
+        );
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let listener = start_mock_accept_loop(&shared, Arc::new(StaticServerListPing::default()));
+
+        shared.tokio_handle().block_on(async {
+            // These are being refused, waiting for a handshake that never comes.
+            let _refusing: Vec<_> = (0..MAX_REFUSED_CONNECTIONS)
+                .map(|_| connect_mock(&listener))
+                .collect();
+
+            // The next connection is closed without waiting for the handshake.
+            let mut stream = connect_mock(&listener);
+            let mut rest = vec![];
+            timeout(Duration::from_millis(500), stream.read_to_end(&mut rest))
+                .await
+                .expect("connection was not closed")
+                .unwrap();
+            assert!(rest.is_empty());
+        });
+    }
+
+    #[test]
+    fn offline_login_with_compression() {
+        use valence_protocol::packets::c2s::handshake::Handshake;
+
+        let mut app = App::new();
+        app.add_plugin(
+            ServerPlugin::new(())
+                .with_connection_mode(ConnectionMode::Offline)
+                .with_compression_threshold(Some(0)),
+        );
+
+        let shared = app.world.resource::<Server>().shared().clone();
+        let listener = start_mock_accept_loop(&shared, Arc::new(()));
+
+        let client = shared.tokio_handle().block_on(async {
+            let mut stream = connect_mock(&listener);
+            let mut enc = PacketEncoder::new();
+            let mut dec = PacketDecoder::new();
+
+            enc.append_packet(&Handshake {
+                protocol_version: VarInt(PROTOCOL_VERSION),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state: HandshakeNextState::Login,
+            })
+            .unwrap();
+            enc.append_packet(&LoginStart {
+                username: Username::new("offline_player").unwrap(),
+                profile_id: None,
+            })
+            .unwrap();
+            stream.write_all(&enc.take()).await.unwrap();
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let compression = dec.try_next_packet::<SetCompression>().unwrap().unwrap();
+            assert_eq!(compression.threshold, VarInt(0));
+
+            dec.set_compression(true);
+
+            read_until_packet(&mut stream, &mut dec).await;
+            let success = dec.try_next_packet::<LoginSuccess>().unwrap().unwrap();
+            assert_eq!(success.uuid, offline_uuid("offline_player"));
+            assert!(success.properties.is_empty());
+
+            shared.0.new_clients_recv.recv_async().await.unwrap()
+        });
+
+        assert_eq!(client.username().as_str(), "offline_player");
+        assert_eq!(client.uuid(), offline_uuid("offline_player"));
+        assert_ne!(offline_uuid("offline_player"), offline_uuid("other_player"));
+    }
+
+    #[test]
+    fn offline_uuid_matches_vanilla() {
+        assert_eq!(
+            offline_uuid("Notch"),
+            Uuid::parse_str("b50ad385-829d-3141-a216-7e7d7539ba7f").unwrap()
+        );
+        assert_eq!(offline_uuid("Notch").get_version_num(), 3);
+    }
 }
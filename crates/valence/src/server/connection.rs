@@ -214,6 +214,14 @@ impl ClientConnection for RealClientConnection {
         }
     }
 
+    fn remaining_capacity(&self) -> Option<usize> {
+        Some(self.send.remaining_capacity())
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        Some(self.send.limit())
+    }
+
     fn try_recv(&mut self) -> anyhow::Result<BytesMut> {
         match self.recv.try_recv() {
             Ok(bytes) => Ok(bytes),
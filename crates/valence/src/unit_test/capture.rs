@@ -0,0 +1,238 @@
+//! Recording the packets sent to a mock client so they can be compared against
+//! a later test run, golden file style.
+//!
+//! A capture is stored as the packet frames exactly as they were sent, with
+//! compression and encryption disabled.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use anyhow::{bail, ensure};
+use valence_protocol::packets::S2cPlayPacket;
+use valence_protocol::{DecodePacket, VarInt};
+
+/// Set this environment variable to overwrite golden files with the packets
+/// sent in the current run.
+pub const UPDATE_GOLDEN_VAR: &str = "VALENCE_UPDATE_GOLDEN";
+
+/// A recorded sequence of packets sent to a client.
+#[derive(Clone, PartialEq, Eq, Default, Debug)]
+pub struct PacketCapture {
+    /// Every packet frame, including its length prefix.
+    frames: Vec<Vec<u8>>,
+}
+
+impl PacketCapture {
+    /// Splits uncompressed packet data into a capture. Returns an error if the
+    /// data ends in the middle of a packet.
+    pub fn from_bytes(mut bytes: &[u8]) -> anyhow::Result<Self> {
+        let mut frames = vec![];
+
+        while !bytes.is_empty() {
+            let Some((len, prefix_len)) = VarInt::decode_prefix(bytes)? else {
+                bail!("packet length is incomplete");
+            };
+
+            ensure!(len >= 0, "packet length is negative");
+
+            let frame_len = prefix_len + len as usize;
+            ensure!(bytes.len() >= frame_len, "packet is incomplete");
+
+            frames.push(bytes[..frame_len].to_vec());
+            bytes = &bytes[frame_len..];
+        }
+
+        Ok(Self { frames })
+    }
+
+    /// Returns the number of packets in the capture.
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Returns `true` if there are no packets in the capture.
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Decodes the packets in the capture.
+    pub fn packets(&self) -> anyhow::Result<Vec<S2cPlayPacket>> {
+        self.frames.iter().map(|f| decode_frame(f)).collect()
+    }
+
+    /// Writes the capture in the same format accepted by [`Self::read_from`].
+    pub fn write_to(&self, mut w: impl Write) -> io::Result<()> {
+        for frame in &self.frames {
+            w.write_all(frame)?;
+        }
+
+        w.flush()
+    }
+
+    /// Reads a capture written by [`Self::write_to`].
+    pub fn read_from(mut r: impl Read) -> anyhow::Result<Self> {
+        let mut bytes = vec![];
+        r.read_to_end(&mut bytes)?;
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Panics if this capture differs from `expected`. The message describes
+    /// the first packet which differs.
+    #[track_caller]
+    pub fn assert_matches(&self, expected: &PacketCapture) {
+        for (i, (actual, expected)) in self.frames.iter().zip(&expected.frames).enumerate() {
+            if actual != expected {
+                panic!(
+                    "packet {i} differs from the capture\n  actual: {:?}\nexpected: {:?}",
+                    decode_frame(actual),
+                    decode_frame(expected),
+                );
+            }
+        }
+
+        assert_eq!(
+            self.len(),
+            expected.len(),
+            "number of packets differs from the capture"
+        );
+    }
+
+    /// Compares this capture against the golden file at `path`. If the
+    /// [`UPDATE_GOLDEN_VAR`] environment variable is set, the file is written
+    /// instead.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the capture differs from the golden file, or if the file does
+    /// not exist and the environment variable is not set.
+    #[track_caller]
+    pub fn assert_golden(&self, path: impl AsRef<Path>) {
+        let path = path.as_ref();
+
+        if std::env::var_os(UPDATE_GOLDEN_VAR).is_some() {
+            self.write_golden(path);
+            return;
+        }
+
+        assert!(
+            path.exists(),
+            "golden file {} does not exist, set {UPDATE_GOLDEN_VAR} to create it",
+            path.display()
+        );
+
+        let file = File::open(path).expect("failed to open golden file");
+        let expected = Self::read_from(BufReader::new(file)).expect("failed to read golden file");
+
+        self.assert_matches(&expected);
+    }
+
+    fn write_golden(&self, path: &Path) {
+        let file = File::create(path).expect("failed to create golden file");
+        self.write_to(BufWriter::new(file))
+            .expect("failed to write golden file");
+    }
+}
+
+fn decode_frame(frame: &[u8]) -> anyhow::Result<S2cPlayPacket> {
+    let mut r = frame;
+    VarInt::decode_partial(&mut r)?;
+
+    let pkt = S2cPlayPacket::decode_packet(&mut r)?;
+    ensure!(r.is_empty(), "{} bytes remain after packet", r.len());
+
+    Ok(pkt)
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy_app::App;
+
+    use super::*;
+    use crate::client::Client;
+    use crate::instance::weather::Weather;
+    use crate::unit_test::util::scenario_single_client;
+
+    fn record_weather_packets() -> PacketCapture {
+        let mut app = App::new();
+        let (client_ent, mut client_helper) = scenario_single_client(&mut app);
+
+        let instance_ent = app.world.get::<Client>(client_ent).unwrap().instance();
+
+        app.update();
+        client_helper.clear_sent();
+
+        app.world.entity_mut(instance_ent).insert(Weather {
+            rain: Some(1.0),
+            thunder: Some(0.5),
+        });
+        app.update();
+
+        app.world.entity_mut(instance_ent).remove::<Weather>();
+        app.update();
+
+        client_helper.capture_sent()
+    }
+
+    #[test]
+    fn weather_packets_replay() {
+        let mut golden = vec![];
+        record_weather_packets().write_to(&mut golden).unwrap();
+
+        let expected = PacketCapture::read_from(golden.as_slice()).unwrap();
+        assert!(!expected.is_empty());
+        assert!(expected
+            .packets()
+            .unwrap()
+            .iter()
+            .any(|p| matches!(p, S2cPlayPacket::GameEvent(_))));
+
+        record_weather_packets().assert_matches(&expected);
+    }
+
+    fn temp_golden_path() -> std::path::PathBuf {
+        std::env::temp_dir().join(format!(
+            "valence-weather-golden-{}.bin",
+            uuid::Uuid::new_v4()
+        ))
+    }
+
+    #[test]
+    fn golden_file_is_written_then_compared() {
+        let path = temp_golden_path();
+
+        record_weather_packets().write_golden(&path);
+        assert!(path.exists());
+
+        record_weather_packets().assert_golden(&path);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "does not exist")]
+    fn missing_golden_file_panics() {
+        let path = temp_golden_path();
+
+        record_weather_packets().assert_golden(path);
+    }
+
+    #[test]
+    #[should_panic(expected = "differs from the capture")]
+    fn mismatched_capture_panics() {
+        let mut expected = record_weather_packets();
+        expected.frames.pop();
+
+        record_weather_packets().assert_matches(&expected);
+    }
+
+    #[test]
+    fn incomplete_capture_is_an_error() {
+        let mut bytes = vec![];
+        record_weather_packets().write_to(&mut bytes).unwrap();
+        bytes.pop();
+
+        assert!(PacketCapture::from_bytes(&bytes).is_err());
+    }
+}
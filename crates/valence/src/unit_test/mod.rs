@@ -1,2 +1,3 @@
+pub(crate) mod capture;
 mod example;
 pub(crate) mod util;
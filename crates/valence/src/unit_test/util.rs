@@ -9,8 +9,10 @@ use valence_protocol::{EncodePacket, PacketDecoder, PacketEncoder, Username};
 use crate::client::{Client, ClientConnection};
 use crate::config::{ConnectionMode, ServerPlugin};
 use crate::dimension::DimensionId;
+use crate::entity::OnGround;
 use crate::inventory::{Inventory, InventoryKind};
 use crate::server::{NewClientInfo, Server};
+use crate::unit_test::capture::PacketCapture;
 
 /// Creates a mock client that can be used for unit testing.
 ///
@@ -49,6 +51,8 @@ struct MockClientBuffers {
     recv_buf: BytesMut,
     /// The queue of packets to send from the server to the client.
     send_buf: BytesMut,
+    /// The maximum size of `send_buf` reported to the server, if any.
+    capacity: Option<usize>,
 }
 
 impl MockClientConnection {
@@ -57,6 +61,7 @@ impl MockClientConnection {
             buffers: Arc::new(Mutex::new(MockClientBuffers {
                 recv_buf: BytesMut::new(),
                 send_buf: BytesMut::new(),
+                capacity: None,
             })),
         }
     }
@@ -72,6 +77,10 @@ impl MockClientConnection {
     pub fn clear_sent(&mut self) {
         self.buffers.lock().unwrap().send_buf.clear();
     }
+
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.buffers.lock().unwrap().capacity = capacity;
+    }
 }
 
 impl ClientConnection for MockClientConnection {
@@ -83,6 +92,17 @@ impl ClientConnection for MockClientConnection {
     fn try_recv(&mut self) -> anyhow::Result<BytesMut> {
         Ok(self.buffers.lock().unwrap().recv_buf.split())
     }
+
+    fn remaining_capacity(&self) -> Option<usize> {
+        let buffers = self.buffers.lock().unwrap();
+        buffers
+            .capacity
+            .map(|cap| cap.saturating_sub(buffers.send_buf.len()))
+    }
+
+    fn capacity(&self) -> Option<usize> {
+        self.buffers.lock().unwrap().capacity
+    }
 }
 
 #[cfg(test)]
@@ -150,15 +170,39 @@ impl MockClientHelper {
     pub fn clear_sent(&mut self) {
         self.conn.clear_sent();
     }
+
+    /// Takes all packets that have been sent to the client as a
+    /// [`PacketCapture`]. Panics if the packets are malformed.
+    pub fn capture_sent(&mut self) -> PacketCapture {
+        PacketCapture::from_bytes(&self.conn.take_sent()).expect("failed to capture packets")
+    }
+
+    /// Limits the number of unread bytes the mock connection reports room
+    /// for. `None` removes the limit.
+    pub fn set_capacity(&mut self, capacity: Option<usize>) {
+        self.conn.set_capacity(capacity);
+    }
 }
 
 /// Sets up valence with a single mock client. Returns the Entity of the client
 /// and the corresponding MockClientHelper.
 ///
+/// Keepalives are disabled so they aren't mixed into the sent packets of tests
+/// running many ticks.
+///
 /// Reduces boilerplate in unit tests.
 pub fn scenario_single_client(app: &mut App) -> (Entity, MockClientHelper) {
+    scenario_single_client_with_plugin(app, ServerPlugin::new(()).with_keepalives(false))
+}
+
+/// Like [`scenario_single_client`], but uses the given [`ServerPlugin`].
+/// Compression and authentication are always disabled.
+pub fn scenario_single_client_with_plugin(
+    app: &mut App,
+    plugin: ServerPlugin<()>,
+) -> (Entity, MockClientHelper) {
     app.add_plugin(
-        ServerPlugin::new(())
+        plugin
             .with_compression_threshold(None)
             .with_connection_mode(ConnectionMode::Offline),
     );
@@ -171,7 +215,11 @@ pub fn scenario_single_client(app: &mut App) -> (Entity, MockClientHelper) {
     client.set_instance(instance_ent);
     let client_ent = app
         .world
-        .spawn((client, Inventory::new(InventoryKind::Player)))
+        .spawn((
+            client,
+            Inventory::new(InventoryKind::Player),
+            OnGround::default(),
+        ))
         .id();
     (client_ent, client_helper)
 }
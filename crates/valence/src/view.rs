@@ -1,9 +1,14 @@
 use glam::DVec3;
+use serde::{Deserialize, Serialize};
 use valence_protocol::BlockPos;
 
 /// The X and Z position of a chunk in an
 /// [`Instance`](crate::instance::Instance).
-#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Debug)]
+///
+/// Chunk positions are ordered by X, then Z.
+#[derive(
+    Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Hash, Debug, Serialize, Deserialize,
+)]
 pub struct ChunkPos {
     /// The X position of the chunk.
     pub x: i32,
@@ -145,7 +150,7 @@ impl ChunkView {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::BTreeSet;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
 
     use super::*;
 
@@ -190,4 +195,36 @@ mod tests {
         assert_eq!(ChunkPos::from(<(i32, i32)>::from(p)), p);
         assert_eq!(ChunkPos::from(<[i32; 2]>::from(p)), p);
     }
+
+    #[test]
+    fn chunk_pos_as_map_key() {
+        let positions = [
+            ChunkPos::new(3, -1),
+            ChunkPos::new(-2, 5),
+            ChunkPos::new(3, -4),
+        ];
+
+        let hashed: HashMap<_, _> = positions.iter().map(|&p| (p, p.x + p.z)).collect();
+        assert_eq!(hashed[&ChunkPos::new(-2, 5)], 3);
+        assert!(!hashed.contains_key(&ChunkPos::new(-1, 3)));
+
+        let sorted: BTreeMap<_, _> = positions.iter().map(|&p| (p, ())).collect();
+        assert_eq!(
+            sorted.keys().copied().collect::<Vec<_>>(),
+            [
+                ChunkPos::new(-2, 5),
+                ChunkPos::new(3, -4),
+                ChunkPos::new(3, -1)
+            ]
+        );
+    }
+
+    #[test]
+    fn chunk_pos_serde() {
+        let pos = ChunkPos::new(-7, 12);
+        let json = serde_json::to_string(&pos).unwrap();
+
+        assert_eq!(json, r#"{"x":-7,"z":12}"#);
+        assert_eq!(serde_json::from_str::<ChunkPos>(&json).unwrap(), pos);
+    }
 }
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flume::Sender;
+use thiserror::Error;
+use valence::bevy_ecs::prelude::*;
+use valence::biome::BiomeId;
+use valence::instance::{Chunk, Instance};
+use valence::protocol::{ident, Ident};
+use valence::server::Server;
+use valence::view::ChunkPos;
+
+use crate::{from_valence, AnvilChunk, AnvilWorld, WriteChunkError};
+
+/// A component for [`Instance`] entities whose dirty chunks should be saved
+/// to an Anvil world periodically.
+///
+/// Every `interval` ticks, [`autosave_instances`] copies the chunks of the
+/// instance which are [dirty] and clears their dirty flags. The copies are
+/// converted and written to the world's region files on a background thread
+/// so the tick is not blocked. Chunks which are removed from the instance
+/// before the next save are not saved.
+///
+/// [dirty]: Chunk::is_dirty
+#[derive(Component)]
+pub struct Autosave {
+    interval: u32,
+    ticks_until_save: u32,
+    sect_offset: i32,
+    sender: Sender<Message>,
+}
+
+enum Message {
+    Save {
+        chunks: Vec<(ChunkPos, Chunk)>,
+        sect_offset: i32,
+        biome_names: Arc<HashMap<BiomeId, Ident<String>>>,
+    },
+    Flush(Sender<Result<(), AutosaveError>>),
+}
+
+/// An error encountered while autosaving chunks. Returned by
+/// [`Autosave::flush`].
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum AutosaveError {
+    #[error(transparent)]
+    Write(#[from] WriteChunkError),
+    /// The chunk at `pos` contains a biome which is not registered with the
+    /// server. The chunk is not written.
+    #[error("chunk at {pos:?} contains unregistered biome {biome:?}")]
+    UnknownBiome { pos: ChunkPos, biome: BiomeId },
+    #[error("autosave thread is no longer running")]
+    Stopped,
+}
+
+impl Autosave {
+    /// Creates a new `Autosave` which saves to the world at `world_root` every
+    /// `interval` ticks. The "region" subdirectory is created if it does not
+    /// exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn new(world_root: impl Into<PathBuf>, interval: u32) -> Self {
+        assert!(interval > 0, "autosave interval must be nonzero");
+
+        let mut world = AnvilWorld::new(world_root);
+        let (sender, receiver) = flume::unbounded();
+
+        thread::spawn(move || {
+            let mut error = None;
+
+            for msg in receiver {
+                match msg {
+                    Message::Save {
+                        chunks,
+                        sect_offset,
+                        biome_names,
+                    } => {
+                        let timestamp = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map_or(0, |d| d.as_secs() as u32);
+
+                        for (pos, chunk) in chunks {
+                            let mut unknown_biome = None;
+
+                            let data = from_valence(&chunk, pos, sect_offset, |b| {
+                                biome_names.get(&b).cloned().unwrap_or_else(|| {
+                                    unknown_biome = Some(b);
+                                    ident!("plains")
+                                })
+                            });
+
+                            if let Some(biome) = unknown_biome {
+                                error.get_or_insert(AutosaveError::UnknownBiome { pos, biome });
+                                continue;
+                            }
+
+                            if let Err(e) =
+                                world.write_chunk(pos.x, pos.z, &AnvilChunk { data, timestamp })
+                            {
+                                error.get_or_insert(e.into());
+                            }
+                        }
+                    }
+                    Message::Flush(reply) => {
+                        let _ = reply.send(error.take().map_or(Ok(()), Err));
+                    }
+                }
+            }
+        });
+
+        Self {
+            interval,
+            ticks_until_save: interval,
+            sect_offset: 0,
+            sender,
+        }
+    }
+
+    /// Sets the section offset used when converting chunks with
+    /// [`from_valence`]. Use the same offset that is passed to
+    /// [`to_valence`](crate::to_valence) when loading the world.
+    ///
+    /// # Default Value
+    ///
+    /// `0`
+    #[must_use]
+    pub fn with_section_offset(mut self, sect_offset: i32) -> Self {
+        self.sect_offset = sect_offset;
+        self
+    }
+
+    /// Returns the number of ticks between saves.
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    /// Sets the number of ticks between saves. The time until the next save
+    /// is reset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `interval` is zero.
+    pub fn set_interval(&mut self, interval: u32) {
+        assert!(interval > 0, "autosave interval must be nonzero");
+
+        self.interval = interval;
+        self.ticks_until_save = interval;
+    }
+
+    /// Blocks until all chunks sent to the background thread so far have been
+    /// written. Returns the first error encountered while writing since the
+    /// last flush, if any, or [`AutosaveError::Stopped`] if the background
+    /// thread is no longer running.
+    pub fn flush(&self) -> Result<(), AutosaveError> {
+        let (reply_sender, reply_receiver) = flume::bounded(1);
+
+        if self.sender.send(Message::Flush(reply_sender)).is_err() {
+            return Err(AutosaveError::Stopped);
+        }
+
+        reply_receiver.recv().unwrap_or(Err(AutosaveError::Stopped))
+    }
+}
+
+/// Saves the dirty chunks of instances with an [`Autosave`] component once
+/// their interval has elapsed. Add this system to your app to enable
+/// autosaving.
+pub fn autosave_instances(
+    server: Res<Server>,
+    mut instances: Query<(&mut Instance, &mut Autosave)>,
+) {
+    let mut biome_names = None;
+
+    for (mut instance, mut autosave) in &mut instances {
+        autosave.ticks_until_save -= 1;
+
+        if autosave.ticks_until_save > 0 {
+            continue;
+        }
+
+        autosave.ticks_until_save = autosave.interval;
+
+        let mut chunks = vec![];
+
+        for (pos, chunk) in instance.chunks_mut() {
+            if chunk.is_dirty() {
+                chunks.push((pos, chunk.to_unloaded()));
+                chunk.clear_dirty();
+            }
+        }
+
+        if chunks.is_empty() {
+            continue;
+        }
+
+        let biome_names = biome_names
+            .get_or_insert_with(|| {
+                Arc::new(
+                    server
+                        .biomes()
+                        .map(|(id, biome)| (id, biome.name.clone()))
+                        .collect(),
+                )
+            })
+            .clone();
+
+        let _ = autosave.sender.send(Message::Save {
+            chunks,
+            sect_offset: autosave.sect_offset,
+            biome_names,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence::bevy_app::App;
+    use valence::config::ServerPlugin;
+    use valence::dimension::DimensionId;
+    use valence::protocol::block::BlockState;
+
+    use super::*;
+    use crate::to_valence;
+
+    #[test]
+    fn dirty_chunks_are_saved_after_interval() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut app = App::new();
+        app.add_plugin(ServerPlugin::new(()))
+            .add_system(autosave_instances);
+
+        let mut instance = app
+            .world
+            .resource::<Server>()
+            .new_instance(DimensionId::default());
+
+        instance.insert_chunk([0, 0], Chunk::default());
+        instance.insert_chunk([1, 0], Chunk::default());
+
+        instance
+            .chunk_mut([0, 0])
+            .unwrap()
+            .set_block_state(1, 2, 3, BlockState::GRASS_BLOCK);
+
+        let section_count = instance.section_count();
+
+        let instance_ent = app
+            .world
+            .spawn((instance, Autosave::new(dir.path(), 3)))
+            .id();
+
+        app.update();
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(instance.chunk([0, 0]).unwrap().is_dirty());
+
+        app.update();
+
+        let instance = app.world.get::<Instance>(instance_ent).unwrap();
+        assert!(!instance.chunk([0, 0]).unwrap().is_dirty());
+
+        app.world
+            .get::<Autosave>(instance_ent)
+            .unwrap()
+            .flush()
+            .unwrap();
+
+        let mut world = AnvilWorld::new(dir.path());
+
+        // Chunks which were never modified are not written.
+        assert!(world.read_chunk(1, 0).unwrap().is_none());
+
+        let anvil_chunk = world.read_chunk(0, 0).unwrap().unwrap();
+
+        let mut chunk = Chunk::new(section_count);
+        to_valence(&anvil_chunk.data, &mut chunk, 0, |_| BiomeId::default()).unwrap();
+
+        assert_eq!(chunk.block_state(1, 2, 3), BlockState::GRASS_BLOCK);
+        assert_eq!(chunk.block_state(0, 0, 0), BlockState::AIR);
+
+        // Loaded chunks are not saved again until they are modified.
+        assert!(!chunk.is_dirty());
+    }
+}
@@ -0,0 +1,168 @@
+use num_integer::div_ceil;
+use valence::biome::BiomeId;
+use valence::instance::Chunk;
+use valence::protocol::block::BlockState;
+use valence::protocol::Ident;
+use valence::view::ChunkPos;
+use valence_nbt::{compound, Compound, List, Value};
+
+/// The data version of chunks written by [`from_valence`]. This is the data
+/// version of Minecraft 1.19.3.
+pub const DATA_VERSION: i32 = 3218;
+
+/// Converts a Valence [`Chunk`] into an Anvil chunk in NBT form. This is the
+/// inverse of [`to_valence`]. Only blocks and biomes are written.
+///
+/// # Arguments
+///
+/// - `chunk`: The Valence chunk to read from.
+/// - `pos`: The position of the chunk.
+/// - `sect_offset`: The same section offset that would be passed to
+///   [`to_valence`] when reading the chunk back. Section `i` of `chunk` is
+///   written with a Y position of `i - sect_offset`.
+/// - `map_biome`: A function to map Valence [`BiomeId`]s to biome resource
+///   identifiers.
+///
+/// [`to_valence`]: crate::to_valence
+pub fn from_valence<F, const LOADED: bool>(
+    chunk: &Chunk<LOADED>,
+    pos: ChunkPos,
+    sect_offset: i32,
+    mut map_biome: F,
+) -> Compound
+where
+    F: FnMut(BiomeId) -> Ident<String>,
+{
+    let mut sections = vec![];
+
+    let mut block_palette: Vec<BlockState> = vec![];
+    let mut block_idxs = vec![0_u16; BLOCKS_PER_SECTION];
+    let mut biome_palette: Vec<BiomeId> = vec![];
+    let mut biome_idxs = vec![0_u16; BIOMES_PER_SECTION];
+
+    for sect_y in 0..chunk.section_count() {
+        block_palette.clear();
+
+        for (i, idx) in block_idxs.iter_mut().enumerate() {
+            let x = i % 16;
+            let z = i / 16 % 16;
+            let y = i / (16 * 16);
+
+            let block = chunk.block_state(x, sect_y * 16 + y, z);
+            *idx = palette_index(&mut block_palette, block);
+        }
+
+        let mut block_states = compound! {
+            "palette" => List::Compound(block_palette.iter().map(|&b| block_nbt(b)).collect()),
+        };
+
+        if block_palette.len() > 1 {
+            let bits_per_idx = bit_width(block_palette.len() - 1).max(4);
+            block_states.insert("data", pack_indices(&block_idxs, bits_per_idx));
+        }
+
+        biome_palette.clear();
+
+        for (i, idx) in biome_idxs.iter_mut().enumerate() {
+            let x = i % 4;
+            let z = i / 4 % 4;
+            let y = i / (4 * 4);
+
+            let biome = chunk.biome(x, sect_y * 4 + y, z);
+            *idx = palette_index(&mut biome_palette, biome);
+        }
+
+        let mut biomes = compound! {
+            "palette" => List::String(
+                biome_palette
+                    .iter()
+                    .map(|&b| map_biome(b).as_str().to_owned())
+                    .collect(),
+            ),
+        };
+
+        if biome_palette.len() > 1 {
+            let bits_per_idx = bit_width(biome_palette.len() - 1);
+            biomes.insert("data", pack_indices(&biome_idxs, bits_per_idx));
+        }
+
+        sections.push(compound! {
+            "Y" => (sect_y as i32 - sect_offset) as i8,
+            "block_states" => block_states,
+            "biomes" => biomes,
+        });
+    }
+
+    compound! {
+        "DataVersion" => DATA_VERSION,
+        "xPos" => pos.x,
+        "zPos" => pos.z,
+        "yPos" => -sect_offset,
+        "Status" => "full",
+        "sections" => List::Compound(sections),
+    }
+}
+
+const BLOCKS_PER_SECTION: usize = 16 * 16 * 16;
+const BIOMES_PER_SECTION: usize = 4 * 4 * 4;
+
+/// Returns the index of `value` in `palette`, adding it to the end if it is
+/// not already present.
+fn palette_index<T: PartialEq>(palette: &mut Vec<T>, value: T) -> u16 {
+    match palette.iter().position(|v| *v == value) {
+        Some(idx) => idx as u16,
+        None => {
+            palette.push(value);
+            (palette.len() - 1) as u16
+        }
+    }
+}
+
+fn block_nbt(block: BlockState) -> Compound {
+    let kind = block.to_kind();
+
+    let mut nbt = compound! {
+        "Name" => format!("minecraft:{}", kind.to_str()),
+    };
+
+    if !kind.props().is_empty() {
+        let mut properties = Compound::new();
+
+        for &name in kind.props() {
+            if let Some(value) = block.get(name) {
+                properties.insert(name.to_str(), value.to_str());
+            }
+        }
+
+        nbt.insert("Properties", properties);
+    }
+
+    nbt
+}
+
+/// Packs palette indices into longs the same way [`to_valence`] unpacks them.
+/// Indices do not span across longs.
+///
+/// [`to_valence`]: crate::to_valence
+fn pack_indices(idxs: &[u16], bits_per_idx: usize) -> Value {
+    let idxs_per_long = 64 / bits_per_idx;
+
+    let mut data = Vec::with_capacity(div_ceil(idxs.len(), idxs_per_long));
+
+    for chunk in idxs.chunks(idxs_per_long) {
+        let mut long = 0_u64;
+
+        for (j, &idx) in chunk.iter().enumerate() {
+            long |= (idx as u64) << (bits_per_idx * j);
+        }
+
+        data.push(long as i64);
+    }
+
+    Value::LongArray(data)
+}
+
+/// Returns the minimum number of bits needed to represent the integer `n`.
+const fn bit_width(n: usize) -> usize {
+    (usize::BITS - n.leading_zeros()) as _
+}
@@ -1,17 +1,28 @@
 use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
 use std::fs::File;
-use std::io;
-use std::io::{ErrorKind, Read, Seek, SeekFrom};
+use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
 use std::path::PathBuf;
+use std::{fs, io};
 
-use byteorder::{BigEndian, ReadBytesExt};
+#[cfg(feature = "valence")]
+pub use autosave::*;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use flate2::bufread::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use flate2::Compression;
+#[cfg(feature = "valence")]
+pub use from_valence::*;
+use num_integer::div_ceil;
 use thiserror::Error;
 #[cfg(feature = "valence")]
 pub use to_valence::*;
 use valence_nbt::Compound;
 
+#[cfg(feature = "valence")]
+mod autosave;
+#[cfg(feature = "valence")]
+mod from_valence;
 #[cfg(feature = "valence")]
 mod to_valence;
 
@@ -21,6 +32,19 @@ pub struct AnvilWorld {
     region_root: PathBuf,
     /// Maps region (x, z) positions to region files.
     regions: BTreeMap<(i32, i32), Region>,
+    /// The compression used for chunks written with
+    /// [`AnvilWorld::write_chunk`].
+    compression: ChunkCompression,
+}
+
+/// The compression scheme of chunk data in a region file. Chunks compressed
+/// with either scheme can be read.
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub enum ChunkCompression {
+    Gzip = 1,
+    /// The scheme used by the vanilla server.
+    #[default]
+    Zlib = 2,
 }
 
 #[derive(Clone, PartialEq, Debug)]
@@ -49,6 +73,17 @@ pub enum ReadChunkError {
     IncompleteNbtRead,
 }
 
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum WriteChunkError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error(transparent)]
+    Nbt(#[from] valence_nbt::Error),
+    #[error("chunk is too large to fit in a region file")]
+    ChunkTooLarge,
+}
+
 #[derive(Debug)]
 struct Region {
     file: File,
@@ -57,6 +92,8 @@ struct Region {
 }
 
 const SECTOR_SIZE: usize = 4096;
+/// The largest number of sectors a chunk can occupy in a region file.
+const MAX_SECTOR_COUNT: usize = 255;
 
 impl AnvilWorld {
     pub fn new(world_root: impl Into<PathBuf>) -> Self {
@@ -66,9 +103,22 @@ impl AnvilWorld {
         Self {
             region_root,
             regions: BTreeMap::new(),
+            compression: ChunkCompression::default(),
         }
     }
 
+    /// Returns the compression used for chunks written with
+    /// [`Self::write_chunk`]. This is [`ChunkCompression::Zlib`] by default.
+    pub fn compression(&self) -> ChunkCompression {
+        self.compression
+    }
+
+    /// Sets the compression used for chunks written with [`Self::write_chunk`].
+    /// Chunks which were already written are not recompressed.
+    pub fn set_compression(&mut self, compression: ChunkCompression) {
+        self.compression = compression;
+    }
+
     /// Reads a chunk from the file system with the given chunk coordinates. If
     /// no chunk exists at the position, then `None` is returned.
     pub fn read_chunk(
@@ -76,30 +126,9 @@ impl AnvilWorld {
         chunk_x: i32,
         chunk_z: i32,
     ) -> Result<Option<AnvilChunk>, ReadChunkError> {
-        let region_x = chunk_x.div_euclid(32);
-        let region_z = chunk_z.div_euclid(32);
-
-        let region = match self.regions.entry((region_x, region_z)) {
-            Entry::Vacant(ve) => {
-                // Load the region file if it exists. Otherwise, the chunk is considered absent.
-
-                let path = self
-                    .region_root
-                    .join(format!("r.{region_x}.{region_z}.mca"));
-
-                let mut file = match File::options().read(true).write(true).open(path) {
-                    Ok(file) => file,
-                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
-                    Err(e) => return Err(e.into()),
-                };
-
-                let mut header = [0; SECTOR_SIZE * 2];
-
-                file.read_exact(&mut header)?;
-
-                ve.insert(Region { file, header })
-            }
-            Entry::Occupied(oe) => oe.into_mut(),
+        // Load the region file if it exists. Otherwise, the chunk is considered absent.
+        let Some(region) = self.region(chunk_x, chunk_z, false)? else {
+            return Ok(None)
         };
 
         let chunk_idx = (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize;
@@ -168,4 +197,213 @@ impl AnvilWorld {
 
         Ok(Some(AnvilChunk { data, timestamp }))
     }
+
+    /// Writes a chunk to the file system at the given chunk coordinates. The
+    /// region file and the "region" directory are created if they do not
+    /// exist. The chunk data is compressed with the scheme set by
+    /// [`Self::set_compression`].
+    ///
+    /// If the new chunk data does not fit in the sectors used by the old chunk
+    /// data, it is appended to the end of the region file. The old sectors are
+    /// not reused.
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk: &AnvilChunk,
+    ) -> Result<(), WriteChunkError> {
+        let mut compressed = vec![];
+
+        match self.compression {
+            ChunkCompression::Gzip => {
+                let mut z = GzEncoder::new(&mut compressed, Compression::default());
+                valence_nbt::to_binary_writer(&mut z, &chunk.data, "")?;
+                z.finish()?;
+            }
+            ChunkCompression::Zlib => {
+                let mut z = ZlibEncoder::new(&mut compressed, Compression::default());
+                valence_nbt::to_binary_writer(&mut z, &chunk.data, "")?;
+                z.finish()?;
+            }
+        }
+
+        // The payload is the length, compression scheme, and compressed data padded
+        // to a whole number of sectors.
+        let exact_chunk_size = compressed.len() + 1;
+        let sector_count = div_ceil(exact_chunk_size + 4, SECTOR_SIZE);
+
+        if sector_count > MAX_SECTOR_COUNT {
+            return Err(WriteChunkError::ChunkTooLarge);
+        }
+
+        let mut payload = Vec::with_capacity(sector_count * SECTOR_SIZE);
+        payload.write_u32::<BigEndian>(exact_chunk_size as u32)?;
+        payload.write_u8(self.compression as u8)?;
+        payload.extend_from_slice(&compressed);
+        payload.resize(sector_count * SECTOR_SIZE, 0);
+
+        let region = self
+            .region(chunk_x, chunk_z, true)?
+            .expect("region should be created");
+
+        let chunk_idx = (chunk_x.rem_euclid(32) + chunk_z.rem_euclid(32) * 32) as usize;
+
+        let location_bytes = (&region.header[chunk_idx * 4..]).read_u32::<BigEndian>()?;
+        let old_sector_offset = (location_bytes >> 8) as u64;
+        let old_sector_count = (location_bytes & 0xff) as usize;
+
+        let sector_offset = if old_sector_offset >= 2 && sector_count <= old_sector_count {
+            old_sector_offset
+        } else {
+            let file_len = region.file.seek(SeekFrom::End(0))?;
+            div_ceil(file_len, SECTOR_SIZE as u64)
+        };
+
+        region
+            .file
+            .seek(SeekFrom::Start(sector_offset * SECTOR_SIZE as u64))?;
+        region.file.write_all(&payload)?;
+
+        // Update the location and timestamp in the header.
+        let location_bytes = (sector_offset as u32) << 8 | sector_count as u32;
+        (&mut region.header[chunk_idx * 4..]).write_u32::<BigEndian>(location_bytes)?;
+        (&mut region.header[chunk_idx * 4 + SECTOR_SIZE..])
+            .write_u32::<BigEndian>(chunk.timestamp)?;
+
+        region.file.seek(SeekFrom::Start((chunk_idx * 4) as u64))?;
+        region.file.write_u32::<BigEndian>(location_bytes)?;
+        region
+            .file
+            .seek(SeekFrom::Start((chunk_idx * 4 + SECTOR_SIZE) as u64))?;
+        region.file.write_u32::<BigEndian>(chunk.timestamp)?;
+
+        Ok(())
+    }
+
+    /// Gets the region containing the given chunk coordinates, opening the
+    /// region file if necessary. If the region file does not exist, it is
+    /// created when `create` is `true`. Otherwise, `None` is returned.
+    fn region(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        create: bool,
+    ) -> io::Result<Option<&mut Region>> {
+        let region_x = chunk_x.div_euclid(32);
+        let region_z = chunk_z.div_euclid(32);
+
+        match self.regions.entry((region_x, region_z)) {
+            Entry::Vacant(ve) => {
+                let path = self
+                    .region_root
+                    .join(format!("r.{region_x}.{region_z}.mca"));
+
+                let mut header = [0; SECTOR_SIZE * 2];
+
+                let file = match File::options().read(true).write(true).open(&path) {
+                    Ok(mut file) => {
+                        file.read_exact(&mut header)?;
+                        file
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound && create => {
+                        fs::create_dir_all(&self.region_root)?;
+
+                        let mut file = File::options()
+                            .read(true)
+                            .write(true)
+                            .create_new(true)
+                            .open(&path)?;
+
+                        file.write_all(&header)?;
+                        file
+                    }
+                    Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+
+                Ok(Some(ve.insert(Region { file, header })))
+            }
+            Entry::Occupied(oe) => Ok(Some(oe.into_mut())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use valence_nbt::compound;
+
+    use super::*;
+
+    fn test_chunk() -> AnvilChunk {
+        AnvilChunk {
+            data: compound! {
+                "xPos" => 3,
+                "zPos" => -2,
+                "Status" => "full",
+            },
+            timestamp: 1234,
+        }
+    }
+
+    /// Writes a region file by hand with a single chunk at (0, 0) made of the
+    /// given compression scheme and compressed data.
+    fn write_fixture(world_root: &std::path::Path, scheme: u8, compressed: &[u8]) {
+        let region_root = world_root.join("region");
+        fs::create_dir_all(&region_root).unwrap();
+
+        let mut file = vec![0; SECTOR_SIZE * 2];
+        (&mut file[..]).write_u32::<BigEndian>(2 << 8 | 1).unwrap();
+        (&mut file[SECTOR_SIZE..])
+            .write_u32::<BigEndian>(test_chunk().timestamp)
+            .unwrap();
+
+        file.write_u32::<BigEndian>(compressed.len() as u32 + 1)
+            .unwrap();
+        file.write_u8(scheme).unwrap();
+        file.extend_from_slice(compressed);
+        file.resize(SECTOR_SIZE * 3, 0);
+
+        fs::write(region_root.join("r.0.0.mca"), file).unwrap();
+    }
+
+    #[test]
+    fn read_gzip_and_zlib_fixtures() {
+        let mut gzip = GzEncoder::new(vec![], Compression::default());
+        valence_nbt::to_binary_writer(&mut gzip, &test_chunk().data, "").unwrap();
+        let gzip = gzip.finish().unwrap();
+
+        let mut zlib = ZlibEncoder::new(vec![], Compression::default());
+        valence_nbt::to_binary_writer(&mut zlib, &test_chunk().data, "").unwrap();
+        let zlib = zlib.finish().unwrap();
+
+        for (scheme, compressed) in [(1, gzip), (2, zlib)] {
+            let dir = tempfile::tempdir().unwrap();
+            write_fixture(dir.path(), scheme, &compressed);
+
+            let mut world = AnvilWorld::new(dir.path());
+            assert_eq!(world.read_chunk(0, 0).unwrap(), Some(test_chunk()));
+        }
+    }
+
+    #[test]
+    fn write_and_read_back_with_each_compression() {
+        for compression in [ChunkCompression::Gzip, ChunkCompression::Zlib] {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut world = AnvilWorld::new(dir.path());
+            assert_eq!(world.compression(), ChunkCompression::Zlib);
+            world.set_compression(compression);
+            world.write_chunk(33, -1, &test_chunk()).unwrap();
+
+            // The compression scheme is stored after the length of the chunk data.
+            let file = fs::read(dir.path().join("region/r.1.-1.mca")).unwrap();
+            let chunk_idx = 1 + 31 * 32;
+            let location = (&file[chunk_idx * 4..]).read_u32::<BigEndian>().unwrap();
+            let offset = (location >> 8) as usize * SECTOR_SIZE;
+            assert_eq!(file[offset + 4], compression as u8);
+
+            let mut world = AnvilWorld::new(dir.path());
+            assert_eq!(world.read_chunk(33, -1).unwrap(), Some(test_chunk()));
+        }
+    }
 }
@@ -57,6 +57,9 @@ pub enum ToValenceError {
 /// An error is returned if the NBT data does not match the expected structure
 /// for an Anvil chunk.
 ///
+/// The [dirty] flag of the chunk is cleared after it is written successfully,
+/// since its contents are already saved in the Anvil data.
+///
 /// # Arguments
 ///
 /// - `nbt`: The Anvil chunk to read from. This is usually the value returned by
@@ -68,6 +71,7 @@ pub enum ToValenceError {
 /// - `map_biome`: A function to map biome resource identifiers in the NBT data
 ///   to Valence [`BiomeId`]s.
 ///
+/// [dirty]: Chunk::is_dirty
 /// [`AnvilWorld::read_chunk`]: crate::AnvilWorld::read_chunk
 pub fn to_valence<F, const LOADED: bool>(
     nbt: &Compound,
@@ -254,6 +258,8 @@ where
         }
     }
 
+    chunk.clear_dirty();
+
     Ok(())
 }
 
@@ -70,6 +70,36 @@ impl Compound {
         self.map.get(k)
     }
 
+    /// Returns the byte array associated with the key, or `None` if the key
+    /// is absent or the value is not a byte array.
+    pub fn get_byte_array<Q>(&self, k: &Q) -> Option<&[i8]>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Eq + Ord + Hash,
+    {
+        self.get(k)?.as_byte_array().map(Vec::as_slice)
+    }
+
+    /// Returns the int array associated with the key, or `None` if the key is
+    /// absent or the value is not an int array.
+    pub fn get_int_array<Q>(&self, k: &Q) -> Option<&[i32]>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Eq + Ord + Hash,
+    {
+        self.get(k)?.as_int_array().map(Vec::as_slice)
+    }
+
+    /// Returns the long array associated with the key, or `None` if the key
+    /// is absent or the value is not a long array.
+    pub fn get_long_array<Q>(&self, k: &Q) -> Option<&[i64]>
+    where
+        String: Borrow<Q>,
+        Q: ?Sized + Eq + Ord + Hash,
+    {
+        self.get(k)?.as_long_array().map(Vec::as_slice)
+    }
+
     pub fn contains_key<Q>(&self, k: &Q) -> bool
     where
         String: Borrow<Q>,
@@ -188,6 +218,115 @@ impl Compound {
     {
         self.map.retain(f)
     }
+
+    /// Inserts every entry of `other` into this compound, overwriting the
+    /// values of keys present in both. Nested compounds are replaced
+    /// entirely. See [`Self::deep_merge`] to merge nested compounds instead.
+    pub fn merge(&mut self, other: Compound) {
+        self.extend(other);
+    }
+
+    /// Like [`Self::merge`], but when both compounds have a compound under the
+    /// same key, the two are merged recursively. All other values, including
+    /// lists, are overwritten by the value in `other`.
+    ///
+    /// This is useful for layering overrides on top of a base compound.
+    pub fn deep_merge(&mut self, other: Compound) {
+        for (k, v) in other {
+            match v {
+                Value::Compound(other) => match self.get_mut(k.as_str()) {
+                    Some(Value::Compound(this)) => this.deep_merge(other),
+                    _ => {
+                        self.insert(k, other);
+                    }
+                },
+                v => {
+                    self.insert(k, v);
+                }
+            }
+        }
+    }
+
+    /// Compares this compound against `other` and returns the differences
+    /// between them. Nested compounds are compared recursively while all other
+    /// values are compared for equality.
+    ///
+    /// The path of each difference is the sequence of keys leading to the
+    /// differing value, joined with `.`.
+    ///
+    /// This is mostly useful for tests and debugging where the compounds being
+    /// compared are too large to inspect by hand.
+    pub fn diff(&self, other: &Compound) -> Vec<NbtDiff> {
+        let mut diffs = vec![];
+        diff_compounds(self, other, "", &mut diffs);
+        diffs
+    }
+}
+
+/// A single difference between two [`Compound`]s as reported by
+/// [`Compound::diff`].
+#[derive(Clone, PartialEq, Debug)]
+pub enum NbtDiff {
+    /// The key is present in the other compound but not this one.
+    Added { path: String, value: Value },
+    /// The key is present in this compound but not the other one.
+    Removed { path: String, value: Value },
+    /// The key is present in both compounds but with different values.
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl NbtDiff {
+    /// Returns the path of the key that differs.
+    pub fn path(&self) -> &str {
+        match self {
+            NbtDiff::Added { path, .. } => path,
+            NbtDiff::Removed { path, .. } => path,
+            NbtDiff::Changed { path, .. } => path,
+        }
+    }
+}
+
+fn diff_compounds(old: &Compound, new: &Compound, prefix: &str, diffs: &mut Vec<NbtDiff>) {
+    let path_of = |k: &str| {
+        if prefix.is_empty() {
+            k.to_owned()
+        } else {
+            format!("{prefix}.{k}")
+        }
+    };
+
+    for (k, old_v) in old {
+        match new.get(k.as_str()) {
+            Some(new_v) => match (old_v, new_v) {
+                (Value::Compound(old_c), Value::Compound(new_c)) => {
+                    diff_compounds(old_c, new_c, &path_of(k), diffs)
+                }
+                _ if old_v != new_v => diffs.push(NbtDiff::Changed {
+                    path: path_of(k),
+                    old: old_v.clone(),
+                    new: new_v.clone(),
+                }),
+                _ => {}
+            },
+            None => diffs.push(NbtDiff::Removed {
+                path: path_of(k),
+                value: old_v.clone(),
+            }),
+        }
+    }
+
+    for (k, new_v) in new {
+        if !old.contains_key(k.as_str()) {
+            diffs.push(NbtDiff::Added {
+                path: path_of(k),
+                value: new_v.clone(),
+            });
+        }
+    }
 }
 
 impl Extend<(String, Value)> for Compound {
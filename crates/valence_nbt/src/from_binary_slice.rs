@@ -9,8 +9,25 @@ use crate::{Compound, Error, List, Result, Value};
 /// Decodes uncompressed NBT binary data from the provided slice.
 ///
 /// The string returned is the name of the root compound.
+///
+/// This uses the [default limits](DecodeLimits::default). To decode
+/// untrusted data with tighter bounds, see [`from_binary_slice_with_limits`].
 pub fn from_binary_slice(slice: &mut &[u8]) -> Result<(Compound, String)> {
-    let mut state = DecodeState { slice, depth: 0 };
+    from_binary_slice_with_limits(slice, DecodeLimits::default())
+}
+
+/// Like [`from_binary_slice`], but the decoded data is bounded by the given
+/// [`DecodeLimits`]. An error is returned if the limits are exceeded.
+pub fn from_binary_slice_with_limits(
+    slice: &mut &[u8],
+    limits: DecodeLimits,
+) -> Result<(Compound, String)> {
+    let mut state = DecodeState {
+        slice,
+        depth: 0,
+        elements: 0,
+        limits,
+    };
 
     let root_tag = state.read_tag()?;
 
@@ -35,6 +52,45 @@ pub fn from_binary_slice(slice: &mut &[u8]) -> Result<(Compound, String)> {
     Ok((root, root_name))
 }
 
+/// Bounds on the NBT data accepted by [`from_binary_slice_with_limits`].
+///
+/// Decoding attacker-controlled NBT without limits can exhaust memory or
+/// overflow the call stack.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodeLimits {
+    /// The maximum nesting depth of lists and compounds.
+    pub max_depth: usize,
+    /// The maximum total number of elements across all compounds, lists, and
+    /// arrays.
+    pub max_elements: usize,
+}
+
+impl DecodeLimits {
+    #[must_use]
+    pub const fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    #[must_use]
+    pub const fn with_max_elements(mut self, max_elements: usize) -> Self {
+        self.max_elements = max_elements;
+        self
+    }
+}
+
+/// The default limits allow a depth of 512 and an unbounded number of
+/// elements. The number of elements is still implicitly bounded by the length
+/// of the input.
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: MAX_DEPTH,
+            max_elements: usize::MAX,
+        }
+    }
+}
+
 /// Maximum recursion depth to prevent overflowing the call stack.
 const MAX_DEPTH: usize = 512;
 
@@ -42,12 +98,15 @@ struct DecodeState<'a, 'b> {
     slice: &'a mut &'b [u8],
     /// Current recursion depth.
     depth: usize,
+    /// Number of elements decoded so far.
+    elements: usize,
+    limits: DecodeLimits,
 }
 
 impl DecodeState<'_, '_> {
     #[inline]
     fn check_depth<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
-        if self.depth >= MAX_DEPTH {
+        if self.depth >= self.limits.max_depth {
             return Err(Error::new_static("reached maximum recursion depth"));
         }
 
@@ -57,6 +116,22 @@ impl DecodeState<'_, '_> {
         res
     }
 
+    /// Accounts for `count` more elements, failing if this would exceed the
+    /// maximum element count.
+    #[inline]
+    fn add_elements(&mut self, count: usize) -> Result<()> {
+        self.elements = self.elements.saturating_add(count);
+
+        if self.elements > self.limits.max_elements {
+            return Err(Error::new_owned(format!(
+                "exceeded maximum element count of {}",
+                self.limits.max_elements
+            )));
+        }
+
+        Ok(())
+    }
+
     fn read_tag(&mut self) -> Result<Tag> {
         match self.slice.read_u8()? {
             0 => Ok(Tag::End),
@@ -133,6 +208,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_elements(len as usize)?;
+
         let (left, right) = self.slice.split_at(len as usize);
 
         let array = left.iter().map(|b| *b as i8).collect();
@@ -230,6 +307,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_elements(len as usize)?;
+
         let mut list = Vec::with_capacity(len as usize);
         for _ in 0..len {
             list.push(read_elem(self)?);
@@ -247,6 +326,8 @@ impl DecodeState<'_, '_> {
                 return Ok(compound);
             }
 
+            self.add_elements(1)?;
+
             compound.insert(self.read_string()?, self.read_value(tag)?);
         }
     }
@@ -266,6 +347,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_elements(len as usize)?;
+
         let mut array = Vec::with_capacity(len as usize);
         for _ in 0..len {
             array.push(self.read_int()?);
@@ -289,6 +372,8 @@ impl DecodeState<'_, '_> {
             )));
         }
 
+        self.add_elements(len as usize)?;
+
         let mut array = Vec::with_capacity(len as usize);
         for _ in 0..len {
             array.push(self.read_long()?);
@@ -68,7 +68,8 @@
 
 pub use compound::Compound;
 pub use error::Error;
-pub use from_binary_slice::from_binary_slice;
+pub use from_binary_slice::{from_binary_slice, from_binary_slice_with_limits, DecodeLimits};
+pub use nbt_writer::NbtWriter;
 pub use tag::Tag;
 pub use to_binary_writer::to_binary_writer;
 pub use value::{List, Value};
@@ -77,6 +78,7 @@ pub mod compound;
 mod error;
 mod from_binary_slice;
 mod modified_utf8;
+mod nbt_writer;
 pub mod snbt;
 mod to_binary_writer;
 pub mod value;
@@ -0,0 +1,159 @@
+use std::io::Write;
+
+use crate::tag::Tag;
+use crate::to_binary_writer::EncodeState;
+use crate::{Compound, Error, List, Result, Value};
+
+/// Encodes uncompressed NBT binary data directly to a writer without building
+/// a [`Compound`] in memory first.
+///
+/// This is useful when writing large amounts of data such as chunks, where
+/// constructing the full compound before encoding would double the memory
+/// required. The bytes written are identical to those written by
+/// [`to_binary_writer`] for an equivalent compound.
+///
+/// Every [`begin_compound`] must be balanced by a call to [`end_compound`]
+/// before the writer is [finished](Self::finish).
+///
+/// # Examples
+///
+/// ```
+/// use valence_nbt::NbtWriter;
+///
+/// let mut buf = vec![];
+///
+/// let mut writer = NbtWriter::new(&mut buf, "").unwrap();
+/// writer.write_int("xPos", 5).unwrap();
+/// writer.begin_compound("Heightmaps").unwrap();
+/// writer
+///     .write_long_array("MOTION_BLOCKING", &[0; 37])
+///     .unwrap();
+/// writer.end_compound().unwrap();
+/// writer.finish().unwrap();
+/// ```
+///
+/// [`to_binary_writer`]: crate::to_binary_writer()
+/// [`begin_compound`]: Self::begin_compound
+/// [`end_compound`]: Self::end_compound
+pub struct NbtWriter<W> {
+    state: EncodeState<W>,
+    /// Number of compounds opened with `begin_compound` that have not been
+    /// closed yet.
+    open_compounds: usize,
+}
+
+impl<W: Write> NbtWriter<W> {
+    /// Begins writing the root compound with the given name. Typically the
+    /// empty string `""` is used.
+    pub fn new(writer: W, root_name: &str) -> Result<Self> {
+        let mut state = EncodeState { writer };
+
+        state.write_tag(Tag::Compound)?;
+        state.write_string(root_name)?;
+
+        Ok(Self {
+            state,
+            open_compounds: 0,
+        })
+    }
+
+    fn write_field_header(&mut self, tag: Tag, name: &str) -> Result<()> {
+        self.state.write_tag(tag)?;
+        self.state.write_string(name)
+    }
+
+    /// Writes a field of any type.
+    pub fn write_value(&mut self, name: &str, value: &Value) -> Result<()> {
+        self.write_field_header(Tag::element_type(value), name)?;
+        self.state.write_value(value)
+    }
+
+    pub fn write_byte(&mut self, name: &str, byte: i8) -> Result<()> {
+        self.write_value(name, &Value::Byte(byte))
+    }
+
+    pub fn write_short(&mut self, name: &str, short: i16) -> Result<()> {
+        self.write_value(name, &Value::Short(short))
+    }
+
+    pub fn write_int(&mut self, name: &str, int: i32) -> Result<()> {
+        self.write_value(name, &Value::Int(int))
+    }
+
+    pub fn write_long(&mut self, name: &str, long: i64) -> Result<()> {
+        self.write_value(name, &Value::Long(long))
+    }
+
+    pub fn write_float(&mut self, name: &str, float: f32) -> Result<()> {
+        self.write_value(name, &Value::Float(float))
+    }
+
+    pub fn write_double(&mut self, name: &str, double: f64) -> Result<()> {
+        self.write_value(name, &Value::Double(double))
+    }
+
+    pub fn write_string(&mut self, name: &str, s: &str) -> Result<()> {
+        self.write_field_header(Tag::String, name)?;
+        self.state.write_string(s)
+    }
+
+    pub fn write_byte_array(&mut self, name: &str, bytes: &[i8]) -> Result<()> {
+        self.write_field_header(Tag::ByteArray, name)?;
+        self.state.write_byte_array(bytes)
+    }
+
+    pub fn write_int_array(&mut self, name: &str, ints: &[i32]) -> Result<()> {
+        self.write_field_header(Tag::IntArray, name)?;
+        self.state.write_int_array(ints)
+    }
+
+    pub fn write_long_array(&mut self, name: &str, longs: &[i64]) -> Result<()> {
+        self.write_field_header(Tag::LongArray, name)?;
+        self.state.write_long_array(longs)
+    }
+
+    pub fn write_list(&mut self, name: &str, list: &List) -> Result<()> {
+        self.write_field_header(Tag::List, name)?;
+        self.state.write_any_list(list)
+    }
+
+    /// Writes an entire compound that has already been built in memory.
+    pub fn write_compound(&mut self, name: &str, compound: &Compound) -> Result<()> {
+        self.write_field_header(Tag::Compound, name)?;
+        self.state.write_compound(compound)
+    }
+
+    /// Begins a nested compound with the given name. Subsequent fields are
+    /// written to this compound until [`Self::end_compound`] is called.
+    pub fn begin_compound(&mut self, name: &str) -> Result<()> {
+        self.write_field_header(Tag::Compound, name)?;
+        self.open_compounds += 1;
+        Ok(())
+    }
+
+    /// Ends the compound most recently opened with [`Self::begin_compound`].
+    pub fn end_compound(&mut self) -> Result<()> {
+        if self.open_compounds == 0 {
+            return Err(Error::new_static(
+                "attempt to end compound without a matching begin",
+            ));
+        }
+
+        self.state.write_tag(Tag::End)?;
+        self.open_compounds -= 1;
+        Ok(())
+    }
+
+    /// Ends the root compound and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        if self.open_compounds != 0 {
+            return Err(Error::new_owned(format!(
+                "{} compound(s) were not ended before finishing",
+                self.open_compounds
+            )));
+        }
+
+        self.state.write_tag(Tag::End)?;
+        Ok(self.state.writer)
+    }
+}
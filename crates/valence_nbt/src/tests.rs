@@ -1,5 +1,9 @@
+use crate::compound::NbtDiff;
 use crate::tag::Tag;
-use crate::{compound, from_binary_slice, to_binary_writer, Compound, List, Value};
+use crate::{
+    compound, from_binary_slice, from_binary_slice_with_limits, to_binary_writer, Compound,
+    DecodeLimits, List, NbtWriter, Value,
+};
 
 const ROOT_NAME: &str = "The root name‽";
 
@@ -87,6 +91,111 @@ fn deeply_nested_list_decode() {
     let _ = from_binary_slice(&mut buf.as_slice());
 }
 
+#[test]
+fn depth_limit_exceeded() {
+    let mut buf = vec![Tag::Compound as u8, 0, 0]; // Root compound
+    let n = 100;
+
+    for _ in 0..n {
+        buf.extend([Tag::Compound as u8, 0, 0]);
+    }
+
+    buf.extend((0..n).map(|_| Tag::End as u8));
+
+    buf.push(Tag::End as u8); // End root compound
+
+    let limits = DecodeLimits::default().with_max_depth(n);
+    assert!(from_binary_slice_with_limits(&mut buf.as_slice(), limits).is_ok());
+
+    let limits = DecodeLimits::default().with_max_depth(n - 1);
+    let err = from_binary_slice_with_limits(&mut buf.as_slice(), limits).unwrap_err();
+    assert!(err.to_string().contains("depth"), "{err}");
+}
+
+#[test]
+fn element_limit_exceeded() {
+    let c = compound! {
+        "huge_list" => List::Byte(vec![0; 100_000]),
+    };
+
+    let mut buf = vec![];
+    to_binary_writer(&mut buf, &c, "").unwrap();
+
+    let limits = DecodeLimits::default().with_max_elements(100_001);
+    let (decoded, _) = from_binary_slice_with_limits(&mut buf.as_slice(), limits).unwrap();
+    assert_eq!(c, decoded);
+
+    let limits = DecodeLimits::default().with_max_elements(1000);
+    let err = from_binary_slice_with_limits(&mut buf.as_slice(), limits).unwrap_err();
+    assert!(err.to_string().contains("element count"), "{err}");
+}
+
+#[test]
+fn typed_array_getters() {
+    let c = compound! {
+        "bytes" => vec![1_i8, 2, 3],
+        "ints" => vec![4_i32, 5, 6],
+        "longs" => vec![7_i64, 8, 9],
+        "list_of_long" => List::Long(vec![10, 11]),
+    };
+
+    assert_eq!(c.get_byte_array("bytes"), Some([1, 2, 3].as_slice()));
+    assert_eq!(c.get_int_array("ints"), Some([4, 5, 6].as_slice()));
+    assert_eq!(c.get_long_array("longs"), Some([7, 8, 9].as_slice()));
+
+    // Absent keys.
+    assert_eq!(c.get_byte_array("missing"), None);
+    assert_eq!(c.get_int_array("missing"), None);
+    assert_eq!(c.get_long_array("missing"), None);
+
+    // Wrong types.
+    assert_eq!(c.get_byte_array("longs"), None);
+    assert_eq!(c.get_int_array("bytes"), None);
+    assert_eq!(c.get_long_array("ints"), None);
+    assert_eq!(c.get_long_array("list_of_long"), None);
+}
+
+#[test]
+fn streamed_matches_built() {
+    let c = example_compound();
+
+    let mut expected = vec![];
+    to_binary_writer(&mut expected, &c, ROOT_NAME).unwrap();
+
+    let mut writer = NbtWriter::new(vec![], ROOT_NAME).unwrap();
+
+    // Fields are written in the same order the compound iterates them.
+    for (k, v) in &c {
+        match v {
+            Value::Compound(inner) => {
+                writer.begin_compound(k).unwrap();
+                for (k, v) in inner {
+                    writer.write_value(k, v).unwrap();
+                }
+                writer.end_compound().unwrap();
+            }
+            Value::Byte(b) => writer.write_byte(k, *b).unwrap(),
+            Value::String(s) => writer.write_string(k, s).unwrap(),
+            Value::ByteArray(ba) => writer.write_byte_array(k, ba).unwrap(),
+            Value::IntArray(ia) => writer.write_int_array(k, ia).unwrap(),
+            Value::LongArray(la) => writer.write_long_array(k, la).unwrap(),
+            Value::List(l) => writer.write_list(k, l).unwrap(),
+            v => writer.write_value(k, v).unwrap(),
+        }
+    }
+
+    assert_eq!(writer.finish().unwrap(), expected);
+}
+
+#[test]
+fn streamed_unbalanced_compound() {
+    let mut writer = NbtWriter::new(vec![], "").unwrap();
+    assert!(writer.end_compound().is_err());
+
+    writer.begin_compound("foo").unwrap();
+    assert!(writer.finish().is_err());
+}
+
 #[test]
 fn correct_length() {
     let c = example_compound();
@@ -97,6 +206,114 @@ fn correct_length() {
     assert_eq!(c.written_size("abc"), buf.len());
 }
 
+#[test]
+fn diff_heightmaps() {
+    let old = compound! {
+        "Heightmaps" => compound! {
+            "MOTION_BLOCKING" => vec![1_i64, 2, 3],
+            "WORLD_SURFACE" => vec![4_i64, 5, 6],
+        },
+        "Status" => "full",
+    };
+
+    let mut new = old.clone();
+
+    assert!(old.diff(&new).is_empty());
+
+    new["Heightmaps"]
+        .as_compound_mut()
+        .unwrap()
+        .insert("WORLD_SURFACE", vec![4_i64, 5, 7]);
+
+    assert_eq!(
+        old.diff(&new),
+        [NbtDiff::Changed {
+            path: "Heightmaps.WORLD_SURFACE".into(),
+            old: vec![4_i64, 5, 6].into(),
+            new: vec![4_i64, 5, 7].into(),
+        }]
+    );
+
+    new.remove("Status");
+    new.insert("xPos", 0);
+
+    let diffs = new.diff(&old);
+    let paths: Vec<_> = diffs.iter().map(|d| d.path()).collect();
+
+    assert_eq!(diffs.len(), 3);
+    assert!(paths.contains(&"Heightmaps.WORLD_SURFACE"));
+    assert!(diffs.contains(&NbtDiff::Removed {
+        path: "xPos".into(),
+        value: 0.into(),
+    }));
+    assert!(diffs.contains(&NbtDiff::Added {
+        path: "Status".into(),
+        value: "full".into(),
+    }));
+}
+
+#[test]
+fn merge_compounds() {
+    let base = compound! {
+        "name" => "base",
+        "list" => vec![1_i32, 2, 3],
+        "nested" => compound! {
+            "a" => 1_i32,
+            "b" => 2_i32,
+            "inner" => compound! {
+                "x" => 0_i8,
+            },
+        },
+    };
+
+    let overrides = compound! {
+        "name" => "override",
+        "list" => vec![4_i32],
+        "nested" => compound! {
+            "b" => 20_i32,
+            "c" => 30_i32,
+            "inner" => compound! {
+                "y" => 1_i8,
+            },
+        },
+        "extra" => true,
+    };
+
+    let mut shallow = base.clone();
+    shallow.merge(overrides.clone());
+
+    assert_eq!(
+        shallow,
+        compound! {
+            "name" => "override",
+            "list" => vec![4_i32],
+            "nested" => overrides["nested"].clone(),
+            "extra" => true,
+        }
+    );
+
+    let mut deep = base;
+    deep.deep_merge(overrides);
+
+    assert_eq!(
+        deep,
+        compound! {
+            "name" => "override",
+            "list" => vec![4_i32],
+            "nested" => compound! {
+                "a" => 1_i32,
+                "b" => 20_i32,
+                "c" => 30_i32,
+                "inner" => compound! {
+                    "x" => 0_i8,
+                    "y" => 1_i8,
+                },
+            },
+            "extra" => true,
+        }
+    );
+}
+
 #[cfg(feature = "preserve_order")]
 #[test]
 fn preserves_order() {
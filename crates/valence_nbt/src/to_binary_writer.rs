@@ -74,16 +74,16 @@ pub(crate) fn written_size(compound: &Compound, root_name: &str) -> usize {
     1 + string_size(root_name) + compound_size(compound)
 }
 
-struct EncodeState<W> {
-    writer: W,
+pub(crate) struct EncodeState<W> {
+    pub(crate) writer: W,
 }
 
 impl<W: Write> EncodeState<W> {
-    fn write_tag(&mut self, tag: Tag) -> Result<()> {
+    pub(crate) fn write_tag(&mut self, tag: Tag) -> Result<()> {
         Ok(self.writer.write_u8(tag as u8)?)
     }
 
-    fn write_value(&mut self, v: &Value) -> Result<()> {
+    pub(crate) fn write_value(&mut self, v: &Value) -> Result<()> {
         match v {
             Value::Byte(b) => self.write_byte(*b),
             Value::Short(s) => self.write_short(*s),
@@ -124,7 +124,7 @@ impl<W: Write> EncodeState<W> {
         Ok(self.writer.write_f64::<BigEndian>(double)?)
     }
 
-    fn write_byte_array(&mut self, bytes: &[i8]) -> Result<()> {
+    pub(crate) fn write_byte_array(&mut self, bytes: &[i8]) -> Result<()> {
         match bytes.len().try_into() {
             Ok(len) => self.write_int(len)?,
             Err(_) => {
@@ -141,7 +141,7 @@ impl<W: Write> EncodeState<W> {
         Ok(self.writer.write_all(bytes)?)
     }
 
-    fn write_string(&mut self, s: &str) -> Result<()> {
+    pub(crate) fn write_string(&mut self, s: &str) -> Result<()> {
         let len = modified_utf8::encoded_len(s);
 
         match len.try_into() {
@@ -165,7 +165,7 @@ impl<W: Write> EncodeState<W> {
         Ok(())
     }
 
-    fn write_any_list(&mut self, list: &List) -> Result<()> {
+    pub(crate) fn write_any_list(&mut self, list: &List) -> Result<()> {
         match list {
             List::End => {
                 self.write_tag(Tag::End)?;
@@ -234,7 +234,7 @@ impl<W: Write> EncodeState<W> {
         Ok(())
     }
 
-    fn write_compound(&mut self, c: &Compound) -> Result<()> {
+    pub(crate) fn write_compound(&mut self, c: &Compound) -> Result<()> {
         for (k, v) in c.iter() {
             self.write_tag(Tag::element_type(v))?;
             self.write_string(k)?;
@@ -245,7 +245,7 @@ impl<W: Write> EncodeState<W> {
         Ok(())
     }
 
-    fn write_int_array(&mut self, ia: &[i32]) -> Result<()> {
+    pub(crate) fn write_int_array(&mut self, ia: &[i32]) -> Result<()> {
         match ia.len().try_into() {
             Ok(len) => self.write_int(len)?,
             Err(_) => {
@@ -263,7 +263,7 @@ impl<W: Write> EncodeState<W> {
         Ok(())
     }
 
-    fn write_long_array(&mut self, la: &[i64]) -> Result<()> {
+    pub(crate) fn write_long_array(&mut self, la: &[i64]) -> Result<()> {
         match la.len().try_into() {
             Ok(len) => self.write_int(len)?,
             Err(_) => {
@@ -10,14 +10,14 @@ use valence_protocol::packets::s2c::play::{
 use valence_protocol::text::Color;
 use valence_protocol::{
     encode_packet, encode_packet_compressed, ByteAngle, Decode, Encode, ItemKind,
-    LengthPrefixedArray, PacketDecoder, PacketEncoder, TextFormat, VarInt,
+    LengthPrefixedArray, PacketDecoder, PacketEncoder, PrecodedPacket, TextFormat, VarInt, VarLong,
 };
 
 criterion_group! {
     name = benches;
     config = Criterion::default()
         .measurement_time(Duration::from_secs(5)).confidence_level(0.99);
-    targets = blocks, packets, var_int, decode_array
+    targets = blocks, packets, var_int, var_long, decode_array
 }
 criterion_main!(benches);
 
@@ -88,6 +88,16 @@ fn blocks(c: &mut Criterion) {
         })
     });
 
+    c.bench_function("BlockState::collision_shapes", |b| {
+        b.iter(|| {
+            for state in black_box(states) {
+                for shape in state.collision_shapes() {
+                    black_box(shape);
+                }
+            }
+        })
+    });
+
     c.bench_function("BlockKind::to_item_kind", |b| {
         b.iter(|| {
             for kind in black_box(BlockKind::ALL) {
@@ -175,6 +185,22 @@ fn packets(c: &mut Criterion) {
         });
     });
 
+    let precoded_tab_list_header_footer_packet =
+        PrecodedPacket::new(&tab_list_header_footer_packet).unwrap();
+
+    c.bench_function("encode_tab_list_header_footer_precoded", |b| {
+        b.iter(|| {
+            let encoder = black_box(&mut encoder);
+
+            encoder.clear();
+            encoder
+                .append_packet(&precoded_tab_list_header_footer_packet)
+                .unwrap();
+
+            black_box(encoder);
+        });
+    });
+
     c.bench_function("encode_spawn_entity", |b| {
         b.iter(|| {
             let encoder = black_box(&mut encoder);
@@ -358,6 +384,36 @@ fn var_int(c: &mut Criterion) {
     });
 }
 
+fn var_long(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+
+    c.bench_function("VarLong::encode", |b| {
+        b.iter_with_setup(
+            || rng.gen(),
+            |i| {
+                let i: i64 = black_box(i);
+
+                let mut buf = [0; VarLong::MAX_SIZE];
+                let _ = black_box(VarLong(i).encode(buf.as_mut_slice()));
+            },
+        );
+    });
+
+    c.bench_function("VarLong::decode", |b| {
+        b.iter_with_setup(
+            || {
+                let mut buf = [0; VarLong::MAX_SIZE];
+                VarLong(rng.gen()).encode(buf.as_mut_slice()).unwrap();
+                buf
+            },
+            |buf| {
+                let mut r = black_box(buf.as_slice());
+                let _ = black_box(VarLong::decode(&mut r));
+            },
+        )
+    });
+}
+
 fn decode_array(c: &mut Criterion) {
     let floats = [123.0, 456.0, 789.0];
     let mut buf = [0u8; 24];
@@ -544,6 +544,32 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 matches!(self.to_kind(), BlockKind::Water | BlockKind::Lava)
             }
 
+            /// If this block has the `waterlogged` property and it is `true`.
+            pub const fn is_waterlogged(self) -> bool {
+                matches!(self.get(PropName::Waterlogged), Some(PropValue::True))
+            }
+
+            /// Sets the `waterlogged` property of this block, returning the
+            /// modified block. All other properties are preserved.
+            ///
+            /// If this block cannot be waterlogged, then the original block is
+            /// returned unchanged.
+            #[must_use]
+            pub const fn set_waterlogged(self, waterlogged: bool) -> Self {
+                self.set(PropName::Waterlogged, PropValue::from_bool(waterlogged))
+            }
+
+            /// Returns the fluid in this block. This is the liquid itself for
+            /// water and lava, and water for waterlogged blocks.
+            pub const fn fluid_state(self) -> FluidState {
+                match self.to_kind() {
+                    BlockKind::Water => FluidState::Water,
+                    BlockKind::Lava => FluidState::Lava,
+                    _ if self.is_waterlogged() => FluidState::Water,
+                    _ => FluidState::Empty,
+                }
+            }
+
             pub const fn is_opaque(self) -> bool {
                 match self.0 {
                     #state_to_opaque_arms
@@ -562,6 +588,10 @@ pub fn build() -> anyhow::Result<TokenStream> {
                 #(#shapes,)*
             ];
 
+            /// Returns the axis-aligned boxes making up the collision shape of
+            /// this block state. Each box is
+            /// `[min_x, min_y, min_z, max_x, max_y, max_z]` in block-local
+            /// coordinates. Blocks without collision have no boxes.
             pub fn collision_shapes(self) -> impl ExactSizeIterator<Item = [f64; 6]> + FusedIterator + Clone {
                 let shape_idxs: &'static [u16] = match self.0 {
                     #state_to_collision_shapes_arms
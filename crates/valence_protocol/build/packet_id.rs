@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use serde::Deserialize;
+
+/// The extracted packet data for every supported protocol version. Add an
+/// entry here to make the IDs of another version available.
+///
+/// `packets.json` is regenerated by the extractor for the current version.
+/// The data of older versions is kept in `packets_<version>.json`.
+const VERSIONS: &[(i32, &str)] = &[
+    (760, include_str!("../../../extracted/packets_760.json")),
+    (761, include_str!("../../../extracted/packets.json")),
+];
+
+#[derive(Deserialize, Debug)]
+struct Packet {
+    name: String,
+    id: i32,
+}
+
+/// Packets by direction ("serverbound" or "clientbound"), then by connection
+/// state.
+type Packets = BTreeMap<String, BTreeMap<String, Vec<Packet>>>;
+
+pub fn build() -> anyhow::Result<TokenStream> {
+    let mut arms = vec![];
+
+    for &(version, json) in VERSIONS {
+        let packets: Packets = serde_json::from_str(json)?;
+
+        for (direction, states) in &packets {
+            let direction = match direction.as_str() {
+                "serverbound" => quote!(PacketDirection::C2s),
+                "clientbound" => quote!(PacketDirection::S2c),
+                other => anyhow::bail!("unknown packet direction \"{other}\""),
+            };
+
+            for (state, packets) in states {
+                let state = match state.as_str() {
+                    "handshaking" => quote!(ConnectionState::Handshaking),
+                    "status" => quote!(ConnectionState::Status),
+                    "login" => quote!(ConnectionState::Login),
+                    "play" => quote!(ConnectionState::Play),
+                    other => anyhow::bail!("unknown connection state \"{other}\""),
+                };
+
+                for packet in packets {
+                    let name = &packet.name;
+                    let id = packet.id;
+
+                    arms.push(quote! {
+                        (#version, #state, #direction, #name) => Some(#id),
+                    });
+                }
+            }
+        }
+    }
+
+    let versions = VERSIONS.iter().map(|&(version, _)| version);
+
+    Ok(quote! {
+        /// The protocol versions with packet IDs available from
+        /// [`packet_id_for`].
+        pub const PACKET_ID_VERSIONS: &[i32] = &[#(#versions,)*];
+
+        /// Returns the ID of the packet named `name` in the given protocol
+        /// version, connection state, and direction.
+        ///
+        /// `name` is the name of the packet's class in the vanilla game with
+        /// Yarn mappings, such as `"KeepAliveS2CPacket"`. Returns `None` if the
+        /// packet does not exist or the version is not one of
+        /// [`PACKET_ID_VERSIONS`].
+        pub fn packet_id_for(
+            protocol_version: i32,
+            state: ConnectionState,
+            direction: PacketDirection,
+            name: &str,
+        ) -> Option<i32> {
+            match (protocol_version, state, direction, name) {
+                #(#arms)*
+                _ => None,
+            }
+        }
+    })
+}
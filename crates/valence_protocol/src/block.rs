@@ -95,6 +95,14 @@ pub enum BlockFace {
     East,
 }
 
+/// The fluid contained in a block. See [`BlockState::fluid_state`].
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum FluidState {
+    Empty,
+    Water,
+    Lava,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -110,4 +118,81 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn waterlogging() {
+        let fence = BlockState::OAK_FENCE.set(PropName::North, PropValue::True);
+        assert!(!fence.is_waterlogged());
+        assert_eq!(fence.fluid_state(), FluidState::Empty);
+
+        let wet_fence = fence.set_waterlogged(true);
+        assert!(wet_fence.is_waterlogged());
+        assert_eq!(wet_fence.get(PropName::North), Some(PropValue::True));
+        assert_eq!(wet_fence.fluid_state(), FluidState::Water);
+        assert_eq!(wet_fence.set_waterlogged(false), fence);
+
+        // Blocks without the property are unaffected.
+        assert_eq!(BlockState::STONE.set_waterlogged(true), BlockState::STONE);
+        assert!(!BlockState::STONE.set_waterlogged(true).is_waterlogged());
+        assert_eq!(BlockState::STONE.fluid_state(), FluidState::Empty);
+
+        let stairs = BlockState::OAK_STAIRS.set_waterlogged(true);
+        assert!(!stairs.is_liquid());
+        assert_eq!(stairs.fluid_state(), FluidState::Water);
+
+        assert_eq!(BlockState::WATER.fluid_state(), FluidState::Water);
+        assert_eq!(BlockState::LAVA.fluid_state(), FluidState::Lava);
+    }
+
+    #[test]
+    fn collision_shapes() {
+        fn shapes(state: BlockState) -> Vec<[f64; 6]> {
+            state.collision_shapes().collect()
+        }
+
+        assert_eq!(shapes(BlockState::STONE), [[0.0, 0.0, 0.0, 1.0, 1.0, 1.0]]);
+        assert!(shapes(BlockState::AIR).is_empty());
+        assert!(shapes(BlockState::WATER).is_empty());
+        assert!(shapes(BlockState::GRASS).is_empty());
+
+        let slab = BlockState::OAK_SLAB;
+        assert_eq!(
+            shapes(slab.set(PropName::Type, PropValue::Bottom)),
+            [[0.0, 0.0, 0.0, 1.0, 0.5, 1.0]]
+        );
+        assert_eq!(
+            shapes(slab.set(PropName::Type, PropValue::Top)),
+            [[0.0, 0.5, 0.0, 1.0, 1.0, 1.0]]
+        );
+        assert_eq!(
+            shapes(slab.set(PropName::Type, PropValue::Double)),
+            [[0.0, 0.0, 0.0, 1.0, 1.0, 1.0]]
+        );
+
+        let volume = |shapes: &[[f64; 6]]| {
+            shapes
+                .iter()
+                .map(|s| (s[3] - s[0]) * (s[4] - s[1]) * (s[5] - s[2]))
+                .sum::<f64>()
+        };
+
+        let stairs = BlockState::OAK_STAIRS.set(PropName::Shape, PropValue::Straight);
+
+        let bottom = shapes(stairs.set(PropName::Half, PropValue::Bottom));
+        assert_eq!(volume(&bottom), 0.75);
+        assert!(bottom.iter().all(|s| s[1] == 0.0));
+        assert!(bottom.iter().any(|s| s[4] == 0.5));
+
+        let top = shapes(stairs.set(PropName::Half, PropValue::Top));
+        assert_eq!(volume(&top), 0.75);
+        assert!(top.iter().all(|s| s[4] == 1.0));
+        assert!(top.iter().any(|s| s[1] == 0.5));
+
+        let fence = BlockState::OAK_FENCE;
+        assert_eq!(shapes(fence), [[0.375, 0.0, 0.375, 0.625, 1.5, 0.625]]);
+        assert_eq!(
+            shapes(fence.set(PropName::West, PropValue::True)),
+            [[0.0, 0.0, 0.375, 0.625, 1.5, 0.625]]
+        );
+    }
 }
@@ -1,11 +1,16 @@
 use std::io::Write;
 
 use anyhow::bail;
+use serde::{Deserialize, Serialize};
 
 use crate::{BlockFace, Decode, Encode};
 
 /// Represents an absolute block position in world space.
-#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug)]
+///
+/// Block positions are ordered by X, then Y, then Z.
+#[derive(
+    Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Serialize, Deserialize,
+)]
 pub struct BlockPos {
     pub x: i32,
     pub y: i32,
@@ -98,8 +103,44 @@ impl From<BlockPos> for [i32; 3] {
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
     use super::*;
 
+    #[test]
+    fn block_pos_as_map_key() {
+        let positions = [
+            BlockPos::new(1, 2, 3),
+            BlockPos::new(-1, 70, 3),
+            BlockPos::new(1, -2, 3),
+            BlockPos::new(1, 2, -3),
+        ];
+
+        let hashed: HashMap<_, _> = positions.iter().map(|&p| (p, p.y)).collect();
+        assert_eq!(hashed[&BlockPos::new(1, -2, 3)], -2);
+        assert!(!hashed.contains_key(&BlockPos::new(3, 2, 1)));
+
+        let sorted: BTreeMap<_, _> = positions.iter().map(|&p| (p, ())).collect();
+        assert_eq!(
+            sorted.keys().copied().collect::<Vec<_>>(),
+            [
+                BlockPos::new(-1, 70, 3),
+                BlockPos::new(1, -2, 3),
+                BlockPos::new(1, 2, -3),
+                BlockPos::new(1, 2, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_pos_serde() {
+        let pos = BlockPos::new(-5, 64, 1234);
+        let json = serde_json::to_string(&pos).unwrap();
+
+        assert_eq!(json, r#"{"x":-5,"y":64,"z":1234}"#);
+        assert_eq!(serde_json::from_str::<BlockPos>(&json).unwrap(), pos);
+    }
+
     #[test]
     fn position() {
         let xzs = [
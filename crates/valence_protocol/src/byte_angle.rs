@@ -8,12 +8,17 @@ use crate::{Decode, Encode, Result};
 pub struct ByteAngle(pub u8);
 
 impl ByteAngle {
+    /// Converts an angle in degrees to the nearest byte angle. Angles outside
+    /// of `0..360` wrap around.
     pub fn from_degrees(f: f32) -> ByteAngle {
-        ByteAngle((f.rem_euclid(360.0) / 360.0 * 256.0).round() as u8)
+        // Rounding can produce 256, which must wrap to 0 rather than saturate.
+        ByteAngle((f.rem_euclid(360.0) / 360.0 * 256.0).round() as u32 as u8)
     }
 
+    /// Converts an angle in radians to the nearest byte angle. Angles outside
+    /// of `0..τ` wrap around.
     pub fn from_radians(f: f32) -> ByteAngle {
-        ByteAngle((f.rem_euclid(TAU) / TAU * 256.0).round() as u8)
+        ByteAngle((f.rem_euclid(TAU) / TAU * 256.0).round() as u32 as u8)
     }
 
     pub fn to_degrees(self) -> f32 {
@@ -36,3 +41,26 @@ impl Decode<'_> for ByteAngle {
         u8::decode(r).map(ByteAngle)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn degrees_conversion() {
+        for (deg, byte) in [(0.0, 0), (90.0, 64), (180.0, 128), (270.0, 192)] {
+            assert_eq!(ByteAngle::from_degrees(deg), ByteAngle(byte));
+            assert_eq!(ByteAngle(byte).to_degrees(), deg);
+        }
+    }
+
+    #[test]
+    fn degrees_wrap_around() {
+        assert_eq!(ByteAngle::from_degrees(360.0), ByteAngle(0));
+        assert_eq!(ByteAngle::from_degrees(450.0), ByteAngle(64));
+        assert_eq!(ByteAngle::from_degrees(-90.0), ByteAngle(192));
+        // Rounds up to a full turn.
+        assert_eq!(ByteAngle::from_degrees(359.9), ByteAngle(0));
+        assert_eq!(ByteAngle::from_radians(TAU - 0.001), ByteAngle(0));
+    }
+}
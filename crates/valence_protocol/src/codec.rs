@@ -1,26 +1,163 @@
+use std::any::type_name;
+use std::io;
+use std::ops::Range;
+
 #[cfg(feature = "encryption")]
 use aes::cipher::{AsyncStreamCipher, NewCipher};
 use anyhow::{bail, ensure};
 use bytes::{Buf, BufMut, BytesMut};
 use tracing::debug;
 
+use crate::packets::{ConnectionState, PacketDirection, PacketState};
 use crate::var_int::{VarInt, VarIntDecodeError};
-use crate::{DecodePacket, Encode, EncodePacket, Result, MAX_PACKET_SIZE};
+use crate::{Decode, DecodePacket, Encode, EncodePacket, Result, MAX_PACKET_SIZE};
 
 /// The AES block cipher with a 128 bit key, using the CFB-8 mode of
 /// operation.
 #[cfg(feature = "encryption")]
 type Cipher = cfb8::Cfb8<aes::Aes128>;
 
-#[derive(Default)]
+/// Compresses the data of packets above the compression threshold.
+///
+/// The vanilla protocol requires zlib, so [`ZlibCompressor`] is the only
+/// implementation that can be used with a real client. Other implementations
+/// are useful for testing and benchmarking.
+#[cfg(feature = "compression")]
+pub trait Compressor: Send + Sync + 'static {
+    /// Compresses `data` and appends the result to `out`.
+    fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<()>;
+}
+
+/// Decompresses the data of compressed packets. This is the counterpart to
+/// [`Compressor`].
+#[cfg(feature = "compression")]
+pub trait Decompressor: Send + Sync + 'static {
+    /// Decompresses `data` and appends the result to `out`. No more than
+    /// `data_len` bytes are appended, and an error is returned if `data`
+    /// decompresses to more than `data_len` bytes.
+    fn decompress(&mut self, data: &[u8], data_len: usize, out: &mut Vec<u8>) -> Result<()>;
+}
+
+/// The default [`Compressor`], using zlib.
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, Debug)]
+pub struct ZlibCompressor {
+    /// The compression level from 0 to 9.
+    pub level: u32,
+}
+
+#[cfg(feature = "compression")]
+impl Default for ZlibCompressor {
+    fn default() -> Self {
+        Self { level: 4 }
+    }
+}
+
+#[cfg(feature = "compression")]
+impl Compressor for ZlibCompressor {
+    fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+        use std::io::Read;
+
+        use flate2::bufread::ZlibEncoder;
+        use flate2::Compression;
+
+        ZlibEncoder::new(data, Compression::new(self.level)).read_to_end(out)?;
+        Ok(())
+    }
+}
+
+/// The default [`Decompressor`], using zlib.
+#[cfg(feature = "compression")]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct ZlibDecompressor;
+
+#[cfg(feature = "compression")]
+impl Decompressor for ZlibDecompressor {
+    fn decompress(&mut self, data: &[u8], data_len: usize, out: &mut Vec<u8>) -> Result<()> {
+        use std::io::Read;
+
+        use flate2::bufread::ZlibDecoder;
+
+        out.reserve_exact(data_len);
+
+        let mut z = ZlibDecoder::new(data).take(data_len as u64);
+        z.read_to_end(out)?;
+
+        // Stop after one extra byte instead of decompressing everything.
+        ensure!(
+            z.into_inner().read(&mut [0])? == 0,
+            "decompressed data exceeds the declared length of {data_len}"
+        );
+
+        Ok(())
+    }
+}
+
+/// What to do with a packet after it has been seen by a packet inspector. See
+/// [`PacketEncoder::set_inspector`] and [`PacketDecoder::set_inspector`].
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum InspectAction {
+    /// Keep the packet as it is.
+    Pass,
+    /// Discard the packet.
+    Drop,
+    /// Use the given packet data instead. The data must begin with the packet
+    /// ID and must not include the packet length.
+    Replace(Vec<u8>),
+}
+
+/// A callback which is given the ID and data of a packet and decides what to
+/// do with it. The data includes the packet ID but not the packet length, and
+/// is never compressed or encrypted.
+pub type PacketInspector = Box<dyn FnMut(i32, &[u8]) -> InspectAction + Send + Sync>;
+
+/// Running totals of the packets and bytes which have passed through a
+/// [`PacketEncoder`] or [`PacketDecoder`], for monitoring bandwidth. See
+/// [`PacketEncoder::stats`] and [`PacketDecoder::stats`].
+#[derive(Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct PacketStats {
+    /// The number of packets.
+    pub packets: u64,
+    /// The number of bytes sent or received, including length prefixes and
+    /// after compression.
+    pub bytes: u64,
+    /// The number of bytes of packet data before compression, from the start
+    /// of the packet ID to the end of the packet.
+    pub uncompressed_bytes: u64,
+    /// The number of packets which were compressed.
+    pub compressed_packets: u64,
+}
+
 pub struct PacketEncoder {
     buf: BytesMut,
     #[cfg(feature = "compression")]
     compress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     compression_threshold: Option<u32>,
+    #[cfg(feature = "compression")]
+    compressor: Box<dyn Compressor>,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
+    inspector: Option<PacketInspector>,
+    stats: PacketStats,
+}
+
+impl Default for PacketEncoder {
+    fn default() -> Self {
+        Self {
+            buf: BytesMut::new(),
+            #[cfg(feature = "compression")]
+            compress_buf: vec![],
+            #[cfg(feature = "compression")]
+            compression_threshold: None,
+            #[cfg(feature = "compression")]
+            compressor: Box::<ZlibCompressor>::default(),
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            inspector: None,
+            stats: PacketStats::default(),
+        }
+    }
 }
 
 impl PacketEncoder {
@@ -28,11 +165,59 @@ impl PacketEncoder {
         Self::default()
     }
 
+    /// Reserves capacity for at least `additional` more bytes to be written
+    /// without reallocating. See [`Self::encoded_packet_len`] for sizing a
+    /// packet.
+    pub fn reserve(&mut self, additional: usize) {
+        self.buf.reserve(additional);
+    }
+
+    /// Returns the number of bytes [`Self::append_packet`] would write for
+    /// `pkt`, including the length prefix.
+    ///
+    /// The result is exact unless `pkt` would be compressed. In that case,
+    /// the result is an upper bound assuming [`ZlibCompressor`] is used.
+    /// Packet inspectors are not taken into account.
+    pub fn encoded_packet_len<P>(&self, pkt: &P) -> Result<usize>
+    where
+        P: EncodePacket + ?Sized,
+    {
+        let mut counter = ByteCounter(0);
+        pkt.encode_packet(&mut counter)?;
+        let data_len = counter.0;
+
+        #[cfg(feature = "compression")]
+        if let Some(threshold) = self.compression_threshold {
+            let packet_len = if data_len > threshold as usize && P::COMPRESS {
+                // Same as zlib's `compressBound`.
+                let compressed_bound =
+                    data_len + (data_len >> 12) + (data_len >> 14) + (data_len >> 25) + 13;
+
+                VarInt(data_len as i32).written_size() + compressed_bound
+            } else {
+                // A single byte for the zero data length.
+                1 + data_len
+            };
+
+            return Ok(VarInt(packet_len as i32).written_size() + packet_len);
+        }
+
+        Ok(VarInt(data_len as i32).written_size() + data_len)
+    }
+
     #[inline]
     pub fn append_bytes(&mut self, bytes: &[u8]) {
         self.buf.extend_from_slice(bytes)
     }
 
+    /// Writes a packet to the front of the buffer so that it is sent before
+    /// all packets that have been written but not yet [taken].
+    ///
+    /// This is useful for high priority packets such as disconnects and keep
+    /// alives. The packet is framed and compressed exactly as it would be by
+    /// [`Self::append_packet`].
+    ///
+    /// [taken]: Self::take
     pub fn prepend_packet<P>(&mut self, pkt: &P) -> Result<()>
     where
         P: EncodePacket + ?Sized,
@@ -62,31 +247,46 @@ impl PacketEncoder {
 
         pkt.encode_packet((&mut self.buf).writer())?;
 
+        if let Some(inspector) = &mut self.inspector {
+            let packet_data = &self.buf[start_len..];
+            let id = VarInt::decode(&mut &packet_data[..])?.0;
+
+            match inspector(id, packet_data) {
+                InspectAction::Pass => {}
+                InspectAction::Drop => {
+                    self.buf.truncate(start_len);
+                    return Ok(());
+                }
+                InspectAction::Replace(data) => {
+                    self.buf.truncate(start_len);
+                    self.buf.extend_from_slice(&data);
+                }
+            }
+        }
+
         let data_len = self.buf.len() - start_len;
 
+        self.stats.packets += 1;
+        self.stats.uncompressed_bytes += data_len as u64;
+
         #[cfg(feature = "compression")]
         if let Some(threshold) = self.compression_threshold {
-            use std::io::Read;
-
-            use flate2::bufread::ZlibEncoder;
-            use flate2::Compression;
-
-            if data_len > threshold as usize {
-                let mut z = ZlibEncoder::new(&self.buf[start_len..], Compression::new(4));
-
+            if data_len > threshold as usize && P::COMPRESS {
+                self.stats.compressed_packets += 1;
                 self.compress_buf.clear();
 
+                self.compressor
+                    .compress(&self.buf[start_len..], &mut self.compress_buf)?;
+
                 let data_len_size = VarInt(data_len as i32).written_size();
 
-                let packet_len = data_len_size + z.read_to_end(&mut self.compress_buf)?;
+                let packet_len = data_len_size + self.compress_buf.len();
 
                 ensure!(
                     packet_len <= MAX_PACKET_SIZE as usize,
                     "packet exceeds maximum length"
                 );
 
-                drop(z);
-
                 self.buf.truncate(start_len);
 
                 let mut writer = (&mut self.buf).writer();
@@ -143,6 +343,8 @@ impl PacketEncoder {
     /// Takes all the packets written so far and encrypts them if encryption is
     /// enabled.
     pub fn take(&mut self) -> BytesMut {
+        self.stats.bytes += self.buf.len() as u64;
+
         #[cfg(feature = "encryption")]
         if let Some(cipher) = &mut self.cipher {
             cipher.encrypt(&mut self.buf);
@@ -155,14 +357,65 @@ impl PacketEncoder {
         self.buf.clear();
     }
 
+    /// Returns the totals of the packets written with [`Self::append_packet`]
+    /// and [`Self::prepend_packet`]. Packets dropped by the inspector are not
+    /// counted.
+    ///
+    /// [`PacketStats::bytes`] is the number of bytes returned by
+    /// [`Self::take`], which includes bytes added with [`Self::append_bytes`].
+    pub fn stats(&self) -> PacketStats {
+        self.stats
+    }
+
+    /// Resets all the totals returned by [`Self::stats`] to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = PacketStats::default();
+    }
+
     #[cfg(feature = "compression")]
     pub fn set_compression(&mut self, threshold: Option<u32>) {
         self.compression_threshold = threshold;
     }
 
+    /// Returns the compression threshold set with [`Self::set_compression`].
+    #[cfg(feature = "compression")]
+    pub fn compression_threshold(&self) -> Option<u32> {
+        self.compression_threshold
+    }
+
+    /// Replaces the [`Compressor`] used for packets above the compression
+    /// threshold. The default is [`ZlibCompressor`].
+    #[cfg(feature = "compression")]
+    pub fn set_compressor(&mut self, compressor: impl Compressor) {
+        self.compressor = Box::new(compressor);
+    }
+
+    /// Installs a [`PacketInspector`] which sees every packet passed to
+    /// [`Self::append_packet`] and [`Self::prepend_packet`] before it is
+    /// compressed. Dropped packets are not written. Bytes written with
+    /// [`Self::append_bytes`] are not inspected.
+    pub fn set_inspector(
+        &mut self,
+        inspector: impl FnMut(i32, &[u8]) -> InspectAction + Send + Sync + 'static,
+    ) {
+        self.inspector = Some(Box::new(inspector));
+    }
+
+    /// Removes the [`PacketInspector`] installed with [`Self::set_inspector`],
+    /// if any.
+    pub fn remove_inspector(&mut self) {
+        self.inspector = None;
+    }
+
     /// Encrypts all future packets **and any packets that have
     /// not been [taken] yet.**
     ///
+    /// Encryption is applied after compression.
+    ///
+    /// # Panics
+    ///
+    /// Panics if encryption is already enabled.
+    ///
     /// [taken]: Self::take
     #[cfg(feature = "encryption")]
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
@@ -171,6 +424,28 @@ impl PacketEncoder {
     }
 }
 
+/// Returns the number of bytes written by [`Encode::encode`] for `val` without
+/// allocating a buffer for them.
+pub fn encoded_len(val: &(impl Encode + ?Sized)) -> Result<usize> {
+    let mut counter = ByteCounter(0);
+    val.encode(&mut counter)?;
+    Ok(counter.0)
+}
+
+/// A writer which discards the bytes written to it and counts them instead.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 pub fn encode_packet<P>(buf: &mut Vec<u8>, pkt: &P) -> Result<()>
 where
     P: EncodePacket + ?Sized,
@@ -210,33 +485,26 @@ pub fn encode_packet_compressed<P>(
 where
     P: EncodePacket + ?Sized,
 {
-    use std::io::Read;
-
-    use flate2::bufread::ZlibEncoder;
-    use flate2::Compression;
-
     let start_len = buf.len();
 
     pkt.encode_packet(&mut *buf)?;
 
     let data_len = buf.len() - start_len;
 
-    if data_len > threshold as usize {
-        let mut z = ZlibEncoder::new(&buf[start_len..], Compression::new(4));
-
+    if data_len > threshold as usize && P::COMPRESS {
         scratch.clear();
 
+        ZlibCompressor::default().compress(&buf[start_len..], scratch)?;
+
         let data_len_size = VarInt(data_len as i32).written_size();
 
-        let packet_len = data_len_size + z.read_to_end(scratch)?;
+        let packet_len = data_len_size + scratch.len();
 
         ensure!(
             packet_len <= MAX_PACKET_SIZE as usize,
             "packet exceeds maximum length"
         );
 
-        drop(z);
-
         buf.truncate(start_len);
 
         VarInt(packet_len as i32).encode(&mut *buf)?;
@@ -268,7 +536,6 @@ where
     Ok(())
 }
 
-#[derive(Default)]
 pub struct PacketDecoder {
     buf: BytesMut,
     cursor: usize,
@@ -276,8 +543,49 @@ pub struct PacketDecoder {
     decompress_buf: Vec<u8>,
     #[cfg(feature = "compression")]
     compression_enabled: bool,
+    #[cfg(feature = "compression")]
+    decompressor: Box<dyn Decompressor>,
     #[cfg(feature = "encryption")]
     cipher: Option<Cipher>,
+    expected_state: Option<(PacketDirection, ConnectionState)>,
+    max_packet_len: i32,
+    inspector: Option<PacketInspector>,
+    /// Holds packet data replaced by the inspector.
+    replace_buf: Vec<u8>,
+    stats: PacketStats,
+}
+
+/// The location of the data of a packet in a [`PacketDecoder`].
+enum PacketData {
+    /// A range of `buf`.
+    Buf(Range<usize>),
+    /// All of `decompress_buf`.
+    #[cfg(feature = "compression")]
+    Decompressed,
+    /// All of `replace_buf`.
+    Replaced,
+}
+
+impl Default for PacketDecoder {
+    fn default() -> Self {
+        Self {
+            buf: BytesMut::new(),
+            cursor: 0,
+            #[cfg(feature = "compression")]
+            decompress_buf: vec![],
+            #[cfg(feature = "compression")]
+            compression_enabled: false,
+            #[cfg(feature = "compression")]
+            decompressor: Box::new(ZlibDecompressor),
+            #[cfg(feature = "encryption")]
+            cipher: None,
+            expected_state: None,
+            max_packet_len: MAX_PACKET_SIZE,
+            inspector: None,
+            replace_buf: vec![],
+            stats: PacketStats::default(),
+        }
+    }
 }
 
 impl PacketDecoder {
@@ -285,10 +593,114 @@ impl PacketDecoder {
         Self::default()
     }
 
+    /// Like [`Self::try_next_packet`], but returns an error if the packet type
+    /// does not belong to the direction and state set with
+    /// [`Self::set_expected_state`].
+    ///
+    /// No check is performed if no expected state was set.
+    pub fn try_next_packet_checked<'a, P>(&'a mut self) -> Result<Option<P>>
+    where
+        P: DecodePacket<'a> + PacketState,
+    {
+        if let Some((direction, state)) = self.expected_state {
+            ensure!(
+                P::DIRECTION == direction && P::STATE == state,
+                "attempted to decode {} ({:?} {:?}) but the decoder expects {:?} {:?} packets",
+                type_name::<P>(),
+                P::DIRECTION,
+                P::STATE,
+                direction,
+                state
+            );
+        }
+
+        self.try_next_packet()
+    }
+
     pub fn try_next_packet<'a, P>(&'a mut self) -> Result<Option<P>>
     where
         P: DecodePacket<'a>,
     {
+        self.next_packet(false)
+    }
+
+    /// Like [`Self::try_next_packet`], but a complete packet which fails to
+    /// decompress or decode is skipped over before the error is returned. The
+    /// decoder remains usable and the next call will decode the following
+    /// packet.
+    ///
+    /// Errors in the packet length cannot be recovered from, since the start of
+    /// the next packet is unknown. The decoder should be discarded after such
+    /// an error.
+    pub fn try_next_packet_skip_invalid<'a, P>(&'a mut self) -> Result<Option<P>>
+    where
+        P: DecodePacket<'a>,
+    {
+        self.next_packet(true)
+    }
+
+    fn next_packet<'a, P>(&'a mut self, skip_invalid: bool) -> Result<Option<P>>
+    where
+        P: DecodePacket<'a>,
+    {
+        let (data, total_packet_len) = loop {
+            let (mut data, total_packet_len) = match self.next_packet_data(skip_invalid)? {
+                Some(next) => next,
+                None => return Ok(None),
+            };
+
+            if let Some(inspector) = &mut self.inspector {
+                let packet_data = match &data {
+                    PacketData::Buf(range) => &self.buf[range.clone()],
+                    #[cfg(feature = "compression")]
+                    PacketData::Decompressed => self.decompress_buf.as_slice(),
+                    PacketData::Replaced => self.replace_buf.as_slice(),
+                };
+
+                let id = VarInt::decode(&mut &packet_data[..])?.0;
+
+                match inspector(id, packet_data) {
+                    InspectAction::Pass => {}
+                    InspectAction::Drop => {
+                        self.cursor = total_packet_len;
+                        continue;
+                    }
+                    InspectAction::Replace(replacement) => {
+                        self.replace_buf = replacement;
+                        data = PacketData::Replaced;
+                    }
+                }
+            }
+
+            break (data, total_packet_len);
+        };
+
+        let mut r = match data {
+            PacketData::Buf(range) => &self.buf[range],
+            #[cfg(feature = "compression")]
+            PacketData::Decompressed => self.decompress_buf.as_slice(),
+            PacketData::Replaced => self.replace_buf.as_slice(),
+        };
+
+        let packet = P::decode_packet(&mut r)?;
+
+        if !r.is_empty() {
+            let remaining = r.len();
+
+            debug!("packet after partial decode ({remaining} bytes remain): {packet:?}");
+
+            bail!("packet contents were not read completely ({remaining} bytes remain)");
+        }
+
+        self.cursor = total_packet_len;
+
+        Ok(Some(packet))
+    }
+
+    /// Finds the next complete packet, decompressing it if necessary. Returns
+    /// the location of the packet data and the length of the whole packet in
+    /// `buf`.
+    fn next_packet_data(&mut self, skip_invalid: bool) -> Result<Option<(PacketData, usize)>> {
         self.buf.advance(self.cursor);
         self.cursor = 0;
 
@@ -301,7 +713,7 @@ impl PacketDecoder {
         };
 
         ensure!(
-            (0..=MAX_PACKET_SIZE).contains(&packet_len),
+            (0..=self.max_packet_len).contains(&packet_len),
             "packet length of {packet_len} is out of bounds"
         );
 
@@ -309,56 +721,65 @@ impl PacketDecoder {
             return Ok(None);
         }
 
-        r = &r[..packet_len as usize];
+        let packet_len_size = VarInt(packet_len).written_size();
+        let total_packet_len = packet_len_size + packet_len as usize;
 
-        #[cfg(feature = "compression")]
-        let packet = if self.compression_enabled {
-            use std::io::Read;
+        if skip_invalid {
+            self.cursor = total_packet_len;
+        }
+
+        self.stats.packets += 1;
+        self.stats.bytes += total_packet_len as u64;
 
+        #[cfg(feature = "compression")]
+        if self.compression_enabled {
             use anyhow::Context;
-            use flate2::bufread::ZlibDecoder;
 
-            use crate::Decode;
+            let mut r = &self.buf[packet_len_size..total_packet_len];
 
             let data_len = VarInt::decode(&mut r)?.0;
 
             ensure!(
-                (0..MAX_PACKET_SIZE).contains(&data_len),
+                (0..self.max_packet_len).contains(&data_len),
                 "decompressed packet length of {data_len} is out of bounds"
             );
 
             if data_len != 0 {
+                self.stats.compressed_packets += 1;
+                self.stats.uncompressed_bytes += data_len as u64;
+
                 self.decompress_buf.clear();
-                self.decompress_buf.reserve_exact(data_len as usize);
-                let mut z = ZlibDecoder::new(r).take(data_len as u64);
 
-                z.read_to_end(&mut self.decompress_buf)
+                self.decompressor
+                    .decompress(r, data_len as usize, &mut self.decompress_buf)
                     .context("decompressing packet")?;
 
-                r = &self.decompress_buf;
-                P::decode_packet(&mut r)?
-            } else {
-                P::decode_packet(&mut r)?
-            }
-        } else {
-            P::decode_packet(&mut r)?
-        };
+                ensure!(
+                    self.decompress_buf.len() == data_len as usize,
+                    "decompressed packet length of {} does not match the declared length of \
+                     {data_len}",
+                    self.decompress_buf.len()
+                );
 
-        #[cfg(not(feature = "compression"))]
-        let packet = P::decode_packet(&mut r)?;
+                return Ok(Some((PacketData::Decompressed, total_packet_len)));
+            }
 
-        if !r.is_empty() {
-            let remaining = r.len();
+            self.stats.uncompressed_bytes += r.len() as u64;
 
-            debug!("packet after partial decode ({remaining} bytes remain): {packet:?}");
+            let data_start = total_packet_len - r.len();
 
-            bail!("packet contents were not read completely ({remaining} bytes remain)");
+            return Ok(Some((
+                PacketData::Buf(data_start..total_packet_len),
+                total_packet_len,
+            )));
         }
 
-        let total_packet_len = VarInt(packet_len).written_size() + packet_len as usize;
-        self.cursor = total_packet_len;
+        self.stats.uncompressed_bytes += packet_len as u64;
 
-        Ok(Some(packet))
+        Ok(Some((
+            PacketData::Buf(packet_len_size..total_packet_len),
+            total_packet_len,
+        )))
     }
 
     /// Repeatedly decodes a packet type until all packets in the decoder are
@@ -420,6 +841,10 @@ impl PacketDecoder {
             let total_packet_len = VarInt(packet_len).written_size() + packet_len as usize;
             self.cursor += total_packet_len;
 
+            self.stats.packets += 1;
+            self.stats.bytes += total_packet_len as u64;
+            self.stats.uncompressed_bytes += packet_len as u64;
+
             res.push(packet);
         }
     }
@@ -441,11 +866,73 @@ impl PacketDecoder {
         }
     }
 
+    /// Sets the direction and connection state of the packets this decoder
+    /// expects to see. This is checked by [`Self::try_next_packet_checked`].
+    pub fn set_expected_state(&mut self, direction: PacketDirection, state: ConnectionState) {
+        self.expected_state = Some((direction, state));
+    }
+
+    /// Returns the totals of the complete packets read from the queued bytes,
+    /// including packets which failed to decode or were dropped by the
+    /// inspector.
+    pub fn stats(&self) -> PacketStats {
+        self.stats
+    }
+
+    /// Resets all the totals returned by [`Self::stats`] to zero.
+    pub fn reset_stats(&mut self) {
+        self.stats = PacketStats::default();
+    }
+
+    /// Returns the maximum length of a packet this decoder accepts.
+    pub fn max_packet_len(&self) -> i32 {
+        self.max_packet_len
+    }
+
+    /// Sets the maximum length of a packet this decoder accepts, both before
+    /// and after decompression. Packets declaring a larger length are rejected
+    /// before their contents arrive.
+    ///
+    /// The length is clamped to [`MAX_PACKET_SIZE`], which is also the default.
+    pub fn set_max_packet_len(&mut self, len: i32) {
+        self.max_packet_len = len.clamp(0, MAX_PACKET_SIZE);
+    }
+
     #[cfg(feature = "compression")]
     pub fn set_compression(&mut self, enabled: bool) {
         self.compression_enabled = enabled;
     }
 
+    /// Replaces the [`Decompressor`] used for compressed packets. The default
+    /// is [`ZlibDecompressor`].
+    #[cfg(feature = "compression")]
+    pub fn set_decompressor(&mut self, decompressor: impl Decompressor) {
+        self.decompressor = Box::new(decompressor);
+    }
+
+    /// Installs a [`PacketInspector`] which sees every packet returned by the
+    /// `try_next_packet*` methods after it is decrypted and decompressed.
+    /// Dropped packets are skipped over as if they were never received.
+    pub fn set_inspector(
+        &mut self,
+        inspector: impl FnMut(i32, &[u8]) -> InspectAction + Send + Sync + 'static,
+    ) {
+        self.inspector = Some(Box::new(inspector));
+    }
+
+    /// Removes the [`PacketInspector`] installed with [`Self::set_inspector`],
+    /// if any.
+    pub fn remove_inspector(&mut self) {
+        self.inspector = None;
+    }
+
+    /// Decrypts all bytes queued from now on **and any queued bytes that have
+    /// not been decoded yet.** Bytes are decrypted before they are
+    /// decompressed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if encryption is already enabled.
     #[cfg(feature = "encryption")]
     pub fn enable_encryption(&mut self, key: &[u8; 16]) {
         assert!(self.cipher.is_none(), "encryption is already enabled");
@@ -502,7 +989,6 @@ mod tests {
     use crate::text::{Text, TextFormat};
     use crate::username::Username;
     use crate::var_long::VarLong;
-    use crate::Decode;
 
     #[cfg(feature = "encryption")]
     const CRYPT_KEY: [u8; 16] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
@@ -598,6 +1084,331 @@ mod tests {
             .check("third");
     }
 
+    #[test]
+    fn stats_count_packets_and_bytes() {
+        let mut enc = PacketEncoder::new();
+
+        for n in ["a", "bb", "ccc"] {
+            enc.append_packet(&TestPacket::new(n)).unwrap();
+        }
+
+        let stats = enc.stats();
+        assert_eq!(stats.packets, 3);
+        assert_eq!(stats.compressed_packets, 0);
+        // Nothing has been taken yet.
+        assert_eq!(stats.bytes, 0);
+
+        let mut data_len = 0;
+        let mut prefix_len = 0;
+        for n in ["a", "bb", "ccc"] {
+            let mut data = vec![];
+            TestPacket::new(n).encode_packet(&mut data).unwrap();
+            data_len += data.len();
+            prefix_len += VarInt(data.len() as i32).written_size();
+        }
+
+        let bytes = enc.take();
+        let stats = enc.stats();
+        assert_eq!(stats.bytes, bytes.len() as u64);
+        assert_eq!(stats.bytes, (data_len + prefix_len) as u64);
+        assert_eq!(stats.uncompressed_bytes, data_len as u64);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(bytes);
+
+        for n in ["a", "bb", "ccc"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(n);
+        }
+
+        assert_eq!(dec.stats(), enc.stats());
+
+        enc.reset_stats();
+        dec.reset_stats();
+        assert_eq!(enc.stats(), PacketStats::default());
+        assert_eq!(dec.stats(), PacketStats::default());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn stats_count_compressed_packets() {
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(0));
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        enc.append_packet(&TestPacket::new("second")).unwrap();
+
+        let bytes = enc.take();
+        let stats = enc.stats();
+        assert_eq!(stats.packets, 2);
+        assert_eq!(stats.compressed_packets, 2);
+        assert_eq!(stats.bytes, bytes.len() as u64);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.queue_bytes(bytes);
+
+        for n in ["first", "second"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(n);
+        }
+
+        assert_eq!(dec.stats(), stats);
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn encrypted_packets_round_trip() {
+        let mut enc = PacketEncoder::new();
+        enc.enable_encryption(&CRYPT_KEY);
+        #[cfg(feature = "compression")]
+        enc.set_compression(Some(0));
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        enc.append_packet(&TestPacket::new("second")).unwrap();
+        let bytes = enc.take();
+
+        let mut plain = PacketEncoder::new();
+        #[cfg(feature = "compression")]
+        plain.set_compression(Some(0));
+        plain.append_packet(&TestPacket::new("first")).unwrap();
+        let plain = plain.take();
+
+        // The bytes on the wire differ from the unencrypted packet.
+        assert_ne!(&bytes[..plain.len()], &plain[..]);
+
+        let mut dec = PacketDecoder::new();
+        dec.enable_encryption(&CRYPT_KEY);
+        #[cfg(feature = "compression")]
+        dec.set_compression(true);
+
+        // The cipher is a stream cipher, so bytes may arrive in any number of
+        // pieces.
+        for chunk in bytes.chunks(7) {
+            dec.queue_slice(chunk);
+        }
+
+        for expected in ["first", "second"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(expected);
+        }
+
+        assert!(dec.try_next_packet::<TestPacket>().unwrap().is_none());
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    #[should_panic(expected = "encryption is already enabled")]
+    fn encryption_cannot_be_enabled_twice() {
+        let mut dec = PacketDecoder::new();
+        dec.enable_encryption(&CRYPT_KEY);
+        dec.enable_encryption(&CRYPT_KEY);
+    }
+
+    #[test]
+    fn inspector_drops_every_other_packet() {
+        let mut enc = PacketEncoder::new();
+        #[cfg(feature = "compression")]
+        enc.set_compression(Some(0));
+
+        for n in ["0", "1", "2", "3", "4"] {
+            enc.append_packet(&TestPacket::new(n)).unwrap();
+        }
+
+        let mut dec = PacketDecoder::new();
+        #[cfg(feature = "compression")]
+        dec.set_compression(true);
+
+        let mut seen = 0;
+        dec.set_inspector(move |id, data| {
+            // Inspectors see the plain packet data, starting with the ID.
+            assert_eq!(id, 42);
+            assert_eq!(data[0], 42);
+
+            seen += 1;
+
+            if seen % 2 == 0 {
+                InspectAction::Drop
+            } else {
+                InspectAction::Pass
+            }
+        });
+
+        dec.queue_bytes(enc.take());
+
+        for expected in ["0", "2", "4"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(expected);
+        }
+
+        assert!(dec.try_next_packet::<TestPacket>().unwrap().is_none());
+        assert!(dec.queued_bytes().is_empty());
+    }
+
+    #[test]
+    fn inspector_replaces_packets() {
+        let mut replacement = vec![];
+        TestPacket::new("replaced")
+            .encode_packet(&mut replacement)
+            .unwrap();
+
+        let mut enc = PacketEncoder::new();
+        let mut dec = PacketDecoder::new();
+
+        let encoder_replacement = replacement.clone();
+        enc.set_inspector(move |_, data| {
+            if data.windows(7).any(|w| w == b"dropped") {
+                InspectAction::Drop
+            } else {
+                InspectAction::Replace(encoder_replacement.clone())
+            }
+        });
+
+        enc.append_packet(&TestPacket::new("original")).unwrap();
+        enc.append_packet(&TestPacket::new("dropped")).unwrap();
+        enc.remove_inspector();
+        enc.append_packet(&TestPacket::new("untouched")).unwrap();
+
+        dec.queue_bytes(enc.take());
+
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("replaced");
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("untouched");
+        assert!(dec.try_next_packet::<TestPacket>().unwrap().is_none());
+
+        enc.append_packet(&TestPacket::new("original")).unwrap();
+        dec.queue_bytes(enc.take());
+
+        dec.set_inspector(move |_, _| InspectAction::Replace(replacement.clone()));
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("replaced");
+    }
+
+    #[test]
+    fn encoded_len_matches_written_bytes() {
+        use valence_nbt::{compound, List};
+
+        use crate::packets::s2c::play::{
+            ChunkDataAndUpdateLightEncode, SetTabListHeaderAndFooter, SpawnEntity,
+        };
+        use crate::text::Color;
+        use crate::{ByteAngle, LengthPrefixedArray};
+
+        let sky_light_arrays = [LengthPrefixedArray([0xff_u8; 2048]); 26];
+        let heightmaps = compound! {
+            "MOTION_BLOCKING" => List::Long(vec![123; 256]),
+        };
+
+        let chunk_data = ChunkDataAndUpdateLightEncode {
+            chunk_x: 123,
+            chunk_z: 456,
+            heightmaps: &heightmaps,
+            blocks_and_biomes: &[0x80_u8; 2000],
+            block_entities: &[],
+            trust_edges: false,
+            sky_light_mask: &[],
+            block_light_mask: &[],
+            empty_sky_light_mask: &[],
+            empty_block_light_mask: &[],
+            sky_light_arrays: &sky_light_arrays,
+            block_light_arrays: &[],
+        };
+
+        let tab_list = SetTabListHeaderAndFooter {
+            header: ("this".italic() + " is the " + "header".bold().color(Color::RED)).into(),
+            footer: ("this".italic() + " is the " + "footer".bold().color(Color::BLUE)).into(),
+        };
+
+        let spawn_entity = SpawnEntity {
+            entity_id: VarInt(1234),
+            object_uuid: Default::default(),
+            kind: VarInt(5),
+            position: [123.0, 456.0, 789.0],
+            pitch: ByteAngle(200),
+            yaw: ByteAngle(100),
+            head_yaw: ByteAngle(50),
+            data: VarInt(i32::MIN),
+            velocity: [12, 34, 56],
+        };
+
+        let mut buf = vec![];
+        tab_list.header.encode(&mut buf).unwrap();
+        assert_eq!(encoded_len(&tab_list.header).unwrap(), buf.len());
+
+        fn check<P: EncodePacket + ?Sized>(enc: &mut PacketEncoder, pkt: &P, exact: bool) {
+            let len = enc.encoded_packet_len(pkt).unwrap();
+
+            enc.clear();
+            enc.append_packet(pkt).unwrap();
+            let written = enc.take().len();
+
+            if exact {
+                assert_eq!(len, written);
+            } else {
+                assert!(len >= written, "{len} < {written}");
+            }
+        }
+
+        let mut enc = PacketEncoder::new();
+
+        check(&mut enc, &chunk_data, true);
+        check(&mut enc, &tab_list, true);
+        check(&mut enc, &spawn_entity, true);
+
+        #[cfg(feature = "compression")]
+        {
+            enc.set_compression(Some(256));
+
+            check(&mut enc, &chunk_data, false);
+            // Packets below the threshold are not compressed.
+            check(&mut enc, &tab_list, true);
+            check(&mut enc, &spawn_entity, true);
+        }
+    }
+
+    #[test]
+    fn prepended_packet_decodes_first() {
+        let mut enc = PacketEncoder::new();
+
+        #[cfg(feature = "compression")]
+        enc.set_compression(Some(0));
+
+        enc.append_packet(&TestPacket::new("first")).unwrap();
+        enc.append_packet(&TestPacket::new("second")).unwrap();
+        enc.prepend_packet(&TestPacket::new("priority")).unwrap();
+
+        let mut dec = PacketDecoder::new();
+
+        #[cfg(feature = "compression")]
+        dec.set_compression(true);
+
+        dec.queue_bytes(enc.take());
+
+        for expected in ["priority", "first", "second"] {
+            dec.try_next_packet::<TestPacket>()
+                .unwrap()
+                .unwrap()
+                .check(expected);
+        }
+
+        assert!(dec.try_next_packet::<TestPacket>().unwrap().is_none());
+    }
+
     #[test]
     fn collect_packets_into_vec() {
         let packets = vec![
@@ -618,4 +1429,189 @@ mod tests {
 
         assert_eq!(packets, res);
     }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn compression_framing_is_independent_of_algorithm() {
+        struct PassThrough;
+
+        impl Compressor for PassThrough {
+            fn compress(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<()> {
+                out.extend_from_slice(data);
+                Ok(())
+            }
+        }
+
+        impl Decompressor for PassThrough {
+            fn decompress(
+                &mut self,
+                data: &[u8],
+                data_len: usize,
+                out: &mut Vec<u8>,
+            ) -> Result<()> {
+                ensure!(data.len() == data_len, "unexpected data length");
+                out.extend_from_slice(data);
+                Ok(())
+            }
+        }
+
+        let pkt = TestPacket::new("pass through");
+
+        let mut uncompressed = vec![];
+        encode_packet(&mut uncompressed, &pkt).unwrap();
+
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(0));
+        enc.set_compressor(PassThrough);
+        enc.append_packet(&pkt).unwrap();
+
+        let bytes = enc.take();
+
+        // The packet length and data length prefixes are written as usual, but
+        // the data is unchanged.
+        let mut r = &bytes[..];
+        let packet_len = VarInt::decode(&mut r).unwrap().0 as usize;
+        let data_len = VarInt::decode(&mut r).unwrap().0 as usize;
+
+        assert_eq!(packet_len, r.len() + VarInt(data_len as i32).written_size());
+        assert_eq!(r, &uncompressed[VarInt(data_len as i32).written_size()..]);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.set_decompressor(PassThrough);
+        dec.queue_bytes(bytes);
+
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("pass through");
+    }
+
+    #[test]
+    fn skip_invalid_packet_and_continue() {
+        let mut enc = PacketEncoder::new();
+        // A frame with a valid length but truncated contents.
+        enc.append_bytes(&[3, 42, 1, 2]);
+        enc.append_packet(&TestPacket::new("valid")).unwrap();
+        let bytes = enc.take();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_slice(&bytes);
+
+        // Without skipping, the decoder is stuck on the corrupt packet.
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+
+        assert!(dec.try_next_packet_skip_invalid::<TestPacket>().is_err());
+        dec.try_next_packet_skip_invalid::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("valid");
+        assert!(dec
+            .try_next_packet_skip_invalid::<TestPacket>()
+            .unwrap()
+            .is_none());
+    }
+
+    #[cfg(feature = "compression")]
+    #[test]
+    fn decode_rejects_bad_decompressed_length() {
+        fn compressed_frame(declared_len: i32, data: &[u8]) -> Vec<u8> {
+            let mut compressed = vec![];
+            ZlibCompressor::default()
+                .compress(data, &mut compressed)
+                .unwrap();
+
+            let mut frame = vec![];
+            let packet_len = VarInt(declared_len).written_size() + compressed.len();
+            VarInt(packet_len as i32).encode(&mut frame).unwrap();
+            VarInt(declared_len).encode(&mut frame).unwrap();
+            frame.extend_from_slice(&compressed);
+            frame
+        }
+
+        let mut data = vec![];
+        TestPacket::new("data").encode_packet(&mut data).unwrap();
+
+        // Sanity check that the correct length decodes.
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.queue_slice(&compressed_frame(data.len() as i32, &data));
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("data");
+
+        for declared_len in [
+            data.len() as i32 - 1,
+            data.len() as i32 + 1,
+            MAX_PACKET_SIZE,
+            i32::MAX,
+        ] {
+            let mut dec = PacketDecoder::new();
+            dec.set_compression(true);
+            dec.queue_slice(&compressed_frame(declared_len, &data));
+
+            assert!(dec.try_next_packet::<TestPacket>().is_err());
+        }
+    }
+
+    #[test]
+    fn max_packet_len_rejects_long_packets() {
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&TestPacket::new("data")).unwrap();
+        let bytes = enc.take();
+
+        let mut dec = PacketDecoder::new();
+        dec.set_max_packet_len(16);
+        assert_eq!(dec.max_packet_len(), 16);
+
+        // Only the length prefix has arrived.
+        dec.queue_slice(&bytes[..3]);
+        assert!(dec.try_next_packet::<TestPacket>().is_err());
+
+        let mut dec = PacketDecoder::new();
+        dec.set_max_packet_len(i32::MAX);
+        assert_eq!(dec.max_packet_len(), MAX_PACKET_SIZE);
+
+        dec.queue_bytes(bytes);
+        dec.try_next_packet::<TestPacket>()
+            .unwrap()
+            .unwrap()
+            .check("data");
+    }
+
+    #[test]
+    fn decode_wrong_direction_fails() {
+        use crate::packets::c2s::play::KeepAliveC2s;
+        use crate::packets::{C2sPlayPacket, S2cPlayPacket};
+
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&KeepAliveC2s { id: 123 }).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.set_expected_state(PacketDirection::S2c, ConnectionState::Play);
+        dec.queue_bytes(enc.take());
+
+        let err = dec
+            .try_next_packet_checked::<C2sPlayPacket>()
+            .unwrap_err()
+            .to_string();
+
+        assert!(err.contains("C2sPlayPacket"), "{err}");
+        assert!(err.contains("C2s Play"), "{err}");
+        assert!(err.contains("expects S2c Play packets"), "{err}");
+
+        dec.set_expected_state(PacketDirection::C2s, ConnectionState::Play);
+
+        assert!(matches!(
+            dec.try_next_packet_checked::<C2sPlayPacket>().unwrap(),
+            Some(C2sPlayPacket::KeepAliveC2s(KeepAliveC2s { id: 123 }))
+        ));
+
+        // Individual packets are checked too.
+        dec.set_expected_state(PacketDirection::C2s, ConnectionState::Login);
+        assert!(dec.try_next_packet_checked::<KeepAliveC2s>().is_err());
+        assert!(dec.try_next_packet_checked::<S2cPlayPacket>().is_err());
+    }
 }
@@ -407,16 +407,27 @@ impl<T: Encode> Encode for Vec<T> {
     }
 }
 
+/// Returns the capacity to preallocate for a collection with a declared length
+/// of `len` and `r` left to decode.
+///
+/// The length is attacker controlled, so we don't allocate more memory than
+/// what would roughly fit in a single packet. Every element is expected to take
+/// up at least one byte, so the capacity is also limited by the number of bytes
+/// remaining. This prevents nested collections from preallocating memory for
+/// elements which don't exist.
+fn cautious_capacity<T>(len: usize, r: &[u8]) -> usize {
+    (MAX_PACKET_SIZE as usize / mem::size_of::<T>().max(1))
+        .min(r.len())
+        .min(len)
+}
+
 impl<'a, T: Decode<'a>> Decode<'a> for Vec<T> {
     fn decode(r: &mut &'a [u8]) -> Result<Self> {
         let len = VarInt::decode(r)?.0;
         ensure!(len >= 0, "attempt to decode Vec with negative length");
         let len = len as usize;
 
-        // Don't allocate more memory than what would roughly fit in a single packet in
-        // case we get a malicious array length.
-        let cap = (MAX_PACKET_SIZE as usize / mem::size_of::<T>().max(1)).min(len);
-        let mut vec = Vec::with_capacity(cap);
+        let mut vec = Vec::with_capacity(cautious_capacity::<T>(len, r));
 
         for _ in 0..len {
             vec.push(T::decode(r)?);
@@ -461,10 +472,8 @@ where
         ensure!(len >= 0, "attempt to decode hash set with negative length");
         let len = len as usize;
 
-        // Don't allocate more memory than what would roughly fit in a single packet in
-        // case we get a malicious array length.
-        let cap = (MAX_PACKET_SIZE as usize / mem::size_of::<T>().max(1)).min(len);
-        let mut set = HashSet::with_capacity_and_hasher(cap, S::default());
+        let mut set =
+            HashSet::with_capacity_and_hasher(cautious_capacity::<T>(len, r), S::default());
 
         for _ in 0..len {
             ensure!(
@@ -630,3 +639,48 @@ impl Decode<'_> for Compound {
         Ok(valence_nbt::from_binary_slice(r)?.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LengthPrefixedArray;
+
+    /// Encodes `len` as a length prefix followed by `data`.
+    fn with_len(len: i32, data: &[u8]) -> Vec<u8> {
+        let mut buf = vec![];
+        VarInt(len).encode(&mut buf).unwrap();
+        buf.extend_from_slice(data);
+        buf
+    }
+
+    #[test]
+    fn absurd_lengths_do_not_preallocate() {
+        assert_eq!(cautious_capacity::<u64>(i32::MAX as usize, &[1, 2, 3]), 3);
+        assert_eq!(cautious_capacity::<u64>(2, &[1, 2, 3]), 2);
+        assert_eq!(
+            cautious_capacity::<u8>(i32::MAX as usize, &vec![0; MAX_PACKET_SIZE as usize * 2]),
+            MAX_PACKET_SIZE as usize
+        );
+    }
+
+    #[test]
+    fn absurd_lengths_fail_to_decode() {
+        let buf = with_len(i32::MAX, &[1, 2, 3]);
+
+        assert!(<Vec<u64>>::decode(&mut buf.as_slice()).is_err());
+        assert!(<HashSet<u64>>::decode(&mut buf.as_slice()).is_err());
+        assert!(<BTreeSet<u64>>::decode(&mut buf.as_slice()).is_err());
+        assert!(<String>::decode(&mut buf.as_slice()).is_err());
+        assert!(<&[u8]>::decode(&mut buf.as_slice()).is_err());
+        assert!(<LengthPrefixedArray<u8, 3>>::decode(&mut buf.as_slice()).is_err());
+
+        // Every inner vec claims a huge length.
+        let mut nested = with_len(1000, &[]);
+        for _ in 0..1000 {
+            VarInt(i32::MAX).encode(&mut nested).unwrap();
+        }
+
+        assert!(<Vec<Vec<u64>>>::decode(&mut nested.as_slice()).is_err());
+        assert!(<Vec<Vec<String>>>::decode(&mut nested.as_slice()).is_err());
+    }
+}
@@ -79,6 +79,9 @@ pub use byte_angle::ByteAngle;
 pub use codec::*;
 pub use ident::Ident;
 pub use item::{ItemKind, ItemStack};
+pub use no_compress::NoCompress;
+pub use packet_limits::PacketLimits;
+pub use precoded_packet::PrecodedPacket;
 pub use raw_bytes::RawBytes;
 pub use text::{Text, TextFormat};
 pub use username::Username;
@@ -106,7 +109,11 @@ pub mod entity_meta;
 pub mod ident;
 mod impls;
 mod item;
+mod no_compress;
+pub mod packet_id;
+mod packet_limits;
 pub mod packets;
+mod precoded_packet;
 mod raw_bytes;
 pub mod text;
 pub mod translation_key;
@@ -288,6 +295,10 @@ pub trait EncodePacket: fmt::Debug {
     /// negative value indicates that the packet ID is not statically known.
     const PACKET_ID: i32 = -1;
 
+    /// If the packet may be compressed when compression is enabled and the
+    /// packet is above the compression threshold. See [`NoCompress`].
+    const COMPRESS: bool = true;
+
     /// Like [`Encode::encode`], but a leading [`VarInt`] packet ID must be
     /// written first.
     fn encode_packet(&self, w: impl Write) -> Result<()>;
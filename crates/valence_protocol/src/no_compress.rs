@@ -0,0 +1,64 @@
+use std::io::Write;
+
+use crate::{EncodePacket, Result};
+
+/// Wraps a packet so that it is never compressed, even if it is above the
+/// compression threshold.
+///
+/// This is useful for packets containing data which is already compressed,
+/// such as images in map data, where compressing the packet again would waste
+/// CPU time for little benefit.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct NoCompress<P>(pub P);
+
+impl<P: EncodePacket> EncodePacket for NoCompress<P> {
+    const PACKET_ID: i32 = P::PACKET_ID;
+    const COMPRESS: bool = false;
+
+    fn encode_packet(&self, w: impl Write) -> Result<()> {
+        self.0.encode_packet(w)
+    }
+}
+
+#[cfg(all(test, feature = "compression"))]
+mod tests {
+    use super::*;
+    use crate::packets::s2c::play::PluginMessageS2c;
+    use crate::packets::S2cPlayPacket;
+    use crate::{Decode, Ident, PacketDecoder, PacketEncoder, RawBytes, VarInt};
+
+    #[test]
+    fn no_compress_packet_is_sent_uncompressed() {
+        let data = [0xab; 1000];
+        let pkt = PluginMessageS2c {
+            channel: Ident::new("valence:test").unwrap(),
+            data: RawBytes(&data),
+        };
+
+        let mut enc = PacketEncoder::new();
+        enc.set_compression(Some(256));
+        enc.append_packet(&NoCompress(pkt)).unwrap();
+        let uncompressed = enc.take();
+
+        enc.append_packet(&pkt).unwrap();
+        let compressed = enc.take();
+
+        assert!(compressed.len() < uncompressed.len());
+
+        let mut r = &uncompressed[..];
+        let packet_len = VarInt::decode(&mut r).unwrap().0;
+        assert_eq!(packet_len as usize, r.len());
+        // A data length of zero indicates the packet is not compressed.
+        assert_eq!(VarInt::decode(&mut r).unwrap().0, 0);
+
+        let mut dec = PacketDecoder::new();
+        dec.set_compression(true);
+        dec.queue_slice(&uncompressed);
+
+        let Some(S2cPlayPacket::PluginMessageS2c(decoded)) = dec.try_next_packet().unwrap() else {
+            panic!("failed to decode packet");
+        };
+        assert_eq!(decoded.channel, pkt.channel);
+        assert_eq!(decoded.data.0, &data);
+    }
+}
@@ -0,0 +1,83 @@
+//! Packet IDs for each supported protocol version.
+//!
+//! The IDs are generated at build time from the packet data extracted from
+//! the vanilla game. Use [`packet_id_for`] when the ID of a packet depends on
+//! the protocol version of the connection, rather than hardcoding the IDs of
+//! each version at the call site.
+
+use crate::packets::{ConnectionState, PacketDirection};
+
+include!(concat!(env!("OUT_DIR"), "/packet_id.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::c2s::play::ConfirmTeleport;
+    use crate::packets::s2c::play::KeepAliveS2c;
+    use crate::{DecodePacket, EncodePacket, PROTOCOL_VERSION};
+
+    #[test]
+    fn current_version_matches_packet_definitions() {
+        assert!(PACKET_ID_VERSIONS.contains(&PROTOCOL_VERSION));
+
+        assert_eq!(
+            packet_id_for(
+                PROTOCOL_VERSION,
+                ConnectionState::Play,
+                PacketDirection::S2c,
+                "KeepAliveS2CPacket"
+            ),
+            Some(<KeepAliveS2c as EncodePacket>::PACKET_ID)
+        );
+
+        assert_eq!(
+            packet_id_for(
+                PROTOCOL_VERSION,
+                ConnectionState::Play,
+                PacketDirection::C2s,
+                "TeleportConfirmC2SPacket"
+            ),
+            Some(<ConfirmTeleport as DecodePacket>::PACKET_ID)
+        );
+    }
+
+    #[test]
+    fn ids_differ_by_version_and_direction() {
+        let keep_alive = |version, direction| {
+            packet_id_for(
+                version,
+                ConnectionState::Play,
+                direction,
+                "KeepAliveS2CPacket",
+            )
+        };
+
+        assert_eq!(keep_alive(760, PacketDirection::S2c), Some(0x20));
+        assert_eq!(keep_alive(761, PacketDirection::S2c), Some(0x1f));
+        // Only extracted versions are known.
+        assert_eq!(keep_alive(759, PacketDirection::S2c), None);
+        assert_eq!(keep_alive(761, PacketDirection::C2s), None);
+
+        let c2s_play = |version, name| {
+            packet_id_for(version, ConnectionState::Play, PacketDirection::C2s, name)
+        };
+
+        assert_eq!(c2s_play(760, "KeepAliveC2SPacket"), Some(0x12));
+        assert_eq!(c2s_play(761, "KeepAliveC2SPacket"), Some(0x11));
+        // Chat previews were removed and player sessions added in 761.
+        assert_eq!(c2s_play(760, "RequestChatPreviewC2SPacket"), Some(0x06));
+        assert_eq!(c2s_play(761, "RequestChatPreviewC2SPacket"), None);
+        assert_eq!(c2s_play(760, "PlayerSessionC2SPacket"), None);
+        assert_eq!(c2s_play(761, "PlayerSessionC2SPacket"), Some(0x20));
+
+        assert_eq!(
+            packet_id_for(
+                761,
+                ConnectionState::Handshaking,
+                PacketDirection::C2s,
+                "HandshakeC2SPacket"
+            ),
+            Some(0)
+        );
+    }
+}
@@ -0,0 +1,154 @@
+use anyhow::ensure;
+
+use crate::packets::C2sPlayPacket;
+use crate::Result;
+
+/// Limits on the contents of serverbound packets which are stricter than the
+/// maximum packet size. The default limits match those of the vanilla server.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PacketLimits {
+    /// The maximum number of characters in a chat message.
+    pub chat_message_chars: usize,
+    /// The maximum number of characters in a chat command.
+    pub chat_command_chars: usize,
+    /// The maximum number of characters in a command suggestions request.
+    pub command_suggestion_chars: usize,
+    /// The maximum number of pages in an edited book.
+    pub book_pages: usize,
+    /// The maximum number of characters on one page of an edited book.
+    pub book_page_chars: usize,
+    /// The maximum number of characters in the title of a signed book.
+    pub book_title_chars: usize,
+    /// The maximum number of characters on one line of a sign.
+    pub sign_line_chars: usize,
+}
+
+impl Default for PacketLimits {
+    fn default() -> Self {
+        Self {
+            chat_message_chars: 256,
+            chat_command_chars: 256,
+            command_suggestion_chars: 32500,
+            book_pages: 200,
+            book_page_chars: 8192,
+            book_title_chars: 128,
+            sign_line_chars: 384,
+        }
+    }
+}
+
+impl PacketLimits {
+    /// Returns an error if the packet exceeds any of the limits.
+    pub fn check(&self, pkt: &C2sPlayPacket) -> Result<()> {
+        fn check_chars(s: &str, max: usize, what: &str) -> Result<()> {
+            let count = s.chars().count();
+
+            ensure!(
+                count <= max,
+                "{what} of {count} characters exceeds the limit of {max}"
+            );
+
+            Ok(())
+        }
+
+        match pkt {
+            C2sPlayPacket::ChatMessage(p) => {
+                check_chars(p.message, self.chat_message_chars, "chat message")
+            }
+            C2sPlayPacket::ChatCommand(p) => {
+                check_chars(p.command, self.chat_command_chars, "chat command")
+            }
+            C2sPlayPacket::CommandSuggestionsRequest(p) => check_chars(
+                p.text,
+                self.command_suggestion_chars,
+                "command suggestions request",
+            ),
+            C2sPlayPacket::EditBook(p) => {
+                ensure!(
+                    p.entries.len() <= self.book_pages,
+                    "book of {} pages exceeds the limit of {}",
+                    p.entries.len(),
+                    self.book_pages
+                );
+
+                for page in &p.entries {
+                    check_chars(page, self.book_page_chars, "book page")?;
+                }
+
+                if let Some(title) = p.title {
+                    check_chars(title, self.book_title_chars, "book title")?;
+                }
+
+                Ok(())
+            }
+            C2sPlayPacket::UpdateSign(p) => {
+                for line in p.lines {
+                    check_chars(line, self.sign_line_chars, "sign line")?;
+                }
+
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::c2s::play::{ChatMessage, EditBook};
+    use crate::VarInt;
+
+    #[test]
+    fn over_length_chat_message_rejected() {
+        let limits = PacketLimits::default();
+
+        let msg = |message| {
+            C2sPlayPacket::ChatMessage(ChatMessage {
+                message,
+                timestamp: 0,
+                salt: 0,
+                signature: None,
+                message_count: VarInt(0),
+                acknowledgement: &[0; 3],
+            })
+        };
+
+        let ok = "a".repeat(256);
+        assert!(limits.check(&msg(&ok)).is_ok());
+
+        let too_long = "a".repeat(257);
+        let err = limits.check(&msg(&too_long)).unwrap_err();
+        assert!(err.to_string().contains("chat message"));
+    }
+
+    #[test]
+    fn over_large_book_rejected() {
+        let limits = PacketLimits::default();
+
+        let book = |entries, title| {
+            C2sPlayPacket::EditBook(EditBook {
+                slot: VarInt(0),
+                entries,
+                title,
+            })
+        };
+
+        assert!(limits
+            .check(&book(vec!["page"; 200], Some("title")))
+            .is_ok());
+
+        let err = limits.check(&book(vec!["page"; 201], None)).unwrap_err();
+        assert!(err.to_string().contains("pages"));
+
+        let long_page = "a".repeat(8193);
+        let err = limits.check(&book(vec![&long_page], None)).unwrap_err();
+        assert!(err.to_string().contains("book page"));
+
+        let long_title = "a".repeat(129);
+        let err = limits
+            .check(&book(vec!["page"], Some(&long_title)))
+            .unwrap_err();
+        assert!(err.to_string().contains("book title"));
+    }
+}
@@ -4,6 +4,7 @@
 //! If the packets as defined do not meet your needs, consider using the tools
 //! in this library to redefine the packets yourself.
 
+use anyhow::{bail, ensure};
 pub use c2s::handshake::C2sHandshakePacket;
 pub use c2s::login::C2sLoginPacket;
 pub use c2s::play::C2sPlayPacket;
@@ -12,11 +13,85 @@ pub use s2c::login::S2cLoginPacket;
 pub use s2c::play::S2cPlayPacket;
 pub use s2c::status::S2cStatusPacket;
 
+use crate::packets::s2c::login::DisconnectLogin;
+use crate::packets::s2c::play::DisconnectPlay;
+use crate::text::Text;
+use crate::types::HandshakeNextState;
+use crate::{DecodePacket, Result};
+
+/// The state of a connection, which determines the set of packets that can be
+/// sent and received.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ConnectionState {
+    Handshaking,
+    Status,
+    Login,
+    Play,
+}
+
+/// The direction a packet is sent in.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum PacketDirection {
+    /// Sent from the client to the server.
+    C2s,
+    /// Sent from the server to the client.
+    S2c,
+}
+
+/// Implemented for packets (and enums of packets) which belong to a specific
+/// connection state and direction.
+///
+/// This is used by [`PacketDecoder::try_next_packet_checked`] to catch
+/// packets being decoded in the wrong state or direction.
+///
+/// [`PacketDecoder::try_next_packet_checked`]: crate::PacketDecoder::try_next_packet_checked
+pub trait PacketState {
+    /// The connection state the packet is sent in.
+    const STATE: ConnectionState;
+    /// The direction the packet is sent in.
+    const DIRECTION: PacketDirection;
+}
+
+/// The handshake determines the state the connection switches to.
+impl From<HandshakeNextState> for ConnectionState {
+    fn from(next_state: HandshakeNextState) -> Self {
+        match next_state {
+            HandshakeNextState::Status => ConnectionState::Status,
+            HandshakeNextState::Login => ConnectionState::Login,
+        }
+    }
+}
+
+/// Decodes the reason from a server-to-client disconnect packet sent in the
+/// given connection state.
+///
+/// `bytes` is the packet data starting with the packet ID (without the length
+/// prefix). Disconnect packets only exist in the login and play states, so an
+/// error is returned for other states.
+///
+/// This is mostly useful for proxies which need to report why a connection
+/// was closed.
+pub fn parse_disconnect_reason(state: ConnectionState, mut bytes: &[u8]) -> Result<Text> {
+    let reason = match state {
+        ConnectionState::Login => DisconnectLogin::decode_packet(&mut bytes)?.reason,
+        ConnectionState::Play => DisconnectPlay::decode_packet(&mut bytes)?.reason,
+        state => bail!("there is no disconnect packet in the {state:?} state"),
+    };
+
+    ensure!(
+        bytes.is_empty(),
+        "disconnect packet contents were not read completely ({} bytes remain)",
+        bytes.len()
+    );
+
+    Ok(reason.into_owned())
+}
+
 /// Defines an enum of packets.
 macro_rules! packet_enum {
     (
         $(#[$attrs:meta])*
-        $enum_name:ident<$enum_life:lifetime> {
+        $enum_name:ident<$enum_life:lifetime>($direction:ident, $state:ident) {
             $($packet:ident $(<$life:lifetime>)?),* $(,)?
         }
     ) => {
@@ -76,11 +151,23 @@ macro_rules! packet_enum {
                 }
             }
         }
+
+        impl<$enum_life> crate::packets::PacketState for $enum_name<$enum_life> {
+            const STATE: crate::packets::ConnectionState = crate::packets::ConnectionState::$state;
+            const DIRECTION: crate::packets::PacketDirection = crate::packets::PacketDirection::$direction;
+        }
+
+        $(
+            impl$(<$life>)? crate::packets::PacketState for $packet $(<$life>)? {
+                const STATE: crate::packets::ConnectionState = crate::packets::ConnectionState::$state;
+                const DIRECTION: crate::packets::PacketDirection = crate::packets::PacketDirection::$direction;
+            }
+        )*
     };
     // No lifetime on the enum in this case.
     (
         $(#[$attrs:meta])*
-        $enum_name:ident {
+        $enum_name:ident($direction:ident, $state:ident) {
             $($packet:ident),* $(,)?
         }
     ) => {
@@ -140,8 +227,67 @@ macro_rules! packet_enum {
                 }
             }
         }
+
+        impl crate::packets::PacketState for $enum_name {
+            const STATE: crate::packets::ConnectionState = crate::packets::ConnectionState::$state;
+            const DIRECTION: crate::packets::PacketDirection = crate::packets::PacketDirection::$direction;
+        }
+
+        $(
+            impl crate::packets::PacketState for $packet {
+                const STATE: crate::packets::ConnectionState = crate::packets::ConnectionState::$state;
+                const DIRECTION: crate::packets::PacketDirection = crate::packets::PacketDirection::$direction;
+            }
+        )*
     }
 }
 
 pub mod c2s;
 pub mod s2c;
+
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+
+    use super::*;
+    use crate::text::{Color, TextFormat};
+    use crate::EncodePacket;
+
+    #[test]
+    fn disconnect_reason_login() {
+        let reason = "Server is full".color(Color::RED);
+
+        let mut buf = vec![];
+        DisconnectLogin {
+            reason: Cow::Borrowed(&reason),
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        assert_eq!(
+            parse_disconnect_reason(ConnectionState::Login, &buf).unwrap(),
+            reason
+        );
+    }
+
+    #[test]
+    fn disconnect_reason_play() {
+        let reason = "Kicked".bold() + " for spamming";
+
+        let mut buf = vec![];
+        DisconnectPlay {
+            reason: Cow::Borrowed(&reason),
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        assert_eq!(
+            parse_disconnect_reason(ConnectionState::Play, &buf).unwrap(),
+            reason
+        );
+
+        // The play disconnect packet has a different ID than the login one.
+        assert!(parse_disconnect_reason(ConnectionState::Login, &buf).is_err());
+        assert!(parse_disconnect_reason(ConnectionState::Status, &buf).is_err());
+    }
+}
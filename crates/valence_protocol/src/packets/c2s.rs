@@ -39,7 +39,7 @@ pub mod handshake {
 
     packet_enum! {
         #[derive(Clone)]
-        C2sHandshakePacket<'a> {
+        C2sHandshakePacket<'a>(C2s, Handshaking) {
             Handshake<'a>
         }
     }
@@ -60,7 +60,7 @@ pub mod status {
 
     packet_enum! {
         #[derive(Clone)]
-        C2sStatusPacket {
+        C2sStatusPacket(C2s, Status) {
             StatusRequest,
             PingRequest,
         }
@@ -70,13 +70,32 @@ pub mod status {
 pub mod login {
     use super::*;
 
-    #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
+    #[derive(Clone, Debug, Encode, EncodePacket, DecodePacket)]
     #[packet_id = 0x00]
     pub struct LoginStart<'a> {
         pub username: Username<&'a str>,
         pub profile_id: Option<Uuid>,
     }
 
+    /// Older clients (and some proxies) send only the username. In that case
+    /// the profile ID is decoded as `None`.
+    impl<'a> Decode<'a> for LoginStart<'a> {
+        fn decode(r: &mut &'a [u8]) -> crate::Result<Self> {
+            let username = Username::decode(r)?;
+
+            let profile_id = if r.is_empty() {
+                None
+            } else {
+                Option::<Uuid>::decode(r)?
+            };
+
+            Ok(Self {
+                username,
+                profile_id,
+            })
+        }
+    }
+
     #[derive(Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x01]
     pub struct EncryptionResponse<'a> {
@@ -93,7 +112,7 @@ pub mod login {
 
     packet_enum! {
         #[derive(Clone)]
-        C2sLoginPacket<'a> {
+        C2sLoginPacket<'a>(C2s, Login) {
             LoginStart<'a>,
             EncryptionResponse<'a>,
             LoginPluginResponse<'a>,
@@ -514,7 +533,7 @@ pub mod play {
 
     packet_enum! {
         #[derive(Clone)]
-        C2sPlayPacket<'a> {
+        C2sPlayPacket<'a>(C2s, Play) {
             ConfirmTeleport,
             QueryBlockEntityTag,
             ChangeDifficulty,
@@ -569,3 +588,115 @@ pub mod play {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::handshake::*;
+    use super::login::*;
+    use super::status::*;
+    use super::*;
+    use crate::packets::ConnectionState;
+
+    #[test]
+    fn handshake_next_state() {
+        for (next_state, expected) in [
+            (HandshakeNextState::Status, ConnectionState::Status),
+            (HandshakeNextState::Login, ConnectionState::Login),
+        ] {
+            let mut buf = vec![];
+            Handshake {
+                protocol_version: VarInt(761),
+                server_address: "localhost",
+                server_port: 25565,
+                next_state,
+            }
+            .encode_packet(&mut buf)
+            .unwrap();
+
+            let mut r = buf.as_slice();
+            let pkt = Handshake::decode_packet(&mut r).unwrap();
+
+            assert!(r.is_empty());
+            assert_eq!(pkt.protocol_version, VarInt(761));
+            assert_eq!(pkt.server_address, "localhost");
+            assert_eq!(pkt.server_port, 25565);
+            assert_eq!(pkt.next_state, next_state);
+            assert_eq!(ConnectionState::from(pkt.next_state), expected);
+        }
+    }
+
+    #[test]
+    fn login_start_name_only() {
+        let mut buf = vec![];
+        VarInt(<LoginStart as EncodePacket>::PACKET_ID)
+            .encode(&mut buf)
+            .unwrap();
+        Username::new("jeb_").unwrap().encode(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = LoginStart::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.username.as_str(), "jeb_");
+        assert_eq!(pkt.profile_id, None);
+    }
+
+    #[test]
+    fn login_start_with_profile_id() {
+        let uuid = Uuid::from_u128(0xdead_beef);
+
+        let mut buf = vec![];
+        LoginStart {
+            username: Username::new("jeb_").unwrap(),
+            profile_id: Some(uuid),
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = LoginStart::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.username.as_str(), "jeb_");
+        assert_eq!(pkt.profile_id, Some(uuid));
+    }
+
+    #[test]
+    fn ping_request_round_trip() {
+        let payload = u64::MAX - 1;
+
+        let mut buf = vec![];
+        StatusRequest.encode_packet(&mut buf).unwrap();
+        PingRequest { payload }.encode_packet(&mut buf).unwrap();
+
+        let mut r = buf.as_slice();
+        StatusRequest::decode_packet(&mut r).unwrap();
+        let pkt = PingRequest::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.payload, payload);
+    }
+
+    #[test]
+    fn encryption_response_round_trip() {
+        // The shared secret and verify token are encrypted with the server's
+        // 1024-bit RSA key, making both 128 bytes long.
+        let shared_secret = [0x42; 128];
+        let verify_token: Vec<u8> = (0..128).collect();
+
+        let mut buf = vec![];
+        EncryptionResponse {
+            shared_secret: &shared_secret,
+            verify_token: &verify_token,
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = EncryptionResponse::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.shared_secret, shared_secret);
+        assert_eq!(pkt.verify_token, verify_token);
+    }
+}
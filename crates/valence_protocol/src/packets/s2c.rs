@@ -52,7 +52,7 @@ pub mod status {
 
     packet_enum! {
         #[derive(Clone)]
-        S2cStatusPacket<'a> {
+        S2cStatusPacket<'a>(S2c, Status) {
             StatusResponse<'a>,
             PingResponse,
         }
@@ -84,6 +84,24 @@ pub mod login {
         pub properties: Cow<'a, [Property]>,
     }
 
+    impl<'a> LoginSuccess<'a> {
+        /// Constructs a login success packet without any profile properties.
+        pub fn new(uuid: Uuid, username: Username<&'a str>) -> Self {
+            Self {
+                uuid,
+                username,
+                properties: Cow::Borrowed(&[]),
+            }
+        }
+
+        /// Sets the profile properties (such as skin textures) of this packet.
+        #[must_use]
+        pub fn with_properties(mut self, properties: impl Into<Cow<'a, [Property]>>) -> Self {
+            self.properties = properties.into();
+            self
+        }
+    }
+
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x03]
     pub struct SetCompression {
@@ -100,7 +118,7 @@ pub mod login {
 
     packet_enum! {
         #[derive(Clone)]
-        S2cLoginPacket<'a> {
+        S2cLoginPacket<'a>(S2c, Login) {
             DisconnectLogin<'a>,
             EncryptionRequest<'a>,
             LoginSuccess<'a>,
@@ -124,6 +142,7 @@ pub mod play {
     pub use update_recipe_book::UpdateRecipeBook;
 
     use super::*;
+    use crate::entity_meta::Facing;
     use crate::packets::s2c::declare_recipes::DeclaredRecipe;
     use crate::packets::s2c::update_teams::UpdateTeamsMode;
 
@@ -142,6 +161,125 @@ pub mod play {
         pub velocity: [i16; 3],
     }
 
+    impl SpawnEntity {
+        /// Constructs a spawn packet for an entity with the given look
+        /// `(pitch, yaw)` and velocity. The velocity is given in blocks per
+        /// tick and is converted to the protocol's units of 1/8000 of a block
+        /// per tick, saturating at the limits of an `i16`.
+        pub fn with_velocity(
+            entity_id: VarInt,
+            object_uuid: Uuid,
+            kind: VarInt,
+            position: [f64; 3],
+            (pitch, yaw): (ByteAngle, ByteAngle),
+            velocity_blocks_per_tick: [f32; 3],
+        ) -> Self {
+            Self {
+                entity_id,
+                object_uuid,
+                kind,
+                position,
+                pitch,
+                yaw,
+                head_yaw: yaw,
+                data: VarInt(0),
+                // The saturating casts to i16 are desirable.
+                velocity: velocity_blocks_per_tick.map(|v| (v * 8000.0) as i16),
+            }
+        }
+
+        /// Constructs a spawn packet for a painting hanging on a wall in the
+        /// direction of `facing`.
+        ///
+        /// The variant of the painting is not part of the spawn packet. It is
+        /// sent afterwards in the entity metadata as a
+        /// [`PaintingKind`](crate::entity_meta::PaintingKind).
+        ///
+        /// # Panics
+        ///
+        /// Panics if `facing` is [`Facing::Up`] or [`Facing::Down`], since
+        /// paintings can only hang on walls.
+        pub fn painting(
+            entity_id: VarInt,
+            object_uuid: Uuid,
+            position: [f64; 3],
+            facing: Facing,
+        ) -> Self {
+            assert!(
+                !matches!(facing, Facing::Up | Facing::Down),
+                "paintings cannot face {facing:?}"
+            );
+
+            Self::hanging(entity_id, object_uuid, PAINTING_KIND, position, facing)
+        }
+
+        /// Constructs a spawn packet for an item frame attached to a block in
+        /// the direction of `facing`. Item frames lying on the ground face
+        /// [`Facing::Up`] and item frames on a ceiling face [`Facing::Down`].
+        ///
+        /// The item in the frame and its rotation are sent afterwards in the
+        /// entity metadata.
+        pub fn item_frame(
+            entity_id: VarInt,
+            object_uuid: Uuid,
+            position: [f64; 3],
+            facing: Facing,
+        ) -> Self {
+            Self::hanging(entity_id, object_uuid, ITEM_FRAME_KIND, position, facing)
+        }
+
+        /// Constructs a spawn packet for a leash knot on a fence.
+        pub fn leash_knot(entity_id: VarInt, object_uuid: Uuid, position: [f64; 3]) -> Self {
+            Self {
+                entity_id,
+                object_uuid,
+                kind: VarInt(LEASH_KNOT_KIND),
+                position,
+                pitch: ByteAngle(0),
+                yaw: ByteAngle(0),
+                head_yaw: ByteAngle(0),
+                data: VarInt(0),
+                velocity: [0; 3],
+            }
+        }
+
+        /// Hanging entities use the data field for the direction they face.
+        /// The look is set to match, although the client only uses the data.
+        fn hanging(
+            entity_id: VarInt,
+            object_uuid: Uuid,
+            kind: i32,
+            position: [f64; 3],
+            facing: Facing,
+        ) -> Self {
+            let (pitch, yaw) = match facing {
+                Facing::Down => (90.0, 0.0),
+                Facing::Up => (-90.0, 0.0),
+                Facing::North => (0.0, 180.0),
+                Facing::South => (0.0, 0.0),
+                Facing::West => (0.0, 90.0),
+                Facing::East => (0.0, 270.0),
+            };
+
+            Self {
+                entity_id,
+                object_uuid,
+                kind: VarInt(kind),
+                position,
+                pitch: ByteAngle::from_degrees(pitch),
+                yaw: ByteAngle::from_degrees(yaw),
+                head_yaw: ByteAngle::from_degrees(yaw),
+                data: VarInt(facing as i32),
+                velocity: [0; 3],
+            }
+        }
+    }
+
+    // Protocol IDs of the entity kinds with their own spawn constructors.
+    const ITEM_FRAME_KIND: i32 = 46;
+    const LEASH_KNOT_KIND: i32 = 48;
+    const PAINTING_KIND: i32 = 64;
+
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x01]
     pub struct SpawnExperienceOrb {
@@ -913,8 +1051,8 @@ pub mod play {
 
     #[derive(Copy, Clone, Debug, Encode, EncodePacket, Decode, DecodePacket)]
     #[packet_id = 0x5d]
-    pub struct EntitySoundEffect {
-        pub id: VarInt,
+    pub struct EntitySoundEffect<'a> {
+        pub id: SoundId<'a>,
         pub category: SoundCategory,
         pub entity_id: VarInt,
         pub volume: f32,
@@ -1011,7 +1149,7 @@ pub mod play {
 
     packet_enum! {
         #[derive(Clone)]
-        S2cPlayPacket<'a> {
+        S2cPlayPacket<'a>(S2c, Play) {
             SpawnEntity,
             SpawnExperienceOrb,
             SpawnPlayer,
@@ -1105,7 +1243,7 @@ pub mod play {
             UpdateTime,
             SetTitleText<'a>,
             SetTitleAnimationTimes,
-            EntitySoundEffect,
+            EntitySoundEffect<'a>,
             SoundEffect<'a>,
             StopSound<'a>,
             SystemChatMessage<'a>,
@@ -1122,3 +1260,196 @@ pub mod play {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::login::*;
+    use super::play::*;
+    use super::status::*;
+    use super::*;
+    use crate::entity_meta::Facing;
+    use crate::text::{Color, TextFormat};
+    use crate::{translation_key, PacketDecoder, PacketEncoder};
+
+    #[test]
+    fn encryption_request_round_trip() {
+        // A DER encoded 1024-bit RSA public key is 162 bytes long.
+        let public_key: Vec<u8> = (0..162).map(|i| i as u8).collect();
+        let verify_token = [0xde, 0xad, 0xbe, 0xef];
+
+        let mut buf = vec![];
+        EncryptionRequest {
+            server_id: "",
+            public_key: &public_key,
+            verify_token: &verify_token,
+        }
+        .encode_packet(&mut buf)
+        .unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = EncryptionRequest::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.server_id, "");
+        assert_eq!(pkt.public_key, public_key);
+        assert_eq!(pkt.verify_token, verify_token);
+    }
+
+    #[test]
+    fn painting_spawn_encoding() {
+        let uuid = Uuid::from_u128(0xabcd);
+
+        let mut buf = vec![];
+        SpawnEntity::painting(VarInt(5), uuid, [1.0, 64.0, -3.0], Facing::West)
+            .encode_packet(&mut buf)
+            .unwrap();
+
+        let mut r = buf.as_slice();
+        let pkt = SpawnEntity::decode_packet(&mut r).unwrap();
+
+        assert!(r.is_empty());
+        assert_eq!(pkt.entity_id.0, 5);
+        assert_eq!(pkt.object_uuid, uuid);
+        assert_eq!(pkt.kind.0, 64);
+        assert_eq!(pkt.position, [1.0, 64.0, -3.0]);
+        assert_eq!(pkt.data.0, 4);
+        assert_eq!(pkt.yaw, ByteAngle::from_degrees(90.0));
+        assert_eq!(pkt.velocity, [0; 3]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn painting_cannot_face_up() {
+        SpawnEntity::painting(VarInt(5), Uuid::nil(), [0.0; 3], Facing::Up);
+    }
+
+    #[test]
+    fn item_frame_spawn_encoding() {
+        for (facing, data, pitch) in [
+            (Facing::Down, 0, 90.0),
+            (Facing::Up, 1, -90.0),
+            (Facing::South, 3, 0.0),
+            (Facing::East, 5, 0.0),
+        ] {
+            let mut buf = vec![];
+            SpawnEntity::item_frame(VarInt(7), Uuid::nil(), [0.5, 10.0, 0.5], facing)
+                .encode_packet(&mut buf)
+                .unwrap();
+
+            let mut r = buf.as_slice();
+            let pkt = SpawnEntity::decode_packet(&mut r).unwrap();
+
+            assert!(r.is_empty());
+            assert_eq!(pkt.kind.0, 46);
+            assert_eq!(pkt.data.0, data, "{facing:?}");
+            assert_eq!(pkt.pitch, ByteAngle::from_degrees(pitch), "{facing:?}");
+        }
+    }
+
+    #[test]
+    fn status_and_ping_round_trip() {
+        let json = r#"{"version":{"name":"1.19.3","protocol":761},"players":{"online":1,"max":20,"sample":[]},"description":{"text":"A Valence Server"}}"#;
+        let payload = 0xdead_beef_cafe_babe;
+
+        let mut enc = PacketEncoder::new();
+
+        enc.append_packet(&StatusResponse { json }).unwrap();
+        enc.append_packet(&PingResponse { payload }).unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        let pkt = dec.try_next_packet::<StatusResponse>().unwrap().unwrap();
+        assert_eq!(pkt.json, json);
+
+        let pkt = dec.try_next_packet::<PingResponse>().unwrap().unwrap();
+        assert_eq!(pkt.payload, payload);
+    }
+
+    #[test]
+    fn login_success_round_trip() {
+        let uuid = Uuid::from_u128(0x1234);
+        let username = Username::new("jeb_").unwrap();
+
+        let properties = [Property {
+            name: "textures".to_owned(),
+            value: "e3RleHR1cmVzOnt9fQ==".to_owned(),
+            signature: Some("c2lnbmF0dXJl".to_owned()),
+        }];
+
+        let mut enc = PacketEncoder::new();
+
+        enc.append_packet(&LoginSuccess::new(uuid, username))
+            .unwrap();
+        enc.append_packet(&LoginSuccess::new(uuid, username).with_properties(&properties[..]))
+            .unwrap();
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        let pkt = dec.try_next_packet::<LoginSuccess>().unwrap().unwrap();
+        assert_eq!(pkt.uuid, uuid);
+        assert_eq!(pkt.username, username);
+        assert!(pkt.properties.is_empty());
+
+        let pkt = dec.try_next_packet::<LoginSuccess>().unwrap().unwrap();
+        assert_eq!(pkt.uuid, uuid);
+        assert_eq!(pkt.username, username);
+        assert_eq!(&*pkt.properties, &properties[..]);
+    }
+
+    #[test]
+    fn translated_disconnect_round_trip() {
+        let reasons = [
+            Text::translate(translation_key::MULTIPLAYER_DISCONNECT_SERVER_SHUTDOWN, []),
+            Text::translate(
+                translation_key::MULTIPLAYER_DISCONNECT_INCOMPATIBLE,
+                ["1.19.3".color(Color::RED)],
+            ),
+        ];
+
+        let mut enc = PacketEncoder::new();
+
+        for reason in &reasons {
+            enc.append_packet(&DisconnectPlay {
+                reason: Cow::Borrowed(reason),
+            })
+            .unwrap();
+        }
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(enc.take());
+
+        for reason in &reasons {
+            let pkt = dec.try_next_packet::<DisconnectPlay>().unwrap().unwrap();
+            assert_eq!(&*pkt.reason, reason);
+        }
+    }
+
+    #[test]
+    fn spawn_entity_velocity_units() {
+        let pkt = SpawnEntity::with_velocity(
+            VarInt(1234),
+            Uuid::nil(),
+            VarInt(5),
+            [123.0, 456.0, 789.0],
+            (ByteAngle(200), ByteAngle(100)),
+            [0.5, -1.0, 0.0],
+        );
+
+        assert_eq!(pkt.velocity, [4000, -8000, 0]);
+        assert_eq!(pkt.head_yaw, pkt.yaw);
+
+        // Velocities too large for the protocol are saturated.
+        let pkt = SpawnEntity::with_velocity(
+            VarInt(1234),
+            Uuid::nil(),
+            VarInt(5),
+            [0.0; 3],
+            (ByteAngle(0), ByteAngle(0)),
+            [10.0, -10.0, 0.0],
+        );
+
+        assert_eq!(pkt.velocity, [i16::MAX, i16::MIN, 0]);
+    }
+}
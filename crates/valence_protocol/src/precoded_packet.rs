@@ -0,0 +1,85 @@
+use std::io::Write;
+
+use crate::{Encode, EncodePacket, Result};
+
+/// A packet which has been encoded ahead of time.
+///
+/// Packets which never change, such as a static tab list header or a brand
+/// plugin message, can be encoded once and then written any number of times
+/// as a verbatim copy of the encoded bytes. This is like [`RawBytes`], but the
+/// bytes are owned and include the packet ID.
+///
+/// [`RawBytes`]: crate::RawBytes
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct PrecodedPacket {
+    bytes: Vec<u8>,
+}
+
+impl PrecodedPacket {
+    /// Encodes `pkt` (including its packet ID) into a new `PrecodedPacket`.
+    pub fn new<P>(pkt: &P) -> Result<Self>
+    where
+        P: EncodePacket + ?Sized,
+    {
+        let mut bytes = vec![];
+        pkt.encode_packet(&mut bytes)?;
+
+        Ok(Self { bytes })
+    }
+
+    /// Returns the encoded packet ID and packet data.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl Encode for PrecodedPacket {
+    fn encode(&self, mut w: impl Write) -> Result<()> {
+        Ok(w.write_all(&self.bytes)?)
+    }
+}
+
+impl EncodePacket for PrecodedPacket {
+    fn encode_packet(&self, mut w: impl Write) -> Result<()> {
+        Ok(w.write_all(&self.bytes)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::packets::s2c::play::SetTabListHeaderAndFooter;
+    use crate::packets::S2cPlayPacket;
+    use crate::{PacketDecoder, PacketEncoder, TextFormat};
+
+    #[test]
+    fn precoded_packet_matches_original() {
+        let pkt = SetTabListHeaderAndFooter {
+            header: "header".bold().into(),
+            footer: "footer".italic().into(),
+        };
+
+        let precoded = PrecodedPacket::new(&pkt).unwrap();
+
+        let mut enc = PacketEncoder::new();
+        enc.append_packet(&pkt).unwrap();
+        let expected = enc.take();
+
+        enc.append_packet(&precoded).unwrap();
+        enc.append_packet(&precoded).unwrap();
+        let actual = enc.take();
+
+        assert_eq!(&actual[..expected.len()], &expected[..]);
+        assert_eq!(&actual[expected.len()..], &expected[..]);
+
+        let mut dec = PacketDecoder::new();
+        dec.queue_bytes(actual);
+
+        let packets = dec.collect_into_vec::<S2cPlayPacket>().unwrap();
+        assert_eq!(packets.len(), 2);
+        assert!(matches!(
+            &packets[0],
+            S2cPlayPacket::SetTabListHeaderAndFooter(p) if p.header == pkt.header
+        ));
+    }
+}
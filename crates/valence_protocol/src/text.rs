@@ -1,15 +1,17 @@
 //! Formatted text.
 
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::io::Write;
 use std::{fmt, ops};
 
-use anyhow::Context;
+use anyhow::{bail, ensure, Context};
 use serde::de::Visitor;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
+use valence_nbt::{Compound, List, Value};
 
-use crate::{Decode, Encode, Ident, Result};
+use crate::{Decode, Encode, Ident, Result, VarInt};
 
 /// Represents formatted text in Minecraft's JSON text format.
 ///
@@ -41,6 +43,16 @@ use crate::{Decode, Encode, Ident, Result};
 ///     "The text is Red, Green, and also Blue!\nAnd maybe even Italic."
 /// );
 /// ```
+///
+/// # Equality
+///
+/// Comparing texts with `==` compares their structure. Use
+/// [`Text::eq_normalized`] to check if two texts look the same to the client
+/// regardless of how their styles are structured.
+///
+/// `==` is not based on the normalized form because normalizing allocates a
+/// new copy of both texts, and texts which are encoded differently would
+/// compare equal.
 #[derive(Clone, PartialEq, Default, Serialize)]
 #[serde(transparent)]
 pub struct Text(Box<TextInner>);
@@ -527,6 +539,149 @@ impl Text {
         write_string_inner(self, &mut w)
     }
 
+    /// Returns an equivalent text object with a flat structure.
+    ///
+    /// Styles inherited from parent components are applied explicitly to each
+    /// piece of content, adjacent plain text with identical styles is merged,
+    /// empty plain text is removed, and `false` formatting flags (the
+    /// default) are omitted.
+    ///
+    /// Texts that are displayed identically but are structured differently
+    /// will have equal normalized forms.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_protocol::text::{Color, Text, TextFormat};
+    ///
+    /// let a = ("foo".into_text() + "bar").color(Color::RED);
+    /// let b = "foo".color(Color::RED) + "bar".color(Color::RED);
+    ///
+    /// assert_eq!(a.normalize(), "foobar".color(Color::RED).normalize());
+    /// assert_eq!(a.normalize(), b.normalize());
+    /// ```
+    pub fn normalize(&self) -> Text {
+        normalize_with(self, &TextInner::default())
+    }
+
+    /// Returns `true` if this text and `other` have equal
+    /// [normalized](Self::normalize) forms, meaning they are displayed
+    /// identically. Returns `false` otherwise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_protocol::text::{Color, Text, TextFormat};
+    ///
+    /// let a = ("foo".into_text() + "bar").color(Color::RED);
+    /// let b = "foo".color(Color::RED) + "bar".color(Color::RED);
+    ///
+    /// assert_ne!(a, b);
+    /// assert!(a.eq_normalized(&b));
+    /// ```
+    pub fn eq_normalized(&self, other: &Text) -> bool {
+        self.normalize() == other.normalize()
+    }
+
+    /// Parses a string using the legacy `§` formatting codes into text.
+    ///
+    /// Color codes `§0` through `§f` set the color and, like in vanilla, reset
+    /// any active styles. `§k`, `§l`, `§m`, `§n` and `§o` enable obfuscated,
+    /// bold, strikethrough, underlined and italic respectively, and `§r`
+    /// resets all formatting. Codes are case insensitive. Unknown codes and a
+    /// trailing `§` are kept as literal text.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use valence_protocol::text::{Color, Text, TextFormat};
+    ///
+    /// let txt = Text::from_legacy("§c§lHello §r§9world");
+    ///
+    /// assert_eq!(
+    ///     txt,
+    ///     "".into_text() + "Hello ".color(Color::RED).bold() + "world".color(Color::BLUE)
+    /// );
+    /// ```
+    pub fn from_legacy(s: &str) -> Self {
+        #[derive(Copy, Clone, PartialEq, Default)]
+        struct Style {
+            color: Option<Color>,
+            obfuscated: bool,
+            bold: bool,
+            strikethrough: bool,
+            underlined: bool,
+            italic: bool,
+        }
+
+        fn flush(segment: &mut String, style: Style, out: &mut Vec<Text>) {
+            if segment.is_empty() {
+                return;
+            }
+
+            let mut txt = Text::text(std::mem::take(segment));
+            txt.0.color = style.color;
+            txt.0.obfuscated = style.obfuscated.then_some(true);
+            txt.0.bold = style.bold.then_some(true);
+            txt.0.strikethrough = style.strikethrough.then_some(true);
+            txt.0.underlined = style.underlined.then_some(true);
+            txt.0.italic = style.italic.then_some(true);
+
+            out.push(txt);
+        }
+
+        let mut extra = vec![];
+        let mut segment = String::new();
+        let mut style = Style::default();
+        let mut chars = s.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            if c != '§' {
+                segment.push(c);
+                continue;
+            }
+
+            let mut new_style = style;
+
+            match chars.peek().map(char::to_ascii_lowercase) {
+                Some(code @ ('0'..='9' | 'a'..='f')) => {
+                    new_style = Style {
+                        color: legacy_color(code),
+                        ..Default::default()
+                    }
+                }
+                Some('k') => new_style.obfuscated = true,
+                Some('l') => new_style.bold = true,
+                Some('m') => new_style.strikethrough = true,
+                Some('n') => new_style.underlined = true,
+                Some('o') => new_style.italic = true,
+                Some('r') => new_style = Style::default(),
+                _ => {
+                    segment.push(c);
+                    continue;
+                }
+            }
+
+            chars.next();
+
+            if new_style != style {
+                flush(&mut segment, style, &mut extra);
+                style = new_style;
+            }
+        }
+
+        flush(&mut segment, style, &mut extra);
+
+        match extra.len() {
+            0 => Text::default(),
+            1 => extra.pop().unwrap(),
+            _ => Text(Box::new(TextInner {
+                extra,
+                ..Default::default()
+            })),
+        }
+    }
+
     /// Returns `true` if the text contains no characters. Returns `false`
     /// otherwise.
     pub fn is_empty(&self) -> bool {
@@ -553,6 +708,285 @@ impl Text {
             TextContent::StorageNbt { nbt, .. } => nbt.is_empty(),
         }
     }
+
+    /// Converts this text to an NBT compound with the same structure as its
+    /// JSON representation. Booleans are stored as bytes.
+    pub fn to_nbt(&self) -> Result<Compound> {
+        match json_to_nbt(serde_json::to_value(self)?)? {
+            Value::Compound(c) => Ok(c),
+            _ => bail!("text is not an NBT compound"),
+        }
+    }
+
+    /// Converts an NBT compound created by [`Self::to_nbt`] back to text.
+    pub fn from_nbt(nbt: &Compound) -> Result<Self> {
+        serde_json::from_value(compound_to_json(nbt)).context("decoding text NBT")
+    }
+
+    /// Encodes this text in the given format. Using [`TextEncoding::Json`] is
+    /// equivalent to the [`Encode`] implementation.
+    pub fn encode_with(&self, encoding: TextEncoding, w: impl Write) -> Result<()> {
+        match encoding {
+            TextEncoding::Json => self.encode(w),
+            TextEncoding::Nbt => self.to_nbt()?.encode(w),
+        }
+    }
+
+    /// Decodes text in the given format. Using [`TextEncoding::Json`] is
+    /// equivalent to the [`Decode`] implementation.
+    pub fn decode_with(encoding: TextEncoding, r: &mut &[u8]) -> Result<Self> {
+        match encoding {
+            TextEncoding::Json => Self::decode(r),
+            TextEncoding::Nbt => Self::from_nbt(&Compound::decode(r)?),
+        }
+    }
+}
+
+/// Normalizes `txt` as if it were displayed inside a component with the style
+/// `base`. See [`Text::normalize`].
+fn normalize_with(txt: &Text, base: &TextInner) -> Text {
+    fn flatten(txt: &Text, parent_style: &TextInner, base: &TextInner, out: &mut Vec<TextInner>) {
+        let inner = &*txt.0;
+        let mut style = parent_style.clone();
+
+        macro_rules! inherit {
+            ($($field:ident),*) => {
+                $(
+                    if inner.$field.is_some() {
+                        style.$field = inner.$field.clone();
+                    }
+                )*
+            }
+        }
+
+        inherit!(
+            color,
+            font,
+            bold,
+            italic,
+            underlined,
+            strikethrough,
+            obfuscated,
+            insertion,
+            click_event,
+            hover_event
+        );
+
+        let content = match &inner.content {
+            TextContent::Text { text } if text.is_empty() => None,
+            // Translation arguments are displayed with the style of the
+            // component they are in.
+            TextContent::Translate { translate, with } => Some(TextContent::Translate {
+                translate: translate.clone(),
+                with: with.iter().map(|arg| normalize_with(arg, &style)).collect(),
+            }),
+            content => Some(content.clone()),
+        };
+
+        if let Some(content) = content {
+            let mut leaf = TextInner {
+                content,
+                ..style.clone()
+            };
+
+            // A `false` flag is only needed to override a `true` flag of the
+            // component the text is displayed in.
+            for (flag, base_flag) in [
+                (&mut leaf.bold, base.bold),
+                (&mut leaf.italic, base.italic),
+                (&mut leaf.underlined, base.underlined),
+                (&mut leaf.strikethrough, base.strikethrough),
+                (&mut leaf.obfuscated, base.obfuscated),
+            ] {
+                if *flag == Some(false) && base_flag != Some(true) {
+                    *flag = None;
+                }
+            }
+
+            out.push(leaf);
+        }
+
+        for child in &inner.extra {
+            flatten(child, &style, base, out);
+        }
+    }
+
+    fn same_style(a: &TextInner, b: &TextInner) -> bool {
+        a.color == b.color
+            && a.font == b.font
+            && a.bold == b.bold
+            && a.italic == b.italic
+            && a.underlined == b.underlined
+            && a.strikethrough == b.strikethrough
+            && a.obfuscated == b.obfuscated
+            && a.insertion == b.insertion
+            && a.click_event == b.click_event
+            && a.hover_event == b.hover_event
+    }
+
+    let mut leaves: Vec<TextInner> = vec![];
+    flatten(txt, base, base, &mut leaves);
+
+    // Merge adjacent plain text with identical styles.
+    let mut merged: Vec<TextInner> = Vec::with_capacity(leaves.len());
+    for leaf in leaves {
+        if let Some(last) = merged.last_mut() {
+            if let (TextContent::Text { text: a }, TextContent::Text { text: b }) =
+                (&last.content, &leaf.content)
+            {
+                if same_style(last, &leaf) {
+                    let text = format!("{a}{b}").into();
+                    last.content = TextContent::Text { text };
+                    continue;
+                }
+            }
+        }
+
+        merged.push(leaf);
+    }
+
+    match merged.len() {
+        0 => Text::default(),
+        1 => Text(Box::new(merged.pop().unwrap())),
+        _ => Text(Box::new(TextInner {
+            extra: merged.into_iter().map(|l| Text(Box::new(l))).collect(),
+            ..Default::default()
+        })),
+    }
+}
+
+/// The format used to encode [`Text`] in a packet.
+///
+/// Text is encoded as a JSON string in most packets, but newer versions of the
+/// protocol encode it as NBT in some packets. Use [`Text::encode_with`] and
+/// [`Text::decode_with`] to select the format.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Default, Debug)]
+pub enum TextEncoding {
+    /// A string containing the text as JSON. This is what the [`Encode`] and
+    /// [`Decode`] implementations of [`Text`] use.
+    #[default]
+    Json,
+    /// An NBT compound. See [`Text::to_nbt`].
+    Nbt,
+}
+
+fn json_to_nbt(json: serde_json::Value) -> Result<Value> {
+    use serde_json::Value as Json;
+
+    Ok(match json {
+        Json::Null => bail!("text JSON contains null"),
+        Json::Bool(b) => b.into(),
+        Json::Number(n) => {
+            if let Some(n) = n.as_i64() {
+                match i32::try_from(n) {
+                    Ok(n) => n.into(),
+                    Err(_) => n.into(),
+                }
+            } else {
+                n.as_f64()
+                    .context("text JSON number is out of range")?
+                    .into()
+            }
+        }
+        Json::String(s) => s.into(),
+        Json::Array(array) => {
+            let elems = array
+                .into_iter()
+                .map(json_to_nbt)
+                .collect::<Result<Vec<_>>>()?;
+
+            if elems.is_empty() {
+                List::End.into()
+            } else if elems.iter().all(|e| matches!(e, Value::Compound(_))) {
+                List::Compound(
+                    elems
+                        .into_iter()
+                        .filter_map(|e| match e {
+                            Value::Compound(c) => Some(c),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+                .into()
+            } else if elems.iter().all(|e| matches!(e, Value::String(_))) {
+                List::String(
+                    elems
+                        .into_iter()
+                        .filter_map(|e| match e {
+                            Value::String(s) => Some(s),
+                            _ => None,
+                        })
+                        .collect(),
+                )
+                .into()
+            } else {
+                bail!("text JSON array is not homogeneous")
+            }
+        }
+        Json::Object(obj) => obj
+            .into_iter()
+            // Absent optional fields are skipped rather than stored as null.
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| Ok((k, json_to_nbt(v)?)))
+            .collect::<Result<Compound>>()?
+            .into(),
+    })
+}
+
+fn nbt_to_json(nbt: &Value) -> serde_json::Value {
+    use serde_json::Value as Json;
+
+    match nbt {
+        // Text only contains bytes where the JSON has booleans.
+        Value::Byte(b) => Json::Bool(*b != 0),
+        Value::Short(n) => (*n).into(),
+        Value::Int(n) => (*n).into(),
+        Value::Long(n) => (*n).into(),
+        Value::Float(n) => (*n).into(),
+        Value::Double(n) => (*n).into(),
+        Value::ByteArray(a) => a.as_slice().into(),
+        Value::String(s) => s.as_str().into(),
+        Value::List(l) => list_to_json(l),
+        Value::Compound(c) => compound_to_json(c),
+        Value::IntArray(a) => a.as_slice().into(),
+        Value::LongArray(a) => a.as_slice().into(),
+    }
+}
+
+fn list_to_json(list: &List) -> serde_json::Value {
+    match list {
+        List::End => serde_json::Value::Array(vec![]),
+        List::Byte(l) => l.iter().map(|&b| serde_json::Value::Bool(b != 0)).collect(),
+        List::Short(l) => l.as_slice().into(),
+        List::Int(l) => l.as_slice().into(),
+        List::Long(l) => l.as_slice().into(),
+        List::Float(l) => l.as_slice().into(),
+        List::Double(l) => l.as_slice().into(),
+        List::ByteArray(l) => l
+            .iter()
+            .map(|a| serde_json::Value::from(a.as_slice()))
+            .collect(),
+        List::String(l) => l.as_slice().into(),
+        List::List(l) => l.iter().map(list_to_json).collect(),
+        List::Compound(l) => l.iter().map(compound_to_json).collect(),
+        List::IntArray(l) => l
+            .iter()
+            .map(|a| serde_json::Value::from(a.as_slice()))
+            .collect(),
+        List::LongArray(l) => l
+            .iter()
+            .map(|a| serde_json::Value::from(a.as_slice()))
+            .collect(),
+    }
+}
+
+fn compound_to_json(compound: &Compound) -> serde_json::Value {
+    serde_json::Value::Object(
+        compound
+            .iter()
+            .map(|(k, v)| (k.clone(), nbt_to_json(v)))
+            .collect(),
+    )
 }
 
 /// Provides the methods necessary for working with [`Text`] objects.
@@ -842,8 +1276,28 @@ impl fmt::Display for Text {
 }
 
 impl Encode for Text {
-    fn encode(&self, w: impl Write) -> Result<()> {
-        serde_json::to_string(self)?.encode(w)
+    fn encode(&self, mut w: impl Write) -> Result<()> {
+        thread_local! {
+            /// Reused between calls to avoid allocating a new string for the
+            /// JSON every time text is encoded.
+            static JSON_BUF: RefCell<Vec<u8>> = RefCell::new(vec![]);
+        }
+
+        JSON_BUF.with(|buf| {
+            let mut buf = buf.borrow_mut();
+            buf.clear();
+
+            serde_json::to_writer(&mut *buf, self)?;
+
+            ensure!(
+                buf.len() <= i32::MAX as usize,
+                "byte length of text JSON ({}) exceeds i32::MAX",
+                buf.len()
+            );
+
+            VarInt(buf.len() as i32).encode(&mut w)?;
+            Ok(w.write_all(&buf)?)
+        })
     }
 }
 
@@ -890,7 +1344,17 @@ impl Color {
 
 impl Serialize for Color {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        format!("#{:02x}{:02x}{:02x}", self.r, self.g, self.b).serialize(serializer)
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+
+        // Format the color on the stack instead of allocating a string.
+        let mut buf = [b'#'; 7];
+        for (i, c) in [self.r, self.g, self.b].into_iter().enumerate() {
+            buf[1 + i * 2] = HEX[(c >> 4) as usize];
+            buf[2 + i * 2] = HEX[(c & 0xf) as usize];
+        }
+
+        // All bytes in the buffer are ASCII.
+        serializer.serialize_str(std::str::from_utf8(&buf).unwrap())
     }
 }
 
@@ -950,6 +1414,28 @@ fn color_from_str(s: &str) -> Option<Color> {
     }
 }
 
+fn legacy_color(code: char) -> Option<Color> {
+    match code {
+        '0' => Some(Color::BLACK),
+        '1' => Some(Color::DARK_BLUE),
+        '2' => Some(Color::DARK_GREEN),
+        '3' => Some(Color::DARK_AQUA),
+        '4' => Some(Color::DARK_RED),
+        '5' => Some(Color::DARK_PURPLE),
+        '6' => Some(Color::GOLD),
+        '7' => Some(Color::GRAY),
+        '8' => Some(Color::DARK_GRAY),
+        '9' => Some(Color::BLUE),
+        'a' => Some(Color::GREEN),
+        'b' => Some(Color::AQUA),
+        'c' => Some(Color::RED),
+        'd' => Some(Color::LIGHT_PURPLE),
+        'e' => Some(Color::YELLOW),
+        'f' => Some(Color::WHITE),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -976,6 +1462,108 @@ mod tests {
         assert_eq!(before.to_string(), after.to_string());
     }
 
+    #[test]
+    fn text_json_and_nbt_encodings() {
+        let before = "foo".color(Color::RED).bold()
+            + Text::translate(
+                translation_key::CHAT_TYPE_TEXT,
+                ["bar".into(), "baz".italic()],
+            )
+            .on_click_run_command("/help");
+
+        let mut json = vec![];
+        before.encode_with(TextEncoding::Json, &mut json).unwrap();
+
+        let mut nbt = vec![];
+        before.encode_with(TextEncoding::Nbt, &mut nbt).unwrap();
+
+        assert_ne!(json, nbt);
+
+        let mut plain = vec![];
+        before.encode(&mut plain).unwrap();
+        assert_eq!(json, plain);
+
+        let mut r = json.as_slice();
+        let from_json = Text::decode_with(TextEncoding::Json, &mut r).unwrap();
+        assert!(r.is_empty());
+
+        let mut r = nbt.as_slice();
+        let from_nbt = Text::decode_with(TextEncoding::Nbt, &mut r).unwrap();
+        assert!(r.is_empty());
+
+        assert_eq!(from_json, before);
+        assert_eq!(from_nbt, before);
+
+        let compound = before.to_nbt().unwrap();
+        assert_eq!(compound.get("text"), Some(&Value::String("foo".into())));
+        assert_eq!(compound.get("bold"), Some(&Value::Byte(1)));
+        assert!(matches!(
+            compound.get("extra"),
+            Some(Value::List(List::Compound(_)))
+        ));
+    }
+
+    #[test]
+    fn styled_text_encoding() {
+        let txt = "this".italic() + " is the " + "header".bold().color(Color::RED);
+
+        let mut buf = vec![];
+        txt.encode(&mut buf).unwrap();
+
+        let expected = r##"{"text":"this","italic":true,"extra":[{"text":" is the "},{"text":"header","color":"#ff5555","bold":true}]}"##;
+
+        let mut r = buf.as_slice();
+        assert_eq!(VarInt::decode(&mut r).unwrap().0 as usize, expected.len());
+        assert_eq!(r, expected.as_bytes());
+
+        // Encoding again reuses the buffer and must give identical output.
+        let mut buf2 = vec![];
+        txt.encode(&mut buf2).unwrap();
+        assert_eq!(buf, buf2);
+
+        assert_eq!(Text::decode(&mut buf.as_slice()).unwrap(), txt);
+    }
+
+    #[test]
+    fn normalized_equality() {
+        let a = ("foo".into_text() + "bar".italic() + "baz")
+            .color(Color::RED)
+            .bold();
+        let b = "foo".color(Color::RED).bold()
+            + "bar".color(Color::RED).bold().italic()
+            + "".into_text()
+            + "baz".color(Color::RED).bold().not_italic();
+
+        assert_ne!(a, b);
+        assert!(a.eq_normalized(&b));
+        assert_eq!(a.normalize(), b.normalize());
+
+        let normalized = a.normalize();
+        assert_eq!(normalized.0.extra.len(), 3);
+        assert!(normalized.0.extra.iter().all(|t| t.0.extra.is_empty()));
+
+        assert!(!a.eq_normalized(&"foobarbaz".color(Color::RED).bold()));
+        assert!(!a.eq_normalized(&("foo".color(Color::RED).bold() + "barbaz")));
+    }
+
+    #[test]
+    fn normalized_translate_args_inherit_style() {
+        let a = Text::translate("foo", ["bar".into_text(), "baz".not_bold()]).bold();
+        let b = Text::translate("foo", ["bar".bold(), "baz".not_bold()]).bold();
+
+        assert!(a.eq_normalized(&b));
+
+        // The argument must stay unbolded inside the bold translation.
+        let TextContent::Translate { with, .. } = &a.normalize().0.content else {
+            panic!("expected translated text");
+        };
+        assert_eq!(with[0].0.bold, Some(true));
+        assert_eq!(with[1].0.bold, Some(false));
+
+        let plain = Text::translate("foo", ["bar".into_text(), "baz".into_text()]).bold();
+        assert!(!a.eq_normalized(&plain));
+    }
+
     #[test]
     fn text_color() {
         assert_eq!(
@@ -990,6 +1578,87 @@ mod tests {
         assert_eq!(color_from_str("blue"), Some(Color::BLUE));
     }
 
+    #[test]
+    fn decoration_fields() {
+        let cases = [
+            ("bold", "foo".bold(), "foo".not_bold()),
+            ("italic", "foo".italic(), "foo".not_italic()),
+            ("underlined", "foo".underlined(), "foo".not_underlined()),
+            (
+                "strikethrough",
+                "foo".strikethrough(),
+                "foo".not_strikethrough(),
+            ),
+            ("obfuscated", "foo".obfuscated(), "foo".not_obfuscated()),
+        ];
+
+        for (field, enabled, disabled) in cases {
+            assert_eq!(
+                serde_json::to_string(&enabled).unwrap(),
+                format!(r#"{{"text":"foo","{field}":true}}"#)
+            );
+            assert_eq!(
+                serde_json::to_string(&disabled).unwrap(),
+                format!(r#"{{"text":"foo","{field}":false}}"#)
+            );
+        }
+
+        assert_eq!(
+            serde_json::to_string(&"foo".obfuscated().clear_obfuscated()).unwrap(),
+            r#"{"text":"foo"}"#
+        );
+    }
+
+    #[test]
+    fn explicit_false_overrides_parent() {
+        let txt = "parent "
+            .bold()
+            .italic()
+            .add_child("child".not_bold().not_italic());
+
+        assert_eq!(
+            serde_json::to_string(&txt).unwrap(),
+            r#"{"text":"parent ","bold":true,"italic":true,"extra":[{"text":"child","bold":false,"italic":false}]}"#
+        );
+
+        // The child is displayed without the parent's styling.
+        assert!(txt.eq_normalized(&("".into_text() + "parent ".bold().italic() + "child")));
+    }
+
+    #[test]
+    fn legacy_formatting() {
+        assert_eq!(
+            Text::from_legacy("§c§lHello §r§9world"),
+            "".into_text() + "Hello ".color(Color::RED).bold() + "world".color(Color::BLUE)
+        );
+
+        // Styles accumulate until a reset or a color code.
+        assert_eq!(
+            Text::from_legacy("§o§nfoo§mbar§ebaz§kqux"),
+            "".into_text()
+                + "foo".italic().underlined()
+                + "bar".italic().underlined().strikethrough()
+                + "baz".color(Color::YELLOW)
+                + "qux".color(Color::YELLOW).obfuscated()
+        );
+
+        // Codes are case insensitive.
+        assert_eq!(
+            Text::from_legacy("§AGreen§Rplain"),
+            "".into_text() + "Green".color(Color::GREEN) + "plain"
+        );
+
+        // Unknown codes and a dangling section sign are kept as they are.
+        assert_eq!(Text::from_legacy("§zfoo§"), "§zfoo§".into_text());
+        assert_eq!(
+            Text::from_legacy("§§6gold"),
+            "".into_text() + "§" + "gold".color(Color::GOLD)
+        );
+
+        assert_eq!(Text::from_legacy("plain text"), "plain text".into_text());
+        assert!(Text::from_legacy("§a§l").is_empty());
+    }
+
     #[test]
     fn non_object_data_types() {
         let input = r#"["foo", true, false, 1.9E10, 9999]"#;
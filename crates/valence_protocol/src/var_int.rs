@@ -37,6 +37,27 @@ impl VarInt {
 
         Err(VarIntDecodeError::TooLarge)
     }
+
+    /// Decodes a VarInt from the front of `buf` without consuming it. Returns
+    /// the value and the number of bytes it occupies, or `None` if `buf` ends
+    /// before the VarInt is complete.
+    ///
+    /// This is useful for reading the length prefix of a packet frame which
+    /// may not have been fully received yet.
+    pub fn decode_prefix(buf: &[u8]) -> Result<Option<(i32, usize)>, VarIntDecodeError> {
+        let mut val = 0;
+        for i in 0..Self::MAX_SIZE {
+            let Some(&byte) = buf.get(i) else {
+                return Ok(None)
+            };
+            val |= (byte as i32 & 0b01111111) << (i * 7);
+            if byte & 0b10000000 == 0 {
+                return Ok(Some((val, i + 1)));
+            }
+        }
+
+        Err(VarIntDecodeError::TooLarge)
+    }
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Error)]
@@ -129,4 +150,33 @@ mod tests {
             buf.clear();
         }
     }
+
+    #[test]
+    fn varint_decode_prefix() {
+        let mut buf = vec![];
+        VarInt(300).encode(&mut buf).unwrap();
+        assert_eq!(buf.len(), 2);
+
+        // Trailing bytes are not part of the VarInt.
+        buf.extend_from_slice(&[0xff, 0xff]);
+        assert_eq!(VarInt::decode_prefix(&buf), Ok(Some((300, 2))));
+
+        // Incomplete.
+        assert_eq!(VarInt::decode_prefix(&buf[..1]), Ok(None));
+        assert_eq!(VarInt::decode_prefix(&[]), Ok(None));
+        assert_eq!(VarInt::decode_prefix(&[0x80; 4]), Ok(None));
+
+        let mut min = vec![];
+        VarInt(i32::MIN).encode(&mut min).unwrap();
+        assert_eq!(
+            VarInt::decode_prefix(&min),
+            Ok(Some((i32::MIN, VarInt::MAX_SIZE)))
+        );
+
+        // Too long.
+        assert_eq!(
+            VarInt::decode_prefix(&[0x80; 6]),
+            Err(VarIntDecodeError::TooLarge)
+        );
+    }
 }
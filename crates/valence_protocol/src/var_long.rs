@@ -49,7 +49,19 @@ impl Decode<'_> for VarLong {
                 return Ok(VarLong(val));
             }
         }
-        bail!("VarInt is too large")
+        bail!("VarLong is too large")
+    }
+}
+
+impl From<i64> for VarLong {
+    fn from(i: i64) -> Self {
+        VarLong(i)
+    }
+}
+
+impl From<VarLong> for i64 {
+    fn from(i: VarLong) -> Self {
+        i.0
     }
 }
 
@@ -59,6 +71,22 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn varlong_written_size() {
+        let mut rng = thread_rng();
+        let mut buf = vec![];
+
+        for n in (0..100_000)
+            .map(|_| rng.gen())
+            .chain([0, -1, i64::MIN, i64::MAX])
+            .map(VarLong)
+        {
+            buf.clear();
+            n.encode(&mut buf).unwrap();
+            assert_eq!(buf.len(), n.written_size());
+        }
+    }
+
     #[test]
     fn encode_decode() {
         let mut rng = thread_rng();
@@ -66,7 +94,7 @@ mod tests {
 
         for n in (0..1_000_000)
             .map(|_| rng.gen())
-            .chain([0, i64::MIN, i64::MAX])
+            .chain([0, -1, i64::MIN, i64::MAX])
         {
             VarLong(n).encode(&mut buf).unwrap();
 
@@ -78,4 +106,16 @@ mod tests {
             buf.clear();
         }
     }
+
+    #[test]
+    fn decode_too_large() {
+        let bytes = [0xff; VarLong::MAX_SIZE + 1];
+
+        let mut r = bytes.as_slice();
+        let err = VarLong::decode(&mut r).unwrap_err();
+        assert_eq!(err.to_string(), "VarLong is too large");
+
+        let mut r = &bytes[..3];
+        assert!(VarLong::decode(&mut r).is_err());
+    }
 }